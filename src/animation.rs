@@ -0,0 +1,131 @@
+//!  A module for animated images.
+
+// from rust
+
+// from external crate
+
+// from local crate
+use error::RasterResult;
+use Image;
+
+/// A struct for representing an animated image as a sequence of frames.
+#[derive(Debug, Clone)]
+pub struct Animation {
+    /// Width of the animation canvas in pixels.
+    pub width: i32,
+
+    /// Height of the animation canvas in pixels.
+    pub height: i32,
+
+    /// Frames in display order.
+    pub frames: Vec<Frame>,
+
+    /// How many times the animation is played.
+    pub loop_count: LoopCount,
+}
+
+impl Animation {
+    /// Create an animation from a list of frames. The canvas size is taken from the first frame
+    /// and the animation loops forever.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{Animation, Frame, Image};
+    ///
+    /// let frames = vec![
+    ///     Frame::new(Image::blank(20, 10), 100),
+    ///     Frame::new(Image::blank(20, 10), 100),
+    /// ];
+    /// let animation = Animation::new(frames);
+    ///
+    /// assert_eq!(20, animation.width);
+    /// assert_eq!(10, animation.height);
+    /// ```
+    pub fn new(frames: Vec<Frame>) -> Animation {
+        let (width, height) = frames
+            .first()
+            .map_or((0, 0), |frame| (frame.image.width, frame.image.height));
+        Animation {
+            width,
+            height,
+            frames,
+            loop_count: LoopCount::Infinite,
+        }
+    }
+
+    /// Apply an operation to the image of every frame. Any function from the editor, filter or
+    /// transform modules that takes a `&mut Image` can be used. The canvas size is updated
+    /// afterwards to match the first frame.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{editor, ResizeMode};
+    ///
+    /// let mut animation = raster::open_animation("tests/in/animated.gif").unwrap();
+    /// animation.map_frames(|image| editor::resize(image, 22, 58, ResizeMode::Fit)).unwrap();
+    ///
+    /// assert_eq!(22, animation.width);
+    /// assert_eq!(58, animation.height);
+    /// raster::save_animation(&animation, "tests/out/test_animation_resize.gif").unwrap();
+    /// ```
+    pub fn map_frames<F>(&mut self, mut f: F) -> RasterResult<()>
+    where
+        F: FnMut(&mut Image) -> RasterResult<()>,
+    {
+        for frame in &mut self.frames {
+            f(&mut frame.image)?;
+        }
+        if let Some(frame) = self.frames.first() {
+            self.width = frame.image.width;
+            self.height = frame.image.height;
+        }
+
+        Ok(())
+    }
+}
+
+/// A single frame of an animation.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// The fully composited canvas shown for this frame.
+    pub image: Image,
+
+    /// How long the frame is shown, in milliseconds.
+    pub delay: u32,
+
+    /// What happens to the frame once its delay is over.
+    pub disposal: DisposalMethod,
+}
+
+impl Frame {
+    /// Create a frame shown for `delay` milliseconds.
+    pub fn new(image: Image, delay: u32) -> Frame {
+        Frame {
+            image,
+            delay,
+            disposal: DisposalMethod::None,
+        }
+    }
+}
+
+/// Enumeration of the ways a frame is disposed of before the next frame is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisposalMethod {
+    /// Leave the frame in place.
+    None,
+    /// Clear the frame area to transparent.
+    Background,
+    /// Restore the frame area to what it was before the frame was drawn.
+    Previous,
+}
+
+/// Enumeration for the number of times an animation is played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopCount {
+    /// Play the animation forever.
+    Infinite,
+    /// Play the animation a fixed number of times.
+    Finite(u16),
+}
@@ -2,13 +2,14 @@
 // See https://en.wikipedia.org/wiki/Alpha_compositing
 
 // from rust
+use std::ops::Range;
 
 // from external crate
 
 // from local crate
-use error::RasterResult;
+use error::{RasterError, RasterResult};
+use parallel;
 use Image;
-use Color;
 
 /// Enumeration for blending modes.
 #[derive(Debug)]
@@ -31,38 +32,15 @@ pub fn difference(
     offset_y: i32,
     opacity: f32,
 ) -> RasterResult<Image> {
-    let mut canvas = image1.clone();
-
-    for y in loop_start_y..loop_end_y {
-        for x in loop_start_x..loop_end_x {
-            let canvas_x = x + offset_x;
-            let canvas_y = y + offset_y;
-            let rgba1 = image1.get_pixel(canvas_x, canvas_y)?;
-            let a1 = rgba1.a as f32 / 255.0; // convert to 0.0 - 1.0
-            let r1 = rgba1.r as f32 * a1;
-            let g1 = rgba1.g as f32 * a1;
-            let b1 = rgba1.b as f32 * a1;
-
-            let rgba2 = image2.get_pixel(x, y)?;
-            let a2 = rgba2.a as f32 / 255.0 * opacity; // convert to 0.0 - 1.0
-            let r2 = rgba2.r as f32;
-            let g2 = rgba2.g as f32;
-            let b2 = rgba2.b as f32;
-
-            let r3 = ch_alpha_f(r1, r2, BlendFunction::Difference, a2);
-            let g3 = ch_alpha_f(g1, g2, BlendFunction::Difference, a2);
-            let b3 = ch_alpha_f(b1, b2, BlendFunction::Difference, a2);
-            let a3 = 255;
-
-            canvas.set_pixel(
-                canvas_x,
-                canvas_y,
-                &Color::rgba(r3 as u8, g3 as u8, b3 as u8, a3 as u8),
-            )?;
-        }
-    }
-
-    Ok(canvas)
+    composite(
+        image1,
+        image2,
+        loop_start_y..loop_end_y,
+        loop_start_x..loop_end_x,
+        (offset_x, offset_y),
+        opacity,
+        |base, top, opacity| ch_alpha_f(base, top, BlendFunction::Difference, opacity),
+    )
 }
 
 pub fn multiply(
@@ -76,38 +54,15 @@ pub fn multiply(
     offset_y: i32,
     opacity: f32,
 ) -> RasterResult<Image> {
-    let mut canvas = image1.clone();
-
-    for y in loop_start_y..loop_end_y {
-        for x in loop_start_x..loop_end_x {
-            let canvas_x = x + offset_x;
-            let canvas_y = y + offset_y;
-            let rgba1 = image1.get_pixel(canvas_x, canvas_y)?;
-            let a1 = rgba1.a as f32 / 255.0; // convert to 0.0 - 1.0
-            let r1 = rgba1.r as f32 * a1;
-            let g1 = rgba1.g as f32 * a1;
-            let b1 = rgba1.b as f32 * a1;
-
-            let rgba2 = image2.get_pixel(x, y)?;
-            let a2 = rgba2.a as f32 / 255.0 * opacity; // convert to 0.0 - 1.0
-            let r2 = rgba2.r as f32;
-            let g2 = rgba2.g as f32;
-            let b2 = rgba2.b as f32;
-
-            let r3 = ch_alpha_f(r1, r2, BlendFunction::Multiply, a2);
-            let g3 = ch_alpha_f(g1, g2, BlendFunction::Multiply, a2);
-            let b3 = ch_alpha_f(b1, b2, BlendFunction::Multiply, a2);
-            let a3 = 255;
-
-            canvas.set_pixel(
-                canvas_x,
-                canvas_y,
-                &Color::rgba(r3 as u8, g3 as u8, b3 as u8, a3 as u8),
-            )?;
-        }
-    }
-
-    Ok(canvas)
+    composite(
+        image1,
+        image2,
+        loop_start_y..loop_end_y,
+        loop_start_x..loop_end_x,
+        (offset_x, offset_y),
+        opacity,
+        |base, top, opacity| ch_alpha_f(base, top, BlendFunction::Multiply, opacity),
+    )
 }
 
 pub fn normal(
@@ -121,38 +76,15 @@ pub fn normal(
     offset_y: i32,
     opacity: f32,
 ) -> RasterResult<Image> {
-    let mut canvas = image1.clone();
-
-    for y in loop_start_y..loop_end_y {
-        for x in loop_start_x..loop_end_x {
-            let canvas_x = x + offset_x;
-            let canvas_y = y + offset_y;
-            let color1 = image1.get_pixel(canvas_x, canvas_y)?;
-            let a1 = color1.a as f32 / 255.0; // convert to 0.0 - 1.0
-            let r1 = color1.r as f32 * a1;
-            let g1 = color1.g as f32 * a1;
-            let b1 = color1.b as f32 * a1;
-
-            let color2 = image2.get_pixel(x, y)?;
-            let a2 = color2.a as f32 / 255.0 * opacity; // convert to 0.0 - 1.0
-            let r2 = color2.r as f32;
-            let g2 = color2.g as f32;
-            let b2 = color2.b as f32;
-
-            let r3 = (a2 * r2) + ((1.0 - a2) * r1);
-            let g3 = (a2 * g2) + ((1.0 - a2) * g1);
-            let b3 = (a2 * b2) + ((1.0 - a2) * b1);
-            let a3 = 255;
-
-            canvas.set_pixel(
-                canvas_x,
-                canvas_y,
-                &Color::rgba(r3 as u8, g3 as u8, b3 as u8, a3 as u8),
-            )?;
-        }
-    }
-
-    Ok(canvas)
+    composite(
+        image1,
+        image2,
+        loop_start_y..loop_end_y,
+        loop_start_x..loop_end_x,
+        (offset_x, offset_y),
+        opacity,
+        ch_alpha,
+    )
 }
 
 pub fn overlay(
@@ -166,38 +98,15 @@ pub fn overlay(
     offset_y: i32,
     opacity: f32,
 ) -> RasterResult<Image> {
-    let mut canvas = image1.clone();
-
-    for y in loop_start_y..loop_end_y {
-        for x in loop_start_x..loop_end_x {
-            let canvas_x = x + offset_x;
-            let canvas_y = y + offset_y;
-            let rgba1 = image1.get_pixel(canvas_x, canvas_y)?;
-            let a1 = rgba1.a as f32 / 255.0; // convert to 0.0 - 1.0
-            let r1 = rgba1.r as f32 * a1;
-            let g1 = rgba1.g as f32 * a1;
-            let b1 = rgba1.b as f32 * a1;
-
-            let rgba2 = image2.get_pixel(x, y)?;
-            let a2 = rgba2.a as f32 / 255.0 * opacity; // convert to 0.0 - 1.0
-            let r2 = rgba2.r as f32;
-            let g2 = rgba2.g as f32;
-            let b2 = rgba2.b as f32;
-
-            let r3 = ch_alpha_f(r1, r2, BlendFunction::Overlay, a2);
-            let g3 = ch_alpha_f(g1, g2, BlendFunction::Overlay, a2);
-            let b3 = ch_alpha_f(b1, b2, BlendFunction::Overlay, a2);
-            let a3 = 255;
-
-            canvas.set_pixel(
-                canvas_x,
-                canvas_y,
-                &Color::rgba(r3 as u8, g3 as u8, b3 as u8, a3 as u8),
-            )?;
-        }
-    }
-
-    Ok(canvas)
+    composite(
+        image1,
+        image2,
+        loop_start_y..loop_end_y,
+        loop_start_x..loop_end_x,
+        (offset_x, offset_y),
+        opacity,
+        |base, top, opacity| ch_alpha_f(base, top, BlendFunction::Overlay, opacity),
+    )
 }
 
 pub fn screen(
@@ -211,41 +120,69 @@ pub fn screen(
     offset_y: i32,
     opacity: f32,
 ) -> RasterResult<Image> {
-    let mut canvas = image1.clone();
-
-    for y in loop_start_y..loop_end_y {
-        for x in loop_start_x..loop_end_x {
-            let canvas_x = x + offset_x;
-            let canvas_y = y + offset_y;
-            let rgba1 = image1.get_pixel(canvas_x, canvas_y)?;
-            let a1 = rgba1.a as f32 / 255.0; // convert to 0.0 - 1.0
-            let r1 = rgba1.r as f32 * a1;
-            let g1 = rgba1.g as f32 * a1;
-            let b1 = rgba1.b as f32 * a1;
+    composite(
+        image1,
+        image2,
+        loop_start_y..loop_end_y,
+        loop_start_x..loop_end_x,
+        (offset_x, offset_y),
+        opacity,
+        |base, top, opacity| ch_alpha_f(base, top, BlendFunction::Screen, opacity),
+    )
+}
 
-            let rgba2 = image2.get_pixel(x, y)?;
-            let a2 = rgba2.a as f32 / 255.0 * opacity; // convert to 0.0 - 1.0
-            let r2 = rgba2.r as f32;
-            let g2 = rgba2.g as f32;
-            let b2 = rgba2.b as f32;
+// PRIVATE FNs
 
-            let r3 = ch_alpha_f(r1, r2, BlendFunction::Screen, a2);
-            let g3 = ch_alpha_f(g1, g2, BlendFunction::Screen, a2);
-            let b3 = ch_alpha_f(b1, b2, BlendFunction::Screen, a2);
-            let a3 = 255;
+// Blend the area of image2 within the loop ranges onto a copy of image1, moved by the offset.
+// Each channel is blended by a function of the base and top channel and the opacity. The rows of
+// the canvas are blended on their own, so they can be shared between threads.
+fn composite<F>(
+    image1: &Image,
+    image2: &Image,
+    loop_y: Range<i32>,
+    loop_x: Range<i32>,
+    (offset_x, offset_y): (i32, i32),
+    opacity: f32,
+    f: F,
+) -> RasterResult<Image>
+where
+    F: Fn(f32, f32, f32) -> f32 + Send + Sync,
+{
+    let mut canvas = image1.clone();
+    let top_rows: Vec<&[u8]> = image2.rows().collect();
+    if top_rows.len() < loop_y.end.max(0) as usize {
+        return Err(RasterError::PixelOutOfBounds(0, top_rows.len() as i32));
+    }
+    if canvas.rows().len() < (loop_y.end + offset_y).max(0) as usize {
+        return Err(RasterError::PixelOutOfBounds(0, canvas.rows().len() as i32));
+    }
 
-            canvas.set_pixel(
-                canvas_x,
-                canvas_y,
-                &Color::rgba(r3 as u8, g3 as u8, b3 as u8, a3 as u8),
-            )?;
+    let row_len = canvas.width.max(0) as usize * 4;
+    parallel::for_each_row(&mut canvas.bytes, row_len, |canvas_y, row| {
+        let y = canvas_y as i32 - offset_y;
+        if !loop_y.contains(&y) {
+            return;
         }
-    }
+        let top_row = top_rows[y as usize];
+        for x in loop_x.clone() {
+            let start = (x + offset_x) as usize * 4;
+            let rgba1 = &mut row[start..start + 4];
+            let a1 = rgba1[3] as f32 / 255.0; // convert to 0.0 - 1.0
+
+            let rgba2 = &top_row[x as usize * 4..x as usize * 4 + 4];
+            let a2 = rgba2[3] as f32 / 255.0 * opacity; // convert to 0.0 - 1.0
+
+            // red, green and blue, with the base premultiplied by its alpha
+            for (c1, &c2) in rgba1[..3].iter_mut().zip(rgba2) {
+                *c1 = f(*c1 as f32 * a1, c2 as f32, a2) as u8;
+            }
+            rgba1[3] = 255;
+        }
+    });
 
     Ok(canvas)
 }
 
-// PRIVATE FNs
 // base, top 0.0 - 255.0
 // opacity 0.0 - 1.0
 
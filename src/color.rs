@@ -181,42 +181,7 @@ impl<'a> Color {
     /// ```
     // Using f32 for s,v for accuracy when converting from RGB-HSV and vice-versa.
     pub fn to_hsv(r: u8, g: u8, b: u8) -> (u16, f32, f32) {
-        let r = r as f32 / 255.0;
-        let g = g as f32 / 255.0;
-        let b = b as f32 / 255.0;
-
-        let min = rgb_min(r, g, b);
-        let max = rgb_max(r, g, b);
-
-        let chroma = max - min;
-
-        let h = {
-            let mut h = 0.0;
-
-            if chroma != 0.0 {
-                if (max - r).abs() < std::f32::EPSILON {
-                    h = 60.0 * ((g - b) / chroma);
-                    if h < 0.0 {
-                        h += 360.0;
-                    }
-                } else if (max - g).abs() < std::f32::EPSILON {
-                    h = 60.0 * (((b - r) / chroma) + 2.0);
-                } else if (max - b).abs() < std::f32::EPSILON {
-                    h = 60.0 * (((r - g) / chroma) + 4.0);
-                }
-            }
-
-            if h > 359.0 {
-                h = 360.0 - h; // Invert if > 0 to 359
-            }
-
-            h
-        };
-
-        let v = max;
-        let s = if v != 0.0 { chroma / v } else { 0.0 };
-
-        (h.round() as u16, s * 100.0, v * 100.0)
+        hsv_from_unit(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0)
     }
 
     /// Convert HSV/HSB (Hue, Saturation, Brightness) to RGB.
@@ -235,50 +200,7 @@ impl<'a> Color {
     /// ```
     // Using f32 for s,v for accuracy when converting from RGB-HSV and vice-versa.
     pub fn to_rgb(h: u16, s: f32, v: f32) -> (u8, u8, u8) {
-        let h = h as f32 / 60.0;
-        let s = s as f32 / 100.0; // Convert to 0.0 - 1.0
-        let v = v as f32 / 100.0;
-
-        let chroma = v * s;
-
-        let x = chroma * (1.0 - ((h % 2.0) - 1.0).abs());
-
-        let mut r = 0.0;
-        let mut g = 0.0;
-        let mut b = 0.0;
-
-        if h >= 0.0 {
-            if h < 1.0 {
-                r = chroma;
-                g = x;
-                b = 0.0;
-            } else if h < 2.0 {
-                r = x;
-                g = chroma;
-                b = 0.0;
-            } else if h < 3.0 {
-                r = 0.0;
-                g = chroma;
-                b = x;
-            } else if h < 4.0 {
-                r = 0.0;
-                g = x;
-                b = chroma;
-            } else if h < 5.0 {
-                r = x;
-                g = 0.0;
-                b = chroma;
-            } else if h < 6.0 {
-                r = chroma;
-                g = 0.0;
-                b = x;
-            }
-        }
-
-        let m = v - chroma;
-        r += m;
-        g += m;
-        b += m;
+        let (r, g, b) = unit_from_hsv(h, s, v);
         (
             (r * 255.0).round() as u8,
             (g * 255.0).round() as u8,
@@ -299,6 +221,93 @@ impl<'a> Color {
 
 // Private functions
 
+// Convert red, green and blue from 0.0 - 1.0 to hue in degrees and saturation and value in
+// percent.
+pub fn hsv_from_unit(r: f32, g: f32, b: f32) -> (u16, f32, f32) {
+    let min = rgb_min(r, g, b);
+    let max = rgb_max(r, g, b);
+
+    let chroma = max - min;
+
+    let h = {
+        let mut h = 0.0;
+
+        if chroma != 0.0 {
+            if (max - r).abs() < std::f32::EPSILON {
+                h = 60.0 * ((g - b) / chroma);
+                if h < 0.0 {
+                    h += 360.0;
+                }
+            } else if (max - g).abs() < std::f32::EPSILON {
+                h = 60.0 * (((b - r) / chroma) + 2.0);
+            } else if (max - b).abs() < std::f32::EPSILON {
+                h = 60.0 * (((r - g) / chroma) + 4.0);
+            }
+        }
+
+        if h > 359.0 {
+            h = 360.0 - h; // Invert if > 0 to 359
+        }
+
+        h
+    };
+
+    let v = max;
+    let s = if v != 0.0 { chroma / v } else { 0.0 };
+
+    (h.round() as u16, s * 100.0, v * 100.0)
+}
+
+// Convert hue in degrees and saturation and value in percent to red, green and blue from 0.0 -
+// 1.0.
+pub fn unit_from_hsv(h: u16, s: f32, v: f32) -> (f32, f32, f32) {
+    let h = h as f32 / 60.0;
+    let s = s as f32 / 100.0; // Convert to 0.0 - 1.0
+    let v = v as f32 / 100.0;
+
+    let chroma = v * s;
+
+    let x = chroma * (1.0 - ((h % 2.0) - 1.0).abs());
+
+    let mut r = 0.0;
+    let mut g = 0.0;
+    let mut b = 0.0;
+
+    if h >= 0.0 {
+        if h < 1.0 {
+            r = chroma;
+            g = x;
+            b = 0.0;
+        } else if h < 2.0 {
+            r = x;
+            g = chroma;
+            b = 0.0;
+        } else if h < 3.0 {
+            r = 0.0;
+            g = chroma;
+            b = x;
+        } else if h < 4.0 {
+            r = 0.0;
+            g = x;
+            b = chroma;
+        } else if h < 5.0 {
+            r = x;
+            g = 0.0;
+            b = chroma;
+        } else if h < 6.0 {
+            r = chroma;
+            g = 0.0;
+            b = x;
+        }
+    }
+
+    let m = v - chroma;
+    r += m;
+    g += m;
+    b += m;
+    (r, g, b)
+}
+
 // Convert a hex string to decimal. Eg. "00" -> 0. "FF" -> 255.
 fn _hex_dec(hex_string: &str) -> RasterResult<u8> {
     u8::from_str_radix(hex_string, 16)
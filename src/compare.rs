@@ -5,14 +5,16 @@
 // from external crate
 
 // from local crate
-use error::RasterResult;
-use Image;
 use editor::{self, ResizeMode};
+use error::RasterResult;
+use pixel::{self, GenericImageView, Pixel};
 
 /// Compare two images and returns a hamming distance. A value of 0 indicates a likely similar
 /// picture. A value between 1 and 10 is potentially a variation. A value greater than 10 is
 /// likely a different image.
 ///
+/// Any `GenericImageView` can be compared, like a view of part of an image or an `ImageBuffer`.
+///
 /// # Examples
 /// ```
 /// use raster::compare;
@@ -23,7 +25,11 @@ use editor::{self, ResizeMode};
 /// let hamming_distance = compare::similar(&image1, &image2).unwrap();
 /// println!("{}", hamming_distance);
 /// ```
-pub fn similar(image1: &Image, image2: &Image) -> RasterResult<u8> {
+pub fn similar<A, B>(image1: &A, image2: &B) -> RasterResult<u8>
+where
+    A: GenericImageView,
+    B: GenericImageView,
+{
     let bin1 = diff_hash(image1)?;
     let bin2 = diff_hash(image2)?;
     let mut distance = 0;
@@ -40,6 +46,9 @@ pub fn similar(image1: &Image, image2: &Image) -> RasterResult<u8> {
 /// loop through each pixels. If one of the pixel don't match, it will return false. The pixels
 /// are compared using their RGB (Red, Green, Blue) values.
 ///
+/// Any `GenericImageView` can be compared. Pixels of different types are compared by their
+/// values from 0.0 to 1.0.
+///
 /// # Examples
 /// ```
 /// use raster::compare;
@@ -50,22 +59,26 @@ pub fn similar(image1: &Image, image2: &Image) -> RasterResult<u8> {
 /// let equal = compare::equal(&image1, &image2).unwrap();
 /// assert_eq!(true, equal);
 /// ```
-pub fn equal(image1: &Image, image2: &Image) -> RasterResult<bool> {
+pub fn equal<A, B>(image1: &A, image2: &B) -> RasterResult<bool>
+where
+    A: GenericImageView,
+    B: GenericImageView,
+{
     // Check if image dimensions are equal
-    if image1.width != image2.width || image1.height != image2.height {
+    if image1.width() != image2.width() || image1.height() != image2.height() {
         Ok(false)
     } else {
         // Loop using image1
-        for y in 0..image1.height {
-            for x in 0..image1.width {
+        for y in 0..image1.height() {
+            for x in 0..image1.width() {
                 // Get image1 pixel
-                let pixel1 = image1.get_pixel(x, y)?;
+                let pixel1 = image1.get_pixel(x, y)?.to_rgba();
 
                 // Get image2 pixel
-                let pixel2 = image2.get_pixel(x, y)?;
+                let pixel2 = image2.get_pixel(x, y)?.to_rgba();
 
                 // Compare pixel value
-                if pixel1.r != pixel2.r || pixel1.g != pixel2.g || pixel1.b != pixel2.b {
+                if pixel1[..3] != pixel2[..3] {
                     return Ok(false);
                 }
             }
@@ -91,11 +104,11 @@ pub fn equal(image1: &Image, image2: &Image) -> RasterResult<bool> {
 // http://www.hackerfactor.com/blog/index.php?/archives/529-Kind-of-Like-That.html
 //
 //
-fn diff_hash(image: &Image) -> RasterResult<Vec<u8>> {
+fn diff_hash<I: GenericImageView>(image: &I) -> RasterResult<Vec<u8>> {
     let width = 9;
     let height = 8;
 
-    let mut image = image.clone(); // copy it since resize is desctructive
+    let mut image = pixel::copy_image(image)?; // copy it since resize is desctructive
     editor::resize(&mut image, width, height, ResizeMode::Exact)?; // Resize to exactly 9x8
 
     // Build hash
@@ -1,98 +1,560 @@
 //!  A module for encoding/decoding.
 
+mod bmp;
+mod farbfeld;
+mod hdr;
+mod ico;
+mod jpeg;
+mod png;
+mod pnm;
+mod qoi;
+mod raw;
+mod tga;
+mod tiff;
+mod vp8l;
+mod webp;
+
+pub use self::bmp::{decode_bmp, encode_bmp, probe_bmp};
+pub use self::farbfeld::{
+    decode_farbfeld, decode_farbfeld_rows, encode_farbfeld, encode_farbfeld_rows, probe_farbfeld,
+};
+pub use self::hdr::{decode_hdr, encode_hdr, probe_hdr};
+pub use self::ico::{decode_ico, decode_ico_all, encode_ico, probe_ico};
+pub use self::jpeg::{decode_jpeg, encode_jpeg, probe_jpeg};
+pub use self::png::{
+    decode_png, decode_png_animation, decode_png_rows, encode_png, encode_png_animation,
+    encode_png_rows, probe_png,
+};
+pub use self::pnm::{decode_pnm, encode_pnm, probe_pnm};
+pub use self::qoi::{decode_qoi, encode_qoi, probe_qoi};
+pub use self::raw::{decode_raw, decode_raw_rows, encode_raw, encode_raw_rows};
+pub use self::tga::{decode_tga, encode_tga, probe_tga};
+pub use self::tiff::{decode_tiff, decode_tiff_all, encode_tiff, probe_tiff};
+pub use self::webp::{
+    decode_webp, decode_webp_animation, encode_webp, encode_webp_animation, probe_webp,
+};
+
 // from rust
-use std::path::Path;
-use std::fs::File;
-use std::io::BufWriter;
+use std::cmp;
+use std::io::{self, Read, Write};
 
 // from external crate
 use gif;
-use png;
 
 // from local crate
+use animation::{Animation, DisposalMethod, Frame, LoopCount};
 use error::{RasterError, RasterResult};
+use image::{ColorType, ImageInfo};
+use options::{GifOptions, Limits};
+use quantize;
 use Image;
 use ImageFormat;
 
-// Decode GIF
-pub fn decode_gif(image_file: &File) -> RasterResult<Image> {
-    let mut decoder = gif::Decoder::new(image_file);
+// Signatures found at the start of each supported format.
+const SIGNATURES: &[(&[u8], ImageFormat)] = &[
+    (b"GIF87a", ImageFormat::Gif),
+    (b"GIF89a", ImageFormat::Gif),
+    (&[0xFF, 0xD8, 0xFF], ImageFormat::Jpeg),
+    (
+        &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A],
+        ImageFormat::Png,
+    ),
+    (b"BM", ImageFormat::Bmp),
+    (b"qoif", ImageFormat::Qoi),
+    (b"farbfeld", ImageFormat::Farbfeld),
+    (b"II*\0", ImageFormat::Tiff),
+    (b"MM\0*", ImageFormat::Tiff),
+    (b"#?RADIANCE", ImageFormat::Hdr),
+    (b"#?RGBE", ImageFormat::Hdr),
+];
 
-    // Configure the decoder such that it will expand the image to RGBA.
-    gif::SetParameter::set(&mut decoder, gif::ColorOutput::RGBA);
+// Number of bytes needed to tell every format apart, the RIFF header of WebP being the longest.
+const SIGNATURE_LEN: usize = 12;
 
-    // Read the file header
-    let mut reader = decoder.read_info()?;
+// Guess the image format from the first few bytes of an image.
+pub fn guess_format(buffer: &[u8]) -> Option<ImageFormat> {
+    SIGNATURES
+        .iter()
+        .find(|&&(signature, _)| buffer.starts_with(signature))
+        .map(|&(_, format)| format)
+        .or_else(|| netpbm_format(buffer))
+        .or_else(|| icon_format(buffer))
+        .or_else(|| webp_format(buffer))
+}
 
-    // Read frame 1.
-    // TODO: Work on all frames
-    if let Some(_) = reader.next_frame_info()? {
-        let mut bytes = vec![0; reader.buffer_size()];
-        reader.read_into_buffer(&mut bytes)?;
-        Ok(Image {
-            width: reader.width() as i32,
-            height: reader.height() as i32,
-            bytes: bytes,
-        })
-    } else {
+// A reader that gives the signature read by read_signature before the rest of the data.
+pub type SignatureReader<R> = io::Chain<io::Cursor<Vec<u8>>, R>;
+
+// Read the bytes guess_format looks at, which is less only if the data ends first, and guess the
+// format from them. The reader that is returned gives those bytes again before the rest.
+pub fn read_signature<R: Read>(
+    mut reader: R,
+) -> io::Result<(Option<ImageFormat>, SignatureReader<R>)> {
+    let mut signature = vec![0; SIGNATURE_LEN];
+    let mut len = 0;
+    while len < signature.len() {
+        match reader.read(&mut signature[len..]) {
+            Ok(0) => break,
+            Ok(read) => len += read,
+            Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err),
+        }
+    }
+    signature.truncate(len);
+    let format = guess_format(&signature);
+    Ok((format, io::Cursor::new(signature).chain(reader)))
+}
+
+// Decode GIF
+pub fn decode_gif<R: Read>(reader: R, limits: &Limits) -> RasterResult<Image> {
+    let mut frames = read_gif_frames(reader, Some(1), limits)?;
+    if frames.is_empty() {
         Err(RasterError::Decode(
             ImageFormat::Gif,
             "Error getting frame info".to_string(),
         ))
+    } else {
+        Ok(frames.remove(0).image)
     }
 }
 
+// Decode all frames of a GIF
+pub fn decode_gif_animation<R: Read>(reader: R, limits: &Limits) -> RasterResult<Animation> {
+    // The loop count lives in an extension the gif crate does not expose, so keep the bytes
+    // around to look for it.
+    let buffer = read_limited(reader, limits)?;
+
+    let frames = read_gif_frames(&buffer[..], None, limits)?;
+    let (width, height) = match frames.first() {
+        Some(frame) => (frame.image.width, frame.image.height),
+        None => {
+            return Err(RasterError::Decode(
+                ImageFormat::Gif,
+                "Error getting frame info".to_string(),
+            ))
+        }
+    };
+    Ok(Animation {
+        width,
+        height,
+        frames,
+        loop_count: gif_loop_count(&buffer),
+    })
+}
+
+// Read the size and frame count of a GIF without decoding the image data
+pub fn probe_gif<R: Read>(mut reader: R) -> RasterResult<ImageInfo> {
+    // Header and logical screen descriptor
+    let mut header = [0; 13];
+    if reader.read_exact(&mut header).is_err() || guess_format(&header) != Some(ImageFormat::Gif) {
+        return Err(RasterError::Decode(
+            ImageFormat::Gif,
+            "Invalid header".to_string(),
+        ));
+    }
+
+    // The frames are counted by skipping over the blocks that follow. Stop counting at the first
+    // problem, the header is all that is needed.
+    let flags = header[10];
+    let mut frames = 0;
+    let _ = count_gif_frames(&mut reader, flags, &mut frames);
+    Ok(ImageInfo {
+        width: (header[6] as u16 | (header[7] as u16) << 8) as i32,
+        height: (header[8] as u16 | (header[9] as u16) << 8) as i32,
+        format: ImageFormat::Gif,
+        color_type: ColorType::Indexed,
+        // Size of the global color table, if there is one
+        bit_depth: if flags & 0x80 != 0 {
+            (flags & 0x07) + 1
+        } else {
+            8
+        },
+        frames,
+    })
+}
+
 // Encode GIF
-pub fn encode_gif(image: &Image, path: &Path) -> RasterResult<()> {
-    // Open the file with basic error check
-    let file = File::create(path)?;
-    let writer = BufWriter::new(file);
-    let frame = gif::Frame::from_rgba(
-        image.width as u16,
-        image.height as u16,
-        &mut image.bytes.clone(),
-    ); // TODO: Perf issue?
+pub fn encode_gif<W: Write>(image: &Image, writer: W, options: &GifOptions) -> RasterResult<()> {
+    let frame = gif_frame(image, options)?;
     let mut encoder = gif::Encoder::new(writer, frame.width, frame.height, &[])?;
     encoder.write_frame(&frame).map_err(RasterError::Io)?;
     Ok(())
 }
 
-// Decode PNG
-pub fn decode_png(image_file: &File) -> RasterResult<Image> {
-    let decoder = png::Decoder::new(image_file);
-    let (info, mut reader) = decoder.read_info()?;
-    let mut bytes = vec![0; info.buffer_size()];
+// Encode all frames of an animation as GIF
+pub fn encode_gif_animation<W: Write>(
+    animation: &Animation,
+    writer: W,
+    options: &GifOptions,
+) -> RasterResult<()> {
+    let mut encoder =
+        gif::Encoder::new(writer, animation.width as u16, animation.height as u16, &[])?;
+    let repeat = match animation.loop_count {
+        LoopCount::Infinite => gif::Repeat::Infinite,
+        // The NETSCAPE extension counts repeats after the first play
+        LoopCount::Finite(plays) => gif::Repeat::Finite(plays.saturating_sub(1)),
+    };
+    gif::SetParameter::set(&mut encoder, repeat)?;
+
+    for frame in &animation.frames {
+        let image = &frame.image;
+        if image.width != animation.width || image.height != animation.height {
+            return Err(RasterError::Encode(
+                ImageFormat::Gif,
+                "Frame size does not match animation size".to_string(),
+            ));
+        }
+        let mut gif_frame = gif_frame(image, options)?;
+        // GIF delays are in hundredths of a second
+        gif_frame.delay = cmp::min(frame.delay / 10, u16::MAX as u32) as u16;
+        gif_frame.dispose = match frame.disposal {
+            DisposalMethod::None => gif::DisposalMethod::Keep,
+            DisposalMethod::Background => gif::DisposalMethod::Background,
+            DisposalMethod::Previous => gif::DisposalMethod::Previous,
+        };
+        encoder.write_frame(&gif_frame).map_err(RasterError::Io)?;
+    }
+    Ok(())
+}
+
+// Private functions
+
+// Netpbm files start with P and a digit followed by whitespace.
+fn netpbm_format(buffer: &[u8]) -> Option<ImageFormat> {
+    match buffer.get(0..3) {
+        Some(&[b'P', digit, space]) if space.is_ascii_whitespace() => match digit {
+            b'1' | b'4' => Some(ImageFormat::Pbm),
+            b'2' | b'5' => Some(ImageFormat::Pgm),
+            b'3' | b'6' => Some(ImageFormat::Ppm),
+            b'7' => Some(ImageFormat::Pam),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// ICO and CUR files start with a reserved zero, their type and a count of at least one image. The
+// count keeps the short signature from matching uncompressed TGA headers.
+fn icon_format(buffer: &[u8]) -> Option<ImageFormat> {
+    match buffer.get(0..6) {
+        Some(&[0, 0, kind, 0, lo, hi]) if lo != 0 || hi != 0 => match kind {
+            1 => Some(ImageFormat::Ico),
+            2 => Some(ImageFormat::Cur),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// WebP files are RIFF files with a WEBP form type after the file size.
+fn webp_format(buffer: &[u8]) -> Option<ImageFormat> {
+    if buffer.starts_with(b"RIFF") && buffer.get(8..12) == Some(&b"WEBP"[..]) {
+        Some(ImageFormat::WebP)
+    } else {
+        None
+    }
+}
+
+// Read all the data of a reader into memory, failing once it goes over the memory limit.
+fn read_limited<R: Read>(reader: R, limits: &Limits) -> RasterResult<Vec<u8>> {
+    let mut buffer = Vec::new();
+    let max = limits.max_alloc.map_or(u64::MAX, |max| max + 1);
+    reader.take(max).read_to_end(&mut buffer)?;
+    limits.check_alloc(buffer.len() as u64)?;
+    Ok(buffer)
+}
+
+// Read past bytes that are not needed, without keeping them in memory.
+fn skip_bytes<R: Read>(reader: &mut R, len: u64) -> RasterResult<()> {
+    if io::copy(&mut reader.take(len), &mut io::sink())? < len {
+        return Err(RasterError::Io(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "failed to skip bytes",
+        )));
+    }
+    Ok(())
+}
+
+// Check the size of an image that is decoded a row at a time. The memory limit applies to the
+// buffers needed for a row, not to the whole image.
+fn check_row_limits(limits: &Limits, width: u32, height: u32, row_bytes: u64) -> RasterResult<()> {
+    let size_limits = Limits {
+        max_alloc: None,
+        ..limits.clone()
+    };
+    size_limits.check_dimensions(width, height)?;
+    limits.check_alloc(row_bytes)
+}
 
-    reader.next_frame(&mut bytes)?;
+// Draw a pixel over another, mixing the colors by the alpha of both.
+fn blend_pixel(dest: &mut [u8], src: &[u8]) {
+    let src_alpha = src[3] as u32;
+    match src_alpha {
+        0 => {}
+        255 => dest.copy_from_slice(src),
+        _ => {
+            let dest_alpha = dest[3] as u32 * (255 - src_alpha) / 255;
+            let alpha = src_alpha + dest_alpha;
+            for c in 0..3 {
+                let color = src[c] as u32 * src_alpha + dest[c] as u32 * dest_alpha;
+                dest[c] = ((color + alpha / 2) / alpha) as u8;
+            }
+            dest[3] = alpha as u8;
+        }
+    }
+}
 
-    if info.color_type == png::ColorType::RGB {
-        // Applies only to RGB
+// Reduce an image to a palette of at most options.palette_size colors. Pixels that are not fully
+// transparent are made opaque.
+fn gif_frame(image: &Image, options: &GifOptions) -> RasterResult<gif::Frame<'static>> {
+    if options.palette_size < 2 || options.palette_size > 256 {
+        return Err(RasterError::InvalidPaletteSize(
+            options.palette_size as usize,
+        ));
+    }
 
-        let mut insert_count = 0;
-        let len = (info.width * info.height) as usize;
-        for i in 0..len {
-            // TODO: This is slow!
-            let insert_pos = 3 * (i + 1) + insert_count;
-            bytes.insert(insert_pos, 255);
-            insert_count += 1;
+    let mut opaque = image.clone();
+    for pixel in opaque.bytes.chunks_mut(4) {
+        if pixel[3] != 0 {
+            pixel[3] = 255;
         }
-    } //  TODO other ::ColorType
-    Ok(Image {
-        width: info.width as i32,
-        height: info.height as i32,
-        bytes: bytes,
-    })
+    }
+    let indexed = quantize::quantize(
+        &opaque,
+        options.palette_size as usize,
+        options.method,
+        options.dithering,
+    )?;
+
+    let mut palette = Vec::with_capacity(indexed.palette.len() * 3);
+    for color in &indexed.palette {
+        palette.extend_from_slice(&[color.r, color.g, color.b]);
+    }
+    let transparent = indexed.palette.iter().position(|color| color.a == 0);
+
+    Ok(gif::Frame::from_palette_pixels(
+        image.width as u16,
+        image.height as u16,
+        &indexed.indices,
+        &palette,
+        transparent.map(|index| index as u8),
+    ))
 }
 
-// Encode PNG
-pub fn encode_png(image: &Image, path: &Path) -> RasterResult<()> {
-    // Open the file with basic error check
-    let file = File::create(path)?;
-    let ref mut w = BufWriter::new(file);
+// Read up to max_frames GIF frames, compositing each one onto the logical screen.
+//
+// Every frame is checked against the limits before it is allocated. The memory limit applies to all
+// frames together.
+fn read_gif_frames<R: Read>(
+    reader: R,
+    max_frames: Option<usize>,
+    limits: &Limits,
+) -> RasterResult<Vec<Frame>> {
+    let mut decoder = gif::Decoder::new(reader);
+
+    // Configure the decoder such that it will expand the image to RGBA.
+    gif::SetParameter::set(&mut decoder, gif::ColorOutput::RGBA);
+
+    // Read the file header
+    let mut reader = decoder.read_info()?;
+    let width = reader.width() as i32;
+    let height = reader.height() as i32;
+    limits.check_dimensions(width as u32, height as u32)?;
+    let canvas_len = width as u64 * height as u64 * 4;
+    limits.check_alloc(canvas_len)?;
+    let len = (width as usize)
+        .checked_mul(height as usize)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .ok_or_else(|| RasterError::LimitsExceeded("Image too large".to_string()))?;
+
+    // Start with a transparent canvas
+    let mut canvas = Image {
+        width,
+        height,
+        bytes: vec![0; len],
+    };
+    let mut frames = Vec::new();
+    loop {
+        if max_frames == Some(frames.len()) {
+            break;
+        }
+        let (left, top, w, h, delay, dispose) = match reader.next_frame_info()? {
+            Some(info) => (
+                info.left as i32,
+                info.top as i32,
+                info.width as i32,
+                info.height as i32,
+                info.delay,
+                info.dispose,
+            ),
+            None => break,
+        };
+        limits.check_frames(frames.len() + 1)?;
+        limits.check_dimensions(w as u32, h as u32)?;
+        // The canvas, a snapshot for every frame so far and this frame's pixels
+        limits.check_alloc(canvas_len * (frames.len() as u64 + 2) + w as u64 * h as u64 * 4)?;
+        let mut bytes = vec![0; reader.buffer_size()];
+        reader.read_into_buffer(&mut bytes)?;
+
+        let previous = if dispose == gif::DisposalMethod::Previous {
+            Some(canvas.bytes.clone())
+        } else {
+            None
+        };
+
+        // Draw the opaque pixels of the frame that fall within the canvas
+        for y in cmp::max(0, -top)..cmp::min(h, height - top) {
+            for x in cmp::max(0, -left)..cmp::min(w, width - left) {
+                let src = (y as usize * w as usize + x as usize) * 4;
+                if bytes[src + 3] != 0 {
+                    let dest = ((top + y) as usize * width as usize + (left + x) as usize) * 4;
+                    canvas.bytes[dest..dest + 4].copy_from_slice(&bytes[src..src + 4]);
+                }
+            }
+        }
+
+        frames.push(Frame {
+            image: canvas.clone(),
+            delay: delay as u32 * 10,
+            disposal: match dispose {
+                gif::DisposalMethod::Background => DisposalMethod::Background,
+                gif::DisposalMethod::Previous => DisposalMethod::Previous,
+                _ => DisposalMethod::None,
+            },
+        });
 
-    let mut encoder = png::Encoder::new(w, image.width as u32, image.height as u32);
-    png::HasParameters::set(&mut encoder, png::ColorType::RGBA);
-    png::HasParameters::set(&mut encoder, png::BitDepth::Eight);
-    let mut writer = encoder.write_header()?;
-    Ok(writer.write_image_data(&image.bytes)?)
+        // Prepare the canvas for the next frame
+        match dispose {
+            gif::DisposalMethod::Background => {
+                for y in cmp::max(0, top)..cmp::min(height, top + h) {
+                    for x in cmp::max(0, left)..cmp::min(width, left + w) {
+                        let dest = (y as usize * width as usize + x as usize) * 4;
+                        canvas.bytes[dest..dest + 4].copy_from_slice(&[0, 0, 0, 0]);
+                    }
+                }
+            }
+            gif::DisposalMethod::Previous => {
+                if let Some(previous) = previous {
+                    canvas.bytes = previous;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(frames)
+}
+
+// Count the frames of a GIF read after its logical screen descriptor, skipping the color tables,
+// extensions and image data without keeping them.
+fn count_gif_frames<R: Read>(reader: &mut R, flags: u8, frames: &mut usize) -> RasterResult<()> {
+    if flags & 0x80 != 0 {
+        skip_bytes(reader, 3 * (2 << (flags & 0x07)))?;
+    }
+
+    let mut introducer = [0];
+    loop {
+        reader.read_exact(&mut introducer)?;
+        match introducer[0] {
+            // Extension label and data
+            0x21 => {
+                skip_bytes(reader, 1)?;
+                skip_gif_sub_blocks_from(reader)?;
+            }
+            // Image descriptor
+            0x2C => {
+                let mut descriptor = [0; 9];
+                reader.read_exact(&mut descriptor)?;
+                *frames += 1;
+                let flags = descriptor[8];
+                if flags & 0x80 != 0 {
+                    skip_bytes(reader, 3 * (2 << (flags & 0x07)))?;
+                }
+                // Skip the LZW minimum code size and the image data
+                skip_bytes(reader, 1)?;
+                skip_gif_sub_blocks_from(reader)?;
+            }
+            // Trailer or garbage
+            _ => return Ok(()),
+        }
+    }
+}
+
+// Skip a sequence of GIF data sub-blocks in a reader, up to and including the block terminator.
+fn skip_gif_sub_blocks_from<R: Read>(reader: &mut R) -> RasterResult<()> {
+    let mut len = [0];
+    loop {
+        reader.read_exact(&mut len)?;
+        if len[0] == 0 {
+            return Ok(());
+        }
+        skip_bytes(reader, len[0] as u64)?;
+    }
+}
+
+// Find the loop count in the NETSCAPE2.0 application extension of a GIF, without decoding the
+// image data. GIFs without the extension are played once.
+fn gif_loop_count(buffer: &[u8]) -> LoopCount {
+    let mut loop_count = LoopCount::Finite(1);
+
+    // Skip the header, logical screen descriptor and global color table
+    let mut pos = 13;
+    if buffer.len() < pos {
+        return loop_count;
+    }
+    if buffer[10] & 0x80 != 0 {
+        pos += 3 * (2 << (buffer[10] & 0x07));
+    }
+
+    while pos < buffer.len() {
+        match buffer[pos] {
+            // Extension
+            0x21 => {
+                let label = buffer.get(pos + 1).cloned();
+                pos += 2;
+                let is_netscape = label == Some(0xFF)
+                    && buffer.get(pos..pos + 12) == Some(&b"\x0BNETSCAPE2.0"[..]);
+                if is_netscape {
+                    if let Some(data) = buffer.get(pos + 12..pos + 16) {
+                        if data[0] >= 3 && data[1] == 1 {
+                            loop_count = match data[2] as u16 | (data[3] as u16) << 8 {
+                                0 => LoopCount::Infinite,
+                                repeats => LoopCount::Finite(repeats.saturating_add(1)),
+                            };
+                        }
+                    }
+                }
+                pos = skip_gif_sub_blocks(buffer, pos);
+            }
+            // Image descriptor
+            0x2C => {
+                let flags = match buffer.get(pos + 9) {
+                    Some(flags) => *flags,
+                    None => break,
+                };
+                pos += 10;
+                if flags & 0x80 != 0 {
+                    pos += 3 * (2 << (flags & 0x07));
+                }
+                // Skip the LZW minimum code size and the image data
+                pos = skip_gif_sub_blocks(buffer, pos + 1);
+            }
+            // Trailer or garbage
+            _ => break,
+        }
+    }
+
+    loop_count
+}
+
+// Skip a sequence of GIF data sub-blocks and return the position after the block terminator.
+fn skip_gif_sub_blocks(buffer: &[u8], mut pos: usize) -> usize {
+    while let Some(&len) = buffer.get(pos) {
+        pos += 1;
+        if len == 0 {
+            break;
+        }
+        pos += len as usize;
+    }
+    pos
 }
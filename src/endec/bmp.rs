@@ -0,0 +1,598 @@
+//!  A module for encoding/decoding BMP.
+
+// from rust
+use std::cmp;
+use std::io::{Read, Write};
+
+// from external crate
+
+// from local crate
+use endec::read_limited;
+use error::{RasterError, RasterResult};
+use image::{ColorType, ImageInfo};
+use options::Limits;
+use Image;
+use ImageFormat;
+
+// Size of the file header that comes before the DIB header
+const FILE_HEADER_LEN: usize = 14;
+
+// Sizes of the DIB headers
+const CORE_HEADER_LEN: usize = 12;
+const INFO_HEADER_LEN: usize = 40;
+const V4_HEADER_LEN: usize = 108;
+const V5_HEADER_LEN: usize = 124;
+
+// Compression methods
+const BI_RGB: u32 = 0;
+const BI_RLE8: u32 = 1;
+const BI_RLE4: u32 = 2;
+const BI_BITFIELDS: u32 = 3;
+const BI_ALPHABITFIELDS: u32 = 6;
+
+// Resolution written to new files, 72 DPI in pixels per meter
+const PIXELS_PER_METER: u32 = 2835;
+
+// Decode BMP
+//
+// Palettes and bit fields are expanded to 8 bit RGBA. Pixels skipped by RLE deltas are left
+// transparent.
+pub fn decode_bmp<R: Read>(reader: R, limits: &Limits) -> RasterResult<Image> {
+    let buffer = read_limited(reader, limits)?;
+
+    let offset = read_file_header(&buffer)?;
+    let dib = &buffer[FILE_HEADER_LEN..];
+    let header = Header::parse(dib)?;
+    limits.check_dimensions(header.width, header.height)?;
+
+    let palette = header.palette(dib)?;
+    let pixels = buffer
+        .get(offset..)
+        .ok_or_else(|| decode_error("Pixel data missing"))?;
+    let bytes = match header.compression {
+        BI_RLE8 | BI_RLE4 => header.decode_rle(pixels, &palette)?,
+        _ => header.decode_rows(pixels, &palette)?,
+    };
+
+    Ok(Image {
+        width: header.width as i32,
+        height: header.height as i32,
+        bytes,
+    })
+}
+
+// Read the size and color type of a BMP from its headers
+pub fn probe_bmp<R: Read>(reader: R) -> RasterResult<ImageInfo> {
+    // The largest header followed by the largest set of bit masks
+    let mut buffer = Vec::new();
+    reader
+        .take((FILE_HEADER_LEN + V5_HEADER_LEN + 16) as u64)
+        .read_to_end(&mut buffer)?;
+    read_file_header(&buffer)?;
+    let header = Header::parse(&buffer[FILE_HEADER_LEN..])?;
+
+    let (color_type, bit_depth) = if header.bpp <= 8 {
+        (ColorType::Indexed, header.bpp as u8)
+    } else {
+        let masks = header.masks;
+        let bits = masks
+            .iter()
+            .map(|mask| mask.count_ones())
+            .max()
+            .unwrap_or(8);
+        if masks[3] != 0 {
+            (ColorType::Rgba, bits as u8)
+        } else {
+            (ColorType::Rgb, bits as u8)
+        }
+    };
+    Ok(ImageInfo {
+        width: header.width as i32,
+        height: header.height as i32,
+        format: ImageFormat::Bmp,
+        color_type,
+        bit_depth,
+        frames: 1,
+    })
+}
+
+// Encode BMP
+//
+// Opaque images are written as 24 bit with the widely supported BITMAPINFOHEADER. Images with
+// transparency are written as 32 bit with a BITMAPV4HEADER that describes the alpha channel.
+pub fn encode_bmp<W: Write>(image: &Image, mut writer: W) -> RasterResult<()> {
+    if image.width < 1 || image.height < 1 {
+        return Err(encode_error("Invalid dimensions"));
+    }
+    let width = image.width as usize;
+    let height = image.height as usize;
+    let alpha = image.bytes.chunks(4).any(|p| p[3] != 255);
+
+    let (bpp, header_len) = if alpha {
+        (32, V4_HEADER_LEN)
+    } else {
+        (24, INFO_HEADER_LEN)
+    };
+    let stride = row_stride(width, bpp);
+    let data_len = stride * height;
+    let offset = FILE_HEADER_LEN + header_len;
+    let file_len = offset + data_len;
+    if file_len > u32::MAX as usize {
+        return Err(encode_error("Image too large"));
+    }
+
+    let mut out = Vec::with_capacity(file_len);
+    out.extend_from_slice(b"BM");
+    out.extend_from_slice(&(file_len as u32).to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&(offset as u32).to_le_bytes());
+
+    out.extend_from_slice(&(header_len as u32).to_le_bytes());
+    out.extend_from_slice(&image.width.to_le_bytes());
+    // A positive height means the rows are stored bottom-up
+    out.extend_from_slice(&image.height.to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&(bpp as u16).to_le_bytes());
+    let compression = if alpha { BI_BITFIELDS } else { BI_RGB };
+    out.extend_from_slice(&compression.to_le_bytes());
+    out.extend_from_slice(&(data_len as u32).to_le_bytes());
+    out.extend_from_slice(&PIXELS_PER_METER.to_le_bytes());
+    out.extend_from_slice(&PIXELS_PER_METER.to_le_bytes());
+    // No palette
+    out.extend_from_slice(&[0; 8]);
+    if alpha {
+        for mask in &[0x00FF_0000u32, 0x0000_FF00, 0x0000_00FF, 0xFF00_0000] {
+            out.extend_from_slice(&mask.to_le_bytes());
+        }
+        // sRGB color space, which needs no end points or gamma
+        out.extend_from_slice(b"BGRs");
+        out.extend_from_slice(&[0; 48]);
+    }
+
+    for y in (0..height).rev() {
+        let row = &image.bytes[y * width * 4..(y + 1) * width * 4];
+        let start = out.len();
+        for p in row.chunks(4) {
+            out.extend_from_slice(&[p[2], p[1], p[0]]);
+            if alpha {
+                out.push(p[3]);
+            }
+        }
+        out.resize(start + stride, 0);
+    }
+
+    writer.write_all(&out)?;
+    Ok(())
+}
+
+// Decode the DIB of an ICO or CUR entry, which has no file header
+//
+// The height in the header covers the color pixels and the 1 bit AND mask stored after them. 32
+// bit pixels have an alpha channel. Other depths are cleared where the mask is set.
+pub fn decode_icon_dib(dib: &[u8], limits: &Limits) -> RasterResult<Image> {
+    let mut header = Header::parse(dib)?;
+    header.height /= 2;
+    if header.height == 0 {
+        return Err(decode_error("Invalid dimensions"));
+    }
+    if header.bpp == 32 && header.compression == BI_RGB {
+        header.masks[3] = 0xFF00_0000;
+    }
+    limits.check_dimensions(header.width, header.height)?;
+
+    let palette = header.palette(dib)?;
+    let offset = header.table_start() + palette.len() * header.entry_len();
+    let pixels = dib
+        .get(offset..)
+        .ok_or_else(|| decode_error("Pixel data missing"))?;
+    let mut bytes = header.decode_rows(pixels, &palette)?;
+
+    // Icons from before alpha support have 32 bit pixels with empty alpha
+    let alpha = header.masks[3] != 0 && bytes.chunks(4).any(|p| p[3] != 0);
+    if !alpha {
+        let width = header.width as usize;
+        let mask_stride = row_stride(width, 1);
+        let mask_start = row_stride(width, header.bpp as usize) * header.height as usize;
+        for n in 0..header.height as usize {
+            let y = header.row_index(n);
+            for x in 0..width {
+                let pixel = &mut bytes[(y * width + x) * 4..(y * width + x) * 4 + 4];
+                match pixels.get(mask_start + n * mask_stride..) {
+                    Some(row) if row.len() > x / 8 && read_index(row, x, 1) == 1 => {
+                        pixel.copy_from_slice(&[0, 0, 0, 0])
+                    }
+                    _ => pixel[3] = 255,
+                }
+            }
+        }
+    }
+
+    Ok(Image {
+        width: header.width as i32,
+        height: header.height as i32,
+        bytes,
+    })
+}
+
+// Encode the DIB of an ICO or CUR entry as 32 bit pixels followed by the AND mask
+pub fn encode_icon_dib(image: &Image) -> Vec<u8> {
+    let width = image.width as usize;
+    let height = image.height as usize;
+    let mask_stride = row_stride(width, 1);
+    let data_len = (width * 4 + mask_stride) * height;
+
+    let mut out = Vec::with_capacity(INFO_HEADER_LEN + data_len);
+    out.extend_from_slice(&(INFO_HEADER_LEN as u32).to_le_bytes());
+    out.extend_from_slice(&image.width.to_le_bytes());
+    out.extend_from_slice(&(image.height * 2).to_le_bytes());
+    out.extend_from_slice(&1u16.to_le_bytes());
+    out.extend_from_slice(&32u16.to_le_bytes());
+    out.extend_from_slice(&BI_RGB.to_le_bytes());
+    out.extend_from_slice(&(data_len as u32).to_le_bytes());
+    // No resolution and no palette
+    out.extend_from_slice(&[0; 16]);
+
+    for y in (0..height).rev() {
+        let row = &image.bytes[y * width * 4..(y + 1) * width * 4];
+        for p in row.chunks(4) {
+            out.extend_from_slice(&[p[2], p[1], p[0], p[3]]);
+        }
+    }
+    // The mask hides fully transparent pixels from programs that ignore alpha
+    for y in (0..height).rev() {
+        let start = out.len();
+        out.resize(start + mask_stride, 0);
+        for x in 0..width {
+            if image.bytes[(y * width + x) * 4 + 3] == 0 {
+                out[start + x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+    }
+    out
+}
+
+// Private functions
+
+// The fields of a DIB header needed to decode the pixels.
+struct Header {
+    header_len: usize,
+    width: u32,
+    height: u32,
+    top_down: bool,
+    bpp: u16,
+    compression: u32,
+    colors: usize,
+    // Red, green, blue and alpha bit masks
+    masks: [u32; 4],
+}
+
+impl Header {
+    // Parse the DIB header at the start of the data.
+    fn parse(dib: &[u8]) -> RasterResult<Header> {
+        let header_len = read_u32(dib, 0)? as usize;
+        if header_len == CORE_HEADER_LEN {
+            // The old OS/2 header with 16 bit sizes and no compression
+            let bpp = read_u16(dib, 10)?;
+            let header = Header {
+                header_len,
+                width: read_u16(dib, 4)? as u32,
+                height: read_u16(dib, 6)? as u32,
+                top_down: false,
+                bpp,
+                compression: BI_RGB,
+                colors: 0,
+                masks: default_masks(bpp),
+            };
+            return header.validate();
+        }
+        if header_len < INFO_HEADER_LEN {
+            return Err(decode_error("Unsupported header"));
+        }
+
+        let width = read_u32(dib, 4)? as i32;
+        let height = read_u32(dib, 8)? as i32;
+        let bpp = read_u16(dib, 14)?;
+        let compression = read_u32(dib, 16)?;
+        let colors = read_u32(dib, 32)? as usize;
+
+        let masks = match compression {
+            BI_BITFIELDS | BI_ALPHABITFIELDS => {
+                // Newer headers hold the masks, older ones are followed by them
+                let count = if compression == BI_ALPHABITFIELDS || header_len >= 56 {
+                    4
+                } else {
+                    3
+                };
+                let mut masks = [0; 4];
+                for (i, mask) in masks.iter_mut().enumerate().take(count) {
+                    *mask = read_u32(dib, INFO_HEADER_LEN + i * 4)?;
+                }
+                masks
+            }
+            BI_RGB | BI_RLE8 | BI_RLE4 => default_masks(bpp),
+            _ => return Err(decode_error("Unsupported compression")),
+        };
+
+        let header = Header {
+            header_len,
+            width: width.unsigned_abs(),
+            height: height.unsigned_abs(),
+            top_down: height < 0,
+            bpp,
+            compression,
+            colors,
+            masks,
+        };
+        header.validate()
+    }
+
+    // Check that the bit depth suits the compression.
+    fn validate(self) -> RasterResult<Header> {
+        let valid = match self.compression {
+            BI_RLE8 => self.bpp == 8,
+            BI_RLE4 => self.bpp == 4,
+            BI_BITFIELDS | BI_ALPHABITFIELDS => self.bpp == 16 || self.bpp == 32,
+            _ => [1, 4, 8, 16, 24, 32].contains(&self.bpp),
+        };
+        if !valid {
+            return Err(decode_error("Invalid bit depth"));
+        }
+        let max = i32::MAX as u32;
+        if self.width == 0 || self.height == 0 || self.width > max || self.height > max {
+            return Err(decode_error("Invalid dimensions"));
+        }
+        Ok(self)
+    }
+
+    // Read the color table that follows the header, for images of up to 8 bits per pixel.
+    fn palette(&self, dib: &[u8]) -> RasterResult<Vec<[u8; 4]>> {
+        if self.bpp > 8 {
+            return Ok(Vec::new());
+        }
+        let max = 1 << self.bpp;
+        let count = if self.colors == 0 || self.colors > max {
+            max
+        } else {
+            self.colors
+        };
+
+        let start = self.table_start();
+        let entry_len = self.entry_len();
+
+        let mut palette = Vec::with_capacity(count);
+        for i in 0..count {
+            let pos = start + i * entry_len;
+            match dib.get(pos..pos + 3) {
+                Some(bgr) => palette.push([bgr[2], bgr[1], bgr[0], 255]),
+                // Some files have shorter color tables than they claim
+                None if i > 0 => break,
+                None => return Err(decode_error("Color table missing")),
+            }
+        }
+        Ok(palette)
+    }
+
+    // Offset of the color table from the start of the DIB header.
+    fn table_start(&self) -> usize {
+        // Bit masks that follow an info header come before the color table
+        if self.header_len == INFO_HEADER_LEN {
+            self.header_len
+                + match self.compression {
+                    BI_BITFIELDS => 12,
+                    BI_ALPHABITFIELDS => 16,
+                    _ => 0,
+                }
+        } else {
+            self.header_len
+        }
+    }
+
+    // Length of a color table entry.
+    fn entry_len(&self) -> usize {
+        if self.header_len == CORE_HEADER_LEN {
+            3
+        } else {
+            4
+        }
+    }
+
+    // Index of the output row that the nth stored row goes to.
+    fn row_index(&self, n: usize) -> usize {
+        if self.top_down {
+            n
+        } else {
+            self.height as usize - 1 - n
+        }
+    }
+
+    // Decode uncompressed or bit field rows.
+    fn decode_rows(&self, data: &[u8], palette: &[[u8; 4]]) -> RasterResult<Vec<u8>> {
+        let width = self.width as usize;
+        let height = self.height as usize;
+        let stride = row_stride(width, self.bpp as usize);
+        // The padding of the last row is sometimes left out
+        if data.len() < stride * (height - 1) + (width * self.bpp as usize).div_ceil(8) {
+            return Err(decode_error("Image truncated"));
+        }
+
+        let fields: Vec<(u32, u32, u32)> = self
+            .masks
+            .iter()
+            .map(|&mask| (mask, mask.trailing_zeros() % 32, mask.count_ones()))
+            .collect();
+        let mut bytes = vec![0; width * height * 4];
+        for n in 0..height {
+            let row = &data[n * stride..cmp::min(n * stride + stride, data.len())];
+            let y = self.row_index(n);
+            let out = &mut bytes[y * width * 4..(y + 1) * width * 4];
+            for (x, pixel) in out.chunks_mut(4).enumerate() {
+                match self.bpp {
+                    1 | 4 | 8 => {
+                        let index = read_index(row, x, self.bpp as usize);
+                        pixel.copy_from_slice(&palette_color(palette, index));
+                    }
+                    24 => {
+                        let p = &row[x * 3..x * 3 + 3];
+                        pixel.copy_from_slice(&[p[2], p[1], p[0], 255]);
+                    }
+                    _ => {
+                        let value = if self.bpp == 16 {
+                            read_u16(row, x * 2)? as u32
+                        } else {
+                            read_u32(row, x * 4)?
+                        };
+                        for (c, &(mask, shift, bits)) in fields.iter().enumerate() {
+                            pixel[c] = scale_field(value & mask, shift, bits, c == 3);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(bytes)
+    }
+
+    // Decode RLE8 or RLE4 compressed indices.
+    fn decode_rle(&self, data: &[u8], palette: &[[u8; 4]]) -> RasterResult<Vec<u8>> {
+        let width = self.width as usize;
+        let height = self.height as usize;
+        let four_bit = self.compression == BI_RLE4;
+        let mut bytes = vec![0; width * height * 4];
+        let mut set = |x: usize, n: usize, index: usize| {
+            if x < width && n < height {
+                let y = self.row_index(n);
+                let i = (y * width + x) * 4;
+                bytes[i..i + 4].copy_from_slice(&palette_color(palette, index));
+            }
+        };
+
+        let (mut x, mut n, mut pos) = (0, 0, 0);
+        while n < height {
+            let (count, value) = match (data.get(pos), data.get(pos + 1)) {
+                (Some(&count), Some(&value)) => (count as usize, value),
+                _ => return Err(decode_error("Image truncated")),
+            };
+            pos += 2;
+
+            if count > 0 {
+                // A run of one index, or two alternating indices for RLE4
+                for i in 0..count {
+                    let index = match (four_bit, i % 2) {
+                        (false, _) => value,
+                        (true, 0) => value >> 4,
+                        (true, _) => value & 0x0F,
+                    };
+                    set(x, n, index as usize);
+                    x += 1;
+                }
+                continue;
+            }
+
+            match value {
+                // End of line
+                0 => {
+                    x = 0;
+                    n += 1;
+                }
+                // End of bitmap
+                1 => break,
+                // Move right and up
+                2 => {
+                    let (dx, dy) = match (data.get(pos), data.get(pos + 1)) {
+                        (Some(&dx), Some(&dy)) => (dx as usize, dy as usize),
+                        _ => return Err(decode_error("Image truncated")),
+                    };
+                    pos += 2;
+                    x += dx;
+                    n += dy;
+                }
+                // A run of literal indices, padded to a 16 bit boundary
+                _ => {
+                    let count = value as usize;
+                    let len = if four_bit { count.div_ceil(2) } else { count };
+                    let literal = data
+                        .get(pos..pos + len)
+                        .ok_or_else(|| decode_error("Image truncated"))?;
+                    for i in 0..count {
+                        let index = if four_bit {
+                            read_index(literal, i, 4)
+                        } else {
+                            literal[i] as usize
+                        };
+                        set(x, n, index);
+                        x += 1;
+                    }
+                    pos += len + len % 2;
+                }
+            }
+        }
+        Ok(bytes)
+    }
+}
+
+// Check the file header and return the offset of the pixel data.
+fn read_file_header(buffer: &[u8]) -> RasterResult<usize> {
+    if !buffer.starts_with(b"BM") {
+        return Err(decode_error("InvalidSignature"));
+    }
+    Ok(read_u32(buffer, 10)? as usize)
+}
+
+// The bit masks used when a file does not have its own.
+fn default_masks(bpp: u16) -> [u32; 4] {
+    match bpp {
+        16 => [0x7C00, 0x03E0, 0x001F, 0],
+        24 | 32 => [0x00FF_0000, 0x0000_FF00, 0x0000_00FF, 0],
+        _ => [0; 4],
+    }
+}
+
+// Extract a masked field and scale it to 8 bits. A missing alpha field is opaque.
+fn scale_field(value: u32, shift: u32, bits: u32, alpha: bool) -> u8 {
+    if bits == 0 {
+        return if alpha { 255 } else { 0 };
+    }
+    let max = (1u64 << bits) - 1;
+    let value = (value >> shift) as u64;
+    ((value * 255 + max / 2) / max) as u8
+}
+
+// Read the palette index of the pixel at x of a packed row.
+fn read_index(row: &[u8], x: usize, bpp: usize) -> usize {
+    let bit = x * bpp;
+    let shift = 8 - bpp - bit % 8;
+    ((row[bit / 8] >> shift) & ((1 << bpp) - 1) as u8) as usize
+}
+
+// Look up a palette color. Indices past the end of the palette are black.
+fn palette_color(palette: &[[u8; 4]], index: usize) -> [u8; 4] {
+    palette.get(index).cloned().unwrap_or([0, 0, 0, 255])
+}
+
+// Length of a row in bytes. Rows are padded to a multiple of 4 bytes.
+fn row_stride(width: usize, bpp: usize) -> usize {
+    (width * bpp).div_ceil(32) * 4
+}
+
+// Read a little endian 16-bit integer.
+fn read_u16(buffer: &[u8], pos: usize) -> RasterResult<u16> {
+    match buffer.get(pos..pos + 2) {
+        Some(b) => Ok(b[0] as u16 | (b[1] as u16) << 8),
+        None => Err(decode_error("Unexpected end of file")),
+    }
+}
+
+// Read a little endian 32-bit integer.
+fn read_u32(buffer: &[u8], pos: usize) -> RasterResult<u32> {
+    match buffer.get(pos..pos + 4) {
+        Some(b) => Ok(b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24),
+        None => Err(decode_error("Unexpected end of file")),
+    }
+}
+
+fn decode_error(msg: &str) -> RasterError {
+    RasterError::Decode(ImageFormat::Bmp, msg.to_string())
+}
+
+fn encode_error(msg: &str) -> RasterError {
+    RasterError::Encode(ImageFormat::Bmp, msg.to_string())
+}
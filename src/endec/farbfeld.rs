@@ -0,0 +1,227 @@
+//!  A module for encoding/decoding farbfeld.
+
+// from rust
+use std::io::{Read, Write};
+
+// from external crate
+
+// from local crate
+use endec;
+use error::{RasterError, RasterResult};
+use image::{ColorType, ImageInfo};
+use options::Limits;
+use stream::{self, RowReader, RowWriter};
+use Image;
+use ImageFormat;
+
+// Length of the header at the start of every file
+const HEADER_LEN: usize = 16;
+
+// Decode farbfeld
+//
+// The 16 bit samples are rounded to 8 bits. Only the bytes of the image are read, so several
+// images can be decoded one after another from a stream.
+pub fn decode_farbfeld<R: Read>(mut reader: R, limits: &Limits) -> RasterResult<Image> {
+    let mut header = [0; HEADER_LEN];
+    reader
+        .read_exact(&mut header)
+        .map_err(|_| decode_error("Unexpected end of file"))?;
+    let (width, height) = parse_header(&header)?;
+    limits.check_dimensions(width, height)?;
+    let len = width as u64 * height as u64 * 8;
+    limits.check_alloc(len)?;
+
+    let mut data = Vec::with_capacity(len as usize);
+    reader.take(len).read_to_end(&mut data)?;
+    if data.len() as u64 != len {
+        return Err(decode_error("Image truncated"));
+    }
+
+    let bytes = data.chunks(2).map(to_u8).collect();
+    Ok(Image {
+        width: width as i32,
+        height: height as i32,
+        bytes,
+    })
+}
+
+// Read the size of a farbfeld from its header
+pub fn probe_farbfeld<R: Read>(mut reader: R) -> RasterResult<ImageInfo> {
+    let mut header = [0; HEADER_LEN];
+    reader
+        .read_exact(&mut header)
+        .map_err(|_| decode_error("Unexpected end of file"))?;
+    let (width, height) = parse_header(&header)?;
+    Ok(ImageInfo {
+        width: width as i32,
+        height: height as i32,
+        format: ImageFormat::Farbfeld,
+        color_type: ColorType::Rgba,
+        bit_depth: 16,
+        frames: 1,
+    })
+}
+
+// Encode farbfeld
+pub fn encode_farbfeld<W: Write>(image: &Image, mut writer: W) -> RasterResult<()> {
+    if image.width < 1 || image.height < 1 {
+        return Err(encode_error("Invalid dimensions"));
+    }
+
+    let mut out = Vec::with_capacity(HEADER_LEN + image.bytes.len() * 2);
+    out.extend_from_slice(b"farbfeld");
+    out.extend_from_slice(&(image.width as u32).to_be_bytes());
+    out.extend_from_slice(&(image.height as u32).to_be_bytes());
+    for &sample in &image.bytes {
+        out.extend_from_slice(&to_u16(sample));
+    }
+
+    writer.write_all(&out)?;
+    Ok(())
+}
+
+// Decode farbfeld a row at a time
+pub fn decode_farbfeld_rows<'a, R: Read + 'a>(
+    mut reader: R,
+    limits: &Limits,
+) -> RasterResult<Box<dyn RowReader + 'a>> {
+    let mut header = [0; HEADER_LEN];
+    reader
+        .read_exact(&mut header)
+        .map_err(|_| decode_error("Unexpected end of file"))?;
+    let (width, height) = parse_header(&header)?;
+    endec::check_row_limits(limits, width, height, width as u64 * 8)?;
+    Ok(Box::new(FarbfeldRows {
+        reader,
+        width: width as i32,
+        height: height as i32,
+        y: 0,
+        data: vec![0; width as usize * 8],
+    }))
+}
+
+// Encode farbfeld a row at a time
+pub fn encode_farbfeld_rows<'a, W: Write + 'a>(
+    mut writer: W,
+    width: i32,
+    height: i32,
+) -> RasterResult<Box<dyn RowWriter + 'a>> {
+    if width < 1 || height < 1 {
+        return Err(encode_error("Invalid dimensions"));
+    }
+    writer.write_all(b"farbfeld")?;
+    writer.write_all(&(width as u32).to_be_bytes())?;
+    writer.write_all(&(height as u32).to_be_bytes())?;
+    Ok(Box::new(FarbfeldRowWriter {
+        writer,
+        width,
+        height,
+        y: 0,
+        data: Vec::with_capacity(width as usize * 8),
+    }))
+}
+
+// Private functions
+
+// Reads rows of 16 bit samples and rounds them to 8 bits.
+struct FarbfeldRows<R: Read> {
+    reader: R,
+    width: i32,
+    height: i32,
+    y: i32,
+    // Samples of a row
+    data: Vec<u8>,
+}
+
+impl<R: Read> RowReader for FarbfeldRows<R> {
+    fn width(&self) -> i32 {
+        self.width
+    }
+
+    fn height(&self) -> i32 {
+        self.height
+    }
+
+    fn read_row(&mut self, row: &mut [u8]) -> RasterResult<bool> {
+        if self.y >= self.height {
+            return Ok(false);
+        }
+        stream::check_read(ImageFormat::Farbfeld, self.width, row)?;
+        self.reader
+            .read_exact(&mut self.data)
+            .map_err(|_| decode_error("Image truncated"))?;
+        for (v, s) in row.iter_mut().zip(self.data.chunks(2)) {
+            *v = to_u8(s);
+        }
+        self.y += 1;
+        Ok(true)
+    }
+}
+
+// Writes rows of 8 bit samples widened to 16 bits.
+struct FarbfeldRowWriter<W: Write> {
+    writer: W,
+    width: i32,
+    height: i32,
+    y: i32,
+    // Samples of a row
+    data: Vec<u8>,
+}
+
+impl<W: Write> RowWriter for FarbfeldRowWriter<W> {
+    fn width(&self) -> i32 {
+        self.width
+    }
+
+    fn height(&self) -> i32 {
+        self.height
+    }
+
+    fn write_row(&mut self, row: &[u8]) -> RasterResult<()> {
+        stream::check_write(ImageFormat::Farbfeld, self.width, self.height, self.y, row)?;
+        self.data.clear();
+        for &sample in row {
+            self.data.extend_from_slice(&to_u16(sample));
+        }
+        self.writer.write_all(&self.data)?;
+        self.y += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> RasterResult<()> {
+        stream::check_finished(ImageFormat::Farbfeld, self.height, self.y)?;
+        Ok(self.writer.flush()?)
+    }
+}
+
+// Round a big endian 16 bit sample to 8 bits.
+fn to_u8(sample: &[u8]) -> u8 {
+    ((u16::from_be_bytes([sample[0], sample[1]]) as u32 * 255 + 32767) / 65535) as u8
+}
+
+// Widen an 8 bit sample to big endian 16 bits.
+fn to_u16(sample: u8) -> [u8; 2] {
+    (sample as u16 * 257).to_be_bytes()
+}
+
+// Check the magic value and read the width and height.
+fn parse_header(header: &[u8; HEADER_LEN]) -> RasterResult<(u32, u32)> {
+    if &header[..8] != b"farbfeld" {
+        return Err(decode_error("InvalidSignature"));
+    }
+    let width = u32::from_be_bytes([header[8], header[9], header[10], header[11]]);
+    let height = u32::from_be_bytes([header[12], header[13], header[14], header[15]]);
+    let max = i32::MAX as u32;
+    if width == 0 || height == 0 || width > max || height > max {
+        return Err(decode_error("Invalid dimensions"));
+    }
+    Ok((width, height))
+}
+
+fn decode_error(msg: &str) -> RasterError {
+    RasterError::Decode(ImageFormat::Farbfeld, msg.to_string())
+}
+
+fn encode_error(msg: &str) -> RasterError {
+    RasterError::Encode(ImageFormat::Farbfeld, msg.to_string())
+}
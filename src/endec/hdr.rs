@@ -0,0 +1,390 @@
+//!  A module for encoding/decoding Radiance HDR.
+
+// from rust
+use std::io::{Read, Write};
+
+// from external crate
+
+// from local crate
+use endec::read_limited;
+use error::{RasterError, RasterResult};
+use hdr::HdrImage;
+use image::{ColorType, ImageInfo};
+use options::Limits;
+use ImageFormat;
+
+// Longest header accepted, to stop at garbage that never ends a line
+const MAX_HEADER: usize = 64 * 1024;
+
+// Shortest and longest scanlines that can be run length encoded per channel
+const MIN_RLE_WIDTH: usize = 8;
+const MAX_RLE_WIDTH: usize = 0x7FFF;
+
+// Shortest run worth encoding instead of literal bytes
+const MIN_RUN: usize = 4;
+
+// Decode Radiance HDR
+//
+// Both the per channel run length encoding and the older flat or repeat encoded scanlines are
+// read, in any of the 8 orientations. EXPOSURE lines in the header are ignored, like most readers
+// do.
+pub fn decode_hdr<R: Read>(mut reader: R, limits: &Limits) -> RasterResult<HdrImage> {
+    let layout = read_header(&mut reader)?;
+    let (width, height) = layout.dimensions();
+    limits.check_dimensions(width as u32, height as u32)?;
+    limits.check_alloc(width as u64 * height as u64 * 12)?;
+    let data = read_limited(reader, limits)?;
+
+    let mut pixels = vec![0.0; width * height * 3];
+    let mut pos = 0;
+    let mut line = Vec::with_capacity(layout.length);
+    for i in 0..layout.count {
+        read_scanline(&data, &mut pos, layout.length, &mut line)?;
+        for (j, rgbe) in line.iter().enumerate() {
+            let (x, y) = layout.position(i, j);
+            let start = (y * width + x) * 3;
+            pixels[start..start + 3].copy_from_slice(&rgbe_to_rgb(*rgbe));
+        }
+    }
+
+    Ok(HdrImage {
+        width: width as i32,
+        height: height as i32,
+        pixels,
+    })
+}
+
+// Read the size of a Radiance HDR from its header
+pub fn probe_hdr<R: Read>(mut reader: R) -> RasterResult<ImageInfo> {
+    let (width, height) = read_header(&mut reader)?.dimensions();
+    Ok(ImageInfo {
+        width: width as i32,
+        height: height as i32,
+        format: ImageFormat::Hdr,
+        color_type: ColorType::Rgb,
+        bit_depth: 32,
+        frames: 1,
+    })
+}
+
+// Encode Radiance HDR
+//
+// Scanlines are run length encoded per channel, except for widths the encoding cannot describe.
+pub fn encode_hdr<W: Write>(image: &HdrImage, mut writer: W) -> RasterResult<()> {
+    if image.width < 1 || image.height < 1 {
+        return Err(encode_error("Invalid dimensions"));
+    }
+    let width = image.width as usize;
+    if image.pixels.len() != width * image.height as usize * 3 {
+        return Err(encode_error("Pixel data does not match the dimensions"));
+    }
+
+    let mut out = Vec::with_capacity(image.pixels.len() + 64);
+    out.extend_from_slice(b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n");
+    out.extend_from_slice(format!("-Y {} +X {}\n", image.height, image.width).as_bytes());
+
+    let mut channel = Vec::with_capacity(width);
+    for row in image.pixels.chunks(width * 3) {
+        let line: Vec<[u8; 4]> = row.chunks(3).map(rgb_to_rgbe).collect();
+        if (MIN_RLE_WIDTH..=MAX_RLE_WIDTH).contains(&width) {
+            out.extend_from_slice(&[2, 2, (width >> 8) as u8, width as u8]);
+            for c in 0..4 {
+                channel.clear();
+                channel.extend(line.iter().map(|rgbe| rgbe[c]));
+                write_runs(&channel, &mut out);
+            }
+        } else {
+            for rgbe in &line {
+                out.extend_from_slice(rgbe);
+            }
+        }
+    }
+
+    writer.write_all(&out)?;
+    Ok(())
+}
+
+// Private functions
+
+// How the scanlines of a file map onto the image
+struct Layout {
+    // Number of scanlines
+    count: usize,
+
+    // Pixels in a scanline
+    length: usize,
+
+    // Scanlines are columns instead of rows
+    columns: bool,
+
+    // Scanlines go from the bottom or the right
+    reverse_lines: bool,
+
+    // Pixels in a scanline go to the top or the left
+    reverse_pixels: bool,
+}
+
+impl Layout {
+    // Width and height of the image
+    fn dimensions(&self) -> (usize, usize) {
+        if self.columns {
+            (self.count, self.length)
+        } else {
+            (self.length, self.count)
+        }
+    }
+
+    // Position in the image of pixel j of scanline i
+    fn position(&self, i: usize, j: usize) -> (usize, usize) {
+        let i = if self.reverse_lines {
+            self.count - 1 - i
+        } else {
+            i
+        };
+        let j = if self.reverse_pixels {
+            self.length - 1 - j
+        } else {
+            j
+        };
+        if self.columns {
+            (i, j)
+        } else {
+            (j, i)
+        }
+    }
+}
+
+// Read the header lines and the resolution line after them.
+fn read_header<R: Read>(reader: &mut R) -> RasterResult<Layout> {
+    let mut total = 0;
+    let mut first = true;
+    loop {
+        let line = read_line(reader, &mut total)?;
+        if first {
+            if !line.starts_with("#?") {
+                return Err(decode_error("Invalid signature"));
+            }
+            first = false;
+        } else if line.is_empty() {
+            break;
+        } else if let Some(format) = line.strip_prefix("FORMAT=") {
+            match format.trim() {
+                "32-bit_rle_rgbe" => {}
+                "32-bit_rle_xyze" => return Err(decode_error("XYZE color not supported")),
+                _ => return Err(decode_error("Unknown pixel format")),
+            }
+        }
+    }
+
+    let line = read_line(reader, &mut total)?;
+    parse_resolution(&line).ok_or_else(|| decode_error("Invalid resolution"))
+}
+
+// Read a line of the header, without the line feed.
+fn read_line<R: Read>(reader: &mut R, total: &mut usize) -> RasterResult<String> {
+    let mut line = Vec::new();
+    let mut byte = [0];
+    loop {
+        reader
+            .read_exact(&mut byte)
+            .map_err(|_| decode_error("Unexpected end of file"))?;
+        *total += 1;
+        if *total > MAX_HEADER {
+            return Err(decode_error("Header too long"));
+        }
+        if byte[0] == b'\n' {
+            return Ok(String::from_utf8_lossy(&line).into_owned());
+        }
+        line.push(byte[0]);
+    }
+}
+
+// Parse a resolution line like "-Y 480 +X 640". The first axis is the one scanlines step along.
+fn parse_resolution(line: &str) -> Option<Layout> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.len() != 4 {
+        return None;
+    }
+    let axis = |token: &str| -> Option<(bool, bool)> {
+        // Whether the axis is X, and whether it runs against the reading order
+        match token {
+            "-Y" => Some((false, false)),
+            "+Y" => Some((false, true)),
+            "+X" => Some((true, false)),
+            "-X" => Some((true, true)),
+            _ => None,
+        }
+    };
+    let (major_x, reverse_lines) = axis(tokens[0])?;
+    let (minor_x, reverse_pixels) = axis(tokens[2])?;
+    let count: usize = tokens[1].parse().ok()?;
+    let length: usize = tokens[3].parse().ok()?;
+    if major_x == minor_x || count == 0 || length == 0 || count.max(length) > i32::MAX as usize {
+        return None;
+    }
+    Some(Layout {
+        count,
+        length,
+        columns: major_x,
+        reverse_lines,
+        reverse_pixels,
+    })
+}
+
+// Read a scanline of RGBE pixels starting at pos.
+fn read_scanline(
+    data: &[u8],
+    pos: &mut usize,
+    length: usize,
+    line: &mut Vec<[u8; 4]>,
+) -> RasterResult<()> {
+    line.clear();
+    let start = take(data, pos, 4)?;
+    let rle = (MIN_RLE_WIDTH..=MAX_RLE_WIDTH).contains(&length)
+        && start[0] == 2
+        && start[1] == 2
+        && start[2] & 0x80 == 0;
+    if rle {
+        if ((start[2] as usize) << 8 | start[3] as usize) != length {
+            return Err(decode_error("Scanline length mismatch"));
+        }
+        line.resize(length, [0; 4]);
+        for c in 0..4 {
+            let mut x = 0;
+            while x < length {
+                let code = take(data, pos, 1)?[0] as usize;
+                if code > 128 {
+                    let run = code - 128;
+                    let value = take(data, pos, 1)?[0];
+                    if x + run > length {
+                        return Err(decode_error("Run overflows scanline"));
+                    }
+                    for pixel in &mut line[x..x + run] {
+                        pixel[c] = value;
+                    }
+                    x += run;
+                } else {
+                    if code == 0 || x + code > length {
+                        return Err(decode_error("Invalid run length"));
+                    }
+                    for (pixel, &value) in line[x..x + code].iter_mut().zip(take(data, pos, code)?)
+                    {
+                        pixel[c] = value;
+                    }
+                    x += code;
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    // Flat pixels, where 1, 1, 1 repeats the previous pixel. Consecutive repeats count in higher
+    // bytes.
+    let mut rgbe = [start[0], start[1], start[2], start[3]];
+    let mut shift = 0;
+    loop {
+        if rgbe[..3] == [1, 1, 1] {
+            let previous = *line
+                .last()
+                .ok_or_else(|| decode_error("Repeat without a pixel"))?;
+            let count = (rgbe[3] as usize)
+                .checked_shl(shift)
+                .filter(|&count| shift < 32 && line.len() + count <= length)
+                .ok_or_else(|| decode_error("Run overflows scanline"))?;
+            line.extend((0..count).map(|_| previous));
+            shift += 8;
+        } else {
+            line.push(rgbe);
+            shift = 0;
+        }
+        if line.len() == length {
+            return Ok(());
+        }
+        let next = take(data, pos, 4)?;
+        rgbe = [next[0], next[1], next[2], next[3]];
+    }
+}
+
+// Take len bytes at pos.
+fn take<'a>(data: &'a [u8], pos: &mut usize, len: usize) -> RasterResult<&'a [u8]> {
+    let bytes = data
+        .get(*pos..*pos + len)
+        .ok_or_else(|| decode_error("Image truncated"))?;
+    *pos += len;
+    Ok(bytes)
+}
+
+// Write a channel as runs of equal bytes and literal bytes.
+fn write_runs(channel: &[u8], out: &mut Vec<u8>) {
+    let mut x = 0;
+    while x < channel.len() {
+        // Find the next run long enough to be worth it
+        let mut run_start = x;
+        let mut run_len = 0;
+        while run_start < channel.len() {
+            run_len = channel[run_start..]
+                .iter()
+                .take(127)
+                .take_while(|&&v| v == channel[run_start])
+                .count();
+            if run_len >= MIN_RUN {
+                break;
+            }
+            run_start += run_len;
+        }
+
+        while x < run_start {
+            let count = (run_start - x).min(128);
+            out.push(count as u8);
+            out.extend_from_slice(&channel[x..x + count]);
+            x += count;
+        }
+        if run_start < channel.len() {
+            out.extend_from_slice(&[128 + run_len as u8, channel[run_start]]);
+            x = run_start + run_len;
+        }
+    }
+}
+
+// Convert a pixel with a shared exponent to floats.
+fn rgbe_to_rgb(rgbe: [u8; 4]) -> [f32; 3] {
+    if rgbe[3] == 0 {
+        return [0.0; 3];
+    }
+    let scale = 2f64.powi(rgbe[3] as i32 - 136);
+    [
+        (rgbe[0] as f64 * scale) as f32,
+        (rgbe[1] as f64 * scale) as f32,
+        (rgbe[2] as f64 * scale) as f32,
+    ]
+}
+
+// Convert floats to a pixel with a shared exponent. The mantissas are truncated, so pixels that
+// were decoded from RGBE are stored exactly. Negative channels are clamped to 0.
+fn rgb_to_rgbe(rgb: &[f32]) -> [u8; 4] {
+    let max = rgb[0].max(rgb[1]).max(rgb[2]);
+    if max.is_nan() || max < 1e-32 {
+        return [0; 4];
+    }
+    // The exponent of max as a fraction in 0.5 - 1.0, from the bits of the float. Values too
+    // large for the exponent byte are clipped.
+    let exponent = ((max.to_bits() >> 23) & 0xFF) as i32 - 126;
+    if exponent > 127 {
+        return [255; 4];
+    }
+    let scale = 2f64.powi(8 - exponent);
+    [
+        (rgb[0] as f64 * scale) as u8,
+        (rgb[1] as f64 * scale) as u8,
+        (rgb[2] as f64 * scale) as u8,
+        (exponent + 128) as u8,
+    ]
+}
+
+fn decode_error(message: &str) -> RasterError {
+    RasterError::Decode(ImageFormat::Hdr, message.to_string())
+}
+
+fn encode_error(message: &str) -> RasterError {
+    RasterError::Encode(ImageFormat::Hdr, message.to_string())
+}
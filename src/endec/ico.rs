@@ -0,0 +1,227 @@
+//!  A module for encoding/decoding ICO and CUR.
+
+// from rust
+use std::io::{Read, Write};
+
+// from external crate
+
+// from local crate
+use endec::bmp::{decode_icon_dib, encode_icon_dib};
+use endec::read_limited;
+use endec::{decode_png, encode_png};
+use error::{RasterError, RasterResult};
+use image::{ColorType, ImageInfo};
+use metadata::Metadata;
+use options::{IcoOptions, Limits, PngOptions};
+use Image;
+use ImageFormat;
+
+// Length of the directory header and of each entry after it
+const DIR_LEN: usize = 6;
+const ENTRY_LEN: usize = 16;
+
+// Entries that start with this signature hold a PNG instead of a DIB
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+// Decode the largest image of an ICO or CUR
+pub fn decode_ico<R: Read>(reader: R, format: ImageFormat, limits: &Limits) -> RasterResult<Image> {
+    let buffer = read_limited(reader, limits)?;
+    let entries = read_directory(&buffer, format)?;
+    let entry = largest(&entries, format);
+    entry.decode(&buffer, format, limits)
+}
+
+// Decode every image of an ICO or CUR, in the order they are stored
+pub fn decode_ico_all<R: Read>(
+    reader: R,
+    format: ImageFormat,
+    limits: &Limits,
+) -> RasterResult<Vec<Image>> {
+    let buffer = read_limited(reader, limits)?;
+    let entries = read_directory(&buffer, format)?;
+
+    let mut images = Vec::with_capacity(entries.len());
+    let mut total = 0;
+    for entry in &entries {
+        let image = entry.decode(&buffer, format, limits)?;
+        total += image.bytes.len() as u64;
+        limits.check_alloc(total)?;
+        images.push(image);
+    }
+    Ok(images)
+}
+
+// Read the size of the largest image of an ICO or CUR from its directory
+//
+// The number of images is reported as the frame count.
+pub fn probe_ico<R: Read>(mut reader: R, format: ImageFormat) -> RasterResult<ImageInfo> {
+    let mut buffer = vec![0; DIR_LEN];
+    reader
+        .read_exact(&mut buffer)
+        .map_err(|_| decode_error(format, "Unexpected end of file"))?;
+    let count = u16::from_le_bytes([buffer[4], buffer[5]]) as usize;
+    reader
+        .take((count * ENTRY_LEN) as u64)
+        .read_to_end(&mut buffer)?;
+
+    let entries = read_directory(&buffer, format)?;
+    let entry = largest(&entries, format);
+    Ok(ImageInfo {
+        width: entry.width as i32,
+        height: entry.height as i32,
+        format,
+        color_type: ColorType::Rgba,
+        bit_depth: 8,
+        frames: entries.len(),
+    })
+}
+
+// Encode ICO or CUR with one entry for each image
+//
+// Images of 256 pixels are stored as PNG, as Windows expects. Smaller images are stored as 32 bit
+// DIBs, which older programs can read.
+pub fn encode_ico<W: Write>(
+    images: &[Image],
+    mut writer: W,
+    format: ImageFormat,
+    options: &IcoOptions,
+) -> RasterResult<()> {
+    if images.is_empty() || images.len() > u16::MAX as usize {
+        return Err(encode_error(format, "Invalid number of images"));
+    }
+
+    let mut payloads = Vec::with_capacity(images.len());
+    for image in images {
+        if image.width < 1 || image.height < 1 || image.width > 256 || image.height > 256 {
+            return Err(encode_error(format, "Invalid dimensions"));
+        }
+        if image.width == 256 || image.height == 256 {
+            let mut payload = Vec::new();
+            encode_png(
+                image,
+                &mut payload,
+                &PngOptions::default(),
+                &Metadata::default(),
+            )?;
+            payloads.push(payload);
+        } else {
+            payloads.push(encode_icon_dib(image));
+        }
+    }
+
+    let kind: u16 = if format == ImageFormat::Cur { 2 } else { 1 };
+    let mut out = Vec::new();
+    out.extend_from_slice(&[0, 0]);
+    out.extend_from_slice(&kind.to_le_bytes());
+    out.extend_from_slice(&(images.len() as u16).to_le_bytes());
+
+    let mut offset = DIR_LEN + ENTRY_LEN * images.len();
+    for (image, payload) in images.iter().zip(&payloads) {
+        // A size of 256 is stored as 0
+        out.extend_from_slice(&[image.width as u8, image.height as u8, 0, 0]);
+        // Cursors store their hotspot in place of the planes and bit count
+        let (planes, bpp) = if format == ImageFormat::Cur {
+            options.hotspot
+        } else {
+            (1, 32)
+        };
+        out.extend_from_slice(&planes.to_le_bytes());
+        out.extend_from_slice(&bpp.to_le_bytes());
+        out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += payload.len();
+    }
+    for payload in &payloads {
+        out.extend_from_slice(payload);
+    }
+
+    writer.write_all(&out)?;
+    Ok(())
+}
+
+// Private functions
+
+// An entry of the directory that describes one image.
+struct Entry {
+    width: u32,
+    height: u32,
+    bpp: u16,
+    len: usize,
+    offset: usize,
+}
+
+impl Entry {
+    // Decode the PNG or DIB that the entry points to.
+    fn decode(&self, buffer: &[u8], format: ImageFormat, limits: &Limits) -> RasterResult<Image> {
+        let data = buffer
+            .get(self.offset..)
+            .map(|data| &data[..self.len.min(data.len())])
+            .ok_or_else(|| decode_error(format, "Image data missing"))?;
+        let result = if data.starts_with(&PNG_SIGNATURE) {
+            decode_png(data, limits).map(|(image, _)| image)
+        } else {
+            decode_icon_dib(data, limits)
+        };
+        // Report errors of the payload as errors of the icon
+        result.map_err(|e| match e {
+            RasterError::Decode(_, msg) => RasterError::Decode(format, msg),
+            e => e,
+        })
+    }
+}
+
+// Check the directory header and read its entries.
+fn read_directory(buffer: &[u8], format: ImageFormat) -> RasterResult<Vec<Entry>> {
+    let kind = if format == ImageFormat::Cur { 2 } else { 1 };
+    match buffer.get(..DIR_LEN) {
+        Some(b) if b[..4] == [0, 0, kind, 0] => {}
+        Some(_) => return Err(decode_error(format, "InvalidSignature")),
+        None => return Err(decode_error(format, "Unexpected end of file")),
+    }
+    let count = u16::from_le_bytes([buffer[4], buffer[5]]) as usize;
+    if count == 0 {
+        return Err(decode_error(format, "No images"));
+    }
+
+    let mut entries = Vec::with_capacity(count);
+    for i in 0..count {
+        let pos = DIR_LEN + i * ENTRY_LEN;
+        let e = buffer
+            .get(pos..pos + ENTRY_LEN)
+            .ok_or_else(|| decode_error(format, "Directory truncated"))?;
+        // A size of 0 means 256
+        let size = |b: u8| if b == 0 { 256 } else { b as u32 };
+        entries.push(Entry {
+            width: size(e[0]),
+            height: size(e[1]),
+            bpp: u16::from_le_bytes([e[6], e[7]]),
+            len: u32::from_le_bytes([e[8], e[9], e[10], e[11]]) as usize,
+            offset: u32::from_le_bytes([e[12], e[13], e[14], e[15]]) as usize,
+        });
+    }
+    Ok(entries)
+}
+
+// The entry with the most pixels. Icons with the same size are told apart by bit count.
+fn largest(entries: &[Entry], format: ImageFormat) -> &Entry {
+    let bpp = |entry: &Entry| {
+        if format == ImageFormat::Cur {
+            0
+        } else {
+            entry.bpp
+        }
+    };
+    entries
+        .iter()
+        .rev()
+        .max_by_key(|entry| (entry.width * entry.height, bpp(entry)))
+        .expect("directory has entries")
+}
+
+fn decode_error(format: ImageFormat, msg: &str) -> RasterError {
+    RasterError::Decode(format, msg.to_string())
+}
+
+fn encode_error(format: ImageFormat, msg: &str) -> RasterError {
+    RasterError::Encode(format, msg.to_string())
+}
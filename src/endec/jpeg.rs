@@ -0,0 +1,609 @@
+//!  A module for encoding/decoding JPEG.
+
+// from rust
+use std::cmp;
+use std::f32::consts::PI;
+use std::io::{Read, Write};
+
+// from external crate
+use piston_image;
+use piston_image::ImageDecoder;
+
+// from local crate
+use endec::read_limited;
+use error::{RasterError, RasterResult};
+use image::{ColorType, ImageInfo};
+use metadata::{Exif, Metadata};
+use options::{ChromaSubsampling, JpegOptions, Limits};
+use Image;
+use ImageFormat;
+
+// Quantization tables from section K.1 of the JPEG spec, in natural order
+const LUMA_QTABLE: [u8; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, //
+    12, 12, 14, 19, 26, 58, 60, 55, //
+    14, 13, 16, 24, 40, 57, 69, 56, //
+    14, 17, 22, 29, 51, 87, 80, 62, //
+    18, 22, 37, 56, 68, 109, 103, 77, //
+    24, 35, 55, 64, 81, 104, 113, 92, //
+    49, 64, 78, 87, 103, 121, 120, 101, //
+    72, 92, 95, 98, 112, 100, 103, 99, //
+];
+
+const CHROMA_QTABLE: [u8; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99, //
+    18, 21, 26, 66, 99, 99, 99, 99, //
+    24, 26, 56, 99, 99, 99, 99, 99, //
+    47, 66, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+];
+
+// Huffman tables from section K.3 of the JPEG spec, as the number of codes of each length
+// followed by the symbols
+const LUMA_DC_LENGTHS: [u8; 16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+const LUMA_DC_VALUES: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+
+const CHROMA_DC_LENGTHS: [u8; 16] = [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0];
+const CHROMA_DC_VALUES: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+
+const LUMA_AC_LENGTHS: [u8; 16] = [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7D];
+const LUMA_AC_VALUES: [u8; 162] = [
+    0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07,
+    0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xA1, 0x08, 0x23, 0x42, 0xB1, 0xC1, 0x15, 0x52, 0xD1, 0xF0,
+    0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0A, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x25, 0x26, 0x27, 0x28,
+    0x29, 0x2A, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+    0x4A, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+    0x6A, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+    0x8A, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9A, 0xA2, 0xA3, 0xA4, 0xA5, 0xA6, 0xA7,
+    0xA8, 0xA9, 0xAA, 0xB2, 0xB3, 0xB4, 0xB5, 0xB6, 0xB7, 0xB8, 0xB9, 0xBA, 0xC2, 0xC3, 0xC4, 0xC5,
+    0xC6, 0xC7, 0xC8, 0xC9, 0xCA, 0xD2, 0xD3, 0xD4, 0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0xDA, 0xE1, 0xE2,
+    0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9, 0xEA, 0xF1, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8,
+    0xF9, 0xFA,
+];
+
+const CHROMA_AC_LENGTHS: [u8; 16] = [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77];
+const CHROMA_AC_VALUES: [u8; 162] = [
+    0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71,
+    0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xA1, 0xB1, 0xC1, 0x09, 0x23, 0x33, 0x52, 0xF0,
+    0x15, 0x62, 0x72, 0xD1, 0x0A, 0x16, 0x24, 0x34, 0xE1, 0x25, 0xF1, 0x17, 0x18, 0x19, 0x1A, 0x26,
+    0x27, 0x28, 0x29, 0x2A, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48,
+    0x49, 0x4A, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68,
+    0x69, 0x6A, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+    0x88, 0x89, 0x8A, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9A, 0xA2, 0xA3, 0xA4, 0xA5,
+    0xA6, 0xA7, 0xA8, 0xA9, 0xAA, 0xB2, 0xB3, 0xB4, 0xB5, 0xB6, 0xB7, 0xB8, 0xB9, 0xBA, 0xC2, 0xC3,
+    0xC4, 0xC5, 0xC6, 0xC7, 0xC8, 0xC9, 0xCA, 0xD2, 0xD3, 0xD4, 0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0xDA,
+    0xE2, 0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9, 0xEA, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8,
+    0xF9, 0xFA,
+];
+
+// Natural order index of each coefficient in zigzag order
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+// Decode JPEG along with its EXIF data
+pub fn decode_jpeg<R: Read>(reader: R, limits: &Limits) -> RasterResult<(Image, Metadata)> {
+    // The decoder skips APP segments, so keep the bytes around to look for EXIF data
+    let buffer = read_limited(reader, limits)?;
+    let metadata = Metadata {
+        exif: read_exif(&buffer),
+        ..Metadata::default()
+    };
+
+    let mut decoder = piston_image::jpeg::JPEGDecoder::new(&buffer[..]);
+    let (w, h) = decoder.dimensions()?;
+    limits.check_dimensions(w, h)?;
+    let color_type = decoder.colortype()?;
+    let data = match decoder.read_image()? {
+        piston_image::DecodingResult::U8(data) => data,
+        piston_image::DecodingResult::U16(_) => {
+            return Err(RasterError::Decode(
+                ImageFormat::Jpeg,
+                "Unsupported bit depth".to_string(),
+            ))
+        }
+    };
+
+    let mut bytes = Vec::with_capacity((w * h) as usize * 4);
+    match color_type {
+        piston_image::ColorType::Gray(8) => {
+            for l in &data {
+                bytes.extend_from_slice(&[*l, *l, *l, 255]);
+            }
+        }
+        piston_image::ColorType::RGB(8) => {
+            for p in data.chunks(3) {
+                bytes.extend_from_slice(&[p[0], p[1], p[2], 255]);
+            }
+        }
+        _ => {
+            return Err(RasterError::Decode(
+                ImageFormat::Jpeg,
+                "UnsupportedColor".to_string(),
+            ))
+        }
+    }
+    let image = Image {
+        width: w as i32,
+        height: h as i32,
+        bytes,
+    };
+    Ok((image, metadata))
+}
+
+// Read the size and color type of a JPEG from its frame header
+pub fn probe_jpeg<R: Read>(mut reader: R) -> RasterResult<ImageInfo> {
+    let mut soi = [0; 2];
+    reader.read_exact(&mut soi)?;
+    if soi != [0xFF, 0xD8] {
+        return Err(decode_error("InvalidSignature"));
+    }
+
+    loop {
+        let mut byte = [0; 1];
+        reader.read_exact(&mut byte)?;
+        if byte[0] != 0xFF {
+            return Err(decode_error("Invalid marker"));
+        }
+        // Markers can be padded with any number of 0xFF bytes
+        while byte[0] == 0xFF {
+            reader.read_exact(&mut byte)?;
+        }
+        let marker = byte[0];
+        match marker {
+            // Markers without a segment
+            0x01 | 0xD0..=0xD7 => continue,
+            // Start of scan or end of image before any frame header
+            0xD9 | 0xDA => return Err(decode_error("Frame header missing")),
+            _ => {}
+        }
+
+        let mut len = [0; 2];
+        reader.read_exact(&mut len)?;
+        let len = ((len[0] as usize) << 8 | len[1] as usize).saturating_sub(2);
+        let mut data = Vec::with_capacity(len);
+        (&mut reader).take(len as u64).read_to_end(&mut data)?;
+        if data.len() != len {
+            return Err(decode_error("Unexpected end of file"));
+        }
+
+        // Start of frame markers, apart from DHT, JPG and DAC that share the range
+        if let 0xC0..=0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF = marker {
+            if data.len() < 6 {
+                return Err(decode_error("Invalid frame header"));
+            }
+            let color_type = match data[5] {
+                1 => ColorType::Gray,
+                3 => ColorType::Rgb,
+                4 => ColorType::Cmyk,
+                _ => return Err(decode_error("UnsupportedColor")),
+            };
+            return Ok(ImageInfo {
+                width: ((data[3] as i32) << 8) | data[4] as i32,
+                height: ((data[1] as i32) << 8) | data[2] as i32,
+                format: ImageFormat::Jpeg,
+                color_type,
+                bit_depth: data[0],
+                frames: 1,
+            });
+        }
+    }
+}
+
+// Encode JPEG
+//
+// Writes a baseline JFIF file with the standard Huffman tables. The alpha channel is dropped.
+pub fn encode_jpeg<W: Write>(
+    image: &Image,
+    mut writer: W,
+    options: &JpegOptions,
+) -> RasterResult<()> {
+    if options.quality < 1 || options.quality > 100 {
+        return Err(encode_error("Quality must be between 1 and 100"));
+    }
+    if image.width < 1 || image.height < 1 || image.width > 65535 || image.height > 65535 {
+        return Err(encode_error("Invalid dimensions"));
+    }
+
+    // Horizontal and vertical sampling factors of the luma channel
+    let (h, v) = match options.subsampling {
+        ChromaSubsampling::Ratio444 => (1, 1),
+        ChromaSubsampling::Ratio422 => (2, 1),
+        ChromaSubsampling::Ratio420 => (2, 2),
+    };
+    let luma_table = scale_qtable(&LUMA_QTABLE, options.quality);
+    let chroma_table = scale_qtable(&CHROMA_QTABLE, options.quality);
+
+    let mut out = Vec::new();
+    out.extend_from_slice(&[0xFF, 0xD8]);
+
+    // JFIF header with a 1:1 pixel aspect ratio
+    write_segment(&mut out, 0xE0, b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0");
+
+    for (id, table) in [&luma_table, &chroma_table].iter().enumerate() {
+        let mut data = vec![id as u8];
+        data.extend(ZIGZAG.iter().map(|&i| table[i] as u8));
+        write_segment(&mut out, 0xDB, &data);
+    }
+
+    let (width, height) = (image.width as u16, image.height as u16);
+    write_segment(
+        &mut out,
+        0xC0,
+        &[
+            8,
+            (height >> 8) as u8,
+            height as u8,
+            (width >> 8) as u8,
+            width as u8,
+            3,
+            1,
+            (h << 4 | v) as u8,
+            0,
+            2,
+            0x11,
+            1,
+            3,
+            0x11,
+            1,
+        ],
+    );
+
+    let tables = [
+        (0x00, &LUMA_DC_LENGTHS, &LUMA_DC_VALUES[..]),
+        (0x10, &LUMA_AC_LENGTHS, &LUMA_AC_VALUES[..]),
+        (0x01, &CHROMA_DC_LENGTHS, &CHROMA_DC_VALUES[..]),
+        (0x11, &CHROMA_AC_LENGTHS, &CHROMA_AC_VALUES[..]),
+    ];
+    for &(class_id, lengths, values) in &tables {
+        let mut data = vec![class_id];
+        data.extend_from_slice(lengths);
+        data.extend_from_slice(values);
+        write_segment(&mut out, 0xC4, &data);
+    }
+
+    write_segment(&mut out, 0xDA, &[3, 1, 0x00, 2, 0x11, 3, 0x11, 0, 63, 0]);
+
+    let luma_codes = (
+        huffman_codes(&LUMA_DC_LENGTHS, &LUMA_DC_VALUES),
+        huffman_codes(&LUMA_AC_LENGTHS, &LUMA_AC_VALUES),
+    );
+    let chroma_codes = (
+        huffman_codes(&CHROMA_DC_LENGTHS, &CHROMA_DC_VALUES),
+        huffman_codes(&CHROMA_AC_LENGTHS, &CHROMA_AC_VALUES),
+    );
+
+    // Convert to YCbCr planes padded to a whole number of MCUs, then downsample the chroma
+    let mcu_width = 8 * h;
+    let mcu_height = 8 * v;
+    let padded_width = (image.width as usize).div_ceil(mcu_width) * mcu_width;
+    let padded_height = (image.height as usize).div_ceil(mcu_height) * mcu_height;
+    let planes = ycbcr_planes(image, padded_width, padded_height);
+    let cb = downsample(&planes[1], padded_width, h, v);
+    let cr = downsample(&planes[2], padded_width, h, v);
+    let chroma_width = padded_width / h;
+
+    let cosines = dct_cosines();
+    let mut bits = BitWriter::new(out);
+    let mut predictions = [0; 3];
+    let mut block = [0.0; 64];
+    for mcu_y in (0..padded_height).step_by(mcu_height) {
+        for mcu_x in (0..padded_width).step_by(mcu_width) {
+            for block_y in 0..v {
+                for block_x in 0..h {
+                    read_block(
+                        &planes[0],
+                        padded_width,
+                        mcu_x + block_x * 8,
+                        mcu_y + block_y * 8,
+                        &mut block,
+                    );
+                    predictions[0] = encode_block(
+                        &mut bits,
+                        &cosines,
+                        &block,
+                        &luma_table,
+                        predictions[0],
+                        &luma_codes,
+                    );
+                }
+            }
+            let (x, y) = (mcu_x / h, mcu_y / v);
+            read_block(&cb, chroma_width, x, y, &mut block);
+            predictions[1] = encode_block(
+                &mut bits,
+                &cosines,
+                &block,
+                &chroma_table,
+                predictions[1],
+                &chroma_codes,
+            );
+            read_block(&cr, chroma_width, x, y, &mut block);
+            predictions[2] = encode_block(
+                &mut bits,
+                &cosines,
+                &block,
+                &chroma_table,
+                predictions[2],
+                &chroma_codes,
+            );
+        }
+    }
+
+    let mut out = bits.finish();
+    out.extend_from_slice(&[0xFF, 0xD9]);
+
+    writer.write_all(&out)?;
+    Ok(writer.flush()?)
+}
+
+// Private functions
+
+// Huffman codes indexed by symbol, as (code, length).
+type HuffmanCodes = [(u16, u8); 256];
+
+// Packs Huffman codes into bytes, stuffing a zero after every 0xFF.
+struct BitWriter {
+    out: Vec<u8>,
+    acc: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    fn new(out: Vec<u8>) -> BitWriter {
+        BitWriter {
+            out,
+            acc: 0,
+            bits: 0,
+        }
+    }
+
+    fn write(&mut self, code: u16, len: u8) {
+        self.acc = (self.acc << len) | (code as u32 & ((1 << len) - 1));
+        self.bits += len;
+        while self.bits >= 8 {
+            self.bits -= 8;
+            let byte = (self.acc >> self.bits) as u8;
+            self.out.push(byte);
+            if byte == 0xFF {
+                self.out.push(0);
+            }
+        }
+        self.acc &= (1 << self.bits) - 1;
+    }
+
+    // Pad the last byte with ones and return the written bytes
+    fn finish(mut self) -> Vec<u8> {
+        let padding = (8 - self.bits % 8) % 8;
+        self.write(0xFF, padding);
+        self.out
+    }
+}
+
+// Scale a quantization table to a quality the same way libjpeg does.
+fn scale_qtable(table: &[u8; 64], quality: u8) -> [u16; 64] {
+    let quality = quality as u32;
+    let scale = if quality < 50 {
+        5000 / quality
+    } else {
+        200 - quality * 2
+    };
+    let mut scaled = [0; 64];
+    for (s, &q) in scaled.iter_mut().zip(table.iter()) {
+        *s = ((q as u32 * scale + 50) / 100).clamp(1, 255) as u16;
+    }
+    scaled
+}
+
+// Build the code of each symbol from the number of codes of each length.
+fn huffman_codes(lengths: &[u8; 16], values: &[u8]) -> HuffmanCodes {
+    let mut codes = [(0, 0); 256];
+    let mut code = 0u16;
+    let mut symbols = values.iter();
+    for (len, &count) in lengths.iter().enumerate() {
+        for _ in 0..count {
+            if let Some(&symbol) = symbols.next() {
+                codes[symbol as usize] = (code, len as u8 + 1);
+            }
+            code += 1;
+        }
+        code <<= 1;
+    }
+    codes
+}
+
+fn write_segment(out: &mut Vec<u8>, marker: u8, data: &[u8]) {
+    let len = data.len() + 2;
+    out.extend_from_slice(&[0xFF, marker, (len >> 8) as u8, len as u8]);
+    out.extend_from_slice(data);
+}
+
+// Convert an image to Y, Cb and Cr planes of the padded size, repeating the edge pixels.
+fn ycbcr_planes(image: &Image, padded_width: usize, padded_height: usize) -> [Vec<f32>; 3] {
+    let width = image.width as usize;
+    let height = image.height as usize;
+    let mut planes = [
+        Vec::with_capacity(padded_width * padded_height),
+        Vec::with_capacity(padded_width * padded_height),
+        Vec::with_capacity(padded_width * padded_height),
+    ];
+    for y in 0..padded_height {
+        for x in 0..padded_width {
+            let index = (cmp::min(y, height - 1) * width + cmp::min(x, width - 1)) * 4;
+            let r = image.bytes[index] as f32;
+            let g = image.bytes[index + 1] as f32;
+            let b = image.bytes[index + 2] as f32;
+            planes[0].push(0.299 * r + 0.587 * g + 0.114 * b);
+            planes[1].push(-0.168_736 * r - 0.331_264 * g + 0.5 * b + 128.0);
+            planes[2].push(0.5 * r - 0.418_688 * g - 0.081_312 * b + 128.0);
+        }
+    }
+    planes
+}
+
+// Average every h by v block of a plane.
+fn downsample(plane: &[f32], width: usize, h: usize, v: usize) -> Vec<f32> {
+    if h == 1 && v == 1 {
+        return plane.to_vec();
+    }
+    let height = plane.len() / width;
+    let mut out = Vec::with_capacity(plane.len() / (h * v));
+    for y in (0..height).step_by(v) {
+        for x in (0..width).step_by(h) {
+            let mut sum = 0.0;
+            for dy in 0..v {
+                for dx in 0..h {
+                    sum += plane[(y + dy) * width + x + dx];
+                }
+            }
+            out.push(sum / (h * v) as f32);
+        }
+    }
+    out
+}
+
+// Copy the 8x8 block at x, y of a plane, shifted to be centered around zero.
+fn read_block(plane: &[f32], width: usize, x: usize, y: usize, block: &mut [f32; 64]) {
+    for row in 0..8 {
+        let start = (y + row) * width + x;
+        for (b, p) in block[row * 8..row * 8 + 8]
+            .iter_mut()
+            .zip(&plane[start..start + 8])
+        {
+            *b = p - 128.0;
+        }
+    }
+}
+
+// Transform, quantize and entropy code a block. Returns the DC coefficient for the next block to
+// be predicted from.
+fn encode_block(
+    bits: &mut BitWriter,
+    cosines: &[[f32; 8]; 8],
+    block: &[f32; 64],
+    table: &[u16; 64],
+    prediction: i32,
+    codes: &(HuffmanCodes, HuffmanCodes),
+) -> i32 {
+    let coefficients = fdct(cosines, block);
+    let mut quantized = [0i32; 64];
+    for (k, &i) in ZIGZAG.iter().enumerate() {
+        quantized[k] = (coefficients[i] / table[i] as f32).round() as i32;
+    }
+
+    let (dc_codes, ac_codes) = codes;
+    let diff = quantized[0] - prediction;
+    let size = magnitude_size(diff);
+    let (code, len) = dc_codes[size as usize];
+    bits.write(code, len);
+    bits.write(magnitude_bits(diff, size), size);
+
+    let mut run = 0;
+    for &value in &quantized[1..] {
+        if value == 0 {
+            run += 1;
+            continue;
+        }
+        // Runs longer than 15 zeros are split with ZRL symbols
+        while run > 15 {
+            let (code, len) = ac_codes[0xF0];
+            bits.write(code, len);
+            run -= 16;
+        }
+        let size = magnitude_size(value);
+        let (code, len) = ac_codes[(run << 4 | size) as usize];
+        bits.write(code, len);
+        bits.write(magnitude_bits(value, size), size);
+        run = 0;
+    }
+    if run > 0 {
+        // End of block
+        let (code, len) = ac_codes[0x00];
+        bits.write(code, len);
+    }
+
+    quantized[0]
+}
+
+// Basis functions of the DCT, scaled as in the JPEG spec.
+fn dct_cosines() -> [[f32; 8]; 8] {
+    let mut cosines = [[0.0f32; 8]; 8];
+    for (u, row) in cosines.iter_mut().enumerate() {
+        let scale = if u == 0 { (0.125f32).sqrt() } else { 0.5 };
+        for (x, c) in row.iter_mut().enumerate() {
+            *c = scale * ((2 * x + 1) as f32 * u as f32 * PI / 16.0).cos();
+        }
+    }
+    cosines
+}
+
+// Forward DCT of an 8x8 block.
+fn fdct(cosines: &[[f32; 8]; 8], block: &[f32; 64]) -> [f32; 64] {
+    // Rows first, then columns
+    let mut rows = [0.0f32; 64];
+    for y in 0..8 {
+        for u in 0..8 {
+            rows[y * 8 + u] = (0..8).map(|x| cosines[u][x] * block[y * 8 + x]).sum();
+        }
+    }
+    let mut out = [0.0f32; 64];
+    for v in 0..8 {
+        for u in 0..8 {
+            out[v * 8 + u] = (0..8).map(|y| cosines[v][y] * rows[y * 8 + u]).sum();
+        }
+    }
+    out
+}
+
+// Number of bits needed for the magnitude of a coefficient.
+fn magnitude_size(value: i32) -> u8 {
+    (32 - value.unsigned_abs().leading_zeros()) as u8
+}
+
+// The bits written after a coefficient's size. Negative values are stored as one's complement.
+fn magnitude_bits(value: i32, size: u8) -> u16 {
+    if value < 0 {
+        (value - 1) as u16 & ((1u32 << size) - 1) as u16
+    } else {
+        value as u16
+    }
+}
+
+fn decode_error(msg: &str) -> RasterError {
+    RasterError::Decode(ImageFormat::Jpeg, msg.to_string())
+}
+
+fn encode_error(msg: &str) -> RasterError {
+    RasterError::Encode(ImageFormat::Jpeg, msg.to_string())
+}
+
+// Find the EXIF data in the APP1 segments that come before the image data.
+fn read_exif(buffer: &[u8]) -> Option<Exif> {
+    let mut pos = 2;
+    while buffer.get(pos) == Some(&0xFF) {
+        // Markers can be padded with any number of 0xFF bytes
+        while buffer.get(pos) == Some(&0xFF) {
+            pos += 1;
+        }
+        let marker = *buffer.get(pos)?;
+        pos += 1;
+        match marker {
+            0x01 | 0xD0..=0xD7 => continue,
+            0xD9 | 0xDA => return None,
+            _ => {}
+        }
+
+        let len = (*buffer.get(pos)? as usize) << 8 | *buffer.get(pos + 1)? as usize;
+        let segment = buffer.get(pos + 2..pos + len.max(2))?;
+        if marker == 0xE1 && segment.starts_with(b"Exif\0\0") {
+            return Exif::parse(&segment[6..]);
+        }
+        pos += len.max(2);
+    }
+    None
+}
@@ -0,0 +1,1231 @@
+//!  A module for encoding/decoding PNG.
+
+// from rust
+use std::cmp;
+use std::io::{self, Read, Write};
+
+// from external crate
+use deflate;
+use inflate;
+use png;
+
+// from local crate
+use animation::{Animation, DisposalMethod, Frame, LoopCount};
+use endec::{self, blend_pixel};
+use error::{RasterError, RasterResult};
+use image;
+use image::ImageInfo;
+use metadata::{ColorSpace, Density, DensityUnit, Metadata, RenderingIntent, TextEntry};
+use options::{CompressionLevel, Limits, PngFilter, PngOptions};
+use stream::{self, ImageRows, RowReader, RowWriter};
+use Image;
+use ImageFormat;
+
+// The first 8 bytes of every PNG
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+
+// Starting x, starting y, x step and y step of the 7 Adam7 passes
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+// Largest IDAT chunk written when encoding a row at a time
+const IDAT_LEN: usize = 0x10000;
+
+// gAMA stores the gamma times 100000
+const GAMMA_SCALE: f32 = 100_000.0;
+
+// Dispose and blend operations of an APNG frame
+const DISPOSE_NONE: u8 = 0;
+const DISPOSE_BACKGROUND: u8 = 1;
+const DISPOSE_PREVIOUS: u8 = 2;
+const BLEND_SOURCE: u8 = 0;
+
+// Decode PNG
+//
+// Every color type and bit depth is normalized to 8 bit RGBA. Palettes are expanded and tRNS
+// transparency is turned into alpha. APNG files give their default image.
+pub fn decode_png<R: Read>(reader: R, limits: &Limits) -> RasterResult<(Image, Metadata)> {
+    let mut chunks = Chunks::new(reader, false, Some(limits));
+    let result = read_image(&mut chunks, limits);
+    if let Some(err) = chunks.error.take() {
+        return Err(err);
+    }
+    Ok((result?, chunks.metadata()))
+}
+
+// Decode all frames of an APNG. Images that are not animated give a single frame.
+//
+// The memory limit applies to all frames together, as for GIF.
+pub fn decode_png_animation<R: Read>(reader: R, limits: &Limits) -> RasterResult<Animation> {
+    let mut reader = read_info(Chunks::new(reader, true, None), limits)?;
+    check_image(&reader, limits)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let (width, height) = reader.info().size();
+    let (num_frames, plays) = match reader.info().animation_control {
+        Some(control) => (control.num_frames, control.num_plays),
+        None => {
+            let image = read_frame(&mut reader, &mut buffer)?;
+            return Ok(Animation::new(vec![Frame::new(image, 0)]));
+        }
+    };
+    if num_frames == 0 {
+        return Err(decode_error("No frames"));
+    }
+
+    // A frame control before the image data makes the default image the first frame,
+    // otherwise it is skipped
+    let mut control = match reader.info().frame_control {
+        Some(control) if control.width != width || control.height != height => {
+            return Err(decode_error("Frame size does not match its header"))
+        }
+        Some(control) => control,
+        None => *reader.next_frame_info().map_err(png_error)?,
+    };
+
+    let (width, height) = (width as usize, height as usize);
+    let canvas_len = width as u64 * height as u64 * 4;
+    // Start with a transparent canvas
+    let mut canvas = Image {
+        width: width as i32,
+        height: height as i32,
+        bytes: vec![0; rgba_len(width, height)?],
+    };
+    let mut frames = Vec::new();
+    for i in 0..num_frames as usize {
+        limits.check_frames(i + 1)?;
+        if i > 0 {
+            control = *reader.next_frame_info().map_err(png_error)?;
+        }
+        // The canvas, a copy to restore, a snapshot for every frame so far and this frame's pixels
+        let frame_len = control.width as u64 * control.height as u64 * 4;
+        limits.check_alloc(canvas_len * (i as u64 + 3) + frame_len)?;
+        let image = read_frame(&mut reader, &mut buffer)?;
+
+        // Previous on the first frame means clearing to the transparent canvas
+        let disposal = match control.dispose_op {
+            png::DisposeOp::Background => DisposalMethod::Background,
+            png::DisposeOp::Previous if i > 0 => DisposalMethod::Previous,
+            png::DisposeOp::Previous => DisposalMethod::Background,
+            png::DisposeOp::None => DisposalMethod::None,
+        };
+        let previous = if disposal == DisposalMethod::Previous {
+            Some(canvas.bytes.clone())
+        } else {
+            None
+        };
+
+        let (left, top) = (control.x_offset as usize, control.y_offset as usize);
+        let w = control.width as usize;
+        for (y, row) in image.bytes.chunks(w * 4).enumerate() {
+            let start = ((top + y) * width + left) * 4;
+            let dest = &mut canvas.bytes[start..start + w * 4];
+            if control.blend_op == png::BlendOp::Over {
+                for (dest, src) in dest.chunks_mut(4).zip(row.chunks(4)) {
+                    blend_pixel(dest, src);
+                }
+            } else {
+                dest.copy_from_slice(row);
+            }
+        }
+
+        frames.push(Frame {
+            image: canvas.clone(),
+            delay: delay(&control),
+            disposal,
+        });
+
+        match previous {
+            Some(previous) => canvas.bytes = previous,
+            None if disposal == DisposalMethod::Background => {
+                for y in top..top + control.height as usize {
+                    let start = (y * width + left) * 4;
+                    for byte in &mut canvas.bytes[start..start + w * 4] {
+                        *byte = 0;
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+
+    Ok(Animation {
+        width: width as i32,
+        height: height as i32,
+        frames,
+        loop_count: match plays {
+            0 => LoopCount::Infinite,
+            _ => LoopCount::Finite(cmp::min(plays, u16::MAX as u32) as u16),
+        },
+    })
+}
+
+// Read the size and color type of a PNG from its header, and the frame count of an APNG
+pub fn probe_png<R: Read>(reader: R) -> RasterResult<ImageInfo> {
+    let mut decoder = png::Decoder::new(Chunks::new(reader, true, None));
+    let mut info = {
+        let header = read_header(&mut decoder)?;
+        ImageInfo {
+            width: header.width as i32,
+            height: header.height as i32,
+            format: ImageFormat::Png,
+            color_type: match header.color_type {
+                png::ColorType::Grayscale => image::ColorType::Gray,
+                png::ColorType::Rgb => image::ColorType::Rgb,
+                png::ColorType::Indexed => image::ColorType::Indexed,
+                png::ColorType::GrayscaleAlpha => image::ColorType::GrayAlpha,
+                png::ColorType::Rgba => image::ColorType::Rgba,
+            },
+            bit_depth: header.bit_depth as u8,
+            frames: 1,
+        }
+    };
+
+    // acTL must come before the image data. A problem after the header leaves a single frame,
+    // the header is all that is needed.
+    if let Ok(reader) = decoder.read_info() {
+        if let Some(control) = reader.info().animation_control {
+            info.frames = cmp::max(control.num_frames as usize, 1);
+        }
+    }
+    Ok(info)
+}
+
+// Encode PNG
+//
+// Images are always written as 8 bit RGBA. The text, color and density metadata is written
+// before the image data.
+pub fn encode_png<W: Write>(
+    image: &Image,
+    mut writer: W,
+    options: &PngOptions,
+    metadata: &Metadata,
+) -> RasterResult<()> {
+    if image.width < 1 || image.height < 1 {
+        return Err(encode_error("Invalid dimensions"));
+    }
+    let header = Header::rgba(image.width as u32, image.height as u32, options.interlaced);
+
+    writer.write_all(&SIGNATURE)?;
+    write_chunk(&mut writer, b"IHDR", &header.to_bytes())?;
+    write_metadata(&mut writer, metadata)?;
+    write_chunk(&mut writer, b"IDAT", &encode_image(image, options))?;
+    write_chunk(&mut writer, b"IEND", &[])?;
+    Ok(writer.flush()?)
+}
+
+// Encode all frames of an animation as APNG
+//
+// The first frame is also the default image shown by viewers without APNG support. Later frames
+// that are not disposed only store the area that changed.
+pub fn encode_png_animation<W: Write>(
+    animation: &Animation,
+    mut writer: W,
+    options: &PngOptions,
+) -> RasterResult<()> {
+    if animation.frames.is_empty() {
+        return Err(encode_error("No frames"));
+    }
+    if animation.width < 1 || animation.height < 1 {
+        return Err(encode_error("Invalid dimensions"));
+    }
+    let (width, height) = (animation.width as u32, animation.height as u32);
+    let header = Header::rgba(width, height, options.interlaced);
+
+    writer.write_all(&SIGNATURE)?;
+    write_chunk(&mut writer, b"IHDR", &header.to_bytes())?;
+    let mut actl = (animation.frames.len() as u32).to_be_bytes().to_vec();
+    let plays = match animation.loop_count {
+        LoopCount::Infinite => 0,
+        LoopCount::Finite(plays) => cmp::max(plays, 1) as u32,
+    };
+    actl.extend_from_slice(&plays.to_be_bytes());
+    write_chunk(&mut writer, b"acTL", &actl)?;
+
+    // What a decoder shows before each frame is drawn
+    let mut canvas = vec![0; animation.frames[0].image.bytes.len()];
+    let mut sequence = 0u32;
+    for (i, frame) in animation.frames.iter().enumerate() {
+        let image = &frame.image;
+        if image.width != animation.width || image.height != animation.height {
+            return Err(encode_error("Frame size does not match animation size"));
+        }
+
+        let (x, y, w, h) = if i > 0 && frame.disposal == DisposalMethod::None {
+            changed_area(&canvas, image)
+        } else {
+            (0, 0, width, height)
+        };
+        let (delay_num, delay_den) = if frame.delay <= u16::MAX as u32 {
+            (frame.delay as u16, 1000u16)
+        } else {
+            (cmp::min(frame.delay / 10, u16::MAX as u32) as u16, 100)
+        };
+        let dispose = match frame.disposal {
+            DisposalMethod::None => DISPOSE_NONE,
+            DisposalMethod::Background => DISPOSE_BACKGROUND,
+            DisposalMethod::Previous => DISPOSE_PREVIOUS,
+        };
+
+        let mut fctl = Vec::with_capacity(26);
+        for value in &[sequence, w, h, x, y] {
+            fctl.extend_from_slice(&value.to_be_bytes());
+        }
+        fctl.extend_from_slice(&delay_num.to_be_bytes());
+        fctl.extend_from_slice(&delay_den.to_be_bytes());
+        fctl.extend_from_slice(&[dispose, BLEND_SOURCE]);
+        write_chunk(&mut writer, b"fcTL", &fctl)?;
+        sequence += 1;
+
+        let data = encode_image(&crop(image, x, y, w, h), options);
+        if i == 0 {
+            write_chunk(&mut writer, b"IDAT", &data)?;
+        } else {
+            let mut fdat = sequence.to_be_bytes().to_vec();
+            fdat.extend_from_slice(&data);
+            write_chunk(&mut writer, b"fdAT", &fdat)?;
+            sequence += 1;
+        }
+
+        match frame.disposal {
+            DisposalMethod::None => canvas.copy_from_slice(&image.bytes),
+            DisposalMethod::Background => canvas.fill(0),
+            DisposalMethod::Previous => {}
+        }
+    }
+
+    write_chunk(&mut writer, b"IEND", &[])?;
+    Ok(writer.flush()?)
+}
+
+// Decode PNG a row at a time
+//
+// Only the rows being decoded are held in memory. Interlaced images spread their rows over 7
+// passes, so they are decoded whole before the first row is returned. Metadata is skipped.
+pub fn decode_png_rows<'a, R: Read + 'a>(
+    reader: R,
+    limits: &Limits,
+) -> RasterResult<Box<dyn RowReader + 'a>> {
+    let mut reader = read_info(Chunks::new(reader, false, None), limits)?;
+    let (width, height) = reader.info().size();
+
+    if reader.info().interlaced {
+        check_image(&reader, limits)?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let image = read_frame(&mut reader, &mut buffer)?;
+        return Ok(Box::new(ImageRows::new(image, ImageFormat::Png)));
+    }
+
+    // The current and previous rows, the decoded row and the RGBA pixels
+    let row_len = reader.output_line_size(width) as u64;
+    endec::check_row_limits(limits, width, height, row_len * 3 + width as u64 * 4)?;
+    Ok(Box::new(PngRows {
+        rgba: Vec::with_capacity(width as usize * 4),
+        reader,
+        width,
+        height,
+        y: 0,
+    }))
+}
+
+// Encode PNG a row at a time
+//
+// The header is written right away and the image data as the rows come in. Interlaced images
+// need every row at once, so they can't be written this way.
+pub fn encode_png_rows<'a, W: Write + 'a>(
+    mut writer: W,
+    width: i32,
+    height: i32,
+    options: &PngOptions,
+) -> RasterResult<Box<dyn RowWriter + 'a>> {
+    if width < 1 || height < 1 {
+        return Err(encode_error("Invalid dimensions"));
+    }
+    if options.interlaced {
+        return Err(encode_error(
+            "Interlaced images can't be written a row at a time",
+        ));
+    }
+    let header = Header::rgba(width as u32, height as u32, false);
+
+    writer.write_all(&SIGNATURE)?;
+    write_chunk(&mut writer, b"IHDR", &header.to_bytes())?;
+    let data = ImageDataWriter {
+        writer,
+        buffer: Vec::with_capacity(IDAT_LEN),
+    };
+    let row_len = width as usize * 4;
+    Ok(Box::new(PngRowWriter {
+        encoder: Some(deflate::write::ZlibEncoder::new(
+            data,
+            compression(options.compression),
+        )),
+        filter: filter_type(options.filter),
+        prev: vec![0; row_len],
+        data: Vec::with_capacity(row_len + 1),
+        candidates: (0..5).map(|_| Vec::with_capacity(row_len)).collect(),
+        width,
+        height,
+        y: 0,
+    }))
+}
+
+// Private functions
+
+// Header of an 8 bit RGBA image, the only kind that is written.
+struct Header {
+    width: u32,
+    height: u32,
+    interlaced: bool,
+}
+
+impl Header {
+    fn rgba(width: u32, height: u32, interlaced: bool) -> Header {
+        Header {
+            width,
+            height,
+            interlaced,
+        }
+    }
+
+    // Contents of the IHDR chunk for this header
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(13);
+        data.extend_from_slice(&self.width.to_be_bytes());
+        data.extend_from_slice(&self.height.to_be_bytes());
+        data.extend_from_slice(&[8, 6, 0, 0, self.interlaced as u8]);
+        data
+    }
+
+    // The passes the image data is stored in. A non-interlaced image is a single pass.
+    fn passes(&self) -> &'static [(usize, usize, usize, usize)] {
+        if self.interlaced {
+            &ADAM7
+        } else {
+            &[(0, 0, 1, 1)]
+        }
+    }
+}
+
+// Passes the chunks of a PNG on to the png crate, taking out the text, color and density chunks
+// to read them here, so that a chunk that is not valid is skipped instead of failing the image.
+// The chunks of an APNG are taken out too when only the default image is wanted.
+struct Chunks<'a, R: Read> {
+    reader: R,
+    frames: bool,
+    // Limits for reading the metadata, None to skip it
+    limits: Option<&'a Limits>,
+    metadata: Metadata,
+    // Error reading the metadata, which the png crate only sees as an I/O error
+    error: Option<RasterError>,
+    // Header of the current chunk and how much of it is passed on
+    header: [u8; 8],
+    header_pos: usize,
+    // Bytes of the current chunk left to pass on, including its CRC
+    remaining: u64,
+}
+
+impl<'a, R: Read> Chunks<'a, R> {
+    fn new(reader: R, frames: bool, limits: Option<&'a Limits>) -> Chunks<'a, R> {
+        Chunks {
+            reader,
+            frames,
+            limits,
+            metadata: Metadata::default(),
+            error: None,
+            header: [0; 8],
+            header_pos: 8,
+            // The signature is passed on first
+            remaining: SIGNATURE.len() as u64,
+        }
+    }
+
+    // The metadata read from the chunks that were taken out
+    fn metadata(self) -> Metadata {
+        let mut metadata = self.metadata;
+        // A gamma of 1.0 without an sRGB chunk means linear color values
+        if metadata.color_space.is_none() && metadata.gamma == Some(1.0) {
+            metadata.color_space = Some(ColorSpace::Linear);
+        }
+        metadata
+    }
+
+    // Whether a chunk is taken out of the stream
+    fn is_removed(&self, name: &[u8]) -> bool {
+        match name {
+            b"tEXt" | b"zTXt" | b"iTXt" | b"gAMA" | b"sRGB" | b"pHYs" | b"iCCP" => true,
+            b"acTL" | b"fcTL" | b"fdAT" => !self.frames,
+            _ => false,
+        }
+    }
+
+    // Read the header of the next chunk, reading the chunks that are taken out along the way.
+    // Returns false at the end of the file, which the png crate reports.
+    fn next_chunk(&mut self) -> io::Result<bool> {
+        loop {
+            let mut header = [0; 8];
+            if !read_all(&mut self.reader, &mut header)? {
+                return Ok(false);
+            }
+            let len = be_u32(&header[0..4]);
+            if !self.is_removed(&header[4..8]) {
+                self.header = header;
+                self.header_pos = 0;
+                self.remaining = len as u64 + 4;
+                return Ok(true);
+            }
+
+            // Don't trust the length enough to allocate it up front
+            let mut data = Vec::new();
+            (&mut self.reader).take(len as u64).read_to_end(&mut data)?;
+            let mut crc = [0; 4];
+            if data.len() != len as usize || !read_all(&mut self.reader, &mut crc)? {
+                return Ok(false);
+            }
+            let limits = match self.limits {
+                Some(limits) if be_u32(&crc) == crc32(&[&header[4..8], &data]) => limits,
+                _ => continue,
+            };
+            let mut name = [0; 4];
+            name.copy_from_slice(&header[4..8]);
+            if let Err(err) = read_metadata(&mut self.metadata, &name, &data, limits) {
+                self.error = Some(err);
+                return Err(io::Error::other("Invalid metadata"));
+            }
+        }
+    }
+}
+
+impl<'a, R: Read> Read for Chunks<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.header_pos < self.header.len() {
+                let len = cmp::min(buf.len(), self.header.len() - self.header_pos);
+                buf[..len].copy_from_slice(&self.header[self.header_pos..self.header_pos + len]);
+                self.header_pos += len;
+                return Ok(len);
+            }
+            if self.remaining > 0 {
+                let len = cmp::min(buf.len() as u64, self.remaining) as usize;
+                let read = self.reader.read(&mut buf[..len])?;
+                self.remaining -= read as u64;
+                return Ok(read);
+            }
+            if !self.next_chunk()? {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+// Decodes the rows of a non-interlaced image as they are inflated.
+struct PngRows<R: Read> {
+    reader: png::Reader<R>,
+    rgba: Vec<u8>,
+    width: u32,
+    height: u32,
+    y: u32,
+}
+
+impl<R: Read> RowReader for PngRows<R> {
+    fn width(&self) -> i32 {
+        self.width as i32
+    }
+
+    fn height(&self) -> i32 {
+        self.height as i32
+    }
+
+    fn read_row(&mut self, row: &mut [u8]) -> RasterResult<bool> {
+        if self.y >= self.height {
+            return Ok(false);
+        }
+        stream::check_read(ImageFormat::Png, self.width as i32, row)?;
+        let (color_type, bit_depth) = self.reader.output_color_type();
+        let samples = match self.reader.next_row().map_err(png_error)? {
+            Some(samples) => samples,
+            None => return Err(decode_error("Image truncated")),
+        };
+        self.rgba.clear();
+        expand(samples.data(), color_type, bit_depth, &mut self.rgba);
+        row.copy_from_slice(&self.rgba);
+        self.y += 1;
+        if self.y == self.height {
+            // Check the checksums at the end of the image data
+            self.reader.finish().map_err(png_error)?;
+        }
+        Ok(true)
+    }
+}
+
+// Splits compressed image data into IDAT chunks.
+struct ImageDataWriter<W: Write> {
+    writer: W,
+    buffer: Vec<u8>,
+}
+
+impl<W: Write> ImageDataWriter<W> {
+    // Write the buffered data as an IDAT chunk.
+    fn write_buffer(&mut self) -> io::Result<()> {
+        if !self.buffer.is_empty() {
+            write_chunk_data(&mut self.writer, b"IDAT", &self.buffer)?;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for ImageDataWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = cmp::min(buf.len(), IDAT_LEN - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        if self.buffer.len() == IDAT_LEN {
+            self.write_buffer()?;
+        }
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+// Filters and compresses rows of 8 bit RGBA as they are written.
+struct PngRowWriter<W: Write> {
+    // None once finished
+    encoder: Option<deflate::write::ZlibEncoder<ImageDataWriter<W>>>,
+    filter: Option<u8>,
+    prev: Vec<u8>,
+    // Filter type and filtered samples of the current row
+    data: Vec<u8>,
+    candidates: Vec<Vec<u8>>,
+    width: i32,
+    height: i32,
+    y: i32,
+}
+
+impl<W: Write> RowWriter for PngRowWriter<W> {
+    fn width(&self) -> i32 {
+        self.width
+    }
+
+    fn height(&self) -> i32 {
+        self.height
+    }
+
+    fn write_row(&mut self, row: &[u8]) -> RasterResult<()> {
+        stream::check_write(ImageFormat::Png, self.width, self.height, self.y, row)?;
+        self.data.clear();
+        filter_best(
+            self.filter,
+            4,
+            &self.prev,
+            row,
+            &mut self.candidates,
+            &mut self.data,
+        );
+        if let Some(encoder) = self.encoder.as_mut() {
+            encoder.write_all(&self.data)?;
+        }
+        self.prev.copy_from_slice(row);
+        self.y += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> RasterResult<()> {
+        stream::check_finished(ImageFormat::Png, self.height, self.y)?;
+        if let Some(encoder) = self.encoder.take() {
+            let mut data = encoder.finish()?;
+            data.write_buffer()?;
+            write_chunk(&mut data.writer, b"IEND", &[])?;
+            data.writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+// Start decoding a PNG with the png crate, reading the chunks up to the image data. Samples are
+// expanded to grayscale or RGB with an optional alpha channel, of 8 or 16 bits.
+fn read_info<R: Read>(reader: R, limits: &Limits) -> RasterResult<png::Reader<R>> {
+    let max_alloc = limits.max_alloc.unwrap_or(u64::MAX);
+    let mut decoder = png::Decoder::new_with_limits(
+        reader,
+        png::Limits {
+            bytes: cmp::min(max_alloc, usize::MAX as u64) as usize,
+        },
+    );
+    decoder.set_transformations(png::Transformations::EXPAND);
+    read_header(&mut decoder)?;
+    decoder.read_info().map_err(png_error)
+}
+
+// Read the IHDR chunk. Images are at most i32::MAX pixels wide and high.
+fn read_header<R: Read>(decoder: &mut png::Decoder<R>) -> RasterResult<&png::Info<'static>> {
+    let info = decoder.read_header_info().map_err(png_error)?;
+    if info.width > i32::MAX as u32 || info.height > i32::MAX as u32 {
+        return Err(decode_error("Invalid dimensions"));
+    }
+    Ok(info)
+}
+
+// Check the size of an image that is decoded whole against the limits.
+fn check_image<R: Read>(reader: &png::Reader<R>, limits: &Limits) -> RasterResult<()> {
+    let (width, height) = reader.info().size();
+    limits.check_dimensions(width, height)?;
+    // The decoded samples and the RGBA pixels are held at the same time
+    let len = (reader.output_buffer_size() as u64)
+        .checked_add(width as u64 * height as u64 * 4)
+        .ok_or_else(|| decode_error("Image too large"))?;
+    limits.check_alloc(len)
+}
+
+// Decode a PNG that is not animated, or the default image of an APNG.
+fn read_image<R: Read>(reader: R, limits: &Limits) -> RasterResult<Image> {
+    let mut reader = read_info(reader, limits)?;
+    check_image(&reader, limits)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let image = read_frame(&mut reader, &mut buffer)?;
+    // The metadata after the image data is read too
+    reader.finish().map_err(png_error)?;
+    Ok(image)
+}
+
+// Decode the next frame into buffer, which must fit the whole image, and convert it to RGBA.
+fn read_frame<R: Read>(reader: &mut png::Reader<R>, buffer: &mut [u8]) -> RasterResult<Image> {
+    let output = reader.next_frame(buffer).map_err(png_error)?;
+    let (width, height) = (output.width as usize, output.height as usize);
+    let mut bytes = Vec::with_capacity(rgba_len(width, height)?);
+    expand(
+        &buffer[..output.buffer_size()],
+        output.color_type,
+        output.bit_depth,
+        &mut bytes,
+    );
+    Ok(Image {
+        width: width as i32,
+        height: height as i32,
+        bytes,
+    })
+}
+
+// Append the pixels of expanded samples to out as 8 bit RGBA. 16 bit samples are rounded.
+fn expand(samples: &[u8], color_type: png::ColorType, bit_depth: png::BitDepth, out: &mut Vec<u8>) {
+    let depth = if bit_depth == png::BitDepth::Sixteen {
+        2
+    } else {
+        1
+    };
+    let sample = |pixel: &[u8], i: usize| match depth {
+        2 => ((be_u16(&pixel[i * 2..]) as u32 * 255 + 32895) >> 16) as u8,
+        _ => pixel[i],
+    };
+    for pixel in samples.chunks_exact(color_type.samples() * depth) {
+        let rgba = match color_type {
+            png::ColorType::Grayscale => {
+                let l = sample(pixel, 0);
+                [l, l, l, 255]
+            }
+            png::ColorType::GrayscaleAlpha => {
+                let l = sample(pixel, 0);
+                [l, l, l, sample(pixel, 1)]
+            }
+            png::ColorType::Rgb => [sample(pixel, 0), sample(pixel, 1), sample(pixel, 2), 255],
+            _ => [
+                sample(pixel, 0),
+                sample(pixel, 1),
+                sample(pixel, 2),
+                sample(pixel, 3),
+            ],
+        };
+        out.extend_from_slice(&rgba);
+    }
+}
+
+// The delay of an APNG frame in milliseconds.
+fn delay(control: &png::FrameControl) -> u32 {
+    // A denominator of 0 means hundredths of a second
+    let den = match control.delay_den {
+        0 => 100,
+        den => den as u32,
+    };
+    (control.delay_num as u32 * 1000 + den / 2) / den
+}
+
+// Read a text, color or density chunk into the metadata. Chunks that are not valid are skipped.
+fn read_metadata(
+    metadata: &mut Metadata,
+    name: &[u8; 4],
+    data: &[u8],
+    limits: &Limits,
+) -> RasterResult<()> {
+    match name {
+        b"tEXt" | b"zTXt" | b"iTXt" => {
+            if let Some(entry) = read_text(name, data, limits)? {
+                metadata.text.push(entry);
+            }
+        }
+        b"gAMA" if data.len() == 4 => {
+            metadata.gamma = Some(be_u32(data) as f32 / GAMMA_SCALE);
+        }
+        b"sRGB" if data.len() == 1 => {
+            metadata.color_space = Some(ColorSpace::Srgb);
+            metadata.rendering_intent = match data[0] {
+                0 => Some(RenderingIntent::Perceptual),
+                1 => Some(RenderingIntent::RelativeColorimetric),
+                2 => Some(RenderingIntent::Saturation),
+                3 => Some(RenderingIntent::AbsoluteColorimetric),
+                _ => None,
+            };
+        }
+        b"pHYs" if data.len() == 9 && data[8] <= 1 => {
+            metadata.density = Some(Density {
+                x: be_u32(&data[0..4]),
+                y: be_u32(&data[4..8]),
+                unit: if data[8] == 1 {
+                    DensityUnit::Meter
+                } else {
+                    DensityUnit::Unknown
+                },
+            });
+        }
+        b"iCCP" => {
+            // The profile name is not kept
+            if let Some(end) = data.iter().position(|&b| b == 0) {
+                if data.get(end + 1) == Some(&0) {
+                    metadata.icc_profile = inflate_limited(&data[end + 2..], limits)?;
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+// Read a tEXt, zTXt or iTXt chunk.
+fn read_text(name: &[u8; 4], data: &[u8], limits: &Limits) -> RasterResult<Option<TextEntry>> {
+    let end = match data.iter().position(|&b| b == 0) {
+        Some(end) if (1..=79).contains(&end) => end,
+        _ => return Ok(None),
+    };
+    let mut entry = TextEntry::new(&latin1(&data[..end]), "");
+    let data = &data[end + 1..];
+    match name {
+        b"tEXt" => entry.text = latin1(data),
+        b"zTXt" => {
+            entry.compressed = true;
+            match data.split_first() {
+                Some((0, data)) => match inflate_limited(data, limits)? {
+                    Some(text) => entry.text = latin1(&text),
+                    None => return Ok(None),
+                },
+                _ => return Ok(None),
+            }
+        }
+        _ => {
+            // Compression flag and method, then the language and the translated keyword
+            if data.len() < 2 || data[1] != 0 {
+                return Ok(None);
+            }
+            entry.compressed = data[0] == 1;
+            let mut parts = data[2..].splitn(3, |&b| b == 0);
+            let (language, translated, text) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(language), Some(translated), Some(text)) => (language, translated, text),
+                _ => return Ok(None),
+            };
+            let text = if entry.compressed {
+                match inflate_limited(text, limits)? {
+                    Some(text) => text,
+                    None => return Ok(None),
+                }
+            } else {
+                text.to_vec()
+            };
+            match (
+                String::from_utf8(language.to_vec()),
+                String::from_utf8(translated.to_vec()),
+                String::from_utf8(text),
+            ) {
+                (Ok(language), Ok(translated), Ok(text)) => {
+                    entry.language = language;
+                    entry.translated_keyword = translated;
+                    entry.text = text;
+                }
+                _ => return Ok(None),
+            }
+        }
+    }
+    Ok(Some(entry))
+}
+
+// Write the chunks for the metadata that PNG can store.
+fn write_metadata<W: Write>(writer: &mut W, metadata: &Metadata) -> RasterResult<()> {
+    // A profile replaces the sRGB chunk
+    if let Some(ref profile) = metadata.icc_profile {
+        let mut data = b"ICC profile\0\0".to_vec();
+        data.extend_from_slice(&deflate::deflate_bytes_zlib(profile));
+        write_chunk(writer, b"iCCP", &data)?;
+    } else if metadata.color_space == Some(ColorSpace::Srgb) {
+        let intent = match metadata.rendering_intent {
+            Some(RenderingIntent::Perceptual) | None => 0,
+            Some(RenderingIntent::RelativeColorimetric) => 1,
+            Some(RenderingIntent::Saturation) => 2,
+            Some(RenderingIntent::AbsoluteColorimetric) => 3,
+        };
+        write_chunk(writer, b"sRGB", &[intent])?;
+    }
+
+    let gamma = match (metadata.gamma, metadata.color_space) {
+        (Some(gamma), _) => Some(gamma),
+        (None, Some(ColorSpace::Linear)) => Some(1.0),
+        _ => None,
+    };
+    if let Some(gamma) = gamma {
+        let value = (gamma * GAMMA_SCALE).round();
+        if !(value >= 1.0 && value <= u32::MAX as f32) {
+            return Err(RasterError::InvalidGamma(gamma));
+        }
+        write_chunk(writer, b"gAMA", &(value as u32).to_be_bytes())?;
+    }
+
+    if let Some(density) = metadata.density {
+        let mut data = density.x.to_be_bytes().to_vec();
+        data.extend_from_slice(&density.y.to_be_bytes());
+        data.push((density.unit == DensityUnit::Meter) as u8);
+        write_chunk(writer, b"pHYs", &data)?;
+    }
+
+    for entry in &metadata.text {
+        let keyword = match to_latin1(&entry.keyword) {
+            Some(keyword) if (1..=79).contains(&keyword.len()) && !keyword.contains(&0) => keyword,
+            _ => return Err(encode_error("Invalid text keyword")),
+        };
+        let mut data = keyword;
+        data.push(0);
+        let latin1_text = to_latin1(&entry.text);
+        match latin1_text {
+            Some(text) if entry.language.is_empty() && entry.translated_keyword.is_empty() => {
+                if entry.compressed {
+                    data.push(0);
+                    data.extend_from_slice(&deflate::deflate_bytes_zlib(&text));
+                    write_chunk(writer, b"zTXt", &data)?;
+                } else {
+                    data.extend_from_slice(&text);
+                    write_chunk(writer, b"tEXt", &data)?;
+                }
+            }
+            _ => {
+                data.extend_from_slice(&[entry.compressed as u8, 0]);
+                data.extend_from_slice(entry.language.as_bytes());
+                data.push(0);
+                data.extend_from_slice(entry.translated_keyword.as_bytes());
+                data.push(0);
+                if entry.compressed {
+                    data.extend_from_slice(&deflate::deflate_bytes_zlib(entry.text.as_bytes()));
+                } else {
+                    data.extend_from_slice(entry.text.as_bytes());
+                }
+                write_chunk(writer, b"iTXt", &data)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+// Inflate a zlib stream of metadata. Returns `None` if the stream is not valid.
+fn inflate_limited(data: &[u8], limits: &Limits) -> RasterResult<Option<Vec<u8>>> {
+    let max = limits.max_alloc.map_or(u64::MAX, |max| max + 1);
+    let mut out = Vec::new();
+    if inflate::DeflateDecoder::from_zlib(data)
+        .take(max)
+        .read_to_end(&mut out)
+        .is_err()
+    {
+        return Ok(None);
+    }
+    limits.check_alloc(out.len() as u64)?;
+    Ok(Some(out))
+}
+
+// Convert Latin-1 bytes to a string.
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&b| b as char).collect()
+}
+
+// Convert a string to Latin-1 bytes. Returns `None` if it has other characters.
+fn to_latin1(text: &str) -> Option<Vec<u8>> {
+    text.chars()
+        .map(|c| {
+            if (c as u32) < 256 {
+                Some(c as u8)
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+// Filter and compress the pixels of an image as 8 bit RGBA.
+fn encode_image(image: &Image, options: &PngOptions) -> Vec<u8> {
+    let header = Header::rgba(image.width as u32, image.height as u32, options.interlaced);
+    let width = image.width as usize;
+    let height = image.height as usize;
+    let filter = filter_type(options.filter);
+    // The samples and a filter type for each row
+    let mut data = Vec::with_capacity(image.bytes.len() + height);
+    let mut row = Vec::with_capacity(width * 4);
+    let mut candidates: Vec<Vec<u8>> = (0..5).map(|_| Vec::with_capacity(width * 4)).collect();
+    for &(x0, y0, dx, dy) in header.passes() {
+        let pass_width = (width + dx - 1 - x0) / dx;
+        let pass_height = (height + dy - 1 - y0) / dy;
+        if pass_width == 0 || pass_height == 0 {
+            continue;
+        }
+
+        let mut prev = vec![0; pass_width * 4];
+        for pass_y in 0..pass_height {
+            let y = y0 + pass_y * dy;
+            row.clear();
+            for pass_x in 0..pass_width {
+                let index = (y * width + x0 + pass_x * dx) * 4;
+                row.extend_from_slice(&image.bytes[index..index + 4]);
+            }
+
+            filter_best(filter, 4, &prev, &row, &mut candidates, &mut data);
+            prev.copy_from_slice(&row);
+        }
+    }
+
+    deflate::deflate_bytes_zlib_conf(&data, compression(options.compression))
+}
+
+// The filter type for a filter option, None to choose one for every row.
+fn filter_type(filter: PngFilter) -> Option<u8> {
+    match filter {
+        PngFilter::None => Some(0),
+        PngFilter::Sub => Some(1),
+        PngFilter::Up => Some(2),
+        PngFilter::Average => Some(3),
+        PngFilter::Paeth => Some(4),
+        PngFilter::Adaptive => None,
+    }
+}
+
+fn compression(level: CompressionLevel) -> deflate::Compression {
+    match level {
+        CompressionLevel::Fast => deflate::Compression::Fast,
+        CompressionLevel::Default => deflate::Compression::Default,
+        CompressionLevel::Best => deflate::Compression::Best,
+    }
+}
+
+// Append the filter type and the filtered row to out. Without a filter type, the one with the
+// smallest sum of absolute differences is picked, using candidates as scratch space.
+fn filter_best(
+    filter: Option<u8>,
+    bpp: usize,
+    prev: &[u8],
+    row: &[u8],
+    candidates: &mut [Vec<u8>],
+    out: &mut Vec<u8>,
+) {
+    if let Some(filter) = filter {
+        out.push(filter);
+        filter_row(filter, bpp, prev, row, out);
+        return;
+    }
+
+    for (filter, candidate) in candidates.iter_mut().enumerate() {
+        candidate.clear();
+        filter_row(filter as u8, bpp, prev, row, candidate);
+    }
+    let (filter, best) = candidates
+        .iter()
+        .enumerate()
+        .min_by_key(|&(_, candidate)| {
+            candidate
+                .iter()
+                .map(|&b| (b as i8).unsigned_abs() as u64)
+                .sum::<u64>()
+        })
+        .unwrap();
+    out.push(filter as u8);
+    out.extend_from_slice(best);
+}
+
+// The smallest area of an image that differs from the canvas, as x, y, width and height. An
+// unchanged image gives a single pixel.
+fn changed_area(canvas: &[u8], image: &Image) -> (u32, u32, u32, u32) {
+    let width = image.width as usize;
+    let (mut left, mut top, mut right, mut bottom) = (width, image.height as usize, 0, 0);
+    for (i, (a, b)) in canvas.chunks(4).zip(image.bytes.chunks(4)).enumerate() {
+        if a != b {
+            let (x, y) = (i % width, i / width);
+            left = cmp::min(left, x);
+            top = cmp::min(top, y);
+            right = cmp::max(right, x + 1);
+            bottom = cmp::max(bottom, y + 1);
+        }
+    }
+    if right == 0 {
+        (0, 0, 1, 1)
+    } else {
+        (
+            left as u32,
+            top as u32,
+            (right - left) as u32,
+            (bottom - top) as u32,
+        )
+    }
+}
+
+// Copy an area of an image.
+fn crop(image: &Image, x: u32, y: u32, width: u32, height: u32) -> Image {
+    let (x, y, w) = (x as usize, y as usize, width as usize);
+    let mut bytes = Vec::with_capacity(w * height as usize * 4);
+    for row in image
+        .bytes
+        .chunks(image.width as usize * 4)
+        .skip(y)
+        .take(height as usize)
+    {
+        bytes.extend_from_slice(&row[x * 4..(x + w) * 4]);
+    }
+    Image {
+        width: width as i32,
+        height: height as i32,
+        bytes,
+    }
+}
+
+// Number of bytes of RGBA pixels of an image of the given size.
+fn rgba_len(width: usize, height: usize) -> RasterResult<usize> {
+    width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(4))
+        .ok_or_else(|| decode_error("Image too large"))
+}
+
+// Filter a row and append it to out.
+fn filter_row(filter: u8, bpp: usize, prev: &[u8], row: &[u8], out: &mut Vec<u8>) {
+    for i in 0..row.len() {
+        let left = if i >= bpp { row[i - bpp] } else { 0 };
+        let up_left = if i >= bpp { prev[i - bpp] } else { 0 };
+        let predictor = match filter {
+            1 => left,
+            2 => prev[i],
+            3 => ((left as u16 + prev[i] as u16) / 2) as u8,
+            4 => paeth(left, prev[i], up_left),
+            _ => 0,
+        };
+        out.push(row[i].wrapping_sub(predictor));
+    }
+}
+
+// Paeth predictor
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+// Fill buf from the reader. Returns false if the file ends first.
+fn read_all<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<bool> {
+    match reader.read_exact(buf) {
+        Ok(()) => Ok(true),
+        Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+// Write a chunk with its length and CRC.
+fn write_chunk<W: Write>(writer: &mut W, name: &[u8; 4], data: &[u8]) -> RasterResult<()> {
+    if data.len() > i32::MAX as usize {
+        return Err(encode_error("Image too large"));
+    }
+    Ok(write_chunk_data(writer, name, data)?)
+}
+
+// Write a chunk that is known to be small enough.
+fn write_chunk_data<W: Write>(writer: &mut W, name: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(name)?;
+    writer.write_all(data)?;
+    writer.write_all(&crc32(&[name, data]).to_be_bytes())
+}
+
+// Lookup table for the CRC-32 polynomial used by PNG.
+const CRC_TABLE: [u32; 256] = crc_table();
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0; 256];
+    let mut n = 0;
+    while n < 256 {
+        let mut c = n as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 {
+                0xEDB8_8320 ^ (c >> 1)
+            } else {
+                c >> 1
+            };
+            k += 1;
+        }
+        table[n] = c;
+        n += 1;
+    }
+    table
+}
+
+// CRC-32 as used by PNG chunks, computed over several slices.
+fn crc32(parts: &[&[u8]]) -> u32 {
+    !parts.iter().fold(!0, |crc, part| crc_update(crc, part))
+}
+
+// Add bytes to a running CRC that starts with all bits set and is inverted at the end.
+fn crc_update(mut crc: u32, bytes: &[u8]) -> u32 {
+    for &byte in bytes {
+        crc = CRC_TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+fn be_u16(bytes: &[u8]) -> u16 {
+    (bytes[0] as u16) << 8 | bytes[1] as u16
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    (bytes[0] as u32) << 24 | (bytes[1] as u32) << 16 | (bytes[2] as u32) << 8 | bytes[3] as u32
+}
+
+// Turn an error of the png crate into a decoding error. Errors of the reader itself are kept,
+// but a file that ends early is a decoding error.
+fn png_error(err: png::DecodingError) -> RasterError {
+    match err {
+        png::DecodingError::IoError(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+            decode_error("Image truncated")
+        }
+        err => RasterError::from(err),
+    }
+}
+
+fn decode_error(msg: &str) -> RasterError {
+    RasterError::Decode(ImageFormat::Png, msg.to_string())
+}
+
+fn encode_error(msg: &str) -> RasterError {
+    RasterError::Encode(ImageFormat::Png, msg.to_string())
+}
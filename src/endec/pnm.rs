@@ -0,0 +1,467 @@
+//!  A module for encoding/decoding the Netpbm formats PBM, PGM, PPM and PAM.
+
+// from rust
+use std::io::{Read, Write};
+
+// from external crate
+
+// from local crate
+use endec::read_limited;
+use error::{RasterError, RasterResult};
+use image::{ColorType, ImageInfo};
+use options::{Limits, PnmOptions};
+use Image;
+use ImageFormat;
+
+// Longest line allowed in the plain formats
+const MAX_LINE_LEN: usize = 70;
+
+// Decode PBM, PGM, PPM or PAM
+//
+// Samples of any maximum value are scaled to 8 bits. Only the first image of a file with several
+// is read.
+pub fn decode_pnm<R: Read>(reader: R, limits: &Limits) -> RasterResult<Image> {
+    let buffer = read_limited(reader, limits)?;
+    let header = Header::parse(&buffer)?;
+    limits.check_dimensions(header.width, header.height)?;
+
+    let width = header.width as usize;
+    let height = header.height as usize;
+    let count = width * height * header.depth;
+    // The samples and the RGBA pixels are held at the same time
+    limits.check_alloc(count as u64 * 4 + width as u64 * height as u64 * 4)?;
+
+    let data = &buffer[header.data_start..];
+    let samples = if header.ascii {
+        read_ascii_samples(data, count, header.format)?
+    } else if header.magic == b'4' {
+        read_bits(data, width, height)?
+    } else {
+        read_binary_samples(data, count, header.maxval, header.format)?
+    };
+
+    let maxval = header.maxval as u64;
+    let scale = |v: u32| ((v.min(header.maxval) as u64 * 255 + maxval / 2) / maxval) as u8;
+    let mut bytes = Vec::with_capacity(width * height * 4);
+    for pixel in samples.chunks(header.depth) {
+        match header.tuple_type {
+            TupleType::BlackAndWhite => {
+                // PBM stores black as 1, PAM stores white as 1
+                let white = (pixel[0] != 0) != (header.format == ImageFormat::Pbm);
+                let l = if white { 255 } else { 0 };
+                bytes.extend_from_slice(&[l, l, l, 255]);
+            }
+            TupleType::Gray => {
+                let l = scale(pixel[0]);
+                bytes.extend_from_slice(&[l, l, l, 255]);
+            }
+            TupleType::GrayAlpha => {
+                let l = scale(pixel[0]);
+                bytes.extend_from_slice(&[l, l, l, scale(pixel[1])]);
+            }
+            TupleType::Rgb => {
+                bytes.extend_from_slice(&[scale(pixel[0]), scale(pixel[1]), scale(pixel[2]), 255]);
+            }
+            TupleType::RgbAlpha => {
+                bytes.extend_from_slice(&[
+                    scale(pixel[0]),
+                    scale(pixel[1]),
+                    scale(pixel[2]),
+                    scale(pixel[3]),
+                ]);
+            }
+        }
+    }
+
+    Ok(Image {
+        width: width as i32,
+        height: height as i32,
+        bytes,
+    })
+}
+
+// Read the size and color type of a Netpbm image from its header
+pub fn probe_pnm<R: Read>(reader: R) -> RasterResult<ImageInfo> {
+    // Headers are small, but comments can make them any length
+    let mut buffer = Vec::new();
+    reader.take(64 * 1024).read_to_end(&mut buffer)?;
+    let header = Header::parse(&buffer)?;
+
+    let color_type = match header.tuple_type {
+        TupleType::BlackAndWhite | TupleType::Gray => ColorType::Gray,
+        TupleType::GrayAlpha => ColorType::GrayAlpha,
+        TupleType::Rgb => ColorType::Rgb,
+        TupleType::RgbAlpha => ColorType::Rgba,
+    };
+    Ok(ImageInfo {
+        width: header.width as i32,
+        height: header.height as i32,
+        format: header.format,
+        color_type,
+        // Bits needed to hold the maximum value
+        bit_depth: (32 - header.maxval.leading_zeros()) as u8,
+        frames: 1,
+    })
+}
+
+// Encode PBM, PGM, PPM or PAM
+//
+// Samples are written with a maximum value of 255. PBM and PGM use the luma of each pixel, and
+// only PAM keeps the alpha channel. PAM has no plain variant, so options.ascii is ignored for it.
+pub fn encode_pnm<W: Write>(
+    image: &Image,
+    mut writer: W,
+    format: ImageFormat,
+    options: &PnmOptions,
+) -> RasterResult<()> {
+    if image.width < 1 || image.height < 1 {
+        return Err(encode_error(format, "Invalid dimensions"));
+    }
+    let width = image.width as usize;
+    let ascii = options.ascii && format != ImageFormat::Pam;
+
+    let mut out = Vec::new();
+    let magic = match (format, ascii) {
+        (ImageFormat::Pbm, true) => 1,
+        (ImageFormat::Pgm, true) => 2,
+        (ImageFormat::Ppm, true) => 3,
+        (ImageFormat::Pbm, false) => 4,
+        (ImageFormat::Pgm, false) => 5,
+        (ImageFormat::Ppm, false) => 6,
+        _ => 7,
+    };
+    if magic == 7 {
+        out.extend_from_slice(
+            format!(
+                "P7\nWIDTH {}\nHEIGHT {}\nDEPTH 4\nMAXVAL 255\nTUPLTYPE RGB_ALPHA\nENDHDR\n",
+                image.width, image.height
+            )
+            .as_bytes(),
+        );
+    } else if format == ImageFormat::Pbm {
+        out.extend_from_slice(format!("P{}\n{} {}\n", magic, image.width, image.height).as_bytes());
+    } else {
+        out.extend_from_slice(
+            format!("P{}\n{} {}\n255\n", magic, image.width, image.height).as_bytes(),
+        );
+    }
+
+    let luma =
+        |p: &[u8]| (p[0] as f32 * 0.3 + p[1] as f32 * 0.59 + p[2] as f32 * 0.11).round() as u8;
+    let samples: Vec<u8> = match format {
+        // PBM stores black as 1
+        ImageFormat::Pbm => image
+            .bytes
+            .chunks(4)
+            .map(|p| (luma(p) < 128) as u8)
+            .collect(),
+        ImageFormat::Pgm => image.bytes.chunks(4).map(luma).collect(),
+        ImageFormat::Ppm => image
+            .bytes
+            .chunks(4)
+            .flat_map(|p| p[..3].to_vec())
+            .collect(),
+        _ => image.bytes.clone(),
+    };
+
+    if ascii {
+        let mut line_len = 0;
+        for sample in samples {
+            let text = sample.to_string();
+            if line_len > 0 && line_len + 1 + text.len() > MAX_LINE_LEN {
+                out.push(b'\n');
+                line_len = 0;
+            } else if line_len > 0 {
+                out.push(b' ');
+                line_len += 1;
+            }
+            out.extend_from_slice(text.as_bytes());
+            line_len += text.len();
+        }
+        out.push(b'\n');
+    } else if format == ImageFormat::Pbm {
+        // Pack 8 pixels per byte, with each row padded to a whole byte
+        for row in samples.chunks(width) {
+            for bits in row.chunks(8) {
+                let byte = bits
+                    .iter()
+                    .enumerate()
+                    .fold(0, |byte, (i, &bit)| byte | bit << (7 - i));
+                out.push(byte);
+            }
+        }
+    } else {
+        out.extend_from_slice(&samples);
+    }
+
+    writer.write_all(&out)?;
+    Ok(())
+}
+
+// Private functions
+
+// Enumeration of the ways samples are grouped into pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TupleType {
+    BlackAndWhite,
+    Gray,
+    GrayAlpha,
+    Rgb,
+    RgbAlpha,
+}
+
+// The fields of a Netpbm header.
+struct Header {
+    // The digit after the P
+    magic: u8,
+    format: ImageFormat,
+    ascii: bool,
+    width: u32,
+    height: u32,
+    // Samples per pixel
+    depth: usize,
+    maxval: u32,
+    tuple_type: TupleType,
+    // Offset of the first sample
+    data_start: usize,
+}
+
+impl Header {
+    // Parse the header at the start of the data.
+    fn parse(buffer: &[u8]) -> RasterResult<Header> {
+        let magic = match buffer.get(0..2) {
+            Some(&[b'P', magic]) if (b'1'..=b'7').contains(&magic) => magic,
+            _ => return Err(decode_error(ImageFormat::Ppm, "InvalidSignature")),
+        };
+        if magic == b'7' {
+            return Header::parse_pam(buffer);
+        }
+
+        let (format, tuple_type, depth) = match magic {
+            b'1' | b'4' => (ImageFormat::Pbm, TupleType::BlackAndWhite, 1),
+            b'2' | b'5' => (ImageFormat::Pgm, TupleType::Gray, 1),
+            _ => (ImageFormat::Ppm, TupleType::Rgb, 3),
+        };
+        let mut tokens = Tokens { buffer, pos: 2 };
+        let width = tokens.number(format)?;
+        let height = tokens.number(format)?;
+        let maxval = if format == ImageFormat::Pbm {
+            1
+        } else {
+            tokens.number(format)?
+        };
+        // A single whitespace character separates the header from the samples
+        let data_start = tokens.pos + 1;
+        if data_start > buffer.len() {
+            return Err(decode_error(format, "Unexpected end of file"));
+        }
+
+        let header = Header {
+            magic,
+            format,
+            ascii: magic <= b'3',
+            width,
+            height,
+            depth,
+            maxval,
+            tuple_type,
+            data_start,
+        };
+        header.validate()
+    }
+
+    // Parse a PAM header, made of lines of keys and values.
+    fn parse_pam(buffer: &[u8]) -> RasterResult<Header> {
+        let format = ImageFormat::Pam;
+        let (mut width, mut height, mut depth, mut maxval) = (None, None, None, None);
+        let mut tuple_type = String::new();
+
+        let mut pos = 2;
+        loop {
+            let end = match buffer[pos..].iter().position(|&b| b == b'\n') {
+                Some(end) => pos + end,
+                None => return Err(decode_error(format, "ENDHDR missing")),
+            };
+            let line = String::from_utf8_lossy(&buffer[pos..end]);
+            pos = end + 1;
+
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (key, value) = match line.find(char::is_whitespace) {
+                Some(i) => (&line[..i], line[i..].trim()),
+                None => (line, ""),
+            };
+            let number = || {
+                value
+                    .parse::<u32>()
+                    .map_err(|_| decode_error(format, "Invalid header"))
+            };
+            match key {
+                "WIDTH" => width = Some(number()?),
+                "HEIGHT" => height = Some(number()?),
+                "DEPTH" => depth = Some(number()? as usize),
+                "MAXVAL" => maxval = Some(number()?),
+                "TUPLTYPE" => {
+                    if !tuple_type.is_empty() {
+                        tuple_type.push(' ');
+                    }
+                    tuple_type.push_str(value);
+                }
+                "ENDHDR" => break,
+                _ => return Err(decode_error(format, "Invalid header")),
+            }
+        }
+
+        let (width, height, depth, maxval) = match (width, height, depth, maxval) {
+            (Some(w), Some(h), Some(d), Some(m)) => (w, h, d, m),
+            _ => return Err(decode_error(format, "Invalid header")),
+        };
+        let tuple_type = match (tuple_type.as_str(), depth) {
+            ("BLACKANDWHITE", 1) => TupleType::BlackAndWhite,
+            ("GRAYSCALE", 1) | ("", 1) => TupleType::Gray,
+            ("GRAYSCALE_ALPHA", 2) | ("BLACKANDWHITE_ALPHA", 2) | ("", 2) => TupleType::GrayAlpha,
+            ("RGB", 3) | ("", 3) => TupleType::Rgb,
+            ("RGB_ALPHA", 4) | ("", 4) => TupleType::RgbAlpha,
+            _ => return Err(decode_error(format, "Unsupported tuple type")),
+        };
+
+        let header = Header {
+            magic: b'7',
+            format,
+            ascii: false,
+            width,
+            height,
+            depth,
+            maxval,
+            tuple_type,
+            data_start: pos,
+        };
+        header.validate()
+    }
+
+    // Check the size and maximum value.
+    fn validate(self) -> RasterResult<Header> {
+        let max = i32::MAX as u32;
+        if self.width == 0 || self.height == 0 || self.width > max || self.height > max {
+            return Err(decode_error(self.format, "Invalid dimensions"));
+        }
+        if self.maxval == 0 || self.maxval > 65535 {
+            return Err(decode_error(self.format, "Invalid maximum value"));
+        }
+        Ok(self)
+    }
+}
+
+// Reads whitespace separated numbers, skipping comments.
+struct Tokens<'a> {
+    buffer: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Tokens<'a> {
+    // Skip whitespace and comments that run to the end of the line.
+    fn skip(&mut self) {
+        while let Some(&b) = self.buffer.get(self.pos) {
+            if b == b'#' {
+                while self.buffer.get(self.pos).is_some_and(|&b| b != b'\n') {
+                    self.pos += 1;
+                }
+            } else if b.is_ascii_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+    }
+
+    // Read the next number. The position is left on the character after it.
+    fn number(&mut self, format: ImageFormat) -> RasterResult<u32> {
+        self.skip();
+        let start = self.pos;
+        let mut value = 0u32;
+        while let Some(&b) = self.buffer.get(self.pos) {
+            if !b.is_ascii_digit() {
+                break;
+            }
+            value = value
+                .checked_mul(10)
+                .and_then(|v| v.checked_add((b - b'0') as u32))
+                .ok_or_else(|| decode_error(format, "Number too large"))?;
+            self.pos += 1;
+        }
+        if self.pos == start {
+            return Err(decode_error(format, "Invalid header"));
+        }
+        Ok(value)
+    }
+}
+
+// Read samples written as text. Plain PBM samples are single digits that need no whitespace
+// between them.
+fn read_ascii_samples(data: &[u8], count: usize, format: ImageFormat) -> RasterResult<Vec<u32>> {
+    let bits = format == ImageFormat::Pbm;
+    let mut tokens = Tokens {
+        buffer: data,
+        pos: 0,
+    };
+    let mut samples = Vec::with_capacity(count);
+    for _ in 0..count {
+        if bits {
+            tokens.skip();
+            match data.get(tokens.pos) {
+                Some(&b'0') => samples.push(0),
+                Some(&b'1') => samples.push(1),
+                _ => return Err(decode_error(format, "Invalid sample")),
+            }
+            tokens.pos += 1;
+        } else {
+            samples.push(tokens.number(format)?);
+        }
+    }
+    Ok(samples)
+}
+
+// Read one or two byte samples, depending on the maximum value.
+fn read_binary_samples(
+    data: &[u8],
+    count: usize,
+    maxval: u32,
+    format: ImageFormat,
+) -> RasterResult<Vec<u32>> {
+    let size = if maxval > 255 { 2 } else { 1 };
+    let data = data
+        .get(..count * size)
+        .ok_or_else(|| decode_error(format, "Image truncated"))?;
+    Ok(if size == 2 {
+        data.chunks(2)
+            .map(|b| (b[0] as u32) << 8 | b[1] as u32)
+            .collect()
+    } else {
+        data.iter().map(|&b| b as u32).collect()
+    })
+}
+
+// Read packed PBM rows, each padded to a whole byte.
+fn read_bits(data: &[u8], width: usize, height: usize) -> RasterResult<Vec<u32>> {
+    let stride = width.div_ceil(8);
+    let data = data
+        .get(..stride * height)
+        .ok_or_else(|| decode_error(ImageFormat::Pbm, "Image truncated"))?;
+    let mut samples = Vec::with_capacity(width * height);
+    for row in data.chunks(stride) {
+        for x in 0..width {
+            samples.push(((row[x / 8] >> (7 - x % 8)) & 1) as u32);
+        }
+    }
+    Ok(samples)
+}
+
+fn decode_error(format: ImageFormat, msg: &str) -> RasterError {
+    RasterError::Decode(format, msg.to_string())
+}
+
+fn encode_error(format: ImageFormat, msg: &str) -> RasterError {
+    RasterError::Encode(format, msg.to_string())
+}
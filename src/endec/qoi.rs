@@ -0,0 +1,265 @@
+//!  A module for encoding/decoding QOI.
+
+// from rust
+use std::io::{Read, Write};
+
+// from external crate
+
+// from local crate
+use endec::read_limited;
+use error::{RasterError, RasterResult};
+use image::{ColorType, ImageInfo};
+use metadata::{ColorSpace, Metadata};
+use options::{Limits, QoiOptions};
+use Image;
+use ImageFormat;
+
+// Length of the header at the start of every file
+const HEADER_LEN: usize = 14;
+
+// Seven zero bytes and a one that end every file
+const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+// Opcodes. The 8 bit tags take precedence over the 2 bit tags.
+const OP_INDEX: u8 = 0x00;
+const OP_DIFF: u8 = 0x40;
+const OP_LUMA: u8 = 0x80;
+const OP_RUN: u8 = 0xC0;
+const OP_RGB: u8 = 0xFE;
+const OP_RGBA: u8 = 0xFF;
+const MASK_2: u8 = 0xC0;
+
+// Decode QOI along with its color space
+pub fn decode_qoi<R: Read>(reader: R, limits: &Limits) -> RasterResult<(Image, Metadata)> {
+    let buffer = read_limited(reader, limits)?;
+    let header = Header::parse(&buffer)?;
+    limits.check_dimensions(header.width, header.height)?;
+
+    let len = header.width as usize * header.height as usize * 4;
+    let mut bytes = Vec::with_capacity(len);
+    let mut index = [[0u8; 4]; 64];
+    let mut px = [0, 0, 0, 255];
+    let mut pos = HEADER_LEN;
+    let data = &buffer[..];
+    let byte = |pos: usize| -> RasterResult<u8> {
+        data.get(pos)
+            .cloned()
+            .ok_or_else(|| decode_error("Image truncated"))
+    };
+
+    while bytes.len() < len {
+        let b1 = byte(pos)?;
+        pos += 1;
+
+        if b1 == OP_RGB {
+            px[0] = byte(pos)?;
+            px[1] = byte(pos + 1)?;
+            px[2] = byte(pos + 2)?;
+            pos += 3;
+        } else if b1 == OP_RGBA {
+            px = [byte(pos)?, byte(pos + 1)?, byte(pos + 2)?, byte(pos + 3)?];
+            pos += 4;
+        } else {
+            match b1 & MASK_2 {
+                OP_INDEX => px = index[b1 as usize],
+                OP_DIFF => {
+                    px[0] = px[0].wrapping_add((b1 >> 4) & 0x03).wrapping_sub(2);
+                    px[1] = px[1].wrapping_add((b1 >> 2) & 0x03).wrapping_sub(2);
+                    px[2] = px[2].wrapping_add(b1 & 0x03).wrapping_sub(2);
+                }
+                OP_LUMA => {
+                    let b2 = byte(pos)?;
+                    pos += 1;
+                    let vg = (b1 & 0x3F).wrapping_sub(32);
+                    px[0] = px[0]
+                        .wrapping_add(vg)
+                        .wrapping_add((b2 >> 4) & 0x0F)
+                        .wrapping_sub(8);
+                    px[1] = px[1].wrapping_add(vg);
+                    px[2] = px[2]
+                        .wrapping_add(vg)
+                        .wrapping_add(b2 & 0x0F)
+                        .wrapping_sub(8);
+                }
+                _ => {
+                    // A run repeats the previous pixel, which is already in the index
+                    let run = (b1 & 0x3F) as usize + 1;
+                    for _ in 1..run {
+                        if bytes.len() + 4 >= len {
+                            break;
+                        }
+                        bytes.extend_from_slice(&px);
+                    }
+                }
+            }
+        }
+
+        index[hash(&px)] = px;
+        bytes.extend_from_slice(&px);
+    }
+
+    let image = Image {
+        width: header.width as i32,
+        height: header.height as i32,
+        bytes,
+    };
+    let metadata = Metadata {
+        color_space: Some(header.color_space),
+        ..Metadata::default()
+    };
+    Ok((image, metadata))
+}
+
+// Read the size and channels of a QOI from its header
+pub fn probe_qoi<R: Read>(reader: R) -> RasterResult<ImageInfo> {
+    let mut buffer = Vec::new();
+    reader.take(HEADER_LEN as u64).read_to_end(&mut buffer)?;
+    let header = Header::parse(&buffer)?;
+    Ok(ImageInfo {
+        width: header.width as i32,
+        height: header.height as i32,
+        format: ImageFormat::Qoi,
+        color_type: if header.channels == 4 {
+            ColorType::Rgba
+        } else {
+            ColorType::Rgb
+        },
+        bit_depth: 8,
+        frames: 1,
+    })
+}
+
+// Encode QOI
+//
+// Opaque images are marked as having 3 channels. The channel count does not change how the pixels
+// are stored.
+pub fn encode_qoi<W: Write>(
+    image: &Image,
+    mut writer: W,
+    options: &QoiOptions,
+) -> RasterResult<()> {
+    if image.width < 1 || image.height < 1 {
+        return Err(encode_error("Invalid dimensions"));
+    }
+    let alpha = image.bytes.chunks(4).any(|p| p[3] != 255);
+
+    let mut out = Vec::with_capacity(HEADER_LEN + image.bytes.len() / 2);
+    out.extend_from_slice(b"qoif");
+    out.extend_from_slice(&(image.width as u32).to_be_bytes());
+    out.extend_from_slice(&(image.height as u32).to_be_bytes());
+    out.push(if alpha { 4 } else { 3 });
+    out.push(match options.color_space {
+        ColorSpace::Srgb => 0,
+        ColorSpace::Linear => 1,
+    });
+
+    let mut index = [[0u8; 4]; 64];
+    let mut prev = [0, 0, 0, 255];
+    let mut run = 0;
+    let count = image.bytes.len() / 4;
+    for (i, p) in image.bytes.chunks(4).enumerate() {
+        let px = [p[0], p[1], p[2], p[3]];
+        if px == prev {
+            run += 1;
+            if run == 62 || i == count - 1 {
+                out.push(OP_RUN | (run - 1));
+                run = 0;
+            }
+            continue;
+        }
+        if run > 0 {
+            out.push(OP_RUN | (run - 1));
+            run = 0;
+        }
+
+        let hash = hash(&px);
+        if index[hash] == px {
+            out.push(OP_INDEX | hash as u8);
+        } else {
+            index[hash] = px;
+            if px[3] == prev[3] {
+                let vr = px[0].wrapping_sub(prev[0]) as i8;
+                let vg = px[1].wrapping_sub(prev[1]) as i8;
+                let vb = px[2].wrapping_sub(prev[2]) as i8;
+                let vg_r = vr.wrapping_sub(vg);
+                let vg_b = vb.wrapping_sub(vg);
+
+                if (-2..2).contains(&vr) && (-2..2).contains(&vg) && (-2..2).contains(&vb) {
+                    out.push(
+                        OP_DIFF | ((vr + 2) as u8) << 4 | ((vg + 2) as u8) << 2 | (vb + 2) as u8,
+                    );
+                } else if (-8..8).contains(&vg_r)
+                    && (-32..32).contains(&vg)
+                    && (-8..8).contains(&vg_b)
+                {
+                    out.push(OP_LUMA | (vg + 32) as u8);
+                    out.push(((vg_r + 8) as u8) << 4 | (vg_b + 8) as u8);
+                } else {
+                    out.extend_from_slice(&[OP_RGB, px[0], px[1], px[2]]);
+                }
+            } else {
+                out.extend_from_slice(&[OP_RGBA, px[0], px[1], px[2], px[3]]);
+            }
+        }
+        prev = px;
+    }
+    out.extend_from_slice(&END_MARKER);
+
+    writer.write_all(&out)?;
+    Ok(())
+}
+
+// Private functions
+
+// The fields of a QOI header.
+struct Header {
+    width: u32,
+    height: u32,
+    channels: u8,
+    color_space: ColorSpace,
+}
+
+impl Header {
+    // Parse and check the header at the start of the data.
+    fn parse(buffer: &[u8]) -> RasterResult<Header> {
+        let b = buffer
+            .get(..HEADER_LEN)
+            .ok_or_else(|| decode_error("Unexpected end of file"))?;
+        if &b[..4] != b"qoif" {
+            return Err(decode_error("InvalidSignature"));
+        }
+        let width = u32::from_be_bytes([b[4], b[5], b[6], b[7]]);
+        let height = u32::from_be_bytes([b[8], b[9], b[10], b[11]]);
+        let max = i32::MAX as u32;
+        if width == 0 || height == 0 || width > max || height > max {
+            return Err(decode_error("Invalid dimensions"));
+        }
+        if b[12] != 3 && b[12] != 4 {
+            return Err(decode_error("Invalid channels"));
+        }
+        let color_space = match b[13] {
+            0 => ColorSpace::Srgb,
+            1 => ColorSpace::Linear,
+            _ => return Err(decode_error("Invalid color space")),
+        };
+        Ok(Header {
+            width,
+            height,
+            channels: b[12],
+            color_space,
+        })
+    }
+}
+
+// Position of a color in the index of recently seen colors.
+fn hash(px: &[u8; 4]) -> usize {
+    (px[0] as usize * 3 + px[1] as usize * 5 + px[2] as usize * 7 + px[3] as usize * 11) % 64
+}
+
+fn decode_error(msg: &str) -> RasterError {
+    RasterError::Decode(ImageFormat::Qoi, msg.to_string())
+}
+
+fn encode_error(msg: &str) -> RasterError {
+    RasterError::Encode(ImageFormat::Qoi, msg.to_string())
+}
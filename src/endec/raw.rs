@@ -0,0 +1,162 @@
+//!  A module for encoding/decoding headerless RGBA data.
+
+// from rust
+use std::io::{Read, Write};
+
+// from external crate
+
+// from local crate
+use endec;
+use error::{RasterError, RasterResult};
+use options::Limits;
+use stream::{self, RowReader, RowWriter};
+use Image;
+use ImageFormat;
+
+// Decode raw RGBA of the given dimensions
+//
+// Only the bytes of the image are read, so several images can be decoded one after another from
+// a stream.
+pub fn decode_raw<R: Read>(
+    reader: R,
+    dimensions: Option<(u32, u32)>,
+    limits: &Limits,
+) -> RasterResult<Image> {
+    let (width, height) = parse_dimensions(dimensions)?;
+    limits.check_dimensions(width, height)?;
+
+    let len = width as u64 * height as u64 * 4;
+    let mut bytes = Vec::with_capacity(len as usize);
+    reader.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(decode_error("Image truncated"));
+    }
+
+    Ok(Image {
+        width: width as i32,
+        height: height as i32,
+        bytes,
+    })
+}
+
+// Encode raw RGBA, without the dimensions
+pub fn encode_raw<W: Write>(image: &Image, mut writer: W) -> RasterResult<()> {
+    if image.width < 1 || image.height < 1 {
+        return Err(encode_error("Invalid dimensions"));
+    }
+    writer.write_all(&image.bytes)?;
+    Ok(())
+}
+
+// Decode raw RGBA of the given dimensions a row at a time
+pub fn decode_raw_rows<'a, R: Read + 'a>(
+    reader: R,
+    dimensions: Option<(u32, u32)>,
+    limits: &Limits,
+) -> RasterResult<Box<dyn RowReader + 'a>> {
+    let (width, height) = parse_dimensions(dimensions)?;
+    endec::check_row_limits(limits, width, height, width as u64 * 4)?;
+    Ok(Box::new(RawRows {
+        reader,
+        width: width as i32,
+        height: height as i32,
+        y: 0,
+    }))
+}
+
+// Encode raw RGBA a row at a time
+pub fn encode_raw_rows<'a, W: Write + 'a>(
+    writer: W,
+    width: i32,
+    height: i32,
+) -> RasterResult<Box<dyn RowWriter + 'a>> {
+    if width < 1 || height < 1 {
+        return Err(encode_error("Invalid dimensions"));
+    }
+    Ok(Box::new(RawRowWriter {
+        writer,
+        width,
+        height,
+        y: 0,
+    }))
+}
+
+// Private functions
+
+// Reads rows of raw RGBA.
+struct RawRows<R: Read> {
+    reader: R,
+    width: i32,
+    height: i32,
+    y: i32,
+}
+
+impl<R: Read> RowReader for RawRows<R> {
+    fn width(&self) -> i32 {
+        self.width
+    }
+
+    fn height(&self) -> i32 {
+        self.height
+    }
+
+    fn read_row(&mut self, row: &mut [u8]) -> RasterResult<bool> {
+        if self.y >= self.height {
+            return Ok(false);
+        }
+        stream::check_read(ImageFormat::Raw, self.width, row)?;
+        self.reader
+            .read_exact(row)
+            .map_err(|_| decode_error("Image truncated"))?;
+        self.y += 1;
+        Ok(true)
+    }
+}
+
+// Writes rows of raw RGBA.
+struct RawRowWriter<W: Write> {
+    writer: W,
+    width: i32,
+    height: i32,
+    y: i32,
+}
+
+impl<W: Write> RowWriter for RawRowWriter<W> {
+    fn width(&self) -> i32 {
+        self.width
+    }
+
+    fn height(&self) -> i32 {
+        self.height
+    }
+
+    fn write_row(&mut self, row: &[u8]) -> RasterResult<()> {
+        stream::check_write(ImageFormat::Raw, self.width, self.height, self.y, row)?;
+        self.writer.write_all(row)?;
+        self.y += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> RasterResult<()> {
+        stream::check_finished(ImageFormat::Raw, self.height, self.y)?;
+        Ok(self.writer.flush()?)
+    }
+}
+
+// Check that the dimensions are given and fit an image.
+fn parse_dimensions(dimensions: Option<(u32, u32)>) -> RasterResult<(u32, u32)> {
+    let (width, height) = dimensions.ok_or_else(|| decode_error("Dimensions not given"))?;
+    let max = i32::MAX as u32;
+    if width == 0 || height == 0 || width > max || height > max {
+        return Err(decode_error("Invalid dimensions"));
+    }
+    Ok((width, height))
+}
+
+fn decode_error(msg: &str) -> RasterError {
+    RasterError::Decode(ImageFormat::Raw, msg.to_string())
+}
+
+fn encode_error(msg: &str) -> RasterError {
+    RasterError::Encode(ImageFormat::Raw, msg.to_string())
+}
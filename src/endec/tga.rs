@@ -0,0 +1,334 @@
+//!  A module for encoding/decoding TGA.
+
+// from rust
+use std::io::{Read, Write};
+
+// from external crate
+
+// from local crate
+use endec::read_limited;
+use error::{RasterError, RasterResult};
+use image::{ColorType, ImageInfo};
+use options::{Limits, TgaOptions};
+use Image;
+use ImageFormat;
+
+// Length of the header at the start of every file
+const HEADER_LEN: usize = 18;
+
+// Image types, with 8 added for the RLE compressed version of each
+const COLOR_MAPPED: u8 = 1;
+const TRUE_COLOR: u8 = 2;
+const GRAYSCALE: u8 = 3;
+const RLE: u8 = 8;
+
+// Bits of the image descriptor
+const RIGHT_TO_LEFT: u8 = 0x10;
+const TOP_TO_BOTTOM: u8 = 0x20;
+
+// Decode TGA
+//
+// Color maps, 15/16 bit colors and grayscale are expanded to 8 bit RGBA.
+pub fn decode_tga<R: Read>(reader: R, limits: &Limits) -> RasterResult<Image> {
+    let buffer = read_limited(reader, limits)?;
+    let header = Header::parse(&buffer)?;
+    limits.check_dimensions(header.width as u32, header.height as u32)?;
+
+    let mut pos = HEADER_LEN + header.id_len;
+    let palette = if header.color_map_type == 1 {
+        let entry_len = (header.color_map_depth as usize).div_ceil(8);
+        let len = header.color_map_len * entry_len;
+        let data = buffer
+            .get(pos..pos + len)
+            .ok_or_else(|| decode_error("Color map missing"))?;
+        pos += len;
+        data.chunks(entry_len)
+            .map(|entry| read_color(entry, header.color_map_depth, header.alpha_bits))
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let width = header.width as usize;
+    let height = header.height as usize;
+    let pixel_len = (header.depth as usize).div_ceil(8);
+    let data = buffer
+        .get(pos..)
+        .ok_or_else(|| decode_error("Image truncated"))?;
+    let data = if header.image_type & RLE != 0 {
+        decompress(data, width * height, pixel_len)?
+    } else {
+        data.get(..width * height * pixel_len)
+            .ok_or_else(|| decode_error("Image truncated"))?
+            .to_vec()
+    };
+
+    let mut bytes = vec![0; width * height * 4];
+    for (i, pixel) in data.chunks(pixel_len).enumerate() {
+        let color = match header.image_type & !RLE {
+            COLOR_MAPPED => {
+                let mut index = pixel[0] as usize;
+                if pixel_len > 1 {
+                    index |= (pixel[1] as usize) << 8;
+                }
+                index
+                    .checked_sub(header.color_map_first)
+                    .and_then(|index| palette.get(index))
+                    .cloned()
+                    .unwrap_or([0, 0, 0, 255])
+            }
+            GRAYSCALE => {
+                let alpha = if pixel_len > 1 { pixel[1] } else { 255 };
+                [pixel[0], pixel[0], pixel[0], alpha]
+            }
+            _ => read_color(pixel, header.depth, header.alpha_bits),
+        };
+
+        let (mut x, mut y) = (i % width, i / width);
+        if header.descriptor & RIGHT_TO_LEFT != 0 {
+            x = width - 1 - x;
+        }
+        if header.descriptor & TOP_TO_BOTTOM == 0 {
+            y = height - 1 - y;
+        }
+        let index = (y * width + x) * 4;
+        bytes[index..index + 4].copy_from_slice(&color);
+    }
+
+    Ok(Image {
+        width: width as i32,
+        height: height as i32,
+        bytes,
+    })
+}
+
+// Read the size and color type of a TGA from its header
+pub fn probe_tga<R: Read>(reader: R) -> RasterResult<ImageInfo> {
+    let mut buffer = Vec::new();
+    reader.take(HEADER_LEN as u64).read_to_end(&mut buffer)?;
+    let header = Header::parse(&buffer)?;
+
+    let (color_type, bit_depth) = match header.image_type & !RLE {
+        COLOR_MAPPED => (ColorType::Indexed, header.depth),
+        GRAYSCALE if header.depth == 16 => (ColorType::GrayAlpha, 8),
+        GRAYSCALE => (ColorType::Gray, 8),
+        _ => {
+            let bits = if header.depth <= 16 { 5 } else { 8 };
+            if header.alpha_bits > 0 {
+                (ColorType::Rgba, bits)
+            } else {
+                (ColorType::Rgb, bits)
+            }
+        }
+    };
+    Ok(ImageInfo {
+        width: header.width as i32,
+        height: header.height as i32,
+        format: ImageFormat::Tga,
+        color_type,
+        bit_depth,
+        frames: 1,
+    })
+}
+
+// Encode TGA
+//
+// Opaque images are written as 24 bit and images with transparency as 32 bit, with the rows
+// stored from the top.
+pub fn encode_tga<W: Write>(
+    image: &Image,
+    mut writer: W,
+    options: &TgaOptions,
+) -> RasterResult<()> {
+    if image.width < 1 || image.height < 1 || image.width > 65535 || image.height > 65535 {
+        return Err(encode_error("Invalid dimensions"));
+    }
+    let alpha = image.bytes.chunks(4).any(|p| p[3] != 255);
+    let pixel_len = if alpha { 4 } else { 3 };
+
+    let mut out = Vec::with_capacity(HEADER_LEN + image.bytes.len());
+    let image_type = if options.rle {
+        TRUE_COLOR | RLE
+    } else {
+        TRUE_COLOR
+    };
+    out.extend_from_slice(&[0, 0, image_type]);
+    // No color map and no origin offset
+    out.extend_from_slice(&[0; 9]);
+    out.extend_from_slice(&(image.width as u16).to_le_bytes());
+    out.extend_from_slice(&(image.height as u16).to_le_bytes());
+    out.push(pixel_len as u8 * 8);
+    out.push(TOP_TO_BOTTOM | if alpha { 8 } else { 0 });
+
+    let pixels: Vec<[u8; 4]> = image
+        .bytes
+        .chunks(4)
+        .map(|p| [p[2], p[1], p[0], p[3]])
+        .collect();
+    for row in pixels.chunks(image.width as usize) {
+        if options.rle {
+            compress_row(row, pixel_len, &mut out);
+        } else {
+            for pixel in row {
+                out.extend_from_slice(&pixel[..pixel_len]);
+            }
+        }
+    }
+
+    writer.write_all(&out)?;
+    Ok(())
+}
+
+// Private functions
+
+// The fields of a TGA header.
+struct Header {
+    id_len: usize,
+    color_map_type: u8,
+    image_type: u8,
+    color_map_first: usize,
+    color_map_len: usize,
+    color_map_depth: u8,
+    width: u16,
+    height: u16,
+    depth: u8,
+    alpha_bits: u8,
+    descriptor: u8,
+}
+
+impl Header {
+    // Parse and check the header at the start of the data.
+    fn parse(buffer: &[u8]) -> RasterResult<Header> {
+        let b = buffer
+            .get(..HEADER_LEN)
+            .ok_or_else(|| decode_error("Unexpected end of file"))?;
+        let header = Header {
+            id_len: b[0] as usize,
+            color_map_type: b[1],
+            image_type: b[2],
+            color_map_first: u16::from_le_bytes([b[3], b[4]]) as usize,
+            color_map_len: u16::from_le_bytes([b[5], b[6]]) as usize,
+            color_map_depth: b[7],
+            width: u16::from_le_bytes([b[12], b[13]]),
+            height: u16::from_le_bytes([b[14], b[15]]),
+            depth: b[16],
+            alpha_bits: b[17] & 0x0F,
+            descriptor: b[17],
+        };
+
+        if header.color_map_type > 1 {
+            return Err(decode_error("Invalid color map type"));
+        }
+        let valid = match header.image_type & !RLE {
+            COLOR_MAPPED => {
+                header.color_map_type == 1
+                    && (header.depth == 8 || header.depth == 16)
+                    && [15, 16, 24, 32].contains(&header.color_map_depth)
+            }
+            TRUE_COLOR => [15, 16, 24, 32].contains(&header.depth),
+            GRAYSCALE => header.depth == 8 || header.depth == 16,
+            _ => return Err(decode_error("Unsupported image type")),
+        };
+        if !valid {
+            return Err(decode_error("Invalid bit depth"));
+        }
+        if header.width == 0 || header.height == 0 {
+            return Err(decode_error("Invalid dimensions"));
+        }
+        Ok(header)
+    }
+}
+
+// Convert a little endian BGR(A) or 15/16 bit ARGB color to RGBA.
+fn read_color(pixel: &[u8], depth: u8, alpha_bits: u8) -> [u8; 4] {
+    match depth {
+        15 | 16 => {
+            let value = u16::from_le_bytes([pixel[0], pixel[1]]);
+            let scale = |v: u16| ((v & 0x1F) * 255 / 31) as u8;
+            let alpha = if depth == 16 && alpha_bits > 0 && value & 0x8000 == 0 {
+                0
+            } else {
+                255
+            };
+            [scale(value >> 10), scale(value >> 5), scale(value), alpha]
+        }
+        24 => [pixel[2], pixel[1], pixel[0], 255],
+        _ => {
+            let alpha = if alpha_bits > 0 { pixel[3] } else { 255 };
+            [pixel[2], pixel[1], pixel[0], alpha]
+        }
+    }
+}
+
+// Expand RLE packets into count pixels of pixel_len bytes each.
+fn decompress(data: &[u8], count: usize, pixel_len: usize) -> RasterResult<Vec<u8>> {
+    let mut out = Vec::with_capacity(count * pixel_len);
+    let mut pos = 0;
+    while out.len() < count * pixel_len {
+        let packet = *data
+            .get(pos)
+            .ok_or_else(|| decode_error("Image truncated"))?;
+        pos += 1;
+        let n = (packet & 0x7F) as usize + 1;
+        let len = if packet & 0x80 != 0 {
+            pixel_len
+        } else {
+            n * pixel_len
+        };
+        let bytes = data
+            .get(pos..pos + len)
+            .ok_or_else(|| decode_error("Image truncated"))?;
+        pos += len;
+
+        if packet & 0x80 != 0 {
+            // A run of one pixel
+            for _ in 0..n {
+                out.extend_from_slice(bytes);
+            }
+        } else {
+            out.extend_from_slice(bytes);
+        }
+    }
+    // Packets may run past the last pixel
+    out.truncate(count * pixel_len);
+    Ok(out)
+}
+
+// Write a row of BGRA pixels as RLE packets of at most 128 pixels.
+fn compress_row(row: &[[u8; 4]], pixel_len: usize, out: &mut Vec<u8>) {
+    let mut i = 0;
+    while i < row.len() {
+        let run = row[i..]
+            .iter()
+            .take(128)
+            .take_while(|&p| *p == row[i])
+            .count();
+        if run > 1 {
+            out.push(0x80 | (run - 1) as u8);
+            out.extend_from_slice(&row[i][..pixel_len]);
+            i += run;
+            continue;
+        }
+
+        // Collect pixels up to the start of the next run
+        let start = i;
+        while i < row.len() && i - start < 128 {
+            if i + 1 < row.len() && row[i] == row[i + 1] {
+                break;
+            }
+            i += 1;
+        }
+        out.push((i - start - 1) as u8);
+        for pixel in &row[start..i] {
+            out.extend_from_slice(&pixel[..pixel_len]);
+        }
+    }
+}
+
+fn decode_error(msg: &str) -> RasterError {
+    RasterError::Decode(ImageFormat::Tga, msg.to_string())
+}
+
+fn encode_error(msg: &str) -> RasterError {
+    RasterError::Encode(ImageFormat::Tga, msg.to_string())
+}
@@ -0,0 +1,830 @@
+//!  A module for encoding/decoding TIFF.
+
+// from rust
+use std::cmp;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::iter;
+
+// from external crate
+use deflate;
+use inflate;
+
+// from local crate
+use endec::read_limited;
+use error::{RasterError, RasterResult};
+use image::{ColorType, ImageInfo};
+use metadata::{ExifField, ExifIfd, ExifValue, TiffReader};
+use options::{Limits, TiffCompression, TiffOptions};
+use Image;
+use ImageFormat;
+
+// Tags of the fields that describe a page
+const NEW_SUBFILE_TYPE: u16 = 254;
+const IMAGE_WIDTH: u16 = 256;
+const IMAGE_LENGTH: u16 = 257;
+const BITS_PER_SAMPLE: u16 = 258;
+const COMPRESSION: u16 = 259;
+const PHOTOMETRIC: u16 = 262;
+const STRIP_OFFSETS: u16 = 273;
+const SAMPLES_PER_PIXEL: u16 = 277;
+const ROWS_PER_STRIP: u16 = 278;
+const STRIP_BYTE_COUNTS: u16 = 279;
+const X_RESOLUTION: u16 = 282;
+const Y_RESOLUTION: u16 = 283;
+const PLANAR_CONFIGURATION: u16 = 284;
+const RESOLUTION_UNIT: u16 = 296;
+const PAGE_NUMBER: u16 = 297;
+const PREDICTOR: u16 = 317;
+const COLOR_MAP: u16 = 320;
+const TILE_WIDTH: u16 = 322;
+const TILE_LENGTH: u16 = 323;
+const TILE_OFFSETS: u16 = 324;
+const TILE_BYTE_COUNTS: u16 = 325;
+const EXTRA_SAMPLES: u16 = 338;
+const SAMPLE_FORMAT: u16 = 339;
+
+// Compression methods
+const NONE: u16 = 1;
+const LZW: u16 = 5;
+const DEFLATE: u16 = 8;
+const OLD_DEFLATE: u16 = 32946;
+const PACKBITS: u16 = 32773;
+
+// Photometric interpretations
+const WHITE_IS_ZERO: u16 = 0;
+const BLACK_IS_ZERO: u16 = 1;
+const RGB: u16 = 2;
+const PALETTE: u16 = 3;
+
+// Field types used when writing
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const RATIONAL: u16 = 5;
+
+// Special LZW codes
+const LZW_CLEAR: u16 = 256;
+const LZW_EOI: u16 = 257;
+
+// Uncompressed size of the strips written by the encoder
+const STRIP_LEN: usize = 8192;
+
+// Decode the first page of a TIFF
+//
+// Samples are scaled to 8 bits and expanded to RGBA.
+pub fn decode_tiff<R: Read>(reader: R, limits: &Limits) -> RasterResult<Image> {
+    let buffer = read_limited(reader, limits)?;
+    let tiff = read_tiff(&buffer)?;
+    let fields = tiff
+        .ifd(first_ifd(&tiff)?, ExifIfd::Primary)
+        .ok_or_else(|| decode_error("Directory truncated"))?;
+    Page::parse(&fields)?.decode(&tiff, &buffer, limits)
+}
+
+// Decode every page of a TIFF, in the order they are stored
+pub fn decode_tiff_all<R: Read>(reader: R, limits: &Limits) -> RasterResult<Vec<Image>> {
+    let buffer = read_limited(reader, limits)?;
+    let tiff = read_tiff(&buffer)?;
+
+    let mut images = Vec::new();
+    let mut total = 0;
+    for fields in read_pages(&tiff)? {
+        let image = Page::parse(&fields)?.decode(&tiff, &buffer, limits)?;
+        total += image.bytes.len() as u64;
+        limits.check_alloc(total)?;
+        images.push(image);
+    }
+    Ok(images)
+}
+
+// Read the size and color type of the first page of a TIFF
+//
+// The directories can be anywhere in the file, so the whole file is read. The number of pages is
+// reported as the frame count.
+pub fn probe_tiff<R: Read>(reader: R) -> RasterResult<ImageInfo> {
+    let buffer = read_limited(reader, &Limits::default())?;
+    let tiff = read_tiff(&buffer)?;
+    let pages = read_pages(&tiff)?;
+    let page = Page::parse(&pages[0])?;
+
+    let color_type = match (page.photometric, page.alpha.is_some()) {
+        (PALETTE, _) => ColorType::Indexed,
+        (RGB, true) => ColorType::Rgba,
+        (RGB, false) => ColorType::Rgb,
+        (_, true) => ColorType::GrayAlpha,
+        (_, false) => ColorType::Gray,
+    };
+    Ok(ImageInfo {
+        width: page.width as i32,
+        height: page.height as i32,
+        format: ImageFormat::Tiff,
+        color_type,
+        bit_depth: page.bits as u8,
+        frames: pages.len(),
+    })
+}
+
+// Encode TIFF with one page for each image
+//
+// Pages are written as 8 bit RGB, or RGBA if they have transparency, in little endian strips.
+pub fn encode_tiff<W: Write>(
+    images: &[Image],
+    mut writer: W,
+    options: &TiffOptions,
+) -> RasterResult<()> {
+    if images.is_empty() || images.len() > u16::MAX as usize {
+        return Err(encode_error("Invalid number of images"));
+    }
+    let (compression, predictor) = match options.compression {
+        TiffCompression::None => (NONE, 1),
+        TiffCompression::PackBits => (PACKBITS, 1),
+        TiffCompression::Lzw => (LZW, 2),
+        TiffCompression::Deflate => (DEFLATE, 2),
+    };
+
+    let mut out = b"II*\0".to_vec();
+    // Position of the offset to the next directory, filled in once it is written
+    let mut next = out.len();
+    out.extend_from_slice(&[0; 4]);
+
+    for (page, image) in images.iter().enumerate() {
+        if image.width < 1 || image.height < 1 {
+            return Err(encode_error("Invalid dimensions"));
+        }
+        let width = image.width as usize;
+        let alpha = image.bytes.chunks(4).any(|p| p[3] != 255);
+        let samples = if alpha { 4 } else { 3 };
+        let row_len = width * samples;
+        let rows_per_strip = cmp::max(1, STRIP_LEN / row_len);
+
+        let mut offsets = Vec::new();
+        let mut counts = Vec::new();
+        for rows in image.bytes.chunks(width * 4 * rows_per_strip) {
+            let mut data: Vec<u8> = rows
+                .chunks(4)
+                .flat_map(|p| p[..samples].iter().cloned())
+                .collect();
+            if predictor == 2 {
+                for row in data.chunks_mut(row_len) {
+                    for i in (samples..row_len).rev() {
+                        row[i] = row[i].wrapping_sub(row[i - samples]);
+                    }
+                }
+            }
+            let data = match compression {
+                PACKBITS => compress_packbits(&data, row_len),
+                LZW => compress_lzw(&data),
+                DEFLATE => deflate::deflate_bytes_zlib(&data),
+                _ => data,
+            };
+            offsets.push(out.len() as u32);
+            counts.push(data.len() as u32);
+            out.extend_from_slice(&data);
+            // Every value starts on a word boundary
+            if out.len() % 2 == 1 {
+                out.push(0);
+            }
+        }
+
+        let mut fields = vec![
+            (IMAGE_WIDTH, LONG, vec![image.width as u32]),
+            (IMAGE_LENGTH, LONG, vec![image.height as u32]),
+            (BITS_PER_SAMPLE, SHORT, vec![8; samples]),
+            (COMPRESSION, SHORT, vec![compression as u32]),
+            (PHOTOMETRIC, SHORT, vec![RGB as u32]),
+            (STRIP_OFFSETS, LONG, offsets),
+            (SAMPLES_PER_PIXEL, SHORT, vec![samples as u32]),
+            (ROWS_PER_STRIP, LONG, vec![rows_per_strip as u32]),
+            (STRIP_BYTE_COUNTS, LONG, counts),
+            // 72 DPI
+            (X_RESOLUTION, RATIONAL, vec![72, 1]),
+            (Y_RESOLUTION, RATIONAL, vec![72, 1]),
+            (PLANAR_CONFIGURATION, SHORT, vec![1]),
+            (RESOLUTION_UNIT, SHORT, vec![2]),
+        ];
+        if images.len() > 1 {
+            fields.push((NEW_SUBFILE_TYPE, LONG, vec![2]));
+            fields.push((PAGE_NUMBER, SHORT, vec![page as u32, images.len() as u32]));
+        }
+        if predictor == 2 {
+            fields.push((PREDICTOR, SHORT, vec![2]));
+        }
+        if alpha {
+            // Unassociated alpha
+            fields.push((EXTRA_SAMPLES, SHORT, vec![2]));
+        }
+        fields.sort_by_key(|field| field.0);
+
+        let ifd = out.len();
+        if ifd > u32::MAX as usize {
+            return Err(encode_error("Image too large"));
+        }
+        out[next..next + 4].copy_from_slice(&(ifd as u32).to_le_bytes());
+        next = write_ifd(&mut out, &fields);
+    }
+
+    writer.write_all(&out)?;
+    Ok(())
+}
+
+// Private functions
+
+// The fields of a page needed to decode its pixels.
+struct Page {
+    width: u32,
+    height: u32,
+    bits: u16,
+    samples: usize,
+    photometric: u16,
+    compression: u16,
+    predictor: u16,
+    planar: bool,
+    // Whether an extra sample holds alpha, and if it is premultiplied
+    alpha: Option<bool>,
+    palette: Vec<[u8; 4]>,
+    // Size and location of the strips or tiles
+    chunk_width: u32,
+    chunk_height: u32,
+    tiled: bool,
+    offsets: Vec<u32>,
+    counts: Vec<u32>,
+}
+
+impl Page {
+    // Read and check the fields of a directory.
+    fn parse(fields: &[ExifField]) -> RasterResult<Page> {
+        let get = |tag| values(fields, tag);
+        let first = |tag, default| get(tag).and_then(|v| v.first().cloned()).unwrap_or(default);
+
+        let width = first(IMAGE_WIDTH, 0);
+        let height = first(IMAGE_LENGTH, 0);
+        let max = i32::MAX as u32;
+        if width == 0 || height == 0 || width > max || height > max {
+            return Err(decode_error("Invalid dimensions"));
+        }
+
+        let samples = first(SAMPLES_PER_PIXEL, 1) as usize;
+        let bits = get(BITS_PER_SAMPLE).unwrap_or_else(|| vec![1]);
+        if bits.iter().any(|&b| b != bits[0]) {
+            return Err(decode_error("Mixed bits per sample"));
+        }
+        let bits = bits[0] as u16;
+        if get(SAMPLE_FORMAT).is_some_and(|formats| formats.iter().any(|&f| f != 1)) {
+            return Err(decode_error("Unsupported sample format"));
+        }
+
+        let photometric = first(PHOTOMETRIC, BLACK_IS_ZERO as u32) as u16;
+        let (color_samples, valid) = match photometric {
+            WHITE_IS_ZERO | BLACK_IS_ZERO => (1, [1, 2, 4, 8, 16].contains(&bits)),
+            RGB => (3, bits == 8 || bits == 16),
+            PALETTE => (1, [1, 2, 4, 8].contains(&bits)),
+            _ => return Err(decode_error("Unsupported photometric interpretation")),
+        };
+        if !valid {
+            return Err(decode_error("Invalid bit depth"));
+        }
+        if samples < color_samples {
+            return Err(decode_error("Invalid samples per pixel"));
+        }
+        // The first extra sample is alpha if it is marked as associated or unassociated alpha
+        let alpha = match get(EXTRA_SAMPLES).and_then(|v| v.first().cloned()) {
+            Some(1) if samples > color_samples => Some(true),
+            Some(2) if samples > color_samples => Some(false),
+            _ => None,
+        };
+
+        let compression = first(COMPRESSION, NONE as u32) as u16;
+        if ![NONE, LZW, DEFLATE, OLD_DEFLATE, PACKBITS].contains(&compression) {
+            return Err(decode_error("Unsupported compression"));
+        }
+        let predictor = first(PREDICTOR, 1) as u16;
+        if predictor != 1 && !(predictor == 2 && (bits == 8 || bits == 16)) {
+            return Err(decode_error("Unsupported predictor"));
+        }
+
+        let palette = if photometric == PALETTE {
+            let map = get(COLOR_MAP).ok_or_else(|| decode_error("Color map missing"))?;
+            let len = 1 << bits;
+            if map.len() < len * 3 {
+                return Err(decode_error("Color map truncated"));
+            }
+            (0..len)
+                .map(|i| {
+                    let channel = |c: usize| (map[c * len + i] >> 8) as u8;
+                    [channel(0), channel(1), channel(2), 255]
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+
+        let tiled = get(TILE_WIDTH).is_some();
+        let (chunk_width, chunk_height, offsets, counts) = if tiled {
+            (
+                first(TILE_WIDTH, 0),
+                first(TILE_LENGTH, 0),
+                get(TILE_OFFSETS),
+                get(TILE_BYTE_COUNTS),
+            )
+        } else {
+            (
+                width,
+                cmp::min(first(ROWS_PER_STRIP, height), height),
+                get(STRIP_OFFSETS),
+                get(STRIP_BYTE_COUNTS),
+            )
+        };
+        if chunk_width == 0 || chunk_height == 0 {
+            return Err(decode_error("Invalid tile size"));
+        }
+        let (offsets, counts) = match (offsets, counts) {
+            (Some(offsets), Some(counts)) => (offsets, counts),
+            _ => return Err(decode_error("Image data missing")),
+        };
+
+        let page = Page {
+            width,
+            height,
+            bits,
+            samples,
+            photometric,
+            compression,
+            predictor,
+            planar: first(PLANAR_CONFIGURATION, 1) == 2 && samples > 1,
+            alpha,
+            palette,
+            chunk_width,
+            chunk_height,
+            tiled,
+            offsets,
+            counts,
+        };
+        let chunks = page.chunks_across() * page.chunks_down() * page.planes();
+        if page.offsets.len() < chunks || page.counts.len() < chunks {
+            return Err(decode_error("Image data missing"));
+        }
+        Ok(page)
+    }
+
+    // Number of strips or tiles in a row.
+    fn chunks_across(&self) -> usize {
+        self.width.div_ceil(self.chunk_width) as usize
+    }
+
+    // Number of strips or tiles in a column.
+    fn chunks_down(&self) -> usize {
+        self.height.div_ceil(self.chunk_height) as usize
+    }
+
+    // Number of separately stored planes, one for each sample if the planes are separate.
+    fn planes(&self) -> usize {
+        if self.planar {
+            self.samples
+        } else {
+            1
+        }
+    }
+
+    // Decode the strips or tiles of the page into RGBA.
+    fn decode(&self, tiff: &TiffReader, buffer: &[u8], limits: &Limits) -> RasterResult<Image> {
+        limits.check_dimensions(self.width, self.height)?;
+        let width = self.width as usize;
+        let height = self.height as usize;
+        // The 8 bit samples are held along with the RGBA pixels
+        limits.check_alloc(width as u64 * height as u64 * (self.samples as u64 + 4))?;
+
+        let chunk_samples = self.samples / self.planes();
+        let chunk_width = self.chunk_width as usize;
+        let chunk_height = self.chunk_height as usize;
+        let row_len = (chunk_width * chunk_samples * self.bits as usize).div_ceil(8);
+        let across = self.chunks_across();
+        let down = self.chunks_down();
+
+        let mut samples = vec![0; width * height * self.samples];
+        for plane in 0..self.planes() {
+            for cy in 0..down {
+                // The last strip is cut off at the bottom of the image, tiles are not
+                let rows = if self.tiled {
+                    chunk_height
+                } else {
+                    cmp::min(chunk_height, height - cy * chunk_height)
+                };
+                for cx in 0..across {
+                    let index = (plane * down + cy) * across + cx;
+                    let start = self.offsets[index] as usize;
+                    let data = start
+                        .checked_add(self.counts[index] as usize)
+                        .and_then(|end| buffer.get(start..end))
+                        .ok_or_else(|| decode_error("Image truncated"))?;
+                    let mut data = self.decompress(data, row_len * rows)?;
+                    if self.predictor == 2 {
+                        for row in data.chunks_mut(row_len) {
+                            undo_predictor(row, chunk_samples, self.bits, tiff.big_endian());
+                        }
+                    }
+
+                    for (r, row) in data.chunks(row_len).enumerate() {
+                        let y = cy * chunk_height + r;
+                        if y >= height {
+                            break;
+                        }
+                        for x in 0..chunk_width {
+                            let image_x = cx * chunk_width + x;
+                            if image_x >= width {
+                                break;
+                            }
+                            let pos = (y * width + image_x) * self.samples;
+                            for s in 0..chunk_samples {
+                                let value = self.read_sample(row, x * chunk_samples + s, tiff);
+                                samples[pos + plane + s] = value;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut bytes = Vec::with_capacity(width * height * 4);
+        for pixel in samples.chunks(self.samples) {
+            let mut rgba = match self.photometric {
+                WHITE_IS_ZERO => [255 - pixel[0], 255 - pixel[0], 255 - pixel[0], 255],
+                RGB => [pixel[0], pixel[1], pixel[2], 255],
+                PALETTE => self
+                    .palette
+                    .get(pixel[0] as usize)
+                    .cloned()
+                    .unwrap_or([0, 0, 0, 255]),
+                _ => [pixel[0], pixel[0], pixel[0], 255],
+            };
+            if let Some(associated) = self.alpha {
+                let alpha = pixel[if self.photometric == RGB { 3 } else { 1 }];
+                rgba[3] = alpha;
+                if associated && alpha > 0 {
+                    for c in &mut rgba[..3] {
+                        *c = cmp::min(255, (*c as u32 * 255 + alpha as u32 / 2) / alpha as u32)
+                            as u8;
+                    }
+                }
+            }
+            bytes.extend_from_slice(&rgba);
+        }
+
+        Ok(Image {
+            width: width as i32,
+            height: height as i32,
+            bytes,
+        })
+    }
+
+    // Decompress a strip or tile into exactly len bytes.
+    fn decompress(&self, data: &[u8], len: usize) -> RasterResult<Vec<u8>> {
+        let mut out = match self.compression {
+            PACKBITS => decompress_packbits(data, len),
+            LZW => decompress_lzw(data, len)?,
+            DEFLATE | OLD_DEFLATE => {
+                let mut out = Vec::with_capacity(len);
+                inflate::DeflateDecoder::from_zlib(data)
+                    .take(len as u64)
+                    .read_to_end(&mut out)
+                    .map_err(|_| decode_error("CorruptFlateStream"))?;
+                out
+            }
+            _ => data[..cmp::min(len, data.len())].to_vec(),
+        };
+        if out.len() < len {
+            return Err(decode_error("Image truncated"));
+        }
+        out.truncate(len);
+        Ok(out)
+    }
+
+    // Read the nth sample of a row, scaled to 8 bits unless it is a palette index.
+    fn read_sample(&self, row: &[u8], n: usize, tiff: &TiffReader) -> u8 {
+        match self.bits {
+            8 => row[n],
+            16 => {
+                let b = [row[n * 2], row[n * 2 + 1]];
+                let value = if tiff.big_endian() {
+                    u16::from_be_bytes(b)
+                } else {
+                    u16::from_le_bytes(b)
+                };
+                ((value as u32 * 255 + 32767) / 65535) as u8
+            }
+            bits => {
+                let bits = bits as usize;
+                let bit = n * bits;
+                let max = (1 << bits) - 1;
+                let value = (row[bit / 8] >> (8 - bits - bit % 8)) & max;
+                if self.photometric == PALETTE {
+                    value
+                } else {
+                    value * (255 / max)
+                }
+            }
+        }
+    }
+}
+
+// Check the header of a TIFF.
+fn read_tiff(buffer: &[u8]) -> RasterResult<TiffReader<'_>> {
+    TiffReader::new(buffer).ok_or_else(|| decode_error("InvalidSignature"))
+}
+
+// Offset of the first directory.
+fn first_ifd(tiff: &TiffReader) -> RasterResult<usize> {
+    tiff.u32(4)
+        .map(|offset| offset as usize)
+        .ok_or_else(|| decode_error("Unexpected end of file"))
+}
+
+// Read the fields of every directory in the chain, one for each page.
+fn read_pages(tiff: &TiffReader) -> RasterResult<Vec<Vec<ExifField>>> {
+    let mut pages = Vec::new();
+    let mut visited = Vec::new();
+    let mut offset = first_ifd(tiff)?;
+    // Guard against directories that point at each other
+    while offset != 0 && !visited.contains(&offset) {
+        visited.push(offset);
+        let fields = tiff
+            .ifd(offset, ExifIfd::Primary)
+            .ok_or_else(|| decode_error("Directory truncated"))?;
+        pages.push(fields);
+
+        let count = tiff.u16(offset).unwrap_or(0) as usize;
+        offset = tiff.u32(offset + 2 + count * 12).unwrap_or(0) as usize;
+    }
+    if pages.is_empty() {
+        return Err(decode_error("No pages"));
+    }
+    Ok(pages)
+}
+
+// The values of an integer field.
+fn values(fields: &[ExifField], tag: u16) -> Option<Vec<u32>> {
+    let field = fields.iter().find(|field| field.tag == tag)?;
+    let values: Vec<u32> = match field.value {
+        ExifValue::Byte(ref v) => v.iter().map(|&v| v as u32).collect(),
+        ExifValue::Short(ref v) => v.iter().map(|&v| v as u32).collect(),
+        ExifValue::Long(ref v) => v.clone(),
+        _ => return None,
+    };
+    Some(values).filter(|values| !values.is_empty())
+}
+
+// Add each sample of a row to the same sample of the pixel before it.
+fn undo_predictor(row: &mut [u8], samples: usize, bits: u16, big_endian: bool) {
+    if bits == 8 {
+        for i in samples..row.len() {
+            row[i] = row[i].wrapping_add(row[i - samples]);
+        }
+        return;
+    }
+    let read = |b: &[u8]| {
+        if big_endian {
+            u16::from_be_bytes([b[0], b[1]])
+        } else {
+            u16::from_le_bytes([b[0], b[1]])
+        }
+    };
+    for i in samples..row.len() / 2 {
+        let value = read(&row[i * 2..]).wrapping_add(read(&row[(i - samples) * 2..]));
+        let bytes = if big_endian {
+            value.to_be_bytes()
+        } else {
+            value.to_le_bytes()
+        };
+        row[i * 2..i * 2 + 2].copy_from_slice(&bytes);
+    }
+}
+
+// Expand PackBits runs until len bytes are produced or the data ends.
+fn decompress_packbits(data: &[u8], len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(len);
+    let mut pos = 0;
+    while out.len() < len && pos < data.len() {
+        let n = data[pos] as i8;
+        pos += 1;
+        if n >= 0 {
+            let end = cmp::min(pos + n as usize + 1, data.len());
+            out.extend_from_slice(&data[pos..end]);
+            pos = end;
+        } else if n != -128 {
+            if let Some(&value) = data.get(pos) {
+                out.extend(iter::repeat_n(value, 1 + n.unsigned_abs() as usize));
+            }
+            pos += 1;
+        }
+    }
+    out
+}
+
+// Compress each row with PackBits, using runs of at least 3 bytes.
+fn compress_packbits(data: &[u8], row_len: usize) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len() / 128 + 1);
+    for row in data.chunks(row_len) {
+        let mut i = 0;
+        while i < row.len() {
+            let run = row[i..]
+                .iter()
+                .take(128)
+                .take_while(|&&b| b == row[i])
+                .count();
+            if run >= 3 {
+                out.push((1 - run as i32) as u8);
+                out.push(row[i]);
+                i += run;
+                continue;
+            }
+
+            // Collect bytes up to the start of the next run
+            let start = i;
+            while i < row.len() && i - start < 128 {
+                if i + 2 < row.len() && row[i] == row[i + 1] && row[i] == row[i + 2] {
+                    break;
+                }
+                i += 1;
+            }
+            out.push((i - start - 1) as u8);
+            out.extend_from_slice(&row[start..i]);
+        }
+    }
+    out
+}
+
+// Width of the codes read once the table holds next codes. The width grows one code early, and
+// the encoder, which is one code ahead of the decoder, switches one code later.
+fn lzw_width(next: usize) -> u32 {
+    match next {
+        0..=510 => 9,
+        511..=1022 => 10,
+        1023..=2046 => 11,
+        _ => 12,
+    }
+}
+
+// Decompress TIFF LZW data, which packs codes from the most significant bit.
+//
+// Each table entry is stored as the position and length of its bytes in the output.
+fn decompress_lzw(data: &[u8], len: usize) -> RasterResult<Vec<u8>> {
+    let mut out = Vec::with_capacity(len);
+    let mut table: Vec<(usize, usize)> = Vec::with_capacity(4096);
+    let mut prev: Option<(usize, usize)> = None;
+    let (mut bits, mut count) = (0u32, 0u32);
+    let mut pos = 0;
+
+    while out.len() < len {
+        let width = lzw_width(258 + table.len());
+        while count < width {
+            match data.get(pos) {
+                Some(&byte) => bits = bits << 8 | byte as u32,
+                // Missing data ends the strip like an end code
+                None => return Ok(out),
+            }
+            pos += 1;
+            count += 8;
+        }
+        let code = ((bits >> (count - width)) & ((1 << width) - 1)) as u16;
+        count -= width;
+
+        if code == LZW_CLEAR {
+            table.clear();
+            prev = None;
+            continue;
+        }
+        if code == LZW_EOI {
+            break;
+        }
+
+        let start = out.len();
+        let entry = if code < 256 {
+            out.push(code as u8);
+            (start, 1)
+        } else {
+            let index = code as usize - 258;
+            match (table.get(index).cloned(), prev) {
+                (Some((from, n)), _) => {
+                    out.extend_from_within(from..from + n);
+                    (start, n)
+                }
+                // The code being defined is the previous string and its own first byte
+                (None, Some((from, n))) if index == table.len() => {
+                    out.extend_from_within(from..from + n);
+                    out.push(out[from]);
+                    (start, n + 1)
+                }
+                _ => return Err(decode_error("Invalid LZW code")),
+            }
+        };
+        if let Some((from, n)) = prev {
+            // The previous string and the first byte of this one, which follow each other
+            if table.len() < 4094 - 258 {
+                table.push((from, n + 1));
+            }
+        }
+        prev = Some(entry);
+    }
+    Ok(out)
+}
+
+// Compress data with TIFF LZW.
+fn compress_lzw(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() / 2);
+    let (mut bits, mut count) = (0u32, 0u32);
+    let mut emit = |code: u16, width: u32, out: &mut Vec<u8>| {
+        bits = bits << width | code as u32;
+        count += width;
+        while count >= 8 {
+            out.push((bits >> (count - 8)) as u8);
+            count -= 8;
+        }
+    };
+
+    let mut table: HashMap<(u16, u8), u16> = HashMap::new();
+    let mut next = 258;
+    emit(LZW_CLEAR, 9, &mut out);
+    let mut iter = data.iter();
+    let mut prefix = match iter.next() {
+        Some(&byte) => byte as u16,
+        None => {
+            emit(LZW_EOI, 9, &mut out);
+            return finish_bits(out, bits, count);
+        }
+    };
+    for &byte in iter {
+        if let Some(&code) = table.get(&(prefix, byte)) {
+            prefix = code;
+            continue;
+        }
+        emit(prefix, lzw_width(next - 1), &mut out);
+        table.insert((prefix, byte), next as u16);
+        next += 1;
+        if next == 4094 {
+            emit(LZW_CLEAR, 12, &mut out);
+            table.clear();
+            next = 258;
+        }
+        prefix = byte as u16;
+    }
+    emit(prefix, lzw_width(next - 1), &mut out);
+    emit(LZW_EOI, lzw_width(next), &mut out);
+    finish_bits(out, bits, count)
+}
+
+// Flush the bits left over after the last code.
+fn finish_bits(mut out: Vec<u8>, bits: u32, count: u32) -> Vec<u8> {
+    if count > 0 {
+        out.push((bits << (8 - count)) as u8);
+    }
+    out
+}
+
+// Write a directory and the values that do not fit in its entries. Returns the position of the
+// offset to the next directory.
+fn write_ifd(out: &mut Vec<u8>, fields: &[(u16, u16, Vec<u32>)]) -> usize {
+    let ifd = out.len();
+    let mut extra_pos = ifd + 2 + fields.len() * 12 + 4;
+    let mut extra = Vec::new();
+
+    out.extend_from_slice(&(fields.len() as u16).to_le_bytes());
+    for (tag, kind, values) in fields {
+        let mut bytes = Vec::new();
+        for &value in values {
+            if *kind == SHORT {
+                bytes.extend_from_slice(&(value as u16).to_le_bytes());
+            } else {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        // Rationals are stored as pairs of values
+        let count = if *kind == RATIONAL {
+            values.len() / 2
+        } else {
+            values.len()
+        };
+
+        out.extend_from_slice(&tag.to_le_bytes());
+        out.extend_from_slice(&kind.to_le_bytes());
+        out.extend_from_slice(&(count as u32).to_le_bytes());
+        if bytes.len() <= 4 {
+            bytes.resize(4, 0);
+            out.extend_from_slice(&bytes);
+        } else {
+            out.extend_from_slice(&(extra_pos as u32).to_le_bytes());
+            extra_pos += bytes.len();
+            extra.extend_from_slice(&bytes);
+        }
+    }
+    let next = out.len();
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&extra);
+    next
+}
+
+fn decode_error(msg: &str) -> RasterError {
+    RasterError::Decode(ImageFormat::Tiff, msg.to_string())
+}
+
+fn encode_error(msg: &str) -> RasterError {
+    RasterError::Encode(ImageFormat::Tiff, msg.to_string())
+}
@@ -0,0 +1,1184 @@
+//!  A module for encoding/decoding the VP8L lossless bitstream of WebP.
+
+// from rust
+use std::cmp;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+// from external crate
+
+// from local crate
+use error::{RasterError, RasterResult};
+use options::Limits;
+use Image;
+use ImageFormat;
+
+// Byte at the start of every VP8L bitstream
+const SIGNATURE: u8 = 0x2F;
+
+// Largest width or height of a VP8L image
+pub const MAX_SIZE: u32 = 1 << 14;
+
+// Transform types
+const PREDICTOR_TRANSFORM: u32 = 0;
+const COLOR_TRANSFORM: u32 = 1;
+const SUBTRACT_GREEN: u32 = 2;
+const COLOR_INDEXING: u32 = 3;
+
+// Sizes of the alphabets. Length prefix codes come after the literals in the green alphabet,
+// followed by the color cache indices.
+const LITERALS: usize = 256;
+const LENGTH_CODES: usize = 24;
+const DISTANCE_CODES: usize = 40;
+const CODE_LENGTH_CODES: usize = 19;
+
+// Order in which the lengths of the code length code are stored
+const CODE_LENGTH_ORDER: [usize; CODE_LENGTH_CODES] = [
+    17, 18, 0, 1, 2, 3, 4, 5, 16, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+const MAX_CODE_LENGTH: u8 = 15;
+const MAX_CODE_LENGTH_CODE_LENGTH: u8 = 7;
+
+// Codes of up to this many bits are decoded with a single table lookup
+const FAST_BITS: u32 = 9;
+
+// Offsets to the pixels close to the current one, for the first 120 distance codes. The high
+// nibble is the row above and 8 minus the low nibble the column.
+const DISTANCE_MAP: [u8; 120] = [
+    0x18, 0x07, 0x17, 0x19, 0x28, 0x06, 0x27, 0x29, 0x16, 0x1A, 0x26, 0x2A, 0x38, 0x05, 0x37, 0x39,
+    0x15, 0x1B, 0x36, 0x3A, 0x25, 0x2B, 0x48, 0x04, 0x47, 0x49, 0x14, 0x1C, 0x35, 0x3B, 0x46, 0x4A,
+    0x24, 0x2C, 0x58, 0x45, 0x4B, 0x34, 0x3C, 0x03, 0x57, 0x59, 0x13, 0x1D, 0x56, 0x5A, 0x23, 0x2D,
+    0x44, 0x4C, 0x55, 0x5B, 0x33, 0x3D, 0x68, 0x02, 0x67, 0x69, 0x12, 0x1E, 0x66, 0x6A, 0x22, 0x2E,
+    0x54, 0x5C, 0x43, 0x4D, 0x65, 0x6B, 0x32, 0x3E, 0x78, 0x01, 0x77, 0x79, 0x53, 0x5D, 0x11, 0x1F,
+    0x64, 0x6C, 0x42, 0x4E, 0x76, 0x7A, 0x21, 0x2F, 0x75, 0x7B, 0x31, 0x3F, 0x63, 0x6D, 0x52, 0x5E,
+    0x00, 0x74, 0x7C, 0x41, 0x4F, 0x10, 0x20, 0x62, 0x6E, 0x30, 0x73, 0x7D, 0x51, 0x5F, 0x40, 0x72,
+    0x7E, 0x61, 0x6F, 0x50, 0x71, 0x7F, 0x60, 0x70,
+];
+
+// Encoder settings: the size of the predictor blocks and the search for backward references
+const PREDICTOR_BITS: u32 = 4;
+const HASH_BITS: u32 = 16;
+const MAX_CHAIN: usize = 32;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 4096;
+const MAX_DISTANCE: usize = (1 << 20) - 120;
+
+// Read the width, height and alpha hint from the header of a VP8L bitstream
+pub fn read_header(data: &[u8]) -> Option<(u32, u32, bool)> {
+    match data.get(..5) {
+        Some(&[SIGNATURE, a, b, c, d]) => {
+            let bits = u32::from_le_bytes([a, b, c, d]);
+            // The version must be 0
+            if bits >> 29 != 0 {
+                return None;
+            }
+            let width = (bits & 0x3FFF) + 1;
+            let height = ((bits >> 14) & 0x3FFF) + 1;
+            Some((width, height, (bits >> 28) & 1 == 1))
+        }
+        _ => None,
+    }
+}
+
+// Decode a VP8L bitstream
+pub fn decode_vp8l(data: &[u8], limits: &Limits) -> RasterResult<Image> {
+    let (width, height, _) =
+        read_header(data).ok_or_else(|| decode_error("Invalid VP8L header"))?;
+    limits.check_dimensions(width, height)?;
+    // The decoded pixels and the image bytes
+    limits.check_alloc(width as u64 * height as u64 * 8)?;
+
+    let mut reader = BitReader::new(&data[5..]);
+    let pixels = decode_argb(&mut reader, width as usize, height as usize)?;
+
+    let mut bytes = Vec::with_capacity(pixels.len() * 4);
+    for argb in pixels {
+        let [a, r, g, b] = argb.to_be_bytes();
+        bytes.extend_from_slice(&[r, g, b, a]);
+    }
+    Ok(Image {
+        width: width as i32,
+        height: height as i32,
+        bytes,
+    })
+}
+
+// Encode an image as a VP8L bitstream
+//
+// Images of up to 256 colors are stored as palette indices. Other images are stored as the
+// difference to a prediction from their neighbors, after subtracting green from red and blue.
+pub fn encode_vp8l(image: &Image) -> Vec<u8> {
+    let (width, height) = (image.width as usize, image.height as usize);
+    let mut pixels: Vec<u32> = image
+        .bytes
+        .chunks(4)
+        .map(|p| u32::from_be_bytes([p[3], p[0], p[1], p[2]]))
+        .collect();
+    let alpha = pixels.iter().any(|&argb| argb >> 24 != 0xFF);
+
+    let mut writer = BitWriter::new();
+    writer.write(SIGNATURE as u32, 8);
+    writer.write(width as u32 - 1, 14);
+    writer.write(height as u32 - 1, 14);
+    writer.write(alpha as u32, 1);
+    writer.write(0, 3);
+
+    let mut xsize = width;
+    if let Some(palette) = find_palette(&pixels) {
+        writer.write(1, 1);
+        writer.write(COLOR_INDEXING, 2);
+        writer.write(palette.len() as u32 - 1, 8);
+        // Each color is stored as the difference to the one before
+        let mut deltas = palette.clone();
+        for i in (1..deltas.len()).rev() {
+            deltas[i] = sub_pixels(deltas[i], deltas[i - 1]);
+        }
+        write_image_data(&mut writer, &deltas, deltas.len(), false);
+
+        let bits = index_bits(palette.len());
+        pixels = pack_indices(&pixels, width, height, &palette, bits);
+        xsize = subsample(width, bits);
+    } else {
+        writer.write(1, 1);
+        writer.write(SUBTRACT_GREEN, 2);
+        for argb in &mut pixels {
+            let green = (*argb >> 8) & 0xFF;
+            *argb = sub_pixels(*argb, green << 16 | green);
+        }
+
+        writer.write(1, 1);
+        writer.write(PREDICTOR_TRANSFORM, 2);
+        writer.write(PREDICTOR_BITS - 2, 3);
+        let (modes, residuals) = predict(&pixels, width, height);
+        write_image_data(&mut writer, &modes, subsample(width, PREDICTOR_BITS), false);
+        pixels = residuals;
+    }
+    writer.write(0, 1);
+
+    write_image_data(&mut writer, &pixels, xsize, true);
+    writer.finish()
+}
+
+// Private functions
+
+// Reads bits from the least significant bit of each byte first. Reading past the end gives zero
+// bits, which is checked with overrun.
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    bits: u64,
+    count: u32,
+    used: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> BitReader<'a> {
+        BitReader {
+            data,
+            pos: 0,
+            bits: 0,
+            count: 0,
+            used: 0,
+        }
+    }
+
+    fn peek(&mut self, n: u32) -> u32 {
+        while self.count <= 56 {
+            let byte = self.data.get(self.pos).cloned().unwrap_or(0);
+            self.bits |= (byte as u64) << self.count;
+            self.pos += 1;
+            self.count += 8;
+        }
+        (self.bits & ((1 << n) - 1)) as u32
+    }
+
+    fn consume(&mut self, n: u32) {
+        self.bits >>= n;
+        self.count -= n;
+        self.used += n as usize;
+    }
+
+    fn read(&mut self, n: u32) -> u32 {
+        let value = self.peek(n);
+        self.consume(n);
+        value
+    }
+
+    fn overrun(&self) -> bool {
+        self.used > self.data.len() * 8
+    }
+}
+
+// Writes bits from the least significant bit of each byte first.
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn new() -> BitWriter {
+        BitWriter {
+            bytes: Vec::new(),
+            bits: 0,
+            count: 0,
+        }
+    }
+
+    fn write(&mut self, value: u32, n: u32) {
+        self.bits |= (value as u64) << self.count;
+        self.count += n;
+        while self.count >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.bits as u8);
+        }
+        self.bytes
+    }
+}
+
+// A canonical prefix code read from the bitstream.
+struct PrefixCode {
+    // Number of codes of each length, and the symbols ordered by code length and value
+    counts: [u16; MAX_CODE_LENGTH as usize + 1],
+    symbols: Vec<u16>,
+    // Symbol and length of each short code, indexed by the next bits of the stream
+    fast: Vec<(u16, u8)>,
+    // A code with a single symbol takes no bits
+    single: Option<u16>,
+}
+
+impl PrefixCode {
+    fn new(lengths: &[u8]) -> RasterResult<PrefixCode> {
+        let mut counts = [0u16; MAX_CODE_LENGTH as usize + 1];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let used: Vec<usize> = (0..lengths.len()).filter(|&s| lengths[s] != 0).collect();
+        let mut code = PrefixCode {
+            counts,
+            symbols: Vec::new(),
+            fast: Vec::new(),
+            single: None,
+        };
+        match used.len() {
+            0 => return Err(decode_error("Empty prefix code")),
+            1 => {
+                code.single = Some(used[0] as u16);
+                return Ok(code);
+            }
+            _ => {}
+        }
+
+        // Every code must be used exactly once
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err(decode_error("Over-subscribed prefix code"));
+            }
+        }
+        if left != 0 {
+            return Err(decode_error("Incomplete prefix code"));
+        }
+
+        code.symbols = used.iter().map(|&s| s as u16).collect();
+        code.symbols.sort_by_key(|&s| lengths[s as usize]);
+        let codes = canonical_codes(lengths);
+        code.fast = vec![(0, 0); 1 << FAST_BITS];
+        for &s in &used {
+            let len = lengths[s] as u32;
+            if len <= FAST_BITS {
+                let reversed = reverse_bits(codes[s], len);
+                for fill in (reversed as usize..1 << FAST_BITS).step_by(1 << len) {
+                    code.fast[fill] = (s as u16, len as u8);
+                }
+            }
+        }
+        Ok(code)
+    }
+
+    fn read_symbol(&self, reader: &mut BitReader) -> usize {
+        if let Some(symbol) = self.single {
+            return symbol as usize;
+        }
+        let (symbol, len) = self.fast[reader.peek(FAST_BITS) as usize];
+        if len > 0 {
+            reader.consume(len as u32);
+            return symbol as usize;
+        }
+
+        // Longer codes are read one bit at a time
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for &count in &self.counts[1..] {
+            code |= reader.read(1) as usize;
+            let count = count as usize;
+            if code < first + count {
+                return self.symbols[index + code - first] as usize;
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        0
+    }
+}
+
+// The five prefix codes used together for a block of pixels: green with lengths and cache
+// indices, red, blue, alpha and distance.
+type CodeGroup = [PrefixCode; 5];
+
+// A transform read from the bitstream, with the width of the image it applies to.
+enum Transform {
+    Predictor(usize, u32, Vec<u32>),
+    Color(usize, u32, Vec<u32>),
+    SubtractGreen,
+    ColorIndexing(usize, u32, Vec<u32>),
+}
+
+// Read the transforms and the pixels of the image, then undo the transforms.
+fn decode_argb(reader: &mut BitReader, width: usize, height: usize) -> RasterResult<Vec<u32>> {
+    let mut transforms = Vec::new();
+    let mut xsize = width;
+    let mut seen = 0;
+    while reader.read(1) == 1 {
+        let kind = reader.read(2);
+        if seen & (1 << kind) != 0 {
+            return Err(decode_error("Transform used twice"));
+        }
+        seen |= 1 << kind;
+
+        let transform = match kind {
+            PREDICTOR_TRANSFORM | COLOR_TRANSFORM => {
+                let bits = reader.read(3) + 2;
+                let (w, h) = (subsample(xsize, bits), subsample(height, bits));
+                let data = decode_image_data(reader, w, h, false)?;
+                if kind == PREDICTOR_TRANSFORM {
+                    Transform::Predictor(xsize, bits, data)
+                } else {
+                    Transform::Color(xsize, bits, data)
+                }
+            }
+            SUBTRACT_GREEN => Transform::SubtractGreen,
+            _ => {
+                let len = reader.read(8) as usize + 1;
+                let mut palette = decode_image_data(reader, len, 1, false)?;
+                for i in 1..len {
+                    palette[i] = add_pixels(palette[i], palette[i - 1]);
+                }
+                let bits = index_bits(len);
+                let transform = Transform::ColorIndexing(xsize, bits, palette);
+                xsize = subsample(xsize, bits);
+                transform
+            }
+        };
+        transforms.push(transform);
+    }
+
+    let mut pixels = decode_image_data(reader, xsize, height, true)?;
+    for transform in transforms.iter().rev() {
+        match *transform {
+            Transform::Predictor(width, bits, ref modes) => {
+                inverse_predictor(&mut pixels, width, height, bits, modes)
+            }
+            Transform::Color(width, bits, ref data) => {
+                inverse_color(&mut pixels, width, bits, data)
+            }
+            Transform::SubtractGreen => {
+                for argb in &mut pixels {
+                    let green = (*argb >> 8) & 0xFF;
+                    *argb = add_pixels(*argb, green << 16 | green);
+                }
+            }
+            Transform::ColorIndexing(width, bits, ref palette) => {
+                pixels = inverse_indexing(&pixels, width, height, bits, palette)
+            }
+        }
+    }
+    Ok(pixels)
+}
+
+// Read an entropy coded image. Only the main image can use several groups of prefix codes.
+fn decode_image_data(
+    reader: &mut BitReader,
+    xsize: usize,
+    ysize: usize,
+    main: bool,
+) -> RasterResult<Vec<u32>> {
+    let cache_bits = if reader.read(1) == 1 {
+        let bits = reader.read(4);
+        if !(1..=11).contains(&bits) {
+            return Err(decode_error("Invalid color cache size"));
+        }
+        bits
+    } else {
+        0
+    };
+    let cache_size = if cache_bits > 0 { 1 << cache_bits } else { 0 };
+
+    // Each block of the entropy image selects a group of codes in its red and green channels
+    let (group_bits, group_image) = if main && reader.read(1) == 1 {
+        let bits = reader.read(3) + 2;
+        let (w, h) = (subsample(xsize, bits), subsample(ysize, bits));
+        (bits, decode_image_data(reader, w, h, false)?)
+    } else {
+        (0, Vec::new())
+    };
+    let group_count = group_image
+        .iter()
+        .map(|&p| (p >> 8) as usize & 0xFFFF)
+        .max()
+        .unwrap_or(0)
+        + 1;
+
+    let mut groups: Vec<CodeGroup> = Vec::with_capacity(group_count);
+    for _ in 0..group_count {
+        groups.push([
+            read_code(reader, LITERALS + LENGTH_CODES + cache_size)?,
+            read_code(reader, LITERALS)?,
+            read_code(reader, LITERALS)?,
+            read_code(reader, LITERALS)?,
+            read_code(reader, DISTANCE_CODES)?,
+        ]);
+        if reader.overrun() {
+            return Err(decode_error("Unexpected end of data"));
+        }
+    }
+
+    let total = xsize * ysize;
+    let group_width = subsample(xsize, group_bits);
+    let mut pixels = Vec::with_capacity(total);
+    let mut cache = vec![0; cache_size];
+    while pixels.len() < total {
+        let pos = pixels.len();
+        let group = if group_image.is_empty() {
+            &groups[0]
+        } else {
+            let (x, y) = (pos % xsize, pos / xsize);
+            let block = group_image[(y >> group_bits) * group_width + (x >> group_bits)];
+            &groups[(block >> 8) as usize & 0xFFFF]
+        };
+
+        let symbol = group[0].read_symbol(reader);
+        if symbol < LITERALS {
+            let red = group[1].read_symbol(reader) as u32;
+            let blue = group[2].read_symbol(reader) as u32;
+            let alpha = group[3].read_symbol(reader) as u32;
+            pixels.push(alpha << 24 | red << 16 | (symbol as u32) << 8 | blue);
+        } else if symbol < LITERALS + LENGTH_CODES {
+            let length = prefix_value(reader, symbol - LITERALS);
+            let distance_symbol = group[4].read_symbol(reader);
+            let distance = plane_distance(prefix_value(reader, distance_symbol), xsize);
+            if distance > pos || length > total - pos {
+                return Err(decode_error("Invalid backward reference"));
+            }
+            for i in pos..pos + length {
+                let argb = pixels[i - distance];
+                pixels.push(argb);
+            }
+        } else {
+            let index = symbol - LITERALS - LENGTH_CODES;
+            pixels.push(cache[index]);
+        }
+
+        if cache_size > 0 {
+            for &argb in &pixels[pos..] {
+                cache[cache_index(argb, cache_bits)] = argb;
+            }
+        }
+        if reader.overrun() {
+            return Err(decode_error("Unexpected end of data"));
+        }
+    }
+    Ok(pixels)
+}
+
+// Read the code lengths of a prefix code, either as a list of one or two symbols or compressed
+// with a code length code.
+fn read_code(reader: &mut BitReader, alphabet: usize) -> RasterResult<PrefixCode> {
+    let mut lengths = vec![0; alphabet];
+    if reader.read(1) == 1 {
+        let count = reader.read(1) + 1;
+        let first_bits = if reader.read(1) == 1 { 8 } else { 1 };
+        let mut symbols = vec![reader.read(first_bits) as usize];
+        if count == 2 {
+            symbols.push(reader.read(8) as usize);
+        }
+        for symbol in symbols {
+            match lengths.get_mut(symbol) {
+                Some(len) => *len = 1,
+                None => return Err(decode_error("Invalid prefix code")),
+            }
+        }
+        return PrefixCode::new(&lengths);
+    }
+
+    let mut code_length_lengths = [0; CODE_LENGTH_CODES];
+    let count = reader.read(4) as usize + 4;
+    for &i in &CODE_LENGTH_ORDER[..count] {
+        code_length_lengths[i] = reader.read(3) as u8;
+    }
+    let code_length_code = PrefixCode::new(&code_length_lengths)?;
+
+    let mut max_symbol = if reader.read(1) == 1 {
+        let bits = 2 + 2 * reader.read(3);
+        let max_symbol = 2 + reader.read(bits) as usize;
+        if max_symbol > alphabet {
+            return Err(decode_error("Invalid prefix code"));
+        }
+        max_symbol
+    } else {
+        alphabet
+    };
+
+    let mut symbol = 0;
+    let mut previous = 8;
+    while symbol < alphabet && max_symbol > 0 {
+        max_symbol -= 1;
+        let len = code_length_code.read_symbol(reader) as u8;
+        if len < 16 {
+            lengths[symbol] = len;
+            symbol += 1;
+            if len != 0 {
+                previous = len;
+            }
+            continue;
+        }
+        let (repeat, value) = match len {
+            16 => (3 + reader.read(2) as usize, previous),
+            17 => (3 + reader.read(3) as usize, 0),
+            _ => (11 + reader.read(7) as usize, 0),
+        };
+        if symbol + repeat > alphabet {
+            return Err(decode_error("Invalid prefix code"));
+        }
+        for len in &mut lengths[symbol..symbol + repeat] {
+            *len = value;
+        }
+        symbol += repeat;
+    }
+    if reader.overrun() {
+        return Err(decode_error("Unexpected end of data"));
+    }
+    PrefixCode::new(&lengths)
+}
+
+// Read the value of a length or distance from its prefix code and extra bits.
+fn prefix_value(reader: &mut BitReader, prefix: usize) -> usize {
+    if prefix < 4 {
+        return prefix + 1;
+    }
+    let extra = (prefix as u32 - 2) >> 1;
+    let offset = (2 + (prefix & 1)) << extra;
+    offset + reader.read(extra) as usize + 1
+}
+
+// Split a length or distance into its prefix code, number of extra bits and their value.
+fn prefix_encode(value: usize) -> (usize, u32, u32) {
+    let value = value - 1;
+    if value < 4 {
+        return (value, 0, 0);
+    }
+    let highest = usize::BITS - 1 - value.leading_zeros();
+    let second = (value >> (highest - 1)) & 1;
+    let extra = highest - 1;
+    (
+        2 * highest as usize + second,
+        extra,
+        (value & ((1 << extra) - 1)) as u32,
+    )
+}
+
+// The distance in pixels for a distance code. The first 120 codes are offsets to pixels close by
+// in two dimensions.
+fn plane_distance(code: usize, xsize: usize) -> usize {
+    if code > DISTANCE_MAP.len() {
+        return code - DISTANCE_MAP.len();
+    }
+    let offset = DISTANCE_MAP[code - 1];
+    let (x, y) = (8 - (offset & 0xF) as isize, (offset >> 4) as isize);
+    cmp::max(1, x + y * xsize as isize) as usize
+}
+
+// The shortest distance code for each distance covered by the offsets of the distance map.
+fn plane_codes(xsize: usize) -> HashMap<usize, usize> {
+    let mut codes = HashMap::new();
+    for code in 1..=DISTANCE_MAP.len() {
+        codes.entry(plane_distance(code, xsize)).or_insert(code);
+    }
+    codes
+}
+
+fn cache_index(argb: u32, bits: u32) -> usize {
+    (argb.wrapping_mul(0x1E35_A7BD) >> (32 - bits)) as usize
+}
+
+// Size of an image after dividing it into blocks of 2^bits
+fn subsample(size: usize, bits: u32) -> usize {
+    (size + (1 << bits) - 1) >> bits
+}
+
+// Number of bits needed to pack several palette indices into one pixel
+fn index_bits(colors: usize) -> u32 {
+    match colors {
+        0..=2 => 3,
+        3..=4 => 2,
+        5..=16 => 1,
+        _ => 0,
+    }
+}
+
+// Add or subtract each channel of two pixels, modulo 256
+fn add_pixels(a: u32, b: u32) -> u32 {
+    let (a, b) = (a.to_le_bytes(), b.to_le_bytes());
+    u32::from_le_bytes([
+        a[0].wrapping_add(b[0]),
+        a[1].wrapping_add(b[1]),
+        a[2].wrapping_add(b[2]),
+        a[3].wrapping_add(b[3]),
+    ])
+}
+
+fn sub_pixels(a: u32, b: u32) -> u32 {
+    let (a, b) = (a.to_le_bytes(), b.to_le_bytes());
+    u32::from_le_bytes([
+        a[0].wrapping_sub(b[0]),
+        a[1].wrapping_sub(b[1]),
+        a[2].wrapping_sub(b[2]),
+        a[3].wrapping_sub(b[3]),
+    ])
+}
+
+// Apply a function to each channel of two or three pixels
+fn per_channel<F: Fn(i32, i32, i32) -> i32>(a: u32, b: u32, c: u32, f: F) -> u32 {
+    let channel = |shift: u32| {
+        let value = f(
+            (a >> shift & 0xFF) as i32,
+            (b >> shift & 0xFF) as i32,
+            (c >> shift & 0xFF) as i32,
+        );
+        (value.clamp(0, 255) as u32) << shift
+    };
+    channel(24) | channel(16) | channel(8) | channel(0)
+}
+
+fn average2(a: u32, b: u32) -> u32 {
+    (((a ^ b) & 0xFEFE_FEFE) >> 1).wrapping_add(a & b)
+}
+
+// The prediction of a pixel that is not on the top row or left column, from its neighbors.
+fn predictor(mode: u32, pixels: &[u32], i: usize, width: usize) -> u32 {
+    let left = pixels[i - 1];
+    let top = pixels[i - width];
+    let top_left = pixels[i - width - 1];
+    // On the right column this is the first pixel of the current row
+    let top_right = pixels[i - width + 1];
+    match mode {
+        1 => left,
+        2 => top,
+        3 => top_right,
+        4 => top_left,
+        5 => average2(average2(left, top_right), top),
+        6 => average2(left, top_left),
+        7 => average2(left, top),
+        8 => average2(top_left, top),
+        9 => average2(top, top_right),
+        10 => average2(average2(left, top_left), average2(top, top_right)),
+        11 => {
+            // The neighbor closest to the gradient left + top - top left
+            let distance = |a: u32| {
+                (0..4)
+                    .map(|c| {
+                        let (a, b) = ((a >> (c * 8)) & 0xFF, (top_left >> (c * 8)) & 0xFF);
+                        (a as i32 - b as i32).abs()
+                    })
+                    .sum::<i32>()
+            };
+            if distance(top) < distance(left) {
+                left
+            } else {
+                top
+            }
+        }
+        12 => per_channel(left, top, top_left, |l, t, tl| l + t - tl),
+        13 => per_channel(average2(left, top), top_left, 0, |a, tl, _| {
+            a + (a - tl) / 2
+        }),
+        _ => 0xFF00_0000,
+    }
+}
+
+// The prediction of a pixel with the given mode, or the fixed prediction on the edges.
+fn edge_predictor(mode: u32, pixels: &[u32], x: usize, y: usize, width: usize) -> u32 {
+    let i = y * width + x;
+    match (x, y) {
+        (0, 0) => 0xFF00_0000,
+        (_, 0) => pixels[i - 1],
+        (0, _) => pixels[i - width],
+        _ => predictor(mode, pixels, i, width),
+    }
+}
+
+fn inverse_predictor(pixels: &mut [u32], width: usize, height: usize, bits: u32, modes: &[u32]) {
+    let block_width = subsample(width, bits);
+    for y in 0..height {
+        for x in 0..width {
+            let mode = (modes[(y >> bits) * block_width + (x >> bits)] >> 8) & 0xF;
+            let prediction = edge_predictor(mode, pixels, x, y, width);
+            let i = y * width + x;
+            pixels[i] = add_pixels(pixels[i], prediction);
+        }
+    }
+}
+
+fn color_delta(transform: u32, color: u32) -> u32 {
+    ((transform as u8 as i8 as i32 * color as u8 as i8 as i32) >> 5) as u32
+}
+
+fn inverse_color(pixels: &mut [u32], width: usize, bits: u32, data: &[u32]) {
+    let block_width = subsample(width, bits);
+    for (i, argb) in pixels.iter_mut().enumerate() {
+        let (x, y) = (i % width, i / width);
+        let element = data[(y >> bits) * block_width + (x >> bits)];
+        let (green_to_red, green_to_blue, red_to_blue) = (element, element >> 8, element >> 16);
+
+        let green = *argb >> 8;
+        let red = (*argb >> 16).wrapping_add(color_delta(green_to_red, green)) & 0xFF;
+        let blue = argb
+            .wrapping_add(color_delta(green_to_blue, green))
+            .wrapping_add(color_delta(red_to_blue, red))
+            & 0xFF;
+        *argb = (*argb & 0xFF00_FF00) | red << 16 | blue;
+    }
+}
+
+fn inverse_indexing(
+    pixels: &[u32],
+    width: usize,
+    height: usize,
+    bits: u32,
+    palette: &[u32],
+) -> Vec<u32> {
+    let packed_width = subsample(width, bits);
+    let index_bits = 8 >> bits;
+    let mask = (1 << index_bits) - 1;
+    let mut out = Vec::with_capacity(width * height);
+    for y in 0..height {
+        for x in 0..width {
+            let packed = pixels[y * packed_width + (x >> bits)] >> 8;
+            let shift = (x & ((1 << bits) - 1)) * index_bits;
+            let index = (packed >> shift) as usize & mask;
+            // Indices past the end of the palette are transparent black
+            out.push(palette.get(index).cloned().unwrap_or(0));
+        }
+    }
+    out
+}
+
+// The colors of an image, if it has at most 256
+fn find_palette(pixels: &[u32]) -> Option<Vec<u32>> {
+    let mut palette = Vec::new();
+    let mut seen = HashSet::new();
+    for &argb in pixels {
+        if seen.insert(argb) {
+            if palette.len() == 256 {
+                return None;
+            }
+            palette.push(argb);
+        }
+    }
+    palette.sort_unstable();
+    Some(palette)
+}
+
+// Replace each pixel by its palette index, packing several indices into one pixel for small
+// palettes.
+fn pack_indices(
+    pixels: &[u32],
+    width: usize,
+    height: usize,
+    palette: &[u32],
+    bits: u32,
+) -> Vec<u32> {
+    let indices: HashMap<u32, u32> = palette
+        .iter()
+        .enumerate()
+        .map(|(i, &argb)| (argb, i as u32))
+        .collect();
+    let packed_width = subsample(width, bits);
+    let index_bits = 8 >> bits;
+    let mut packed = vec![0xFF00_0000; packed_width * height];
+    for y in 0..height {
+        for x in 0..width {
+            let index = indices[&pixels[y * width + x]];
+            let shift = (x & ((1 << bits) - 1)) as u32 * index_bits + 8;
+            packed[y * packed_width + (x >> bits)] |= index << shift;
+        }
+    }
+    packed
+}
+
+// Choose a predictor for each block of the image and compute the residuals. The mode with the
+// smallest residuals is chosen.
+fn predict(pixels: &[u32], width: usize, height: usize) -> (Vec<u32>, Vec<u32>) {
+    let block_width = subsample(width, PREDICTOR_BITS);
+    let block_height = subsample(height, PREDICTOR_BITS);
+    let size = 1 << PREDICTOR_BITS;
+
+    let mut modes = Vec::with_capacity(block_width * block_height);
+    for by in 0..block_height {
+        for bx in 0..block_width {
+            let cost = |mode: u32| {
+                let mut cost = 0;
+                for y in by * size..cmp::min(height, (by + 1) * size) {
+                    for x in bx * size..cmp::min(width, (bx + 1) * size) {
+                        let prediction = edge_predictor(mode, pixels, x, y, width);
+                        let residual = sub_pixels(pixels[y * width + x], prediction);
+                        for byte in &residual.to_le_bytes() {
+                            cost += cmp::min(*byte as u32, 256 - *byte as u32);
+                        }
+                    }
+                }
+                cost
+            };
+            let mode = (0..14).min_by_key(|&mode| cost(mode)).unwrap_or(0);
+            modes.push(0xFF00_0000 | mode << 8);
+        }
+    }
+
+    let mut residuals = Vec::with_capacity(pixels.len());
+    for y in 0..height {
+        for x in 0..width {
+            let mode =
+                (modes[(y >> PREDICTOR_BITS) * block_width + (x >> PREDICTOR_BITS)] >> 8) & 0xF;
+            let prediction = edge_predictor(mode, pixels, x, y, width);
+            residuals.push(sub_pixels(pixels[y * width + x], prediction));
+        }
+    }
+    (modes, residuals)
+}
+
+// A literal pixel or a copy of earlier pixels, with its length and distance code
+enum Token {
+    Literal(u32),
+    Copy(usize, usize),
+}
+
+// Find runs of pixels that repeat earlier ones, through hash chains of pixel pairs.
+fn backward_references(pixels: &[u32], xsize: usize) -> Vec<Token> {
+    const NONE: usize = usize::MAX;
+    let plane_codes = plane_codes(xsize);
+    let hashes: Vec<usize> = pixels
+        .windows(2)
+        .map(|pair| {
+            let h = pair[0].wrapping_mul(0x1E35_A7BD) ^ pair[1].wrapping_mul(0x9E37_79B9);
+            (h >> (32 - HASH_BITS)) as usize
+        })
+        .collect();
+    // The most recent position of each hash, and the position before it with the same hash
+    let mut head = vec![NONE; 1 << HASH_BITS];
+    let mut previous = vec![NONE; pixels.len()];
+    let insert = |i: usize, head: &mut [usize], previous: &mut [usize]| {
+        if let Some(&h) = hashes.get(i) {
+            previous[i] = head[h];
+            head[h] = i;
+        }
+    };
+
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < pixels.len() {
+        let max_length = cmp::min(MAX_MATCH, pixels.len() - i);
+        let (mut best_length, mut best_distance) = (0, 0);
+        if let Some(&h) = hashes.get(i) {
+            let mut candidate = head[h];
+            let mut chain = 0;
+            while candidate != NONE && i - candidate <= MAX_DISTANCE && chain < MAX_CHAIN {
+                let length = (0..max_length)
+                    .take_while(|&k| pixels[candidate + k] == pixels[i + k])
+                    .count();
+                if length > best_length {
+                    best_length = length;
+                    best_distance = i - candidate;
+                    if length == max_length {
+                        break;
+                    }
+                }
+                candidate = previous[candidate];
+                chain += 1;
+            }
+        }
+
+        if best_length >= MIN_MATCH {
+            let code = plane_codes
+                .get(&best_distance)
+                .cloned()
+                .unwrap_or(best_distance + DISTANCE_MAP.len());
+            tokens.push(Token::Copy(best_length, code));
+            for k in i..i + best_length {
+                insert(k, &mut head, &mut previous);
+            }
+            i += best_length;
+        } else {
+            tokens.push(Token::Literal(pixels[i]));
+            insert(i, &mut head, &mut previous);
+            i += 1;
+        }
+    }
+    tokens
+}
+
+// Write an entropy coded image with a single group of prefix codes and no color cache.
+fn write_image_data(writer: &mut BitWriter, pixels: &[u32], xsize: usize, main: bool) {
+    // No color cache, and for the main image no entropy image
+    writer.write(0, 1);
+    if main {
+        writer.write(0, 1);
+    }
+
+    let tokens = backward_references(pixels, xsize);
+    let mut histograms = [
+        vec![0; LITERALS + LENGTH_CODES],
+        vec![0; LITERALS],
+        vec![0; LITERALS],
+        vec![0; LITERALS],
+        vec![0; DISTANCE_CODES],
+    ];
+    for token in &tokens {
+        match *token {
+            Token::Literal(argb) => {
+                let [a, r, g, b] = argb.to_be_bytes();
+                histograms[0][g as usize] += 1;
+                histograms[1][r as usize] += 1;
+                histograms[2][b as usize] += 1;
+                histograms[3][a as usize] += 1;
+            }
+            Token::Copy(length, distance) => {
+                histograms[0][LITERALS + prefix_encode(length).0] += 1;
+                histograms[4][prefix_encode(distance).0] += 1;
+            }
+        }
+    }
+
+    let codes: Vec<Vec<(u32, u32)>> = histograms
+        .iter()
+        .map(|histogram| {
+            let lengths = code_lengths(histogram, MAX_CODE_LENGTH);
+            write_code(writer, &lengths);
+            emitted_codes(&lengths)
+        })
+        .collect();
+
+    for token in &tokens {
+        match *token {
+            Token::Literal(argb) => {
+                let [a, r, g, b] = argb.to_be_bytes();
+                for &(code, value) in &[(0, g), (1, r), (2, b), (3, a)] {
+                    let (bits, len) = codes[code][value as usize];
+                    writer.write(bits, len);
+                }
+            }
+            Token::Copy(length, distance) => {
+                let (prefix, extra, value) = prefix_encode(length);
+                let (bits, len) = codes[0][LITERALS + prefix];
+                writer.write(bits, len);
+                writer.write(value, extra);
+                let (prefix, extra, value) = prefix_encode(distance);
+                let (bits, len) = codes[4][prefix];
+                writer.write(bits, len);
+                writer.write(value, extra);
+            }
+        }
+    }
+}
+
+// Write the code lengths of a prefix code. Up to two small symbols are listed directly, other
+// codes are compressed with a code length code.
+fn write_code(writer: &mut BitWriter, lengths: &[u8]) {
+    let used: Vec<usize> = (0..lengths.len()).filter(|&s| lengths[s] != 0).collect();
+    if used.len() <= 2 && used.iter().all(|&s| s < LITERALS) {
+        writer.write(1, 1);
+        writer.write(cmp::max(used.len(), 1) as u32 - 1, 1);
+        let first = used.first().cloned().unwrap_or(0) as u32;
+        if first < 2 {
+            writer.write(0, 1);
+            writer.write(first, 1);
+        } else {
+            writer.write(1, 1);
+            writer.write(first, 8);
+        }
+        if let Some(&second) = used.get(1) {
+            writer.write(second as u32, 8);
+        }
+        return;
+    }
+    writer.write(0, 1);
+
+    // Runs of zeros and of repeated lengths are shortened with codes 16 to 18
+    let mut tokens: Vec<(usize, u32, u32)> = Vec::new();
+    let mut previous = 8;
+    let mut i = 0;
+    while i < lengths.len() {
+        let value = lengths[i];
+        let run = lengths[i..].iter().take_while(|&&len| len == value).count();
+        i += run;
+        let mut left = run;
+        if value == 0 {
+            while left >= 3 {
+                if left <= 10 {
+                    tokens.push((17, 3, left as u32 - 3));
+                    left = 0;
+                } else {
+                    let repeat = cmp::min(left, 138);
+                    tokens.push((18, 7, repeat as u32 - 11));
+                    left -= repeat;
+                }
+            }
+        } else {
+            if value != previous {
+                tokens.push((value as usize, 0, 0));
+                previous = value;
+                left -= 1;
+            }
+            while left >= 3 {
+                let repeat = cmp::min(left, 6);
+                tokens.push((16, 2, repeat as u32 - 3));
+                left -= repeat;
+            }
+        }
+        for _ in 0..left {
+            tokens.push((value as usize, 0, 0));
+        }
+    }
+
+    let mut histogram = [0; CODE_LENGTH_CODES];
+    for &(symbol, _, _) in &tokens {
+        histogram[symbol] += 1;
+    }
+    let code_length_lengths = code_lengths(&histogram, MAX_CODE_LENGTH_CODE_LENGTH);
+    let count = CODE_LENGTH_ORDER
+        .iter()
+        .rposition(|&i| code_length_lengths[i] != 0)
+        .map_or(0, |last| last + 1);
+    let count = cmp::max(count, 4);
+    writer.write(count as u32 - 4, 4);
+    for &i in &CODE_LENGTH_ORDER[..count] {
+        writer.write(code_length_lengths[i] as u32, 3);
+    }
+
+    // Every length is written, so there is no last symbol
+    writer.write(0, 1);
+    let codes = emitted_codes(&code_length_lengths);
+    for &(symbol, extra, value) in &tokens {
+        let (bits, len) = codes[symbol];
+        writer.write(bits, len);
+        writer.write(value, extra);
+    }
+}
+
+// Lengths of a prefix code for the symbol counts of a histogram, limited to max_length bits.
+//
+// The code is built by repeatedly merging the two least frequent nodes. When the code is too
+// long, the smallest counts are raised and the code is built again.
+fn code_lengths(histogram: &[u32], max_length: u8) -> Vec<u8> {
+    let mut lengths = vec![0; histogram.len()];
+    let used: Vec<usize> = (0..histogram.len())
+        .filter(|&s| histogram[s] != 0)
+        .collect();
+    if used.len() == 1 {
+        lengths[used[0]] = 1;
+    }
+    if used.len() < 2 {
+        return lengths;
+    }
+
+    let mut floor = 1;
+    loop {
+        let mut parents = vec![0; used.len()];
+        let mut heap = BinaryHeap::new();
+        for (node, &symbol) in used.iter().enumerate() {
+            heap.push(Reverse((cmp::max(histogram[symbol], floor) as u64, node)));
+        }
+        while let (Some(Reverse((a, na))), Some(Reverse((b, nb)))) = (heap.pop(), heap.pop()) {
+            let node = parents.len();
+            parents.push(node);
+            parents[na] = node;
+            parents[nb] = node;
+            heap.push(Reverse((a + b, node)));
+        }
+
+        // The root is its own parent. Depths are found by walking up from each leaf.
+        let mut longest = 0;
+        for (leaf, &symbol) in used.iter().enumerate() {
+            let mut depth = 0;
+            let mut node = leaf;
+            while parents[node] != node {
+                node = parents[node];
+                depth += 1;
+            }
+            lengths[symbol] = depth;
+            longest = cmp::max(longest, depth);
+        }
+        if longest <= max_length {
+            return lengths;
+        }
+        floor *= 2;
+    }
+}
+
+// Canonical codes for a list of code lengths: shorter codes first, then by symbol.
+fn canonical_codes(lengths: &[u8]) -> Vec<u32> {
+    let mut counts = [0u32; MAX_CODE_LENGTH as usize + 1];
+    for &len in lengths {
+        counts[len as usize] += 1;
+    }
+    counts[0] = 0;
+    let mut next = [0u32; MAX_CODE_LENGTH as usize + 2];
+    for len in 1..=MAX_CODE_LENGTH as usize {
+        next[len + 1] = (next[len] + counts[len]) << 1;
+    }
+    lengths
+        .iter()
+        .map(|&len| {
+            let code = next[len as usize];
+            next[len as usize] += 1;
+            code
+        })
+        .collect()
+}
+
+// The bits to write for each symbol of a prefix code and their number. A code with a single
+// symbol takes no bits.
+fn emitted_codes(lengths: &[u8]) -> Vec<(u32, u32)> {
+    let used = lengths.iter().filter(|&&len| len != 0).count();
+    canonical_codes(lengths)
+        .iter()
+        .zip(lengths)
+        .map(|(&code, &len)| {
+            if used < 2 || len == 0 {
+                (0, 0)
+            } else {
+                (reverse_bits(code, len as u32), len as u32)
+            }
+        })
+        .collect()
+}
+
+// Codes are stored from their most significant bit, but bits are read from the least significant
+// bit of each byte.
+fn reverse_bits(code: u32, len: u32) -> u32 {
+    code.reverse_bits() >> (32 - len)
+}
+
+fn decode_error(msg: &str) -> RasterError {
+    RasterError::Decode(ImageFormat::WebP, msg.to_string())
+}
@@ -0,0 +1,476 @@
+//!  A module for encoding/decoding WebP.
+
+// from rust
+use std::cmp;
+use std::io::{Read, Write};
+
+// from external crate
+
+// from local crate
+use animation::{Animation, DisposalMethod, Frame, LoopCount};
+use endec::vp8l::{decode_vp8l, encode_vp8l, read_header, MAX_SIZE};
+use endec::{blend_pixel, read_limited, skip_bytes};
+use error::{RasterError, RasterResult};
+use image::{ColorType, ImageInfo};
+use metadata::{Exif, Metadata};
+use options::Limits;
+use Image;
+use ImageFormat;
+
+// Flags of the VP8X chunk
+const ALPHA_FLAG: u8 = 0x10;
+const ANIMATION_FLAG: u8 = 0x02;
+
+// Bytes of a chunk kept when probing, enough for the VP8X, ANIM, VP8L and VP8 headers
+const CHUNK_HEAD_LEN: u64 = 32;
+
+// Flags of an ANMF chunk
+const NO_BLEND_FLAG: u8 = 0x02;
+const DISPOSE_FLAG: u8 = 0x01;
+
+// Decode WebP
+//
+// Only lossless images are supported. Animations give their first frame.
+pub fn decode_webp<R: Read>(reader: R, limits: &Limits) -> RasterResult<(Image, Metadata)> {
+    let buffer = read_limited(reader, limits)?;
+    let chunks = read_riff(&buffer)?;
+    let metadata = read_metadata(&chunks);
+
+    let image = match read_canvas(&chunks)? {
+        Some(canvas) if canvas.animated => {
+            let mut frames = read_frames(&chunks, &canvas, Some(1), limits)?;
+            if frames.is_empty() {
+                return Err(decode_error("No frames"));
+            }
+            frames.remove(0).image
+        }
+        _ => decode_bitstream(&chunks, limits)?,
+    };
+    Ok((image, metadata))
+}
+
+// Decode all frames of a WebP. Images that are not animated give a single frame.
+pub fn decode_webp_animation<R: Read>(
+    reader: R,
+    limits: &Limits,
+) -> RasterResult<(Animation, Metadata)> {
+    let buffer = read_limited(reader, limits)?;
+    let chunks = read_riff(&buffer)?;
+    let metadata = read_metadata(&chunks);
+
+    let animation = match read_canvas(&chunks)? {
+        Some(canvas) if canvas.animated => {
+            let frames = read_frames(&chunks, &canvas, None, limits)?;
+            if frames.is_empty() {
+                return Err(decode_error("No frames"));
+            }
+            Animation {
+                width: canvas.width as i32,
+                height: canvas.height as i32,
+                frames,
+                loop_count: canvas.loop_count,
+            }
+        }
+        _ => {
+            let image = decode_bitstream(&chunks, limits)?;
+            Animation::new(vec![Frame::new(image, 0)])
+        }
+    };
+    Ok((animation, metadata))
+}
+
+// Read the size, alpha and frame count of a WebP without decoding the image data
+//
+// Lossy images can be probed even though they cannot be decoded.
+pub fn probe_webp<R: Read>(reader: R) -> RasterResult<ImageInfo> {
+    let heads = read_chunk_heads(reader)?;
+    let chunks: Vec<Chunk> = heads
+        .iter()
+        .map(|(id, data)| Chunk { id: *id, data })
+        .collect();
+
+    let (width, height, alpha, frames) = match read_canvas(&chunks)? {
+        Some(canvas) => {
+            let frames = if canvas.animated {
+                chunks.iter().filter(|chunk| chunk.id == *b"ANMF").count()
+            } else {
+                1
+            };
+            (canvas.width, canvas.height, canvas.alpha, frames)
+        }
+        None => match chunks.first() {
+            Some(chunk) if chunk.id == *b"VP8L" => {
+                let (width, height, alpha) =
+                    read_header(chunk.data).ok_or_else(|| decode_error("Invalid VP8L header"))?;
+                (width, height, alpha, 1)
+            }
+            Some(chunk) if chunk.id == *b"VP8 " => {
+                let (width, height) =
+                    lossy_size(chunk.data).ok_or_else(|| decode_error("Invalid VP8 header"))?;
+                (width, height, false, 1)
+            }
+            _ => return Err(decode_error("Image data missing")),
+        },
+    };
+    Ok(ImageInfo {
+        width: width as i32,
+        height: height as i32,
+        format: ImageFormat::WebP,
+        color_type: if alpha {
+            ColorType::Rgba
+        } else {
+            ColorType::Rgb
+        },
+        bit_depth: 8,
+        frames,
+    })
+}
+
+// Encode lossless WebP
+pub fn encode_webp<W: Write>(image: &Image, mut writer: W) -> RasterResult<()> {
+    check_dimensions(image.width, image.height)?;
+    let mut body = Vec::new();
+    write_chunk(&mut body, b"VP8L", &encode_vp8l(image));
+    writer.write_all(&riff(&body))?;
+    Ok(())
+}
+
+// Encode all frames of an animation as lossless WebP
+//
+// Every frame covers the whole canvas and replaces the frame before it.
+pub fn encode_webp_animation<W: Write>(animation: &Animation, mut writer: W) -> RasterResult<()> {
+    if animation.frames.is_empty() {
+        return Err(encode_error("No frames"));
+    }
+    check_dimensions(animation.width, animation.height)?;
+    let (width, height) = (animation.width as u32, animation.height as u32);
+
+    let alpha = animation
+        .frames
+        .iter()
+        .any(|frame| frame.image.bytes.chunks(4).any(|pixel| pixel[3] != 255));
+    let mut header = vec![ANIMATION_FLAG | if alpha { ALPHA_FLAG } else { 0 }, 0, 0, 0];
+    header.extend_from_slice(&u24_bytes(width - 1));
+    header.extend_from_slice(&u24_bytes(height - 1));
+
+    let mut body = Vec::new();
+    write_chunk(&mut body, b"VP8X", &header);
+
+    // Transparent background color, then the loop count with 0 for forever
+    let mut anim = vec![0; 4];
+    let loop_count = match animation.loop_count {
+        LoopCount::Infinite => 0,
+        LoopCount::Finite(plays) => cmp::max(plays, 1),
+    };
+    anim.extend_from_slice(&loop_count.to_le_bytes());
+    write_chunk(&mut body, b"ANIM", &anim);
+
+    for frame in &animation.frames {
+        let image = &frame.image;
+        if image.width != animation.width || image.height != animation.height {
+            return Err(encode_error("Frame size does not match animation size"));
+        }
+        let mut data = vec![0; 6];
+        data.extend_from_slice(&u24_bytes(width - 1));
+        data.extend_from_slice(&u24_bytes(height - 1));
+        data.extend_from_slice(&u24_bytes(cmp::min(frame.delay, 0xFF_FFFF)));
+        let dispose = if frame.disposal == DisposalMethod::Background {
+            DISPOSE_FLAG
+        } else {
+            0
+        };
+        data.push(NO_BLEND_FLAG | dispose);
+        write_chunk(&mut data, b"VP8L", &encode_vp8l(image));
+        write_chunk(&mut body, b"ANMF", &data);
+    }
+
+    writer.write_all(&riff(&body))?;
+    Ok(())
+}
+
+// Private functions
+
+// A chunk of a RIFF file, without its header and padding.
+struct Chunk<'a> {
+    id: [u8; 4],
+    data: &'a [u8],
+}
+
+// The size and animation settings of an extended WebP, from its VP8X and ANIM chunks.
+struct Canvas {
+    width: u32,
+    height: u32,
+    alpha: bool,
+    animated: bool,
+    loop_count: LoopCount,
+}
+
+// Check the RIFF header and read the chunks of a WebP.
+fn read_riff(buffer: &[u8]) -> RasterResult<Vec<Chunk<'_>>> {
+    if buffer.len() < 12 || &buffer[..4] != b"RIFF" || &buffer[8..12] != b"WEBP" {
+        return Err(decode_error("Invalid header"));
+    }
+    let size = u32::from_le_bytes([buffer[4], buffer[5], buffer[6], buffer[7]]) as usize;
+    if size < 4 {
+        return Err(decode_error("Invalid RIFF size"));
+    }
+    let end = cmp::min(buffer.len(), size.saturating_add(8));
+    read_chunks(&buffer[12..end])
+}
+
+// Check the RIFF header and read the start of the chunks needed to probe a WebP, without reading
+// the image data. The first chunk and ANIM keep enough bytes for their headers, and the other
+// chunks of an animation are skipped over to count the frames.
+fn read_chunk_heads<R: Read>(mut reader: R) -> RasterResult<Vec<([u8; 4], Vec<u8>)>> {
+    let mut header = [0; 12];
+    if reader.read_exact(&mut header).is_err()
+        || &header[..4] != b"RIFF"
+        || &header[8..12] != b"WEBP"
+    {
+        return Err(decode_error("Invalid header"));
+    }
+    let size = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as u64;
+    if size < 4 {
+        return Err(decode_error("Invalid RIFF size"));
+    }
+
+    let mut heads = Vec::new();
+    let mut remaining = size - 4;
+    let mut head = [0; 8];
+    while remaining >= 8 && reader.read_exact(&mut head).is_ok() {
+        let id = [head[0], head[1], head[2], head[3]];
+        let len = u32::from_le_bytes([head[4], head[5], head[6], head[7]]) as u64;
+        let first = heads.is_empty();
+        let keep = if first || id == *b"ANIM" {
+            cmp::min(len, CHUNK_HEAD_LEN)
+        } else {
+            0
+        };
+        let mut data = vec![0; keep as usize];
+        reader
+            .read_exact(&mut data)
+            .map_err(|_| decode_error("Chunk truncated"))?;
+        skip_bytes(&mut reader, len - keep).map_err(|_| decode_error("Chunk truncated"))?;
+
+        // Only animations need more than the first chunk
+        let animated = id == *b"VP8X" && data.first().is_some_and(|f| f & ANIMATION_FLAG != 0);
+        heads.push((id, data));
+        if first && !animated {
+            break;
+        }
+        // Chunks are padded to an even length
+        if len % 2 == 1 && skip_bytes(&mut reader, 1).is_err() {
+            break;
+        }
+        remaining = remaining.saturating_sub(8 + len + len % 2);
+    }
+    Ok(heads)
+}
+
+// Read a sequence of chunks. Each chunk is padded to an even length.
+fn read_chunks(mut data: &[u8]) -> RasterResult<Vec<Chunk<'_>>> {
+    let mut chunks = Vec::new();
+    while data.len() >= 8 {
+        let id = [data[0], data[1], data[2], data[3]];
+        let len = u32::from_le_bytes([data[4], data[5], data[6], data[7]]) as usize;
+        let chunk = data
+            .get(8..8 + len)
+            .ok_or_else(|| decode_error("Chunk truncated"))?;
+        chunks.push(Chunk { id, data: chunk });
+        data = data.get(8 + len + len % 2..).unwrap_or(&[]);
+    }
+    Ok(chunks)
+}
+
+fn find<'a, 'b>(chunks: &'b [Chunk<'a>], id: &[u8; 4]) -> Option<&'b Chunk<'a>> {
+    chunks.iter().find(|chunk| chunk.id == *id)
+}
+
+// Read the EXIF data of a WebP.
+fn read_metadata(chunks: &[Chunk]) -> Metadata {
+    Metadata {
+        exif: find(chunks, b"EXIF").and_then(|chunk| {
+            // Some writers keep the prefix of the JPEG segment
+            let data = chunk.data;
+            Exif::parse(data.strip_prefix(&b"Exif\0\0"[..]).unwrap_or(data))
+        }),
+        ..Metadata::default()
+    }
+}
+
+// Read the canvas of an extended WebP, or None for a simple one.
+fn read_canvas(chunks: &[Chunk]) -> RasterResult<Option<Canvas>> {
+    let header = match chunks.first() {
+        Some(chunk) if chunk.id == *b"VP8X" => chunk.data,
+        _ => return Ok(None),
+    };
+    if header.len() < 10 {
+        return Err(decode_error("Invalid VP8X chunk"));
+    }
+    let animated = header[0] & ANIMATION_FLAG != 0;
+    let loop_count = match find(chunks, b"ANIM") {
+        Some(chunk) if chunk.data.len() >= 6 => {
+            match u16::from_le_bytes([chunk.data[4], chunk.data[5]]) {
+                0 => LoopCount::Infinite,
+                plays => LoopCount::Finite(plays),
+            }
+        }
+        Some(_) => return Err(decode_error("Invalid ANIM chunk")),
+        None if animated => return Err(decode_error("ANIM chunk missing")),
+        None => LoopCount::Infinite,
+    };
+    Ok(Some(Canvas {
+        width: u24(&header[4..]) + 1,
+        height: u24(&header[7..]) + 1,
+        alpha: header[0] & ALPHA_FLAG != 0,
+        animated,
+        loop_count,
+    }))
+}
+
+// Decode the image of a simple WebP or of an animation frame.
+fn decode_bitstream(chunks: &[Chunk], limits: &Limits) -> RasterResult<Image> {
+    for chunk in chunks {
+        match &chunk.id {
+            b"VP8L" => return decode_vp8l(chunk.data, limits),
+            b"VP8 " => return Err(decode_error("Lossy WebP not supported")),
+            _ => {}
+        }
+    }
+    Err(decode_error("Image data missing"))
+}
+
+// Read up to max_frames frames of an animation, compositing each one onto the canvas.
+//
+// The memory limit applies to all frames together, as for GIF.
+fn read_frames(
+    chunks: &[Chunk],
+    canvas: &Canvas,
+    max_frames: Option<usize>,
+    limits: &Limits,
+) -> RasterResult<Vec<Frame>> {
+    let (width, height) = (canvas.width as usize, canvas.height as usize);
+    limits.check_dimensions(canvas.width, canvas.height)?;
+    let canvas_len = (width * height) as u64 * 4;
+    limits.check_alloc(canvas_len)?;
+
+    // Start with a transparent canvas
+    let mut image = Image {
+        width: width as i32,
+        height: height as i32,
+        bytes: vec![0; width * height * 4],
+    };
+    let mut frames = Vec::new();
+    for chunk in chunks.iter().filter(|chunk| chunk.id == *b"ANMF") {
+        if max_frames == Some(frames.len()) {
+            break;
+        }
+        let data = chunk.data;
+        if data.len() < 16 {
+            return Err(decode_error("Invalid ANMF chunk"));
+        }
+        let left = u24(&data[0..]) as usize * 2;
+        let top = u24(&data[3..]) as usize * 2;
+        let (w, h) = (u24(&data[6..]) as usize + 1, u24(&data[9..]) as usize + 1);
+        if left + w > width || top + h > height {
+            return Err(decode_error("Frame outside of canvas"));
+        }
+        limits.check_frames(frames.len() + 1)?;
+        // The canvas, a snapshot for every frame so far and this frame's pixels
+        limits.check_alloc(canvas_len * (frames.len() as u64 + 2) + (w * h) as u64 * 4)?;
+
+        let frame = decode_bitstream(&read_chunks(&data[16..])?, limits)?;
+        if frame.width as usize != w || frame.height as usize != h {
+            return Err(decode_error("Frame size does not match its header"));
+        }
+        let blend = data[15] & NO_BLEND_FLAG == 0;
+        let dispose = data[15] & DISPOSE_FLAG != 0;
+
+        for y in 0..h {
+            for x in 0..w {
+                let src = (y * w + x) * 4;
+                let dest = ((top + y) * width + left + x) * 4;
+                let pixel = &frame.bytes[src..src + 4];
+                if blend {
+                    blend_pixel(&mut image.bytes[dest..dest + 4], pixel);
+                } else {
+                    image.bytes[dest..dest + 4].copy_from_slice(pixel);
+                }
+            }
+        }
+
+        frames.push(Frame {
+            image: image.clone(),
+            delay: u24(&data[12..]),
+            disposal: if dispose {
+                DisposalMethod::Background
+            } else {
+                DisposalMethod::None
+            },
+        });
+
+        // Clear the frame area before the next frame
+        if dispose {
+            for y in top..top + h {
+                let start = (y * width + left) * 4;
+                for byte in &mut image.bytes[start..start + w * 4] {
+                    *byte = 0;
+                }
+            }
+        }
+    }
+    Ok(frames)
+}
+
+// The width and height in the frame header of a lossy VP8 bitstream, after the start code
+fn lossy_size(data: &[u8]) -> Option<(u32, u32)> {
+    match data.get(3..10) {
+        Some(&[0x9D, 0x01, 0x2A, a, b, c, d]) => Some((
+            (u16::from_le_bytes([a, b]) & 0x3FFF) as u32,
+            (u16::from_le_bytes([c, d]) & 0x3FFF) as u32,
+        )),
+        _ => None,
+    }
+}
+
+fn check_dimensions(width: i32, height: i32) -> RasterResult<()> {
+    if width < 1 || height < 1 || width as u32 > MAX_SIZE || height as u32 > MAX_SIZE {
+        return Err(encode_error("Invalid dimensions"));
+    }
+    Ok(())
+}
+
+fn u24(data: &[u8]) -> u32 {
+    u32::from_le_bytes([data[0], data[1], data[2], 0])
+}
+
+fn u24_bytes(value: u32) -> [u8; 3] {
+    let [a, b, c, _] = value.to_le_bytes();
+    [a, b, c]
+}
+
+fn write_chunk(out: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+    out.extend_from_slice(id);
+    out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    out.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        out.push(0);
+    }
+}
+
+// Wrap the chunks of a WebP in the RIFF header
+fn riff(body: &[u8]) -> Vec<u8> {
+    let mut out = b"RIFF".to_vec();
+    out.extend_from_slice(&(body.len() as u32 + 4).to_le_bytes());
+    out.extend_from_slice(b"WEBP");
+    out.extend_from_slice(body);
+    out
+}
+
+fn decode_error(msg: &str) -> RasterError {
+    RasterError::Decode(ImageFormat::WebP, msg.to_string())
+}
+
+fn encode_error(msg: &str) -> RasterError {
+    RasterError::Encode(ImageFormat::WebP, msg.to_string())
+}
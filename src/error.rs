@@ -29,12 +29,16 @@ pub enum RasterError {
     BlendingImageFallsOutsideCanvas,
     /// Invalid gamma parameter.
     InvalidGamma(f32),
+    /// Palette size outside of 2 - 256.
+    InvalidPaletteSize(usize),
     /// Error during decoding.
     Decode(ImageFormat, String),
     /// Error during encoding.
     Encode(ImageFormat, String),
     /// Unsupported image format.
     UnsupportedFormat(String),
+    /// The image is larger than the decoding limits allow.
+    LimitsExceeded(String),
     /// Error that does not belong in other variants.
     Unexpected,
 }
@@ -99,20 +103,14 @@ impl From<png::DecodingError> for RasterError {
     fn from(err: png::DecodingError) -> RasterError {
         match err {
             png::DecodingError::IoError(io_err) => RasterError::Io(io_err),
-            png::DecodingError::Format(_) => {
-                RasterError::Decode(ImageFormat::Png, "Format".to_string())
+            png::DecodingError::Format(err) => {
+                RasterError::Decode(ImageFormat::Png, err.to_string())
             }
-            png::DecodingError::InvalidSignature => {
-                RasterError::Decode(ImageFormat::Png, "InvalidSignature".to_string())
+            png::DecodingError::Parameter(err) => {
+                RasterError::Decode(ImageFormat::Png, err.to_string())
             }
-            png::DecodingError::CrcMismatch { .. } => {
-                RasterError::Decode(ImageFormat::Png, "CrcMismatch".to_string())
-            }
-            png::DecodingError::Other(_) => {
-                RasterError::Decode(ImageFormat::Png, "Other".to_string())
-            }
-            png::DecodingError::CorruptFlateStream => {
-                RasterError::Decode(ImageFormat::Png, "CorruptFlateStream".to_string())
+            png::DecodingError::LimitsExceeded => {
+                RasterError::LimitsExceeded("PNG decoder memory limit".to_string())
             }
         }
     }
@@ -123,8 +121,14 @@ impl From<png::EncodingError> for RasterError {
     fn from(err: png::EncodingError) -> RasterError {
         match err {
             png::EncodingError::IoError(io_err) => RasterError::Io(io_err),
-            png::EncodingError::Format(_) => {
-                RasterError::Encode(ImageFormat::Png, "Format".to_string())
+            png::EncodingError::Format(err) => {
+                RasterError::Encode(ImageFormat::Png, err.to_string())
+            }
+            png::EncodingError::Parameter(err) => {
+                RasterError::Encode(ImageFormat::Png, err.to_string())
+            }
+            png::EncodingError::LimitsExceeded => {
+                RasterError::LimitsExceeded("PNG encoder memory limit".to_string())
             }
         }
     }
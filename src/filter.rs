@@ -1,14 +1,14 @@
 //!  A module for filtering pixels.
 
 // from rust
-use std::cmp;
 
 // from external crate
 
 // from local crate
+use color;
 use error::{RasterError, RasterResult};
-use Image;
-use Color;
+use parallel;
+use pixel::{pixel_count, GenericImage, ImageBuffer, Pixel, Subpixel};
 
 /// An enum for the various modes that can be used for blurring.
 #[derive(Debug)]
@@ -63,7 +63,7 @@ pub enum Orientation {
 /// ### After
 /// ![](https://kosinix.github.io/raster/out/test_filter_gaussian_blur.jpg)
 ///
-pub fn blur(src: &mut Image, mode: BlurMode) -> RasterResult<()> {
+pub fn blur<I: GenericImage>(src: &mut I, mode: BlurMode) -> RasterResult<()> {
     match mode {
         BlurMode::Box => blur_box(src),
         BlurMode::Gaussian => blur_gaussian(src),
@@ -89,29 +89,19 @@ pub fn blur(src: &mut Image, mode: BlurMode) -> RasterResult<()> {
 /// ### After
 /// ![](https://kosinix.github.io/raster/out/test_filter_brightness.jpg)
 ///
-pub fn brightness(src: &mut Image, factor: f32) -> RasterResult<()> {
-    let w: i32 = src.width;
-    let h: i32 = src.height;
-
+pub fn brightness<I: GenericImage>(src: &mut I, factor: f32) -> RasterResult<()> {
     // if gamma < 0.01 || gamma > 9.99{
     //     return Err(format!("Incorrect gamma value {}. Must be in range 0.01 - 9.99.", gamma));
     // }
     // let factor = 255.0 * factor;
 
-    for y in 0..h {
-        for x in 0..w {
-            let p = src.get_pixel(x, y)?;
-            let r = cmp::max(0, cmp::min(255, (p.r as f32 * factor) as i32));
-            let g = cmp::max(0, cmp::min(255, (p.g as f32 * factor) as i32));
-            let b = cmp::max(0, cmp::min(255, (p.b as f32 * factor) as i32));
-            // TODO: Should alpha be included?
-            let a = cmp::max(0, cmp::min(255, (p.a as f32 * factor) as i32));
-
-            src.set_pixel(x, y, &Color::rgba(r as u8, g as u8, b as u8, a as u8))?;
+    src.map_pixels(|_, _, mut p| {
+        // TODO: Should alpha be included?
+        for c in p.channels_mut() {
+            *c = Subpixel::from_f64((c.to_f64() as f32 * factor) as f64);
         }
-    }
-
-    Ok(())
+        p
+    })
 }
 
 /// Apply a convolution matrix.
@@ -132,92 +122,72 @@ pub fn brightness(src: &mut Image, factor: f32) -> RasterResult<()> {
 /// filter::convolve(&mut image, matrix, 1).unwrap();
 /// raster::save(&image, "tests/out/test_filter_convolve.jpg").unwrap();
 /// ```
-pub fn convolve(src: &mut Image, matrix: [[i32; 3]; 3], divisor: i32) -> RasterResult<()> {
-    let w: i32 = src.width;
-    let h: i32 = src.height;
-    let m_size = 3; // Matrix size
-
-    let copy = src.clone(); // Create a copy as input of pixels
-
-    for y in 0..h {
-        for x in 0..w {
-            let mstarty = y - 1;
-            let mstartx = x - 1;
-
-            let mut accum_red: i32 = 0;
-            let mut accum_green: i32 = 0;
-            let mut accum_blue: i32 = 0;
-            let mut accum_alpha: i32 = 0;
-
-            for (m_index_y, mut src_y) in (0..).zip(mstarty..mstarty + m_size) {
-                if src_y < 0 {
-                    src_y = 0;
-                } else if src_y > h - 1 {
-                    src_y = h - 1;
-                }
+pub fn convolve<I: GenericImage>(
+    src: &mut I,
+    matrix: [[i32; 3]; 3],
+    divisor: i32,
+) -> RasterResult<()> {
+    let w: i32 = src.width();
+    let h: i32 = src.height();
 
-                for (m_index_x, mut src_x) in (0..).zip(mstartx..mstartx + m_size) {
-                    if src_x < 0 {
-                        src_x = 0;
-                    } else if src_x > w - 1 {
-                        src_x = w - 1;
-                    }
-
-                    let pixel = copy.get_pixel(src_x, src_y)?;
-                    accum_red += pixel.r as i32 * matrix[m_index_y][m_index_x];
-                    accum_green += pixel.g as i32 * matrix[m_index_y][m_index_x];
-                    accum_blue += pixel.b as i32 * matrix[m_index_y][m_index_x];
-                    accum_alpha += pixel.a as i32 * matrix[m_index_y][m_index_x];
-                }
-            }
+    let copy = copy_pixels(src)?; // Create a copy as input of pixels
 
-            if divisor != 1 {
-                accum_red /= divisor;
-                accum_green /= divisor;
-                accum_blue /= divisor;
-                accum_alpha /= divisor;
-            }
+    // Sums of whole numbers are exact in f64, like the integer math of 8 bit channels
+    let mut weights = [[0.0f64; 3]; 3];
+    for (weights, row) in weights.iter_mut().zip(matrix.iter()) {
+        for (weight, &m) in weights.iter_mut().zip(row.iter()) {
+            *weight = m as f64;
+        }
+    }
 
-            if accum_red < 0 {
-                accum_red = 0;
-            }
-            if accum_green < 0 {
-                accum_green = 0;
-            }
-            if accum_blue < 0 {
-                accum_blue = 0;
-            }
-            if accum_alpha < 0 {
-                accum_alpha = 0;
+    // Compute the rows of the result on their own, so they can be shared between threads
+    let width = w.max(0) as usize;
+    let mut convolved = copy.clone();
+    parallel::for_each_row(&mut convolved, width, |y, row| {
+        // The rows above, at and below, with channels as f64. Pixels past the edges repeat the
+        // nearest edge pixel.
+        let y = y as i32;
+        let ys = [(y - 1).max(0), y, (y + 1).min(h - 1)];
+        let mut rows = vec![[0.0f64; 4]; width * 3];
+        for (rows, &src_y) in rows.chunks_mut(width).zip(ys.iter()) {
+            let src_row = &copy[src_y as usize * width..][..width];
+            for (channels, p) in rows.iter_mut().zip(src_row) {
+                for (v, c) in channels.iter_mut().zip(p.channels()) {
+                    *v = c.to_f64();
+                }
             }
+        }
 
-            if accum_red > 255 {
-                accum_red = 255;
-            }
-            if accum_green > 255 {
-                accum_green = 255;
-            }
-            if accum_blue > 255 {
-                accum_blue = 255;
-            }
-            if accum_alpha > 255 {
-                accum_alpha = 255;
+        for (x, pixel) in row.iter_mut().enumerate() {
+            let xs = [x.max(1) - 1, x, (x + 1).min(width - 1)];
+
+            let mut accum = [0.0f64; 4];
+            for (weights, rows) in weights.iter().zip(rows.chunks(width)) {
+                for (&weight, &src_x) in weights.iter().zip(xs.iter()) {
+                    let c = &rows[src_x];
+                    accum = [
+                        accum[0] + c[0] * weight,
+                        accum[1] + c[1] * weight,
+                        accum[2] + c[2] * weight,
+                        accum[3] + c[3] * weight,
+                    ];
+                }
             }
 
-            src.set_pixel(
-                x,
-                y,
-                &Color::rgba(
-                    accum_red as u8,
-                    accum_green as u8,
-                    accum_blue as u8,
-                    accum_alpha as u8,
-                ),
-            )?;
+            // Clamp to the range of the channels
+            for (c, &a) in pixel.channels_mut().iter_mut().zip(accum.iter()) {
+                *c = if divisor != 1 {
+                    Subpixel::from_f64(a / divisor as f64)
+                } else {
+                    Subpixel::from_f64(a)
+                };
+            }
         }
-    }
+    });
 
-    Ok(())
+    let mut convolved = convolved.into_iter();
+    // There is a pixel in the copy for every pixel of the image
+    src.map_pixels(|_, _, _| convolved.next().unwrap())
 }
 
 /// Apply emboss.
@@ -238,7 +208,7 @@ pub fn convolve(src: &mut Image, matrix: [[i32; 3]; 3], divisor: i32) -> RasterR
 /// ### After
 /// ![](https://kosinix.github.io/raster/out/test_filter_emboss.jpg)
 ///
-pub fn emboss(src: &mut Image) -> RasterResult<()> {
+pub fn emboss<I: GenericImage>(src: &mut I) -> RasterResult<()> {
     let matrix: [[i32; 3]; 3] = [[-2, -1, 0], [-1, 1, 1], [0, 1, 2]];
     convolve(src, matrix, 1)
 }
@@ -261,7 +231,7 @@ pub fn emboss(src: &mut Image) -> RasterResult<()> {
 /// ### After
 /// ![](https://kosinix.github.io/raster/out/test_filter_sobel_x.jpg)
 ///
-pub fn sobel(src: &mut Image, mode: Orientation) -> RasterResult<()> {
+pub fn sobel<I: GenericImage>(src: &mut I, mode: Orientation) -> RasterResult<()> {
     grayscale(src)?;
     let matrix = match mode {
         Orientation::Horizontal => [[-1, 0, 1], [-2, 0, 2], [-1, 0, 1]],
@@ -286,33 +256,37 @@ pub fn sobel(src: &mut Image, mode: Orientation) -> RasterResult<()> {
     convolve(src, matrix, 1)
 }
 
-fn sobel_both(
-    src: &mut Image,
+fn sobel_both<I: GenericImage>(
+    src: &mut I,
     matrix_one: [[i32; 3]; 3],
     matrix_two: [[i32; 3]; 3],
 ) -> RasterResult<()> {
-    let mut image_x = src.clone();
-    let mut image_y = src.clone();
+    let pixels = copy_pixels(src)?;
+    let mut image_x = ImageBuffer {
+        width: src.width(),
+        height: src.height(),
+        pixels,
+    };
+    let mut image_y = image_x.clone();
     convolve(&mut image_x, matrix_one, 1)?;
     convolve(&mut image_y, matrix_two, 1)?;
 
-    let w: i32 = src.width;
-    let h: i32 = src.height;
-    for y in 0..h {
-        for x in 0..w {
-            let pixel_x = image_x.get_pixel(x, y)?;
-            let pixel_y = image_y.get_pixel(x, y)?;
-            // Calculate the sum of the derivatives with sqrt((dImage/dx)²+(dImage/dy)²)
-            let pixel = ((pixel_x.r as f64).powi(2) + (pixel_y.r as f64).powi(2)).sqrt();
-            src.set_pixel(
-                x,
-                y,
-                &Color::rgba(pixel as u8, pixel as u8, pixel as u8, pixel_x.a as u8),
-            )?;
+    let colors = I::Pixel::CHANNELS - I::Pixel::HAS_ALPHA as usize;
+    let mut derivatives = image_x.pixels.iter().zip(image_y.pixels.iter());
+    src.map_pixels(|_, _, _| {
+        // Both copies have a pixel for every pixel of the image
+        let (&pixel_x, pixel_y) = derivatives.next().unwrap();
+        let mut pixel_x = pixel_x;
+        // Calculate the sum of the derivatives with sqrt((dImage/dx)²+(dImage/dy)²)
+        let pixel = (pixel_x.channels()[0].to_f64().powi(2)
+            + pixel_y.channels()[0].to_f64().powi(2))
+        .sqrt();
+        // Alpha is kept from the horizontal pass
+        for c in &mut pixel_x.channels_mut()[..colors] {
+            *c = Subpixel::from_f64(pixel);
         }
-    }
-
-    Ok(())
+        pixel_x
+    })
 }
 
 /// Apply a gamma correction.
@@ -336,26 +310,19 @@ fn sobel_both(
 /// ![](https://kosinix.github.io/raster/out/test_filter_gamma.jpg)
 ///
 // http://stackoverflow.com/questions/14088889/changing-a-color-brightness
-pub fn gamma(src: &mut Image, gamma: f32) -> RasterResult<()> {
-    let w: i32 = src.width;
-    let h: i32 = src.height;
-
+pub fn gamma<I: GenericImage>(src: &mut I, gamma: f32) -> RasterResult<()> {
     if gamma < 0.01 || gamma > 9.99 {
         return Err(RasterError::InvalidGamma(gamma));
     }
 
-    for y in 0..h {
-        for x in 0..w {
-            let p = src.get_pixel(x, y)?;
-            let r = (p.r as f32 / 255.0).powf(gamma) * 255.0;
-            let g = (p.g as f32 / 255.0).powf(gamma) * 255.0;
-            let b = (p.b as f32 / 255.0).powf(gamma) * 255.0;
-
-            src.set_pixel(x, y, &Color::rgba(r as u8, g as u8, b as u8, p.a as u8))?;
+    let max = <I::Pixel as Pixel>::Subpixel::MAX.to_f64() as f32;
+    let colors = I::Pixel::CHANNELS - I::Pixel::HAS_ALPHA as usize;
+    src.map_pixels(|_, _, mut p| {
+        for c in &mut p.channels_mut()[..colors] {
+            *c = Subpixel::from_f64(((c.to_f64() as f32 / max).powf(gamma) * max) as f64);
         }
-    }
-
-    Ok(())
+        p
+    })
 }
 
 /// Turn into grayscale image.
@@ -375,24 +342,25 @@ pub fn gamma(src: &mut Image, gamma: f32) -> RasterResult<()> {
 /// ### After
 /// ![](https://kosinix.github.io/raster/out/test_filter_grayscale.jpg)
 ///
-pub fn grayscale(src: &mut Image) -> RasterResult<()> {
-    let w: i32 = src.width;
-    let h: i32 = src.height;
-
-    for y in 0..h {
-        for x in 0..w {
-            let p = src.get_pixel(x, y)?;
-            let gray = (p.r as f32 * 0.3) + (p.g as f32 * 0.59) + (p.b as f32 * 0.11);
+pub fn grayscale<I: GenericImage>(src: &mut I) -> RasterResult<()> {
+    src.map_pixels(|_, _, mut p| {
+        let gray = {
+            let c = p.channels();
+            if I::Pixel::CHANNELS < 3 {
+                c[0]
+            } else {
+                let gray = (c[0].to_f64() as f32 * 0.3)
+                    + (c[1].to_f64() as f32 * 0.59)
+                    + (c[2].to_f64() as f32 * 0.11);
+                Subpixel::from_f64(gray as f64)
+            }
+        };
 
-            src.set_pixel(
-                x,
-                y,
-                &Color::rgba(gray as u8, gray as u8, gray as u8, gray as u8),
-            )?;
+        for c in p.channels_mut() {
+            *c = gray;
         }
-    }
-
-    Ok(())
+        p
+    })
 }
 
 /// Change saturation.
@@ -418,29 +386,40 @@ pub fn grayscale(src: &mut Image) -> RasterResult<()> {
 /// ### After
 /// ![](https://kosinix.github.io/raster/out/test_filter_saturation.jpg)
 ///
-pub fn saturation(src: &mut Image, sat: f32) -> RasterResult<()> {
-    let w: i32 = src.width;
-    let h: i32 = src.height;
-
-    for y in 0..h {
-        for x in 0..w {
-            let p = src.get_pixel(x, y)?;
-            let hsv = Color::to_hsv(p.r, p.g, p.b);
-            let s = hsv.1;
-            let factor = (100.0 - s) * sat; // use % remaining
-            let mut new_s = s + factor;
-            if new_s > 100.0 {
-                new_s = 100.0;
-            } else if new_s < 0.0 {
-                new_s = 0.0;
-            }
-            let rgb = Color::to_rgb(hsv.0, new_s, hsv.2);
+pub fn saturation<I: GenericImage>(src: &mut I, sat: f32) -> RasterResult<()> {
+    if I::Pixel::CHANNELS < 3 {
+        return Ok(());
+    }
 
-            src.set_pixel(x, y, &Color::rgb(rgb.0, rgb.1, rgb.2))?;
+    let max = <I::Pixel as Pixel>::Subpixel::MAX.to_f64() as f32;
+    src.map_pixels(|_, _, mut p| {
+        let hsv = {
+            let c = p.channels();
+            color::hsv_from_unit(
+                c[0].to_f64() as f32 / max,
+                c[1].to_f64() as f32 / max,
+                c[2].to_f64() as f32 / max,
+            )
+        };
+        let s = hsv.1;
+        let factor = (100.0 - s) * sat; // use % remaining
+        let mut new_s = s + factor;
+        if new_s > 100.0 {
+            new_s = 100.0;
+        } else if new_s < 0.0 {
+            new_s = 0.0;
         }
-    }
+        let rgb = color::unit_from_hsv(hsv.0, new_s, hsv.2);
 
-    Ok(())
+        let c = p.channels_mut();
+        for (c, v) in c.iter_mut().zip(&[rgb.0, rgb.1, rgb.2]) {
+            *c = Subpixel::from_unit(*v);
+        }
+        if I::Pixel::HAS_ALPHA {
+            c[3] = Subpixel::MAX;
+        }
+        p
+    })
 }
 
 /// Apply sharpen.
@@ -460,7 +439,7 @@ pub fn saturation(src: &mut Image, sat: f32) -> RasterResult<()> {
 /// ### After
 /// ![](https://kosinix.github.io/raster/out/test_filter_sharpen.jpg)
 ///
-pub fn sharpen(src: &mut Image) -> RasterResult<()> {
+pub fn sharpen<I: GenericImage>(src: &mut I) -> RasterResult<()> {
     let matrix: [[i32; 3]; 3] = [[0, -1, 0], [-1, 5, -1], [0, -1, 0]];
     convolve(src, matrix, 1)
 }
@@ -468,13 +447,20 @@ pub fn sharpen(src: &mut Image) -> RasterResult<()> {
 // Private functions
 
 // Box
-fn blur_box(src: &mut Image) -> RasterResult<()> {
+fn blur_box<I: GenericImage>(src: &mut I) -> RasterResult<()> {
     let matrix: [[i32; 3]; 3] = [[1, 1, 1], [1, 1, 1], [1, 1, 1]];
     convolve(src, matrix, 9)
 }
 
 // Gaussian
-fn blur_gaussian(src: &mut Image) -> RasterResult<()> {
+fn blur_gaussian<I: GenericImage>(src: &mut I) -> RasterResult<()> {
     let matrix: [[i32; 3]; 3] = [[1, 2, 1], [2, 4, 2], [1, 2, 1]];
     convolve(src, matrix, 16)
 }
+
+// Copy the pixels of an image, to read from while the image is changed.
+fn copy_pixels<I: GenericImage>(src: &I) -> RasterResult<Vec<I::Pixel>> {
+    let mut copy = Vec::with_capacity(pixel_count::<I::Pixel>(src.width(), src.height())?);
+    src.for_each_pixel(|_, _, p| copy.push(p))?;
+    Ok(copy)
+}
@@ -0,0 +1,227 @@
+//!  A module for high dynamic range images.
+
+// from rust
+
+// from external crate
+
+// from local crate
+use error::{RasterError, RasterResult};
+use Image;
+
+/// A struct for an image with floating point pixels, like a Radiance HDR environment map. The
+/// channels hold linear light and are not limited to the range 0.0 - 1.0.
+#[derive(Debug, Clone)]
+pub struct HdrImage {
+    /// Width of image in pixels.
+    pub width: i32,
+
+    /// Height of image in pixels.
+    pub height: i32,
+
+    /// Red, green and blue channels of every pixel, from left to right and top to bottom.
+    pub pixels: Vec<f32>,
+}
+
+impl HdrImage {
+    /// Create a black image.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::HdrImage;
+    ///
+    /// let image = HdrImage::new(4, 3);
+    ///
+    /// assert_eq!(4 * 3 * 3, image.pixels.len());
+    /// ```
+    pub fn new(width: i32, height: i32) -> HdrImage {
+        HdrImage {
+            width,
+            height,
+            pixels: vec![0.0; (width.max(0) * height.max(0) * 3) as usize],
+        }
+    }
+
+    /// Create an image from the sRGB pixels of an image, converted to linear light. The alpha
+    /// channel is dropped.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::HdrImage;
+    ///
+    /// let image = raster::open("tests/in/sample.png").unwrap();
+    /// let hdr = HdrImage::from_image(&image);
+    ///
+    /// assert_eq!(image.width, hdr.width);
+    /// ```
+    pub fn from_image(image: &Image) -> HdrImage {
+        let pixels = image
+            .bytes
+            .chunks(4)
+            .flat_map(|p| p[..3].iter().map(|&v| srgb_to_linear(v as f32 / 255.0)))
+            .collect();
+        HdrImage {
+            width: image.width,
+            height: image.height,
+            pixels,
+        }
+    }
+
+    /// Get the red, green and blue channels of a pixel.
+    ///
+    /// # Errors
+    ///
+    /// If either the x or y coordinate falls out of bounds, this will fail with
+    /// `RasterError::PixelOutOfBounds`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::HdrImage;
+    ///
+    /// let image = HdrImage::new(2, 2);
+    ///
+    /// assert_eq!([0.0, 0.0, 0.0], image.get_pixel(1, 1).unwrap());
+    /// assert!(image.get_pixel(2, 0).is_err());
+    /// ```
+    pub fn get_pixel(&self, x: i32, y: i32) -> RasterResult<[f32; 3]> {
+        let start = self.index(x, y)?;
+        let p = &self.pixels[start..start + 3];
+        Ok([p[0], p[1], p[2]])
+    }
+
+    /// Set the red, green and blue channels of a pixel.
+    ///
+    /// # Errors
+    ///
+    /// If either the x or y coordinate falls out of bounds, this will fail with
+    /// `RasterError::PixelOutOfBounds`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::HdrImage;
+    ///
+    /// let mut image = HdrImage::new(2, 2);
+    /// image.set_pixel(0, 1, [4.0, 0.5, 0.0]).unwrap();
+    ///
+    /// assert_eq!([4.0, 0.5, 0.0], image.get_pixel(0, 1).unwrap());
+    /// ```
+    pub fn set_pixel(&mut self, x: i32, y: i32, rgb: [f32; 3]) -> RasterResult<()> {
+        let start = self.index(x, y)?;
+        self.pixels[start..start + 3].copy_from_slice(&rgb);
+        Ok(())
+    }
+
+    /// Convert to an 8 bit sRGB image, compressing the range of the pixels with the given tone
+    /// mapping. The image is fully opaque.
+    ///
+    /// # Errors
+    ///
+    /// `ToneMapping::Exposure` fails with `RasterError::InvalidGamma` if the gamma is not
+    /// between 0.01 and 9.99.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{HdrImage, ToneMapping};
+    ///
+    /// let mut hdr = HdrImage::new(1, 1);
+    /// hdr.set_pixel(0, 0, [1.0, 4.0, 100.0]).unwrap();
+    ///
+    /// let image = hdr.tone_map(ToneMapping::Aces).unwrap();
+    /// assert_eq!(255, image.bytes[2]);
+    ///
+    /// // Two stops darker, with a plain 2.2 gamma
+    /// let mapping = ToneMapping::Exposure {
+    ///     exposure: -2.0,
+    ///     gamma: 2.2,
+    /// };
+    /// let image = hdr.tone_map(mapping).unwrap();
+    /// assert_eq!(136, image.bytes[0]);
+    /// ```
+    pub fn tone_map(&self, mapping: ToneMapping) -> RasterResult<Image> {
+        let map: Box<dyn Fn(f32) -> f32> = match mapping {
+            ToneMapping::Reinhard => Box::new(|v| linear_to_srgb(v / (1.0 + v))),
+            ToneMapping::Aces => Box::new(|v| {
+                let mapped = (v * (2.51 * v + 0.03)) / (v * (2.43 * v + 0.59) + 0.14);
+                linear_to_srgb(mapped)
+            }),
+            ToneMapping::Exposure { exposure, gamma } => {
+                if !(0.01..=9.99).contains(&gamma) {
+                    return Err(RasterError::InvalidGamma(gamma));
+                }
+                let scale = exposure.exp2();
+                Box::new(move |v| (v * scale).clamp(0.0, 1.0).powf(1.0 / gamma))
+            }
+        };
+
+        let mut bytes = Vec::with_capacity(self.pixels.len() / 3 * 4);
+        for p in self.pixels.chunks(3) {
+            for &v in p {
+                // Negative and NaN channels are black
+                let v = if v > 0.0 { map(v) } else { 0.0 };
+                bytes.push((v.clamp(0.0, 1.0) * 255.0).round() as u8);
+            }
+            bytes.push(255);
+        }
+        Ok(Image {
+            width: self.width,
+            height: self.height,
+            bytes,
+        })
+    }
+
+    // Index of the first channel of a pixel.
+    fn index(&self, x: i32, y: i32) -> RasterResult<usize> {
+        let start = (y as usize * self.width as usize + x as usize) * 3;
+        if x < 0 || y < 0 || x >= self.width || y >= self.height || start + 3 > self.pixels.len() {
+            Err(RasterError::PixelOutOfBounds(x, y))
+        } else {
+            Ok(start)
+        }
+    }
+}
+
+/// Enumeration of the ways to bring a high dynamic range image into the displayable range.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum ToneMapping {
+    /// The Reinhard operator `v / (1 + v)`, followed by the sRGB curve. Never clips, but
+    /// flattens bright areas. This is the default.
+    #[default]
+    Reinhard,
+
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve, followed by the sRGB curve. Gives more
+    /// contrast than Reinhard and clips very bright areas to white.
+    Aces,
+
+    /// Scale by 2 to the power of `exposure` stops, clip to 1.0 and apply a `1 / gamma` power.
+    Exposure {
+        /// Exposure adjustment in stops. 0.0 keeps the brightness.
+        exposure: f32,
+
+        /// Display gamma, usually 2.2.
+        gamma: f32,
+    },
+}
+
+// Private functions
+
+// Decode an sRGB channel from 0.0 - 1.0 to linear light.
+fn srgb_to_linear(v: f32) -> f32 {
+    if v <= 0.04045 {
+        v / 12.92
+    } else {
+        ((v + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+// Encode a linear channel from 0.0 - 1.0 to sRGB.
+fn linear_to_srgb(v: f32) -> f32 {
+    if v <= 0.003_130_8 {
+        v * 12.92
+    } else {
+        1.055 * v.powf(1.0 / 2.4) - 0.055
+    }
+}
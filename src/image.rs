@@ -2,12 +2,15 @@
 
 // from rust
 use std::collections::HashMap;
+use std::path::Path;
 
 // from external crate
 
 // from local crate
 use error::{RasterError, RasterResult};
 use color::Color;
+use iter::{self, EnumeratePixels, EnumeratePixelsMut, Pixels, PixelsMut, Rows, RowsMut};
+use view::{self, ImageView, ImageViewMut};
 
 /// A struct for easily representing a raster image.
 #[derive(Debug, Clone)]
@@ -247,6 +250,159 @@ impl<'a> Image {
             Ok(())
         }
     }
+
+    /// Iterate over the RGBA bytes of every pixel, from left to right and top to bottom. This is
+    /// much faster than calling `get_pixel` for every x and y.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let image = raster::open("tests/in/sample.png").unwrap();
+    ///
+    /// let opaque = image.pixels().filter(|p| p[3] == 255).count();
+    /// assert_eq!((image.width * image.height) as usize, opaque);
+    /// ```
+    pub fn pixels(&self) -> Pixels<'_> {
+        iter::pixels(&self.bytes, self.width, self.height)
+    }
+
+    /// Iterate over the RGBA bytes of every pixel, for changing them in place.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut image = raster::open("tests/in/sample.png").unwrap();
+    ///
+    /// // Invert the colors
+    /// for p in image.pixels_mut() {
+    ///     *p = [255 - p[0], 255 - p[1], 255 - p[2], p[3]];
+    /// }
+    /// raster::save(&image, "tests/out/test_pixels_mut.png").unwrap();
+    /// ```
+    pub fn pixels_mut(&mut self) -> PixelsMut<'_> {
+        iter::pixels_mut(&mut self.bytes, self.width, self.height)
+    }
+
+    /// Iterate over every pixel together with its x and y coordinates.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let image = raster::open("tests/in/sample.png").unwrap();
+    ///
+    /// let (x, y, _) = image.enumerate_pixels().max_by_key(|&(_, _, p)| p[0]).unwrap();
+    /// assert!(x < image.width && y < image.height);
+    /// ```
+    pub fn enumerate_pixels(&self) -> EnumeratePixels<'_> {
+        iter::enumerate_pixels(&self.bytes, self.width, self.height)
+    }
+
+    /// Iterate over every pixel together with its x and y coordinates, for changing them in place.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::Image;
+    ///
+    /// let mut image = Image::blank(256, 256);
+    /// for (x, y, p) in image.enumerate_pixels_mut() {
+    ///     *p = [x as u8, y as u8, 128, 255];
+    /// }
+    /// raster::save(&image, "tests/out/test_gradient.png").unwrap();
+    /// ```
+    pub fn enumerate_pixels_mut(&mut self) -> EnumeratePixelsMut<'_> {
+        iter::enumerate_pixels_mut(&mut self.bytes, self.width, self.height)
+    }
+
+    /// Iterate over the rows of the image from top to bottom, each a slice of `width * 4` RGBA
+    /// bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let image = raster::open("tests/in/sample.png").unwrap();
+    ///
+    /// let first = image.rows().next().unwrap();
+    /// assert_eq!(image.width as usize * 4, first.len());
+    /// assert_eq!(image.height as usize, image.rows().count());
+    /// ```
+    pub fn rows(&self) -> Rows<'_> {
+        iter::rows(&self.bytes, self.width, self.height)
+    }
+
+    /// Iterate over the rows of the image, for changing them in place.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// let mut image = raster::open("tests/in/sample.png").unwrap();
+    ///
+    /// // Mirror every row
+    /// for row in image.rows_mut() {
+    ///     let width = row.len() / 4;
+    ///     for x in 0..width / 2 {
+    ///         for c in 0..4 {
+    ///             row.swap(x * 4 + c, (width - x - 1) * 4 + c);
+    ///         }
+    ///     }
+    /// }
+    /// raster::save(&image, "tests/out/test_rows_mut.png").unwrap();
+    /// ```
+    pub fn rows_mut(&mut self) -> RowsMut<'_> {
+        iter::rows_mut(&mut self.bytes, self.width, self.height)
+    }
+
+    /// Borrow a rectangle of the image, for reading it without making a copy. The rectangle starts
+    /// at x and y and is w pixels wide and h pixels tall. The view can be passed to the functions
+    /// of the `compare` module.
+    ///
+    /// # Errors
+    ///
+    /// If the rectangle does not lie within the image, this will fail with
+    /// `RasterError::PixelOutOfBounds`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{compare, GenericImageView};
+    ///
+    /// let image = raster::open("tests/in/sample.png").unwrap();
+    /// let left = image.view(0, 0, 50, 50).unwrap();
+    /// let right = image.view(50, 0, 50, 50).unwrap();
+    ///
+    /// assert_eq!(50, left.width());
+    /// assert!(compare::equal(&left, &left).unwrap());
+    /// assert!(!compare::equal(&left, &right).unwrap());
+    /// assert!(image.view(image.width - 10, 0, 20, 20).is_err());
+    /// ```
+    pub fn view(&self, x: i32, y: i32, w: i32, h: i32) -> RasterResult<ImageView<'_>> {
+        view::view(self, x, y, w, h)
+    }
+
+    /// Borrow a rectangle of the image for reading and writing. Filters and flips applied to the
+    /// view change that part of the image in place.
+    ///
+    /// # Errors
+    ///
+    /// If the rectangle does not lie within the image, this will fail with
+    /// `RasterError::PixelOutOfBounds`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{filter, BlurMode};
+    ///
+    /// let mut image = raster::open("tests/in/sample.jpg").unwrap();
+    /// {
+    ///     let mut face = image.view_mut(100, 40, 80, 80).unwrap();
+    ///     filter::blur(&mut face, BlurMode::Gaussian).unwrap();
+    ///     filter::blur(&mut face, BlurMode::Gaussian).unwrap();
+    /// }
+    /// raster::save(&image, "tests/out/test_view_blur.jpg").unwrap();
+    /// ```
+    pub fn view_mut(&mut self, x: i32, y: i32, w: i32, h: i32) -> RasterResult<ImageViewMut<'_>> {
+        view::view_mut(self, x, y, w, h)
+    }
 }
 
 /// Holds histogram information.
@@ -258,9 +414,101 @@ pub type Histogram = (
 );
 
 /// Enumeration of supported raster formats.
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ImageFormat {
     Gif,
     Jpeg,
     Png,
+    Bmp,
+    Pbm,
+    Pgm,
+    Ppm,
+    Pam,
+    Tga,
+    Qoi,
+    Farbfeld,
+    Raw,
+    Ico,
+    Cur,
+    Tiff,
+    WebP,
+    Hdr,
+}
+
+impl ImageFormat {
+    /// Get the image format for the extension of a file name, in any case.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `RasterError::UnsupportedFormat` if the extension is unknown or missing.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::ImageFormat;
+    ///
+    /// assert_eq!(ImageFormat::Jpeg, ImageFormat::from_path("tests/out/photo.JPG").unwrap());
+    /// assert!(ImageFormat::from_path("tests/out/photo").is_err());
+    /// ```
+    pub fn from_path<P: AsRef<Path>>(path: P) -> RasterResult<ImageFormat> {
+        let ext = path
+            .as_ref()
+            .extension()
+            .and_then(|s| s.to_str())
+            .map_or("".to_string(), |s| s.to_ascii_lowercase());
+
+        match ext.as_str() {
+            "gif" => Ok(ImageFormat::Gif),
+            "jpg" | "jpeg" => Ok(ImageFormat::Jpeg),
+            "png" | "apng" => Ok(ImageFormat::Png),
+            "bmp" | "dib" => Ok(ImageFormat::Bmp),
+            "pbm" => Ok(ImageFormat::Pbm),
+            "pgm" => Ok(ImageFormat::Pgm),
+            "ppm" | "pnm" => Ok(ImageFormat::Ppm),
+            "pam" => Ok(ImageFormat::Pam),
+            "tga" | "icb" | "vda" | "vst" => Ok(ImageFormat::Tga),
+            "qoi" => Ok(ImageFormat::Qoi),
+            "ff" | "farbfeld" => Ok(ImageFormat::Farbfeld),
+            "rgba" => Ok(ImageFormat::Raw),
+            "ico" => Ok(ImageFormat::Ico),
+            "cur" => Ok(ImageFormat::Cur),
+            "tif" | "tiff" => Ok(ImageFormat::Tiff),
+            "webp" => Ok(ImageFormat::WebP),
+            "hdr" => Ok(ImageFormat::Hdr),
+            _ => Err(RasterError::UnsupportedFormat(ext)),
+        }
+    }
+}
+
+/// Information about an encoded image, read from its headers without decoding the pixels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageInfo {
+    /// Width of image in pixels.
+    pub width: i32,
+
+    /// Height of image in pixels.
+    pub height: i32,
+
+    /// Format of the encoded image.
+    pub format: ImageFormat,
+
+    /// How the pixels are stored in the encoded image.
+    pub color_type: ColorType,
+
+    /// Bits per channel, or per palette index for indexed images.
+    pub bit_depth: u8,
+
+    /// Number of frames. Images that are not animated have one frame.
+    pub frames: usize,
+}
+
+/// Enumeration of the ways pixels can be stored in an encoded image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorType {
+    Gray,
+    GrayAlpha,
+    Rgb,
+    Rgba,
+    Indexed,
+    Cmyk,
 }
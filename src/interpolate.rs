@@ -6,9 +6,9 @@ use std::cmp;
 // from external crate
 
 // from local crate
-use error::RasterResult;
+use error::{RasterError, RasterResult};
+use parallel;
 use Image;
-use Color;
 
 /// An enum for the various modes that can be used for interpolation.
 #[derive(Debug)]
@@ -38,14 +38,15 @@ pub fn nearest(src: &mut Image, w: i32, h: i32) -> RasterResult<()> {
     let y_ratio: f64 = src.height as f64 / h as f64;
 
     let mut dest = Image::blank(w, h);
-    for y in 0..h {
-        for x in 0..w {
-            let px: i32 = (x as f64 * x_ratio).floor() as i32;
-            let py: i32 = (y as f64 * y_ratio).floor() as i32;
-            let pixel = src.get_pixel(px, py)?;
-
-            dest.set_pixel(x, y, &pixel)?;
-        }
+    {
+        let rows = src_rows(src)?;
+        parallel::for_each_row(&mut dest.bytes, w.max(0) as usize * 4, |y, row| {
+            let py = (y as f64 * y_ratio).floor() as usize;
+            for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                let px = (x as f64 * x_ratio).floor() as usize;
+                pixel.copy_from_slice(&rows[py][px * 4..px * 4 + 4]);
+            }
+        });
     }
     src.width = dest.width;
     src.height = dest.height;
@@ -72,44 +73,38 @@ fn bilinear_width(src: &mut Image, w2: i32) -> RasterResult<()> {
 
     let offset_x = (w2 / w1 / 2) as i32;
 
-    let x_start = 0 - offset_x;
-    let x_end = w2 - offset_x;
-
-    for y in 0..h1 {
-        for x in x_start..x_end {
-            let src_x = {
-                let src_x = x as f64 * x_ratio;
-                if src_x < 0.0 {
-                    0.0 // limit lower bound to 0
-                } else {
-                    src_x
+    {
+        let rows = src_rows(src)?;
+        parallel::for_each_row(&mut dest.bytes, w2.max(0) as usize * 4, |y, row| {
+            for (dest_x, pixel) in (0..).zip(row.chunks_exact_mut(4)) {
+                let x = dest_x - offset_x;
+                let src_x = {
+                    let src_x = x as f64 * x_ratio;
+                    if src_x < 0.0 {
+                        0.0 // limit lower bound to 0
+                    } else {
+                        src_x
+                    }
+                };
+
+                let src_x_int = (src_x).floor() as i32;
+
+                let src_x_int2 = cmp::min(src_x_int + 1, w1 - 1); // limit range within $w1-1
+
+                // limit range from 0 - 1
+                let t_x = src_x - src_x_int as f64;
+
+                let start1 = src_x_int as usize * 4;
+                let start2 = src_x_int2 as usize * 4;
+                let src_color1 = &rows[y][start1..start1 + 4];
+                let src_color2 = &rows[y][start2..start2 + 4];
+
+                // red, green, blue and alpha
+                for (c, (&a, &b)) in pixel.iter_mut().zip(src_color1.iter().zip(src_color2)) {
+                    *c = _lerp(a, b, t_x);
                 }
-            };
-
-            let src_x_int = (src_x).floor() as i32;
-
-            let src_x_int2 = cmp::min(src_x_int + 1, w1 - 1); // limit range within $w1-1
-
-            // limit range from 0 - 1
-            let t_x = src_x - src_x_int as f64;
-
-            let src_color1 = src.get_pixel(src_x_int, y)?;
-            let src_color2 = src.get_pixel(src_x_int2, y)?;
-
-            // red
-            let red = _lerp(src_color1.r, src_color2.r, t_x);
-
-            // green
-            let green = _lerp(src_color1.g, src_color2.g, t_x);
-
-            // blue
-            let blue = _lerp(src_color1.b, src_color2.b, t_x);
-
-            // alpha
-            let alpha = _lerp(src_color1.a, src_color2.a, t_x);
-
-            dest.set_pixel(x + offset_x, y, &Color::rgba(red, green, blue, alpha))?;
-        }
+            }
+        });
     }
     src.width = dest.width;
     src.height = dest.height;
@@ -129,11 +124,10 @@ fn bilinear_height(src: &mut Image, h2: i32) -> RasterResult<()> {
 
     let offset_y = (h2 / h1 / 2) as i32;
 
-    let y_start = 0 - offset_y;
-    let y_end = h2 - offset_y;
-
-    for x in 0..w1 {
-        for y in y_start..y_end {
+    {
+        let rows = src_rows(src)?;
+        parallel::for_each_row(&mut dest.bytes, w1.max(0) as usize * 4, |dest_y, row| {
+            let y = dest_y as i32 - offset_y;
             let src_y = {
                 let src_y = y as f64 * y_ratio;
                 if src_y < 0.0 {
@@ -150,23 +144,14 @@ fn bilinear_height(src: &mut Image, h2: i32) -> RasterResult<()> {
             // limit range from 0 - 1
             let t_y = src_y - src_y_int as f64;
 
-            let src_color1 = src.get_pixel(x, src_y_int)?;
-            let src_color2 = src.get_pixel(x, src_y_int2)?;
-
-            // red
-            let red = _lerp(src_color1.r, src_color2.r, t_y);
-
-            // green
-            let green = _lerp(src_color1.g, src_color2.g, t_y);
+            let src_row1 = rows[src_y_int as usize];
+            let src_row2 = rows[src_y_int2 as usize];
 
-            // blue
-            let blue = _lerp(src_color1.b, src_color2.b, t_y);
-
-            // alpha
-            let alpha = _lerp(src_color1.a, src_color2.a, t_y);
-
-            dest.set_pixel(x, y + offset_y, &Color::rgba(red, green, blue, alpha))?;
-        }
+            // red, green, blue and alpha of every pixel
+            for (c, (&a, &b)) in row.iter_mut().zip(src_row1.iter().zip(src_row2)) {
+                *c = _lerp(a, b, t_y);
+            }
+        });
     }
     src.width = dest.width;
     src.height = dest.height;
@@ -175,6 +160,15 @@ fn bilinear_height(src: &mut Image, h2: i32) -> RasterResult<()> {
     Ok(())
 }
 
+// Rows of an image to read from, or an error if the image is missing some of its pixels.
+fn src_rows(src: &Image) -> RasterResult<Vec<&[u8]>> {
+    let rows: Vec<&[u8]> = src.rows().collect();
+    if rows.len() < src.height.max(0) as usize {
+        return Err(RasterError::PixelOutOfBounds(0, rows.len() as i32));
+    }
+    Ok(rows)
+}
+
 // Simple linear function
 fn _lerp(a: u8, b: u8, t: f64) -> u8 {
     let a = a as f64;
@@ -0,0 +1,290 @@
+//!  A module for iterating over the pixels and rows of an image.
+
+// from rust
+use std::convert::TryFrom;
+use std::mem;
+use std::slice::{ChunksExact, ChunksExactMut};
+
+// from external crate
+
+// from local crate
+
+/// An iterator over the RGBA pixels of an image, from left to right and top to bottom. Created
+/// with `Image::pixels`.
+#[derive(Debug, Clone)]
+pub struct Pixels<'a> {
+    chunks: ChunksExact<'a, u8>,
+}
+
+/// An iterator over the RGBA pixels of an image that can change them. Created with
+/// `Image::pixels_mut`.
+#[derive(Debug)]
+pub struct PixelsMut<'a> {
+    chunks: ChunksExactMut<'a, u8>,
+}
+
+/// An iterator over the pixels of an image with their x and y coordinates. Created with
+/// `Image::enumerate_pixels`.
+#[derive(Debug, Clone)]
+pub struct EnumeratePixels<'a> {
+    pixels: Pixels<'a>,
+    position: Position,
+}
+
+/// An iterator over the pixels of an image with their x and y coordinates that can change them.
+/// Created with `Image::enumerate_pixels_mut`.
+#[derive(Debug)]
+pub struct EnumeratePixelsMut<'a> {
+    pixels: PixelsMut<'a>,
+    position: Position,
+}
+
+/// An iterator over the rows of an image, as slices of `width * 4` RGBA bytes. Created with
+/// `Image::rows`.
+#[derive(Debug, Clone)]
+pub struct Rows<'a> {
+    bytes: &'a [u8],
+    row_len: usize,
+    rows: usize,
+}
+
+/// An iterator over the rows of an image that can change them. Created with `Image::rows_mut`.
+#[derive(Debug)]
+pub struct RowsMut<'a> {
+    bytes: &'a mut [u8],
+    row_len: usize,
+    rows: usize,
+}
+
+impl<'a> Iterator for Pixels<'a> {
+    type Item = &'a [u8; 4];
+
+    fn next(&mut self) -> Option<&'a [u8; 4]> {
+        self.chunks.next().map(|p| <&[u8; 4]>::try_from(p).unwrap())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for Pixels<'a> {
+    fn next_back(&mut self) -> Option<&'a [u8; 4]> {
+        self.chunks
+            .next_back()
+            .map(|p| <&[u8; 4]>::try_from(p).unwrap())
+    }
+}
+
+impl<'a> ExactSizeIterator for Pixels<'a> {}
+
+impl<'a> Iterator for PixelsMut<'a> {
+    type Item = &'a mut [u8; 4];
+
+    fn next(&mut self) -> Option<&'a mut [u8; 4]> {
+        self.chunks
+            .next()
+            .map(|p| <&mut [u8; 4]>::try_from(p).unwrap())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.chunks.size_hint()
+    }
+}
+
+impl<'a> DoubleEndedIterator for PixelsMut<'a> {
+    fn next_back(&mut self) -> Option<&'a mut [u8; 4]> {
+        self.chunks
+            .next_back()
+            .map(|p| <&mut [u8; 4]>::try_from(p).unwrap())
+    }
+}
+
+impl<'a> ExactSizeIterator for PixelsMut<'a> {}
+
+impl<'a> Iterator for EnumeratePixels<'a> {
+    type Item = (i32, i32, &'a [u8; 4]);
+
+    fn next(&mut self) -> Option<(i32, i32, &'a [u8; 4])> {
+        let pixel = self.pixels.next()?;
+        let (x, y) = self.position.advance();
+        Some((x, y, pixel))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.pixels.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for EnumeratePixels<'a> {}
+
+impl<'a> Iterator for EnumeratePixelsMut<'a> {
+    type Item = (i32, i32, &'a mut [u8; 4]);
+
+    fn next(&mut self) -> Option<(i32, i32, &'a mut [u8; 4])> {
+        let pixel = self.pixels.next()?;
+        let (x, y) = self.position.advance();
+        Some((x, y, pixel))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.pixels.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for EnumeratePixelsMut<'a> {}
+
+impl<'a> Iterator for Rows<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        if self.rows == 0 {
+            return None;
+        }
+        let (row, rest) = self.bytes.split_at(self.row_len);
+        self.bytes = rest;
+        self.rows -= 1;
+        Some(row)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.rows, Some(self.rows))
+    }
+}
+
+impl<'a> DoubleEndedIterator for Rows<'a> {
+    fn next_back(&mut self) -> Option<&'a [u8]> {
+        if self.rows == 0 {
+            return None;
+        }
+        let (rest, row) = self.bytes.split_at(self.row_len * (self.rows - 1));
+        self.bytes = rest;
+        self.rows -= 1;
+        Some(row)
+    }
+}
+
+impl<'a> ExactSizeIterator for Rows<'a> {}
+
+impl<'a> Iterator for RowsMut<'a> {
+    type Item = &'a mut [u8];
+
+    fn next(&mut self) -> Option<&'a mut [u8]> {
+        if self.rows == 0 {
+            return None;
+        }
+        let bytes = mem::take(&mut self.bytes);
+        let (row, rest) = bytes.split_at_mut(self.row_len);
+        self.bytes = rest;
+        self.rows -= 1;
+        Some(row)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.rows, Some(self.rows))
+    }
+}
+
+impl<'a> DoubleEndedIterator for RowsMut<'a> {
+    fn next_back(&mut self) -> Option<&'a mut [u8]> {
+        if self.rows == 0 {
+            return None;
+        }
+        let bytes = mem::take(&mut self.bytes);
+        let (rest, row) = bytes.split_at_mut(self.row_len * (self.rows - 1));
+        self.bytes = rest;
+        self.rows -= 1;
+        Some(row)
+    }
+}
+
+impl<'a> ExactSizeIterator for RowsMut<'a> {}
+
+// Private functions
+
+// Crate-internal constructors, used by the iterator methods of Image. Only whole pixels and rows
+// within the width and height of the image are visited.
+
+pub fn pixels(bytes: &[u8], width: i32, height: i32) -> Pixels<'_> {
+    let len = image_len(bytes.len(), width, height);
+    Pixels {
+        chunks: bytes[..len].chunks_exact(4),
+    }
+}
+
+pub fn pixels_mut(bytes: &mut [u8], width: i32, height: i32) -> PixelsMut<'_> {
+    let len = image_len(bytes.len(), width, height);
+    PixelsMut {
+        chunks: bytes[..len].chunks_exact_mut(4),
+    }
+}
+
+pub fn enumerate_pixels(bytes: &[u8], width: i32, height: i32) -> EnumeratePixels<'_> {
+    EnumeratePixels {
+        pixels: pixels(bytes, width, height),
+        position: Position::new(width),
+    }
+}
+
+pub fn enumerate_pixels_mut(bytes: &mut [u8], width: i32, height: i32) -> EnumeratePixelsMut<'_> {
+    EnumeratePixelsMut {
+        pixels: pixels_mut(bytes, width, height),
+        position: Position::new(width),
+    }
+}
+
+pub fn rows(bytes: &[u8], width: i32, height: i32) -> Rows<'_> {
+    // Rows without width are empty
+    let row_len = width.max(0) as usize * 4;
+    let len = image_len(bytes.len(), width, height);
+    Rows {
+        bytes: &bytes[..len],
+        row_len,
+        rows: len.checked_div(row_len).unwrap_or(height.max(0) as usize),
+    }
+}
+
+pub fn rows_mut(bytes: &mut [u8], width: i32, height: i32) -> RowsMut<'_> {
+    // Rows without width are empty
+    let row_len = width.max(0) as usize * 4;
+    let len = image_len(bytes.len(), width, height);
+    RowsMut {
+        bytes: &mut bytes[..len],
+        row_len,
+        rows: len.checked_div(row_len).unwrap_or(height.max(0) as usize),
+    }
+}
+
+// Number of bytes of whole rows that are in both the buffer and the image.
+fn image_len(len: usize, width: i32, height: i32) -> usize {
+    let row_len = width.max(0) as usize * 4;
+    if row_len == 0 {
+        return 0;
+    }
+    (len / row_len).min(height.max(0) as usize) * row_len
+}
+
+// Coordinates of the next pixel of an iterator.
+#[derive(Debug, Clone)]
+struct Position {
+    x: i32,
+    y: i32,
+    width: i32,
+}
+
+impl Position {
+    fn new(width: i32) -> Position {
+        Position { x: 0, y: 0, width }
+    }
+
+    // Move to the next pixel, returning the current coordinates
+    fn advance(&mut self) -> (i32, i32) {
+        let current = (self.x, self.y);
+        self.x += 1;
+        if self.x >= self.width {
+            self.x = 0;
+            self.y += 1;
+        }
+        current
+    }
+}
@@ -2,7 +2,8 @@
 //!
 //! Raster is an image processing lib for Rust.
 //!
-//! It provides a simplified API for processing raster images (JPEG, PNG and GIF).
+//! It provides a simplified API for processing raster images (JPEG, PNG, GIF, BMP, Netpbm, TGA,
+//! QOI, farbfeld, ICO, TIFF, WebP and Radiance HDR).
 //!
 //! ## Installation
 //! Add this to your Cargo.toml file:
@@ -29,7 +30,20 @@
 //! let image = raster::open("tests/in/sample.png").unwrap();
 //!
 //! ```
-//! Raster will detect the image format based on the file name.
+//! Raster will detect the image format from the contents of the file, falling back to the file
+//! name if the contents are not recognized.
+//! JPEG photos with an EXIF orientation are rotated upright, see `raster::open_with_options` to
+//! turn this off and `raster::open_with_metadata` to read the EXIF data.
+//!
+//! ### From a buffer or reader
+//!
+//! ```rust,ignore
+//! // Create an image from bytes already in memory, eg. an uploaded file
+//! let image = raster::load_from_memory(&buffer).unwrap();
+//!
+//! // Or from anything that implements std::io::Read
+//! let image = raster::decode(reader, raster::ImageFormat::Png).unwrap();
+//! ```
 //!
 //! ### Create a blank image
 //! ```rust,ignore
@@ -52,8 +66,105 @@
 //!
 //! ```
 //!
+//! Images can also be encoded into anything that implements `std::io::Write` using
+//! `raster::encode`.
+//!
+//! Encoder settings like JPEG quality, PNG compression or the GIF palette size can be chosen with
+//! `raster::save_with_options` and `raster::encode_with_options`.
+//!
+//! ## Animations
+//!
+//! Animated GIF, PNG and WebP files can be opened with all their frames. Any operation can then be
+//! applied to every frame:
+//!
+//! ```
+//! use raster::{editor, ResizeMode};
+//!
+//! let mut animation = raster::open_animation("tests/in/animated.gif").unwrap();
+//! animation.map_frames(|image| editor::resize(image, 5, 10, ResizeMode::Fit)).unwrap();
+//! raster::save_animation(&animation, "tests/out/test_animation.gif").unwrap();
+//!
+//! // APNG keeps the full colors of every frame
+//! raster::save_animation(&animation, "tests/out/test_animation.png").unwrap();
+//! ```
+//!
+//! ## Icons
+//!
+//! ICO and CUR files hold the same picture at several sizes. Every size can be opened with
+//! `raster::open_all`, and a favicon can be saved from several resized images:
+//!
+//! ```
+//! use raster::{editor, ResizeMode};
+//!
+//! let logo = raster::open("tests/in/sample.png").unwrap();
+//! let mut sizes = Vec::new();
+//! for &size in &[16, 32, 48, 256] {
+//!     let mut image = logo.clone();
+//!     editor::resize(&mut image, size, size, ResizeMode::Fit).unwrap();
+//!     sizes.push(image);
+//! }
+//! raster::save_all(&sizes, "tests/out/test_favicon.ico").unwrap();
+//! ```
+//!
+//! ## High Dynamic Range
+//!
+//! Radiance HDR files open as 8 bit images through a tone mapping. To keep the full range, open
+//! them as an `HdrImage` of floats and choose the tone mapping when converting:
+//!
+//! ```
+//! use raster::ToneMapping;
+//!
+//! let hdr = raster::open_hdr("tests/in/sample.png").unwrap();
+//! raster::save_hdr(&hdr, "tests/out/test_hdr.hdr").unwrap();
+//!
+//! let image = hdr.tone_map(ToneMapping::Aces).unwrap();
+//! raster::save(&image, "tests/out/test_hdr_aces.png").unwrap();
+//! ```
+//!
+//! ## Pixel Types
+//!
+//! An `Image` holds 8 bit RGBA pixels. For masks, 16 bit color or floating point work use an
+//! `ImageBuffer` of `Gray8`, `Rgb16`, `Rgba32F` or another pixel type. The filters work on both:
+//!
+//! ```
+//! use raster::{filter, ImageBuffer, Rgba16};
+//!
+//! let image = raster::open("tests/in/sample.png").unwrap();
+//! let mut deep: ImageBuffer<Rgba16> = ImageBuffer::from_image(&image);
+//! filter::gamma(&mut deep, 2.2).unwrap();
+//! raster::save(&deep.to_image(), "tests/out/test_pixel_types.png").unwrap();
+//! ```
+//!
+//! ## Large Images
+//!
+//! Images too large to hold in memory can be read and written a row at a time with
+//! `raster::open_rows` and `raster::create_rows`. PNG, farbfeld and raw RGBA files are streamed.
+//! Point operations can be applied a band of rows at a time:
+//!
+//! ```
+//! use raster::{filter, OpenOptions, SaveOptions};
+//!
+//! let mut reader = raster::open_rows("tests/in/sample.png", &OpenOptions::default()).unwrap();
+//! let (width, height) = (reader.width(), reader.height());
+//! let options = SaveOptions::default();
+//! let mut writer =
+//!     raster::create_rows("tests/out/test_rows.png", width, height, &options).unwrap();
+//!
+//! raster::process_bands(&mut reader, &mut writer, 256, filter::grayscale).unwrap();
+//! ```
+//!
+//! ## Parallel Processing
+//!
+//! With the `rayon` cargo feature, convolution filters like `filter::blur`, resizing and blending
+//! share the rows of an image between threads, with the same results as on a single thread:
+//!
+//! ```rust,ignore
+//! [dependencies]
 //!
+//! raster = { version = "x.x.x", features = ["rayon"] }
+//! ```
 //!
+//! `Parallelism::install` runs calls on the calling thread only or on a given thread pool.
 //!
 //! ## Blending 2 Images
 //!
@@ -90,44 +201,79 @@ pub mod editor;
 pub mod error;
 pub mod filter;
 pub mod interpolate;
+pub mod quantize;
 pub mod transform;
+mod animation;
 mod blend;
 mod color;
 mod endec;
+mod hdr;
 mod image;
+mod iter;
+mod metadata;
+mod options;
+mod parallel;
+mod pixel;
 mod position;
+mod stream;
+mod view;
 
 // crates
+extern crate deflate;
 extern crate gif;
 extern crate image as piston_image;
+extern crate inflate;
 extern crate png;
+#[cfg(feature = "rayon")]
+extern crate rayon;
 
 // from rust
-use std::ascii::AsciiExt;
 use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
 use std::path::Path;
-
-// from external crate
-use piston_image::GenericImage;
+use std::slice;
 
 // from local crate
 use error::{RasterError, RasterResult};
+use stream::{ImageRowWriter, ImageRows};
 
 // re-exports
+pub use animation::{Animation, DisposalMethod, Frame, LoopCount};
 pub use blend::BlendMode;
 pub use color::Color;
 pub use editor::ResizeMode;
 pub use filter::BlurMode;
 pub use filter::Orientation;
+pub use hdr::{HdrImage, ToneMapping};
+pub use image::ColorType;
 pub use image::Histogram;
 pub use image::Image;
 pub use image::ImageFormat;
+pub use image::ImageInfo;
 pub use interpolate::InterpolationMode;
+pub use iter::{EnumeratePixels, EnumeratePixelsMut, Pixels, PixelsMut, Rows, RowsMut};
+pub use metadata::{ColorSpace, Density, DensityUnit, Exif, ExifField, ExifIfd, ExifValue};
+pub use metadata::{Metadata, RenderingIntent, TextEntry};
+pub use options::{
+    ChromaSubsampling, CompressionLevel, GifOptions, IcoOptions, JpegOptions, Limits, OpenOptions,
+    PngFilter, PngOptions, PnmOptions, QoiOptions, SaveOptions, TgaOptions, TiffCompression,
+    TiffOptions,
+};
+pub use parallel::Parallelism;
+pub use pixel::{GenericImage, GenericImageView, ImageBuffer, Pixel, Subpixel};
+pub use pixel::{Gray, Gray16, Gray32F, Gray8, GrayAlpha, GrayAlpha16, GrayAlpha32F, GrayAlpha8};
+pub use pixel::{Rgb, Rgb16, Rgb32F, Rgb8, Rgba, Rgba16, Rgba32F, Rgba8};
 pub use position::PositionMode;
+pub use quantize::{Dithering, IndexedImage, QuantizeMethod};
+pub use stream::{process_bands, RowReader, RowWriter};
 pub use transform::TransformMode;
+pub use view::{ImageView, ImageViewMut};
 
 /// Create an image from an image file.
 ///
+/// The image format is detected from the first bytes of the file. The file extension is only used
+/// as a fallback when the contents are not recognized.
+///
 /// # Errors
 ///
 /// This function can return `RasterError::Io`, `RasterError::Decode`, or
@@ -142,37 +288,308 @@ pub use transform::TransformMode;
 /// println!("{:?}", image.bytes);
 /// ```
 pub fn open(image_file: &str) -> RasterResult<Image> {
-    let path = Path::new(image_file);
-    let ext = path.extension()
-        .and_then(|s| s.to_str())
-        .map_or("".to_string(), |s| s.to_ascii_lowercase());
+    open_with_options(image_file, &OpenOptions::default())
+}
 
-    // Open the file with basic error check
-    let file = File::open(image_file)?;
+/// Create an image from an image file using the given decoder options.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode`,
+/// `RasterError::UnsupportedFormat` or `RasterError::LimitsExceeded` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use raster::error::RasterError;
+/// use raster::{Limits, OpenOptions};
+///
+/// // Refuse anything larger than a thumbnail
+/// let options = OpenOptions {
+///     limits: Limits {
+///         max_width: Some(200),
+///         max_height: Some(200),
+///         ..Limits::default()
+///     },
+///     ..OpenOptions::default()
+/// };
+///
+/// match raster::open_with_options("tests/in/sample.png", &options) {
+///     Err(RasterError::LimitsExceeded(_)) => {}
+///     _ => panic!("Expected LimitsExceeded"),
+/// }
+/// ```
+pub fn open_with_options(image_file: &str, options: &OpenOptions) -> RasterResult<Image> {
+    open_with_metadata(image_file, options).map(|(image, _)| image)
+}
+
+/// Create an image from an image file using the given decoder options, along with the metadata
+/// stored in the file, like the EXIF data of a photo.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode`,
+/// `RasterError::UnsupportedFormat` or `RasterError::LimitsExceeded` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{Exif, OpenOptions};
+///
+/// let (image, metadata) =
+///     raster::open_with_metadata("tests/in/sample.jpg", &OpenOptions::default()).unwrap();
+///
+/// if let Some(exif) = metadata.exif {
+///     println!("Taken with {:?}", exif.text(Exif::MODEL));
+/// }
+/// ```
+pub fn open_with_metadata(
+    image_file: &str,
+    options: &OpenOptions,
+) -> RasterResult<(Image, Metadata)> {
+    let (reader, format) = open_file(image_file)?;
+    decode_with_metadata(reader, format, options)
+}
+
+/// Guess the image format from the first bytes of encoded image data. Returns `None` if the data
+/// does not start with the signature of a supported format.
+///
+/// # Examples
+///
+/// ```
+/// use raster::ImageFormat;
+///
+/// let header = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+/// assert_eq!(Some(ImageFormat::Png), raster::guess_format(&header));
+///
+/// assert_eq!(None, raster::guess_format(b"Not an image"));
+/// ```
+pub fn guess_format(buffer: &[u8]) -> Option<ImageFormat> {
+    endec::guess_format(buffer)
+}
+
+/// Create an image from a reader containing encoded image data of the given format.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Decode` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::ImageFormat;
+///
+/// let file = File::open("tests/in/sample.gif").unwrap();
+/// let image = raster::decode(file, ImageFormat::Gif).unwrap();
+/// ```
+pub fn decode<R: Read>(reader: R, format: ImageFormat) -> RasterResult<Image> {
+    decode_with_options(reader, format, &OpenOptions::default())
+}
+
+/// Create an image from a reader containing encoded image data of the given format, using the
+/// given decoder options.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitsExceeded` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::{ImageFormat, Limits, OpenOptions};
+///
+/// let options = OpenOptions {
+///     limits: Limits {
+///         max_pixels: Some(1_000_000),
+///         ..Limits::default()
+///     },
+///     ..OpenOptions::default()
+/// };
+/// let file = File::open("tests/in/sample.jpg").unwrap();
+/// let image = raster::decode_with_options(file, ImageFormat::Jpeg, &options).unwrap();
+/// ```
+pub fn decode_with_options<R: Read>(
+    reader: R,
+    format: ImageFormat,
+    options: &OpenOptions,
+) -> RasterResult<Image> {
+    decode_with_metadata(reader, format, options).map(|(image, _)| image)
+}
 
-    match &ext[..] {
-        "gif" => Ok(endec::decode_gif(&file)?),
-        "jpg" | "jpeg" => {
-            let src = piston_image::open(image_file)?;
-            let (w, h) = src.dimensions();
-            let mut bytes = Vec::with_capacity((w * h) as usize * 4);
-            for y in 0..h {
-                for x in 0..w {
-                    let p = src.get_pixel(x, y);
-                    bytes.extend_from_slice(&p.data[0..4]);
-                }
-            }
-            Ok(Image {
-                width: w as i32,
-                height: h as i32,
-                bytes: bytes,
-            })
+/// Create an image from a reader containing encoded image data of the given format, using the
+/// given decoder options, along with the metadata stored in the data.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitsExceeded` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use raster::{ImageFormat, OpenOptions};
+///
+/// let file = File::open("tests/in/portrait.jpg").unwrap();
+/// let (image, metadata) =
+///     raster::decode_with_metadata(file, ImageFormat::Jpeg, &OpenOptions::default()).unwrap();
+/// ```
+pub fn decode_with_metadata<R: Read>(
+    reader: R,
+    format: ImageFormat,
+    options: &OpenOptions,
+) -> RasterResult<(Image, Metadata)> {
+    let limits = &options.limits;
+    let (mut image, metadata) = match format {
+        ImageFormat::Gif => (endec::decode_gif(reader, limits)?, Metadata::default()),
+        ImageFormat::Jpeg => endec::decode_jpeg(reader, limits)?,
+        ImageFormat::Png => endec::decode_png(reader, limits)?,
+        ImageFormat::Bmp => (endec::decode_bmp(reader, limits)?, Metadata::default()),
+        ImageFormat::Pbm | ImageFormat::Pgm | ImageFormat::Ppm | ImageFormat::Pam => {
+            (endec::decode_pnm(reader, limits)?, Metadata::default())
+        }
+        ImageFormat::Tga => (endec::decode_tga(reader, limits)?, Metadata::default()),
+        ImageFormat::Qoi => endec::decode_qoi(reader, limits)?,
+        ImageFormat::Farbfeld => (endec::decode_farbfeld(reader, limits)?, Metadata::default()),
+        ImageFormat::Raw => {
+            let image = endec::decode_raw(reader, options.raw_dimensions, limits)?;
+            (image, Metadata::default())
         }
-        "png" => Ok(endec::decode_png(&file)?),
-        _ => Err(RasterError::UnsupportedFormat(ext)),
+        ImageFormat::Ico | ImageFormat::Cur => {
+            (endec::decode_ico(reader, format, limits)?, Metadata::default())
+        }
+        ImageFormat::Tiff => (endec::decode_tiff(reader, limits)?, Metadata::default()),
+        ImageFormat::WebP => endec::decode_webp(reader, limits)?,
+        ImageFormat::Hdr => {
+            let hdr = endec::decode_hdr(reader, limits)?;
+            (hdr.tone_map(ToneMapping::default())?, Metadata::default())
+        }
+    };
+    if options.auto_orient {
+        if let Some(orientation) = metadata.orientation() {
+            transform::orient(&mut image, orientation)?;
+        }
+    }
+    Ok((image, metadata))
+}
+
+/// Create an image from a buffer of encoded image data. The image format is detected from the
+/// contents of the buffer.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode`, or
+/// `RasterError::UnsupportedFormat` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+/// use std::io::Read;
+///
+/// let mut buffer = Vec::new();
+/// File::open("tests/in/sample.jpg").unwrap().read_to_end(&mut buffer).unwrap();
+///
+/// let image = raster::load_from_memory(&buffer).unwrap();
+/// ```
+pub fn load_from_memory(buffer: &[u8]) -> RasterResult<Image> {
+    load_from_memory_with_options(buffer, &OpenOptions::default())
+}
+
+/// Create an image from a buffer of encoded image data using the given decoder options. The image
+/// format is detected from the contents of the buffer.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode`,
+/// `RasterError::UnsupportedFormat` or `RasterError::LimitsExceeded` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs;
+/// use raster::OpenOptions;
+///
+/// let buffer = fs::read("tests/in/portrait.jpg").unwrap();
+/// let options = OpenOptions {
+///     auto_orient: false,
+///     ..OpenOptions::default()
+/// };
+///
+/// let image = raster::load_from_memory_with_options(&buffer, &options).unwrap();
+/// ```
+pub fn load_from_memory_with_options(buffer: &[u8], options: &OpenOptions) -> RasterResult<Image> {
+    match guess_format(buffer) {
+        Some(format) => decode_with_options(buffer, format, options),
+        None => Err(RasterError::UnsupportedFormat("unknown".to_string())),
     }
 }
 
+/// Read the size, format, color type and frame count of an image file without decoding its pixels.
+/// The image format is detected the same way as in `raster::open`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode`, or
+/// `RasterError::UnsupportedFormat` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{ColorType, ImageFormat};
+///
+/// let info = raster::probe("tests/in/animated.gif").unwrap();
+///
+/// assert_eq!(ImageFormat::Gif, info.format);
+/// assert_eq!(ColorType::Indexed, info.color_type);
+/// assert_eq!(3, info.frames);
+/// ```
+pub fn probe(image_file: &str) -> RasterResult<ImageInfo> {
+    let (reader, format) = open_file(image_file)?;
+    probe_format(reader, format)
+}
+
+/// Read the size, format, color type and frame count of encoded image data from a reader without
+/// decoding its pixels. The image format is detected from the first bytes of the data.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode`, or
+/// `RasterError::UnsupportedFormat` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use std::fs::File;
+///
+/// let file = File::open("tests/in/sample.jpg").unwrap();
+/// let info = raster::probe_reader(file).unwrap();
+///
+/// let image = raster::open("tests/in/sample.jpg").unwrap();
+/// assert_eq!(image.width, info.width);
+/// assert_eq!(image.height, info.height);
+/// ```
+pub fn probe_reader<R: Read>(reader: R) -> RasterResult<ImageInfo> {
+    let (format, reader) = endec::read_signature(reader)?;
+    let format = match format {
+        Some(format) => format,
+        None => return Err(RasterError::UnsupportedFormat("unknown".to_string())),
+    };
+    probe_format(BufReader::new(reader), format)
+}
+
 /// Save an image to an image file. The image type is detected from the file extension of the file
 /// name.
 ///
@@ -190,23 +607,805 @@ pub fn open(image_file: &str) -> RasterResult<Image> {
 /// raster::save(&image, "tests/out/test.png").unwrap();
 /// ```
 pub fn save(image: &Image, out: &str) -> RasterResult<()> {
+    save_with_options(image, out, &SaveOptions::default())
+}
+
+/// Save an image to an image file using the given encoder options. The image type is detected
+/// from the file extension of the file name.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Encode`, or
+/// `RasterError::UnsupportedFormat` upon failure. Options that are out of range fail with
+/// `RasterError::Encode`.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{ChromaSubsampling, CompressionLevel, JpegOptions};
+/// use raster::{PngFilter, PngOptions, SaveOptions};
+///
+/// let image = raster::open("tests/in/sample.png").unwrap();
+///
+/// // A small thumbnail
+/// let options = SaveOptions {
+///     jpeg: JpegOptions {
+///         quality: 50,
+///         subsampling: ChromaSubsampling::Ratio420,
+///     },
+///     ..SaveOptions::default()
+/// };
+/// raster::save_with_options(&image, "tests/out/test_save_options.jpg", &options).unwrap();
+///
+/// // Archival output
+/// let options = SaveOptions {
+///     png: PngOptions {
+///         compression: CompressionLevel::Best,
+///         filter: PngFilter::Adaptive,
+///         interlaced: false,
+///     },
+///     ..SaveOptions::default()
+/// };
+/// raster::save_with_options(&image, "tests/out/test_save_options.png", &options).unwrap();
+/// ```
+pub fn save_with_options(image: &Image, out: &str, options: &SaveOptions) -> RasterResult<()> {
+    let path = Path::new(out);
+    let format = ImageFormat::from_path(path)?;
+
+    // Open the file with basic error check
+    let file = File::create(path)?;
+    encode_with_options(image, BufWriter::new(file), format, options)
+}
+
+/// Save an image to an image file along with metadata, like the text returned by
+/// `raster::open_with_metadata`. The image type is detected from the file extension of the file
+/// name.
+///
+/// PNG stores the text, gamma, color space, rendering intent, density and ICC profile. Other
+/// formats save the image without its metadata.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Encode`, or
+/// `RasterError::UnsupportedFormat` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{OpenOptions, SaveOptions};
+///
+/// let (image, mut metadata) =
+///     raster::open_with_metadata("tests/in/sample.png", &OpenOptions::default()).unwrap();
+/// metadata.set_text("Title", "Sample");
+/// metadata.set_dpi(144.0, 144.0);
+///
+/// let options = SaveOptions::default();
+/// raster::save_with_metadata(&image, &metadata, "tests/out/test_metadata.png", &options)
+///     .unwrap();
+/// ```
+pub fn save_with_metadata(
+    image: &Image,
+    metadata: &Metadata,
+    out: &str,
+    options: &SaveOptions,
+) -> RasterResult<()> {
     let path = Path::new(out);
-    let ext = path.extension()
-        .and_then(|s| s.to_str())
-        .map_or("".to_string(), |s| s.to_ascii_lowercase());
-
-    match &ext[..] {
-        "gif" => Ok(endec::encode_gif(&image, &path)?),
-        "jpg" | "jpeg" => {
-            piston_image::save_buffer(
-                &path,
-                &image.bytes,
-                image.width as u32,
-                image.height as u32,
-                piston_image::RGBA(8),
-            ).map_err(|_| RasterError::Encode(ImageFormat::Jpeg, "Format".to_string()))
+    let format = ImageFormat::from_path(path)?;
+
+    // Open the file with basic error check
+    let file = File::create(path)?;
+    encode_with_metadata(image, metadata, BufWriter::new(file), format, options)
+}
+
+/// Encode an image into a writer using the given image format.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use raster::ImageFormat;
+///
+/// let image = raster::open("tests/in/sample.png").unwrap();
+///
+/// let mut buffer = Vec::new();
+/// raster::encode(&image, &mut buffer, ImageFormat::Png).unwrap();
+/// ```
+pub fn encode<W: Write>(image: &Image, writer: W, format: ImageFormat) -> RasterResult<()> {
+    encode_with_options(image, writer, format, &SaveOptions::default())
+}
+
+/// Encode an image into a writer using the given image format and encoder options.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{Dithering, GifOptions, ImageFormat, QuantizeMethod, SaveOptions};
+///
+/// let image = raster::open("tests/in/sample.png").unwrap();
+///
+/// let options = SaveOptions {
+///     gif: GifOptions {
+///         palette_size: 16,
+///         method: QuantizeMethod::MedianCut,
+///         dithering: Dithering::FloydSteinberg,
+///     },
+///     ..SaveOptions::default()
+/// };
+/// let mut buffer = Vec::new();
+/// raster::encode_with_options(&image, &mut buffer, ImageFormat::Gif, &options).unwrap();
+/// ```
+pub fn encode_with_options<W: Write>(
+    image: &Image,
+    writer: W,
+    format: ImageFormat,
+    options: &SaveOptions,
+) -> RasterResult<()> {
+    encode_with_metadata(image, &Metadata::default(), writer, format, options)
+}
+
+/// Encode an image and its metadata into a writer using the given image format and encoder
+/// options. Only PNG stores the metadata, see `raster::save_with_metadata`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure. A text
+/// keyword that PNG cannot store fails with `RasterError::Encode`.
+/// See error module for more info.
+pub fn encode_with_metadata<W: Write>(
+    image: &Image,
+    metadata: &Metadata,
+    writer: W,
+    format: ImageFormat,
+    options: &SaveOptions,
+) -> RasterResult<()> {
+    match format {
+        ImageFormat::Gif => endec::encode_gif(image, writer, &options.gif),
+        ImageFormat::Jpeg => endec::encode_jpeg(image, writer, &options.jpeg),
+        ImageFormat::Png => endec::encode_png(image, writer, &options.png, metadata),
+        ImageFormat::Bmp => endec::encode_bmp(image, writer),
+        ImageFormat::Pbm | ImageFormat::Pgm | ImageFormat::Ppm | ImageFormat::Pam => {
+            endec::encode_pnm(image, writer, format, &options.pnm)
+        }
+        ImageFormat::Tga => endec::encode_tga(image, writer, &options.tga),
+        ImageFormat::Qoi => endec::encode_qoi(image, writer, &options.qoi),
+        ImageFormat::Farbfeld => endec::encode_farbfeld(image, writer),
+        ImageFormat::Raw => endec::encode_raw(image, writer),
+        ImageFormat::Ico | ImageFormat::Cur => {
+            endec::encode_ico(slice::from_ref(image), writer, format, &options.ico)
+        }
+        ImageFormat::Tiff => endec::encode_tiff(slice::from_ref(image), writer, &options.tiff),
+        ImageFormat::WebP => endec::encode_webp(image, writer),
+        ImageFormat::Hdr => endec::encode_hdr(&HdrImage::from_image(image), writer),
+    }
+}
+
+/// Create an animation from an image file. Every frame is decoded and composited onto the full
+/// canvas. Formats that are not animated give an animation with a single frame.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode`, or
+/// `RasterError::UnsupportedFormat` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// let animation = raster::open_animation("tests/in/animated.gif").unwrap();
+///
+/// assert_eq!(3, animation.frames.len());
+/// ```
+pub fn open_animation(image_file: &str) -> RasterResult<Animation> {
+    open_animation_with_options(image_file, &OpenOptions::default())
+}
+
+/// Create an animation from an image file using the given decoder options.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode`,
+/// `RasterError::UnsupportedFormat` or `RasterError::LimitsExceeded` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{Limits, OpenOptions};
+///
+/// let options = OpenOptions {
+///     limits: Limits {
+///         max_frames: Some(2),
+///         ..Limits::default()
+///     },
+///     ..OpenOptions::default()
+/// };
+/// assert!(raster::open_animation_with_options("tests/in/animated.gif", &options).is_err());
+/// ```
+pub fn open_animation_with_options(
+    image_file: &str,
+    options: &OpenOptions,
+) -> RasterResult<Animation> {
+    let (reader, format) = open_file(image_file)?;
+    decode_animation_with_options(reader, format, options)
+}
+
+/// Create an animation from a reader containing encoded image data of the given format.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Decode` upon failure.
+/// See error module for more info.
+pub fn decode_animation<R: Read>(reader: R, format: ImageFormat) -> RasterResult<Animation> {
+    decode_animation_with_options(reader, format, &OpenOptions::default())
+}
+
+/// Create an animation from a reader containing encoded image data of the given format, using
+/// the given decoder options. With `OpenOptions::auto_orient` every frame is rotated upright
+/// using the EXIF orientation of the image.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitsExceeded` upon failure.
+/// See error module for more info.
+pub fn decode_animation_with_options<R: Read>(
+    reader: R,
+    format: ImageFormat,
+    options: &OpenOptions,
+) -> RasterResult<Animation> {
+    let limits = &options.limits;
+    let (mut animation, metadata) = match format {
+        ImageFormat::Gif => (endec::decode_gif_animation(reader, limits)?, Metadata::default()),
+        ImageFormat::Png => (endec::decode_png_animation(reader, limits)?, Metadata::default()),
+        ImageFormat::WebP => endec::decode_webp_animation(reader, limits)?,
+        _ => {
+            // Already oriented
+            let image = decode_with_options(reader, format, options)?;
+            (Animation::new(vec![Frame::new(image, 0)]), Metadata::default())
+        }
+    };
+    if options.auto_orient {
+        if let Some(orientation) = metadata.orientation() {
+            animation.map_frames(|image| transform::orient(image, orientation))?;
+        }
+    }
+    Ok(animation)
+}
+
+/// Save an animation to an image file. The image type is detected from the file extension of the
+/// file name.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Encode`, or
+/// `RasterError::UnsupportedFormat` upon failure. Saving to a format that does not support
+/// animation fails with `RasterError::Encode`.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{filter, Animation};
+///
+/// let mut animation = raster::open_animation("tests/in/animated.gif").unwrap();
+/// animation.map_frames(|image| filter::grayscale(image)).unwrap();
+/// raster::save_animation(&animation, "tests/out/test_animation_grayscale.gif").unwrap();
+/// ```
+pub fn save_animation(animation: &Animation, out: &str) -> RasterResult<()> {
+    save_animation_with_options(animation, out, &SaveOptions::default())
+}
+
+/// Save an animation to an image file using the given encoder options. GIF frames use the
+/// palette settings of `SaveOptions::gif` and APNG frames the settings of `SaveOptions::png`.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Encode`, or
+/// `RasterError::UnsupportedFormat` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{Dithering, GifOptions, SaveOptions};
+///
+/// let animation = raster::open_animation("tests/in/animated.gif").unwrap();
+/// let options = SaveOptions {
+///     gif: GifOptions {
+///         palette_size: 16,
+///         dithering: Dithering::Bayer,
+///         ..GifOptions::default()
+///     },
+///     ..SaveOptions::default()
+/// };
+/// raster::save_animation_with_options(&animation, "tests/out/test_animation_16.gif", &options)
+///     .unwrap();
+/// ```
+pub fn save_animation_with_options(
+    animation: &Animation,
+    out: &str,
+    options: &SaveOptions,
+) -> RasterResult<()> {
+    let path = Path::new(out);
+    let format = ImageFormat::from_path(path)?;
+
+    // Open the file with basic error check
+    let file = File::create(path)?;
+    encode_animation_with_options(animation, BufWriter::new(file), format, options)
+}
+
+/// Encode an animation into a writer using the given image format.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure. Formats that
+/// do not support animation fail with `RasterError::Encode`.
+/// See error module for more info.
+pub fn encode_animation<W: Write>(
+    animation: &Animation,
+    writer: W,
+    format: ImageFormat,
+) -> RasterResult<()> {
+    encode_animation_with_options(animation, writer, format, &SaveOptions::default())
+}
+
+/// Encode an animation into a writer using the given image format and encoder options.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure. Formats that
+/// do not support animation fail with `RasterError::Encode`.
+/// See error module for more info.
+pub fn encode_animation_with_options<W: Write>(
+    animation: &Animation,
+    writer: W,
+    format: ImageFormat,
+    options: &SaveOptions,
+) -> RasterResult<()> {
+    match format {
+        ImageFormat::Gif => endec::encode_gif_animation(animation, writer, &options.gif),
+        ImageFormat::Png => endec::encode_png_animation(animation, writer, &options.png),
+        ImageFormat::WebP => endec::encode_webp_animation(animation, writer),
+        _ => Err(RasterError::Encode(
+            format,
+            "Animation not supported".to_string(),
+        )),
+    }
+}
+
+/// Create every image stored in an image file: each size of an ICO or CUR file, each page of a
+/// TIFF file, or each frame of an animation. Other formats give a single image.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode`, or
+/// `RasterError::UnsupportedFormat` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// let images = raster::open_all("tests/in/animated.gif").unwrap();
+///
+/// assert_eq!(3, images.len());
+/// ```
+pub fn open_all(image_file: &str) -> RasterResult<Vec<Image>> {
+    open_all_with_options(image_file, &OpenOptions::default())
+}
+
+/// Create every image stored in an image file using the given decoder options.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode`,
+/// `RasterError::UnsupportedFormat` or `RasterError::LimitsExceeded` upon failure.
+/// See error module for more info.
+pub fn open_all_with_options(image_file: &str, options: &OpenOptions) -> RasterResult<Vec<Image>> {
+    let (reader, format) = open_file(image_file)?;
+    decode_all_with_options(reader, format, options)
+}
+
+/// Create every image stored in a reader containing encoded image data of the given format.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Decode` upon failure.
+/// See error module for more info.
+pub fn decode_all<R: Read>(reader: R, format: ImageFormat) -> RasterResult<Vec<Image>> {
+    decode_all_with_options(reader, format, &OpenOptions::default())
+}
+
+/// Create every image stored in a reader containing encoded image data of the given format,
+/// using the given decoder options.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitsExceeded` upon failure.
+/// See error module for more info.
+pub fn decode_all_with_options<R: Read>(
+    reader: R,
+    format: ImageFormat,
+    options: &OpenOptions,
+) -> RasterResult<Vec<Image>> {
+    match format {
+        ImageFormat::Ico | ImageFormat::Cur => {
+            endec::decode_ico_all(reader, format, &options.limits)
+        }
+        ImageFormat::Tiff => endec::decode_tiff_all(reader, &options.limits),
+        ImageFormat::Gif | ImageFormat::Png | ImageFormat::WebP => {
+            let animation = decode_animation_with_options(reader, format, options)?;
+            Ok(animation.frames.into_iter().map(|frame| frame.image).collect())
+        }
+        _ => Ok(vec![decode_with_options(reader, format, options)?]),
+    }
+}
+
+/// Save several images to a single image file, eg. the sizes of an icon or the pages of a TIFF
+/// file. The image type is detected from the file extension of the file name.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Encode`, or
+/// `RasterError::UnsupportedFormat` upon failure. Saving more than one image to a format that
+/// holds a single image fails with `RasterError::Encode`.
+/// See error module for more info.
+pub fn save_all(images: &[Image], out: &str) -> RasterResult<()> {
+    save_all_with_options(images, out, &SaveOptions::default())
+}
+
+/// Save several images to a single image file using the given encoder options.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Encode`, or
+/// `RasterError::UnsupportedFormat` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{IcoOptions, Image, SaveOptions};
+///
+/// let options = SaveOptions {
+///     ico: IcoOptions { hotspot: (3, 2) },
+///     ..SaveOptions::default()
+/// };
+/// let sizes = [Image::blank(32, 32), Image::blank(16, 16)];
+/// raster::save_all_with_options(&sizes, "tests/out/test_cursor.cur", &options).unwrap();
+/// ```
+pub fn save_all_with_options(
+    images: &[Image],
+    out: &str,
+    options: &SaveOptions,
+) -> RasterResult<()> {
+    let path = Path::new(out);
+    let format = ImageFormat::from_path(path)?;
+
+    // Open the file with basic error check
+    let file = File::create(path)?;
+    encode_all_with_options(images, BufWriter::new(file), format, options)
+}
+
+/// Encode several images into a writer using the given image format.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure.
+/// See error module for more info.
+pub fn encode_all<W: Write>(images: &[Image], writer: W, format: ImageFormat) -> RasterResult<()> {
+    encode_all_with_options(images, writer, format, &SaveOptions::default())
+}
+
+/// Encode several images into a writer using the given image format and encoder options. ICO,
+/// CUR and TIFF store every image. Other formats only accept a single image.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure.
+/// See error module for more info.
+pub fn encode_all_with_options<W: Write>(
+    images: &[Image],
+    writer: W,
+    format: ImageFormat,
+    options: &SaveOptions,
+) -> RasterResult<()> {
+    match format {
+        ImageFormat::Ico | ImageFormat::Cur => {
+            endec::encode_ico(images, writer, format, &options.ico)
+        }
+        ImageFormat::Tiff => endec::encode_tiff(images, writer, &options.tiff),
+        _ if images.len() == 1 => encode_with_options(&images[0], writer, format, options),
+        _ => Err(RasterError::Encode(
+            format,
+            "Multiple images not supported".to_string(),
+        )),
+    }
+}
+
+/// Create a floating point image from an image file. Radiance HDR files keep their full range,
+/// other formats are converted from sRGB to linear light.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode`, or
+/// `RasterError::UnsupportedFormat` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use raster::ToneMapping;
+///
+/// let hdr = raster::open_hdr("tests/in/sample.png").unwrap();
+/// let image = hdr.tone_map(ToneMapping::Reinhard).unwrap();
+/// ```
+pub fn open_hdr(image_file: &str) -> RasterResult<HdrImage> {
+    let (reader, format) = open_file(image_file)?;
+    decode_hdr_with_options(reader, format, &OpenOptions::default())
+}
+
+/// Create a floating point image from a reader containing encoded image data of the given format.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Decode` upon failure.
+/// See error module for more info.
+pub fn decode_hdr<R: Read>(reader: R, format: ImageFormat) -> RasterResult<HdrImage> {
+    decode_hdr_with_options(reader, format, &OpenOptions::default())
+}
+
+/// Create a floating point image from a reader containing encoded image data of the given format,
+/// using the given decoder options.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitsExceeded` upon failure.
+/// See error module for more info.
+pub fn decode_hdr_with_options<R: Read>(
+    reader: R,
+    format: ImageFormat,
+    options: &OpenOptions,
+) -> RasterResult<HdrImage> {
+    match format {
+        ImageFormat::Hdr => endec::decode_hdr(reader, &options.limits),
+        _ => {
+            let image = decode_with_options(reader, format, options)?;
+            Ok(HdrImage::from_image(&image))
+        }
+    }
+}
+
+/// Save a floating point image to an image file. The image type is detected from the file
+/// extension of the file name. Formats other than Radiance HDR are saved with the default tone
+/// mapping.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Encode`, or
+/// `RasterError::UnsupportedFormat` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use raster::HdrImage;
+///
+/// let mut hdr = HdrImage::new(16, 16);
+/// hdr.set_pixel(8, 8, [50.0, 40.0, 30.0]).unwrap();
+/// raster::save_hdr(&hdr, "tests/out/test_save_hdr.hdr").unwrap();
+/// ```
+pub fn save_hdr(image: &HdrImage, out: &str) -> RasterResult<()> {
+    let path = Path::new(out);
+    let format = ImageFormat::from_path(path)?;
+
+    // Open the file with basic error check
+    let file = File::create(path)?;
+    encode_hdr(image, BufWriter::new(file), format)
+}
+
+/// Encode a floating point image into a writer using the given image format. Formats other than
+/// Radiance HDR are encoded with the default tone mapping.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{HdrImage, ImageFormat};
+///
+/// let hdr = HdrImage::new(16, 16);
+///
+/// let mut buffer = Vec::new();
+/// raster::encode_hdr(&hdr, &mut buffer, ImageFormat::Hdr).unwrap();
+/// ```
+pub fn encode_hdr<W: Write>(image: &HdrImage, writer: W, format: ImageFormat) -> RasterResult<()> {
+    match format {
+        ImageFormat::Hdr => endec::encode_hdr(image, writer),
+        _ => encode(&image.tone_map(ToneMapping::default())?, writer, format),
+    }
+}
+
+/// Open an image file for reading a row at a time, to process images too large to hold in
+/// memory. See `raster::process_bands` for an example.
+///
+/// PNG, farbfeld and raw RGBA files are decoded as the rows are read, keeping only a few rows in
+/// memory. Interlaced PNG files and other formats are decoded whole when opened. The memory limit
+/// of the options applies to the rows of PNG, farbfeld and raw images and to the whole image for
+/// other formats.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode`,
+/// `RasterError::UnsupportedFormat` or `RasterError::LimitsExceeded` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use raster::OpenOptions;
+///
+/// let mut reader = raster::open_rows("tests/in/sample.png", &OpenOptions::default()).unwrap();
+/// let mut row = vec![0; reader.width() as usize * 4];
+/// while reader.read_row(&mut row).unwrap() {
+///     // Use the row
+/// }
+/// ```
+pub fn open_rows(image_file: &str, options: &OpenOptions) -> RasterResult<Box<dyn RowReader>> {
+    let (reader, format) = open_file(image_file)?;
+    decode_rows(reader, format, options)
+}
+
+/// Read encoded image data of the given format a row at a time. See `raster::open_rows` for the
+/// formats that are decoded as the rows are read.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Decode` or
+/// `RasterError::LimitsExceeded` upon failure.
+/// See error module for more info.
+pub fn decode_rows<'a, R: Read + 'a>(
+    reader: R,
+    format: ImageFormat,
+    options: &OpenOptions,
+) -> RasterResult<Box<dyn RowReader + 'a>> {
+    match format {
+        ImageFormat::Png => endec::decode_png_rows(reader, &options.limits),
+        ImageFormat::Farbfeld => endec::decode_farbfeld_rows(reader, &options.limits),
+        ImageFormat::Raw => endec::decode_raw_rows(reader, options.raw_dimensions, &options.limits),
+        _ => {
+            let image = decode_with_options(reader, format, options)?;
+            Ok(Box::new(ImageRows::new(image, format)))
+        }
+    }
+}
+
+/// Create an image file to write a row at a time. The image type is detected from the file
+/// extension of the file name. See `raster::process_bands` for an example.
+///
+/// PNG, farbfeld and raw RGBA files are encoded as the rows are written. Other formats collect
+/// the rows in memory and are encoded when the writer is finished. Interlaced PNG can't be
+/// written a row at a time.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io`, `RasterError::Encode`, or
+/// `RasterError::UnsupportedFormat` upon failure.
+/// See error module for more info.
+pub fn create_rows(
+    out: &str,
+    width: i32,
+    height: i32,
+    options: &SaveOptions,
+) -> RasterResult<Box<dyn RowWriter>> {
+    let path = Path::new(out);
+    let format = ImageFormat::from_path(path)?;
+
+    // Open the file with basic error check
+    let file = File::create(path)?;
+    encode_rows(BufWriter::new(file), format, width, height, options)
+}
+
+/// Encode an image of the given size into a writer a row at a time. See `raster::create_rows`
+/// for the formats that are encoded as the rows are written.
+///
+/// # Errors
+///
+/// This function can return `RasterError::Io` or `RasterError::Encode` upon failure.
+/// See error module for more info.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{ImageFormat, SaveOptions};
+///
+/// let mut buffer = Vec::new();
+/// {
+///     let options = SaveOptions::default();
+///     let mut writer =
+///         raster::encode_rows(&mut buffer, ImageFormat::Png, 16, 2, &options).unwrap();
+///     writer.write_row(&[255; 16 * 4]).unwrap();
+///     writer.write_row(&[0; 16 * 4]).unwrap();
+///     writer.finish().unwrap();
+/// }
+///
+/// let image = raster::decode(&buffer[..], ImageFormat::Png).unwrap();
+/// assert_eq!(255, image.bytes[0]);
+/// ```
+pub fn encode_rows<'a, W: Write + 'a>(
+    writer: W,
+    format: ImageFormat,
+    width: i32,
+    height: i32,
+    options: &SaveOptions,
+) -> RasterResult<Box<dyn RowWriter + 'a>> {
+    match format {
+        ImageFormat::Png => endec::encode_png_rows(writer, width, height, &options.png),
+        ImageFormat::Farbfeld => endec::encode_farbfeld_rows(writer, width, height),
+        ImageFormat::Raw => endec::encode_raw_rows(writer, width, height),
+        _ => Ok(Box::new(ImageRowWriter::new(
+            writer, format, width, height, options,
+        ))),
+    }
+}
+
+// Private functions
+
+// Open an image file for reading and detect its format, first from the contents and then from the
+// file extension.
+fn open_file(
+    image_file: &str,
+) -> RasterResult<(BufReader<endec::SignatureReader<File>>, ImageFormat)> {
+    let from_extension = ImageFormat::from_path(image_file);
+
+    // Open the file with basic error check
+    let file = File::open(image_file)?;
+
+    // Read the header and put it back in front. Raw data has no header, so its extension wins.
+    let (from_contents, reader) = endec::read_signature(file)?;
+    let format = match (from_extension, from_contents) {
+        (Ok(ImageFormat::Raw), _) => ImageFormat::Raw,
+        (_, Some(format)) => format,
+        (from_extension, None) => from_extension?,
+    };
+    Ok((BufReader::new(reader), format))
+}
+
+// Read the headers of encoded image data of the given format.
+fn probe_format<R: Read>(reader: R, format: ImageFormat) -> RasterResult<ImageInfo> {
+    match format {
+        ImageFormat::Gif => endec::probe_gif(reader),
+        ImageFormat::Jpeg => endec::probe_jpeg(reader),
+        ImageFormat::Png => endec::probe_png(reader),
+        ImageFormat::Bmp => endec::probe_bmp(reader),
+        ImageFormat::Pbm | ImageFormat::Pgm | ImageFormat::Ppm | ImageFormat::Pam => {
+            endec::probe_pnm(reader)
         }
-        "png" => Ok(endec::encode_png(&image, &path)?),
-        _ => Err(RasterError::UnsupportedFormat(ext)),
+        ImageFormat::Tga => endec::probe_tga(reader),
+        ImageFormat::Qoi => endec::probe_qoi(reader),
+        ImageFormat::Farbfeld => endec::probe_farbfeld(reader),
+        ImageFormat::Raw => Err(RasterError::Decode(
+            ImageFormat::Raw,
+            "Raw data has no header".to_string(),
+        )),
+        ImageFormat::Ico | ImageFormat::Cur => endec::probe_ico(reader, format),
+        ImageFormat::Tiff => endec::probe_tiff(reader),
+        ImageFormat::WebP => endec::probe_webp(reader),
+        ImageFormat::Hdr => endec::probe_hdr(reader),
     }
 }
@@ -0,0 +1,563 @@
+//!  A module for image metadata.
+
+// from rust
+
+// from external crate
+
+// from local crate
+
+/// Metadata stored alongside the pixels of an image, returned by `raster::open_with_metadata`
+/// and `raster::decode_with_metadata`.
+///
+/// # Examples
+///
+/// ```
+/// use raster::OpenOptions;
+///
+/// let (image, metadata) =
+///     raster::open_with_metadata("tests/in/portrait.jpg", &OpenOptions::default()).unwrap();
+///
+/// assert!(metadata.exif.is_some());
+/// assert_eq!(None, metadata.orientation());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Metadata {
+    /// EXIF data, if the image has any.
+    pub exif: Option<Exif>,
+
+    /// The color space the pixels are stored in, if the format records one.
+    pub color_space: Option<ColorSpace>,
+
+    /// Text entries like a title or a copyright notice, in the order they were read.
+    pub text: Vec<TextEntry>,
+
+    /// The gamma the color values are encoded with, like 0.45455 for a display gamma of 2.2.
+    pub gamma: Option<f32>,
+
+    /// How colors of an sRGB image are mapped to a device with a smaller gamut.
+    pub rendering_intent: Option<RenderingIntent>,
+
+    /// The physical size of the pixels.
+    pub density: Option<Density>,
+
+    /// An embedded ICC color profile.
+    pub icc_profile: Option<Vec<u8>>,
+}
+
+impl Metadata {
+    /// The EXIF orientation of the stored pixels, from 1 to 8. See `Exif::orientation`.
+    pub fn orientation(&self) -> Option<u16> {
+        self.exif.as_ref().and_then(|exif| exif.orientation())
+    }
+
+    /// Get the text of the first entry with the given keyword.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::Metadata;
+    ///
+    /// let mut metadata = Metadata::default();
+    /// metadata.set_text("Author", "Jane Doe");
+    ///
+    /// assert_eq!(Some("Jane Doe"), metadata.get_text("Author"));
+    /// assert_eq!(None, metadata.get_text("Title"));
+    /// ```
+    pub fn get_text(&self, keyword: &str) -> Option<&str> {
+        self.text
+            .iter()
+            .find(|entry| entry.keyword == keyword)
+            .map(|entry| entry.text.as_str())
+    }
+
+    /// Set the text of a keyword. The first entry with the keyword keeps its other settings and
+    /// any later ones are removed. A new entry is added at the end if there is none.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{ImageFormat, Metadata, OpenOptions, SaveOptions};
+    ///
+    /// let image = raster::open("tests/in/sample.png").unwrap();
+    /// let mut metadata = Metadata::default();
+    /// metadata.set_text("Copyright", "Copyright 2017 Raster");
+    ///
+    /// let mut buffer = Vec::new();
+    /// let options = SaveOptions::default();
+    /// raster::encode_with_metadata(&image, &metadata, &mut buffer, ImageFormat::Png, &options)
+    ///     .unwrap();
+    ///
+    /// let (_, metadata) =
+    ///     raster::decode_with_metadata(&buffer[..], ImageFormat::Png, &OpenOptions::default())
+    ///         .unwrap();
+    /// assert_eq!(Some("Copyright 2017 Raster"), metadata.get_text("Copyright"));
+    /// ```
+    pub fn set_text(&mut self, keyword: &str, text: &str) {
+        match self.text.iter().position(|entry| entry.keyword == keyword) {
+            Some(index) => {
+                self.text[index].text = text.to_string();
+                let rest = self.text.split_off(index + 1);
+                self.text
+                    .extend(rest.into_iter().filter(|entry| entry.keyword != keyword));
+            }
+            None => self.text.push(TextEntry::new(keyword, text)),
+        }
+    }
+
+    /// Remove every text entry with the given keyword.
+    pub fn remove_text(&mut self, keyword: &str) {
+        self.text.retain(|entry| entry.keyword != keyword);
+    }
+
+    /// The horizontal and vertical resolution in dots per inch. Returns `None` if the density is
+    /// missing or only gives the aspect ratio of the pixels.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::Metadata;
+    ///
+    /// let mut metadata = Metadata::default();
+    /// metadata.set_dpi(300.0, 300.0);
+    ///
+    /// let (x, y) = metadata.dpi().unwrap();
+    /// assert_eq!(300, x.round() as i32);
+    /// assert_eq!(300, y.round() as i32);
+    /// ```
+    pub fn dpi(&self) -> Option<(f32, f32)> {
+        match self.density {
+            Some(Density {
+                x,
+                y,
+                unit: DensityUnit::Meter,
+            }) => Some((x as f32 * 0.0254, y as f32 * 0.0254)),
+            _ => None,
+        }
+    }
+
+    /// Set the horizontal and vertical resolution in dots per inch. It is stored in pixels per
+    /// meter, rounded to a whole number.
+    pub fn set_dpi(&mut self, x: f32, y: f32) {
+        self.density = Some(Density {
+            x: (x / 0.0254).round() as u32,
+            y: (y / 0.0254).round() as u32,
+            unit: DensityUnit::Meter,
+        });
+    }
+}
+
+/// How the color values of an image relate to light intensity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Color values are sRGB encoded. Alpha is linear.
+    Srgb,
+
+    /// All channels are linear.
+    Linear,
+}
+
+/// A text entry of an image. PNG stores entries with a Latin-1 text and no language as tEXt or
+/// zTXt chunks, and all others as iTXt chunks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEntry {
+    /// The keyword, like "Title", "Author", "Description" or "Copyright". It must be 1 to 79
+    /// Latin-1 characters.
+    pub keyword: String,
+
+    /// The text.
+    pub text: String,
+
+    /// The language of the text as a tag like "en-US", or empty if unknown.
+    pub language: String,
+
+    /// The keyword translated to the language of the text, or empty.
+    pub translated_keyword: String,
+
+    /// Whether the text is stored compressed.
+    pub compressed: bool,
+}
+
+impl TextEntry {
+    /// Create an uncompressed entry with no language.
+    pub fn new(keyword: &str, text: &str) -> TextEntry {
+        TextEntry {
+            keyword: keyword.to_string(),
+            text: text.to_string(),
+            language: String::new(),
+            translated_keyword: String::new(),
+            compressed: false,
+        }
+    }
+}
+
+/// Enumeration of the ways colors outside the gamut of a device are mapped, as defined by the
+/// ICC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderingIntent {
+    Perceptual,
+    RelativeColorimetric,
+    Saturation,
+    AbsoluteColorimetric,
+}
+
+/// The number of pixels per unit of length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Density {
+    /// Horizontal pixels per unit.
+    pub x: u32,
+
+    /// Vertical pixels per unit.
+    pub y: u32,
+
+    /// The unit of length.
+    pub unit: DensityUnit,
+}
+
+/// Enumeration of the units of a pixel density.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DensityUnit {
+    /// No unit. Only the aspect ratio of the pixels is known.
+    Unknown,
+
+    /// Pixels per meter.
+    Meter,
+}
+
+/// A read-only view of EXIF data.
+///
+/// The fields of the primary image, the EXIF sub-directory and the GPS sub-directory are read.
+/// Thumbnail fields are skipped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exif {
+    data: Vec<u8>,
+    fields: Vec<ExifField>,
+}
+
+impl Exif {
+    /// Tag of the orientation field.
+    pub const ORIENTATION: u16 = 0x0112;
+
+    /// Tag of the camera maker field.
+    pub const MAKE: u16 = 0x010F;
+
+    /// Tag of the camera model field.
+    pub const MODEL: u16 = 0x0110;
+
+    /// Tag of the date and time the file was changed.
+    pub const DATE_TIME: u16 = 0x0132;
+
+    /// Tag of the date and time the picture was taken.
+    pub const DATE_TIME_ORIGINAL: u16 = 0x9003;
+
+    /// Parse EXIF data in its TIFF layout, as found in a JPEG APP1 segment after the `Exif\0\0`
+    /// header. Returns `None` if the data is not valid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::Exif;
+    ///
+    /// // Big endian, one orientation field with a value of 6
+    /// let data = [
+    ///     b'M', b'M', 0, 42, 0, 0, 0, 8, //
+    ///     0, 1, 0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0, //
+    ///     0, 0, 0, 0,
+    /// ];
+    /// let exif = Exif::parse(&data).unwrap();
+    ///
+    /// assert_eq!(Some(6), exif.orientation());
+    /// ```
+    pub fn parse(data: &[u8]) -> Option<Exif> {
+        let reader = TiffReader::new(data)?;
+
+        let mut fields = Vec::new();
+        let mut ifds = vec![(ExifIfd::Primary, reader.u32(4)?)];
+        let mut visited = Vec::new();
+        while let Some((ifd, offset)) = ifds.pop() {
+            // Guard against directories that point at each other
+            if visited.contains(&offset) {
+                continue;
+            }
+            visited.push(offset);
+
+            let entries = match reader.ifd(offset as usize, ifd) {
+                Some(entries) => entries,
+                // A broken sub-directory should not hide the rest of the data
+                None if ifd != ExifIfd::Primary => continue,
+                None => return None,
+            };
+            for field in entries {
+                let pointer = match field.value {
+                    ExifValue::Long(ref values) if values.len() == 1 => values[0],
+                    _ => 0,
+                };
+                match (ifd, field.tag) {
+                    (ExifIfd::Primary, 0x8769) => ifds.push((ExifIfd::Exif, pointer)),
+                    (ExifIfd::Primary, 0x8825) => ifds.push((ExifIfd::Gps, pointer)),
+                    _ => fields.push(field),
+                }
+            }
+        }
+
+        Some(Exif {
+            data: data.to_vec(),
+            fields,
+        })
+    }
+
+    /// The raw EXIF data in its TIFF layout.
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// All fields in the order they were read.
+    pub fn fields(&self) -> &[ExifField] {
+        &self.fields
+    }
+
+    /// Get the value of a field of the primary image or the EXIF sub-directory.
+    pub fn get(&self, tag: u16) -> Option<&ExifValue> {
+        self.fields
+            .iter()
+            .find(|field| field.tag == tag && field.ifd != ExifIfd::Gps)
+            .map(|field| &field.value)
+    }
+
+    /// Get the value of a text field of the primary image or the EXIF sub-directory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::Exif;
+    ///
+    /// // Little endian, one camera model field
+    /// let data = [
+    ///     b'I', b'I', 42, 0, 8, 0, 0, 0, //
+    ///     1, 0, 0x10, 0x01, 2, 0, 4, 0, 0, 0, b'X', b'1', b'0', 0, //
+    ///     0, 0, 0, 0,
+    /// ];
+    /// let exif = Exif::parse(&data).unwrap();
+    ///
+    /// assert_eq!(Some("X10"), exif.text(Exif::MODEL));
+    /// ```
+    pub fn text(&self, tag: u16) -> Option<&str> {
+        match self.get(tag) {
+            Some(ExifValue::Ascii(text)) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// How the stored pixels must be transformed to show the image upright, from 1 to 8 as
+    /// defined by the EXIF standard. Returns `None` if the field is missing or invalid.
+    ///
+    /// 1. Upright.
+    /// 2. Flipped horizontally.
+    /// 3. Rotated 180 degrees.
+    /// 4. Flipped vertically.
+    /// 5. Flipped across the top left to bottom right diagonal.
+    /// 6. Needs a 90 degree clockwise rotation.
+    /// 7. Flipped across the top right to bottom left diagonal.
+    /// 8. Needs a 90 degree counter-clockwise rotation.
+    pub fn orientation(&self) -> Option<u16> {
+        match self.get(Exif::ORIENTATION) {
+            Some(ExifValue::Short(values)) => {
+                values.first().cloned().filter(|o| (1..=8).contains(o))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A single EXIF field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExifField {
+    /// Tag number of the field.
+    pub tag: u16,
+
+    /// The directory the field was read from.
+    pub ifd: ExifIfd,
+
+    /// Value of the field.
+    pub value: ExifValue,
+}
+
+/// Enumeration of the EXIF directories that fields are read from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExifIfd {
+    /// Fields of the primary image, like orientation and camera model.
+    Primary,
+    /// Fields of the EXIF sub-directory, like exposure and the date taken.
+    Exif,
+    /// Fields of the GPS sub-directory.
+    Gps,
+}
+
+/// Enumeration of EXIF field values, one per TIFF field type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExifValue {
+    /// Unsigned 8-bit integers.
+    Byte(Vec<u8>),
+    /// Text, without the trailing null.
+    Ascii(String),
+    /// Unsigned 16-bit integers.
+    Short(Vec<u16>),
+    /// Unsigned 32-bit integers.
+    Long(Vec<u32>),
+    /// Unsigned fractions as numerator and denominator.
+    Rational(Vec<(u32, u32)>),
+    /// Signed 8-bit integers.
+    SByte(Vec<i8>),
+    /// Bytes whose meaning depends on the field.
+    Undefined(Vec<u8>),
+    /// Signed 16-bit integers.
+    SShort(Vec<i16>),
+    /// Signed 32-bit integers.
+    SLong(Vec<i32>),
+    /// Signed fractions as numerator and denominator.
+    SRational(Vec<(i32, i32)>),
+    /// 32-bit floats.
+    Float(Vec<f32>),
+    /// 64-bit floats.
+    Double(Vec<f64>),
+}
+
+// Private functions
+
+// Reads values from TIFF structured data in either byte order. Also used to read TIFF images.
+pub struct TiffReader<'a> {
+    data: &'a [u8],
+    big_endian: bool,
+}
+
+impl<'a> TiffReader<'a> {
+    // Check the byte order mark at the start of the data.
+    pub fn new(data: &'a [u8]) -> Option<TiffReader<'a>> {
+        let big_endian = match data.get(0..4)? {
+            b"II*\0" => false,
+            b"MM\0*" => true,
+            _ => return None,
+        };
+        Some(TiffReader { data, big_endian })
+    }
+
+    // Whether the data is stored with the most significant byte first.
+    pub fn big_endian(&self) -> bool {
+        self.big_endian
+    }
+
+    // Read the fields of the directory at the given offset.
+    pub fn ifd(&self, offset: usize, ifd: ExifIfd) -> Option<Vec<ExifField>> {
+        let count = self.u16(offset)? as usize;
+        let mut fields = Vec::with_capacity(count);
+        for i in 0..count {
+            let entry = offset + 2 + i * 12;
+            let tag = self.u16(entry)?;
+            let kind = self.u16(entry + 2)?;
+            let count = self.u32(entry + 4)? as usize;
+
+            let size = match kind {
+                1 | 2 | 6 | 7 => 1,
+                3 | 8 => 2,
+                4 | 9 | 11 => 4,
+                5 | 10 | 12 => 8,
+                // Unknown types are skipped as the spec asks
+                _ => continue,
+            };
+            let len = size * count;
+            // Values that fit in four bytes are stored in the entry itself
+            let start = if len <= 4 {
+                entry + 8
+            } else {
+                self.u32(entry + 8)? as usize
+            };
+            // Skip fields that point outside the data
+            let bytes = match start
+                .checked_add(len)
+                .and_then(|end| self.data.get(start..end))
+            {
+                Some(bytes) => bytes,
+                None => continue,
+            };
+
+            if let Some(value) = self.value(kind, bytes, size) {
+                fields.push(ExifField { tag, ifd, value });
+            }
+        }
+        Some(fields)
+    }
+
+    // Decode the bytes of a value of the given type.
+    fn value(&self, kind: u16, bytes: &[u8], size: usize) -> Option<ExifValue> {
+        let words = bytes.chunks(size);
+        let value = match kind {
+            1 => ExifValue::Byte(bytes.to_vec()),
+            2 => {
+                let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+                ExifValue::Ascii(String::from_utf8_lossy(&bytes[..end]).into_owned())
+            }
+            3 => ExifValue::Short(words.map(|w| self.read_u16(w)).collect()),
+            4 => ExifValue::Long(words.map(|w| self.read_u32(w)).collect()),
+            5 => ExifValue::Rational(
+                words
+                    .map(|w| (self.read_u32(&w[..4]), self.read_u32(&w[4..])))
+                    .collect(),
+            ),
+            6 => ExifValue::SByte(bytes.iter().map(|&b| b as i8).collect()),
+            7 => ExifValue::Undefined(bytes.to_vec()),
+            8 => ExifValue::SShort(words.map(|w| self.read_u16(w) as i16).collect()),
+            9 => ExifValue::SLong(words.map(|w| self.read_u32(w) as i32).collect()),
+            10 => ExifValue::SRational(
+                words
+                    .map(|w| (self.read_u32(&w[..4]) as i32, self.read_u32(&w[4..]) as i32))
+                    .collect(),
+            ),
+            11 => ExifValue::Float(words.map(|w| f32::from_bits(self.read_u32(w))).collect()),
+            12 => ExifValue::Double(
+                words
+                    .map(|w| {
+                        let (high, low) = if self.big_endian {
+                            (self.read_u32(&w[..4]), self.read_u32(&w[4..]))
+                        } else {
+                            (self.read_u32(&w[4..]), self.read_u32(&w[..4]))
+                        };
+                        f64::from_bits((high as u64) << 32 | low as u64)
+                    })
+                    .collect(),
+            ),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    // Read a 16-bit integer at the given offset.
+    pub fn u16(&self, offset: usize) -> Option<u16> {
+        self.data
+            .get(offset..offset.checked_add(2)?)
+            .map(|b| self.read_u16(b))
+    }
+
+    // Read a 32-bit integer at the given offset.
+    pub fn u32(&self, offset: usize) -> Option<u32> {
+        self.data
+            .get(offset..offset.checked_add(4)?)
+            .map(|b| self.read_u32(b))
+    }
+
+    // Convert the first two bytes of a slice to a 16-bit integer.
+    fn read_u16(&self, b: &[u8]) -> u16 {
+        if self.big_endian {
+            (b[0] as u16) << 8 | b[1] as u16
+        } else {
+            (b[1] as u16) << 8 | b[0] as u16
+        }
+    }
+
+    // Convert the first four bytes of a slice to a 32-bit integer.
+    fn read_u32(&self, b: &[u8]) -> u32 {
+        if self.big_endian {
+            (b[0] as u32) << 24 | (b[1] as u32) << 16 | (b[2] as u32) << 8 | b[3] as u32
+        } else {
+            (b[3] as u32) << 24 | (b[2] as u32) << 16 | (b[1] as u32) << 8 | b[0] as u32
+        }
+    }
+}
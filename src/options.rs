@@ -0,0 +1,415 @@
+//!  A module for encoding and decoding options.
+
+// from rust
+
+// from external crate
+
+// from local crate
+use error::{RasterError, RasterResult};
+use metadata::ColorSpace;
+use quantize::{Dithering, QuantizeMethod};
+
+/// Decoder settings used by `raster::open_with_options` and `raster::decode_with_options`.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{Limits, OpenOptions};
+///
+/// let options = OpenOptions {
+///     limits: Limits {
+///         max_pixels: Some(4096 * 4096),
+///         ..Limits::default()
+///     },
+///     ..OpenOptions::default()
+/// };
+/// let image = raster::open_with_options("tests/in/sample.png", &options).unwrap();
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpenOptions {
+    /// Limits on the size of decoded images.
+    pub limits: Limits,
+
+    /// Rotate and flip images that have an EXIF orientation so the pixels are upright. The
+    /// orientation is still reported in the metadata. Defaults to true.
+    pub auto_orient: bool,
+
+    /// The width and height of headerless RGBA data, needed to decode `ImageFormat::Raw`.
+    /// Defaults to `None`.
+    pub raw_dimensions: Option<(u32, u32)>,
+}
+
+impl Default for OpenOptions {
+    fn default() -> OpenOptions {
+        OpenOptions {
+            limits: Limits::default(),
+            auto_orient: true,
+            raw_dimensions: None,
+        }
+    }
+}
+
+/// Limits on the images a decoder accepts, to protect against files that claim a huge size to
+/// exhaust memory. A limit of `None` is not checked. Decoding an image that goes over a limit fails
+/// with `RasterError::LimitsExceeded` before the memory is allocated.
+///
+/// By default only the memory used for a single decode is limited, to 512 MiB.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Limits {
+    /// Maximum width in pixels.
+    pub max_width: Option<u32>,
+
+    /// Maximum height in pixels.
+    pub max_height: Option<u32>,
+
+    /// Maximum number of pixels, width times height.
+    pub max_pixels: Option<u64>,
+
+    /// Maximum number of bytes allocated while decoding.
+    pub max_alloc: Option<u64>,
+
+    /// Maximum number of frames of an animation.
+    pub max_frames: Option<usize>,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_width: None,
+            max_height: None,
+            max_pixels: None,
+            max_alloc: Some(512 * 1024 * 1024),
+            max_frames: None,
+        }
+    }
+}
+
+impl Limits {
+    /// Limits that are never exceeded.
+    pub fn none() -> Limits {
+        Limits {
+            max_width: None,
+            max_height: None,
+            max_pixels: None,
+            max_alloc: None,
+            max_frames: None,
+        }
+    }
+
+    /// Check the size of an image, including the memory needed for its RGBA pixels. Can be used
+    /// with `raster::probe` to reject a file before decoding it.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `RasterError::LimitsExceeded` if a limit is exceeded.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::Limits;
+    ///
+    /// let limits = Limits {
+    ///     max_width: Some(1000),
+    ///     ..Limits::default()
+    /// };
+    /// let info = raster::probe("tests/in/sample.png").unwrap();
+    ///
+    /// assert!(limits.check_dimensions(info.width as u32, info.height as u32).is_ok());
+    /// assert!(limits.check_dimensions(1001, 600).is_err());
+    /// ```
+    pub fn check_dimensions(&self, width: u32, height: u32) -> RasterResult<()> {
+        if let Some(max) = self.max_width {
+            if width > max {
+                return Err(exceeded(format!(
+                    "Width {} is over the limit of {}",
+                    width, max
+                )));
+            }
+        }
+        if let Some(max) = self.max_height {
+            if height > max {
+                return Err(exceeded(format!(
+                    "Height {} is over the limit of {}",
+                    height, max
+                )));
+            }
+        }
+        let pixels = width as u64 * height as u64;
+        if let Some(max) = self.max_pixels {
+            if pixels > max {
+                return Err(exceeded(format!(
+                    "{} pixels is over the limit of {}",
+                    pixels, max
+                )));
+            }
+        }
+        // The RGBA bytes of the largest images do not fit in a u64
+        match pixels.checked_mul(4) {
+            Some(bytes) => self.check_alloc(bytes),
+            None => Err(exceeded(format!(
+                "{} pixels is too many to allocate",
+                pixels
+            ))),
+        }
+    }
+
+    /// Check an allocation of `bytes` bytes against the memory limit.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `RasterError::LimitsExceeded` if the limit is exceeded.
+    pub fn check_alloc(&self, bytes: u64) -> RasterResult<()> {
+        match self.max_alloc {
+            Some(max) if bytes > max => Err(exceeded(format!(
+                "Allocating {} bytes is over the limit of {}",
+                bytes, max
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Check a number of frames against the frame limit.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `RasterError::LimitsExceeded` if the limit is exceeded.
+    pub fn check_frames(&self, frames: usize) -> RasterResult<()> {
+        match self.max_frames {
+            Some(max) if frames > max => Err(exceeded(format!(
+                "{} frames is over the limit of {}",
+                frames, max
+            ))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Encoder settings used by `raster::save_with_options` and `raster::encode_with_options`. Only
+/// the options of the format being written are used.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{ChromaSubsampling, JpegOptions, SaveOptions};
+///
+/// let options = SaveOptions {
+///     jpeg: JpegOptions {
+///         quality: 60,
+///         subsampling: ChromaSubsampling::Ratio420,
+///     },
+///     ..SaveOptions::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SaveOptions {
+    /// Options for JPEG output.
+    pub jpeg: JpegOptions,
+
+    /// Options for PNG output.
+    pub png: PngOptions,
+
+    /// Options for GIF output.
+    pub gif: GifOptions,
+
+    /// Options for PBM, PGM, PPM and PAM output.
+    pub pnm: PnmOptions,
+
+    /// Options for TGA output.
+    pub tga: TgaOptions,
+
+    /// Options for QOI output.
+    pub qoi: QoiOptions,
+
+    /// Options for ICO and CUR output.
+    pub ico: IcoOptions,
+
+    /// Options for TIFF output.
+    pub tiff: TiffOptions,
+}
+
+/// Options for JPEG output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JpegOptions {
+    /// Quality from 1 (smallest file) to 100 (best quality). Defaults to 75.
+    pub quality: u8,
+
+    /// How much the color channels are downsampled. Defaults to no subsampling.
+    pub subsampling: ChromaSubsampling,
+}
+
+impl Default for JpegOptions {
+    fn default() -> JpegOptions {
+        JpegOptions {
+            quality: 75,
+            subsampling: ChromaSubsampling::Ratio444,
+        }
+    }
+}
+
+/// Enumeration of JPEG chroma subsampling ratios.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChromaSubsampling {
+    /// Full resolution color.
+    Ratio444,
+    /// Color at half the horizontal resolution.
+    Ratio422,
+    /// Color at half the horizontal and vertical resolution.
+    Ratio420,
+}
+
+/// Options for PNG output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PngOptions {
+    /// How hard the encoder tries to make the file small. Defaults to fast.
+    pub compression: CompressionLevel,
+
+    /// The filter applied to each row before compression. Defaults to sub.
+    pub filter: PngFilter,
+
+    /// Write the image with Adam7 interlacing so it can be shown progressively. Defaults to false.
+    pub interlaced: bool,
+}
+
+impl Default for PngOptions {
+    fn default() -> PngOptions {
+        PngOptions {
+            compression: CompressionLevel::Fast,
+            filter: PngFilter::Sub,
+            interlaced: false,
+        }
+    }
+}
+
+/// Enumeration of compression levels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionLevel {
+    /// Fastest encoding, largest files.
+    Fast,
+    /// A balance between speed and file size.
+    Default,
+    /// Slowest encoding, smallest files.
+    Best,
+}
+
+/// Enumeration of PNG row filters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PngFilter {
+    /// Store rows as they are.
+    None,
+    /// Difference with the pixel to the left.
+    Sub,
+    /// Difference with the pixel above.
+    Up,
+    /// Difference with the average of the pixels to the left and above.
+    Average,
+    /// Difference with the Paeth predictor of the pixels to the left, above and above left.
+    Paeth,
+    /// Pick the filter that works best for each row.
+    Adaptive,
+}
+
+/// Options for GIF output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GifOptions {
+    /// Maximum number of colors in the palette, from 2 to 256. Defaults to 256.
+    pub palette_size: u16,
+
+    /// How the palette is built. Defaults to median cut.
+    pub method: QuantizeMethod,
+
+    /// How colors missing from the palette are approximated. Defaults to no dithering.
+    pub dithering: Dithering,
+}
+
+impl Default for GifOptions {
+    fn default() -> GifOptions {
+        GifOptions {
+            palette_size: 256,
+            method: QuantizeMethod::MedianCut,
+            dithering: Dithering::None,
+        }
+    }
+}
+
+/// Options for Netpbm output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PnmOptions {
+    /// Write the samples as text instead of binary, using the plain PBM, PGM or PPM format. PAM
+    /// has no plain format and is always binary. Defaults to false.
+    pub ascii: bool,
+}
+
+/// Options for TGA output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TgaOptions {
+    /// Compress the pixels with run-length encoding. Defaults to true.
+    pub rle: bool,
+}
+
+impl Default for TgaOptions {
+    fn default() -> TgaOptions {
+        TgaOptions { rle: true }
+    }
+}
+
+/// Options for QOI output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QoiOptions {
+    /// The color space recorded in the header. It is informational and does not change the
+    /// pixels. Defaults to sRGB.
+    pub color_space: ColorSpace,
+}
+
+impl Default for QoiOptions {
+    fn default() -> QoiOptions {
+        QoiOptions {
+            color_space: ColorSpace::Srgb,
+        }
+    }
+}
+
+/// Options for ICO and CUR output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct IcoOptions {
+    /// The point of a cursor that clicks, in pixels from the top left. The same hotspot is
+    /// written for every size. Ignored for icons. Defaults to (0, 0).
+    pub hotspot: (u16, u16),
+}
+
+/// Options for TIFF output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TiffOptions {
+    /// How the strips of each page are compressed. Defaults to LZW.
+    pub compression: TiffCompression,
+}
+
+impl Default for TiffOptions {
+    fn default() -> TiffOptions {
+        TiffOptions {
+            compression: TiffCompression::Lzw,
+        }
+    }
+}
+
+/// Compression methods for TIFF output. LZW and Deflate are combined with horizontal differencing,
+/// which helps with photos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TiffCompression {
+    /// No compression.
+    None,
+
+    /// Run-length encoding of each row.
+    PackBits,
+
+    /// Lempel-Ziv-Welch compression, supported by all TIFF readers.
+    Lzw,
+
+    /// Deflate compression, which gives smaller files than LZW.
+    Deflate,
+}
+
+// Private functions
+
+fn exceeded(msg: String) -> RasterError {
+    RasterError::LimitsExceeded(msg)
+}
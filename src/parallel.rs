@@ -0,0 +1,123 @@
+//!  A module for sharing the rows of an image between threads.
+
+// from rust
+use std::cell::RefCell;
+#[cfg(feature = "rayon")]
+use std::sync::Arc;
+
+// from external crate
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+#[cfg(feature = "rayon")]
+use rayon::ThreadPool;
+
+// from local crate
+
+/// Where `filter::convolve` and the filters built on it, the `interpolate` functions and
+/// `editor::blend` do their work. Each row of the result is computed on its own, so with the
+/// `rayon` cargo feature the rows are shared between threads. The result is the same on any number
+/// of threads.
+///
+/// The setting of the current thread is changed for the calls made within `install`. The `Pool`
+/// variant only exists with the `rayon` feature, so matches need a wildcard arm.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{filter, BlurMode, Parallelism};
+///
+/// let mut image = raster::open("tests/in/sample.png").unwrap();
+///
+/// // Blur on the calling thread only
+/// Parallelism::Serial.install(|| filter::blur(&mut image, BlurMode::Gaussian)).unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub enum Parallelism {
+    /// Work on the calling thread.
+    Serial,
+
+    /// Share the work between the threads of the current rayon thread pool. That is the global
+    /// pool, or the pool of a `rayon::ThreadPool::install` the call is made in. Without the `rayon`
+    /// feature this works on the calling thread. The default.
+    #[default]
+    Parallel,
+
+    /// Share the work between the threads of a given rayon thread pool.
+    #[cfg(feature = "rayon")]
+    Pool(Arc<ThreadPool>),
+}
+
+impl Parallelism {
+    /// Run a function with this setting for the current thread. The previous setting is restored
+    /// when the function returns.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{editor, Parallelism, ResizeMode};
+    ///
+    /// let mut image = raster::open("tests/in/sample.png").unwrap();
+    ///
+    /// Parallelism::Serial.install(|| {
+    ///     editor::resize(&mut image, 100, 100, ResizeMode::Fit).unwrap();
+    /// });
+    /// ```
+    pub fn install<R, F: FnOnce() -> R>(self, f: F) -> R {
+        let previous = CURRENT.with(|current| current.replace(self));
+        // Restore the previous setting even if the function panics
+        let _restore = Restore(Some(previous));
+        f()
+    }
+
+    /// The setting of the current thread.
+    pub fn current() -> Parallelism {
+        CURRENT.with(|current| current.borrow().clone())
+    }
+}
+
+// Private functions
+
+thread_local! {
+    static CURRENT: RefCell<Parallelism> = RefCell::new(Parallelism::default());
+}
+
+// Puts back the setting that was replaced by Parallelism::install.
+struct Restore(Option<Parallelism>);
+
+impl Drop for Restore {
+    fn drop(&mut self) {
+        if let Some(previous) = self.0.take() {
+            CURRENT.with(|current| *current.borrow_mut() = previous);
+        }
+    }
+}
+
+// Call a function with the index and items of every row of `row_len` items, on the threads of the
+// setting of the current thread.
+pub fn for_each_row<T, F>(items: &mut [T], row_len: usize, f: F)
+where
+    T: Send,
+    F: Fn(usize, &mut [T]) + Send + Sync,
+{
+    if row_len == 0 {
+        return;
+    }
+    #[cfg(feature = "rayon")]
+    {
+        let rows = |(y, row): (usize, &mut [T])| f(y, row);
+        match Parallelism::current() {
+            Parallelism::Serial => {}
+            Parallelism::Parallel => {
+                return items.par_chunks_mut(row_len).enumerate().for_each(rows);
+            }
+            Parallelism::Pool(pool) => {
+                return pool.install(|| items.par_chunks_mut(row_len).enumerate().for_each(rows));
+            }
+        }
+    }
+
+    for (y, row) in items.chunks_mut(row_len).enumerate() {
+        f(y, row);
+    }
+}
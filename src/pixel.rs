@@ -0,0 +1,676 @@
+//!  A module for typed pixels and images made of them.
+
+// from rust
+use std::fmt::Debug;
+use std::mem;
+
+// from external crate
+
+// from local crate
+use error::{RasterError, RasterResult};
+use image::ColorType;
+use Image;
+
+/// The type of a single channel of a pixel. Integer channels range from 0 to their maximum and
+/// float channels from 0.0 to 1.0.
+pub trait Subpixel: Copy + Default + PartialEq + PartialOrd + Debug + Send + Sync {
+    /// Value of a channel at full intensity: 255 for `u8`, 65535 for `u16` and 1.0 for `f32`.
+    const MAX: Self;
+
+    /// The value as a float, without scaling.
+    fn to_f64(self) -> f64;
+
+    /// A value from a float without scaling, clamped to 0 - `MAX`. Integer channels drop the
+    /// fraction.
+    fn from_f64(value: f64) -> Self;
+
+    /// The value scaled to 0.0 - 1.0.
+    fn to_unit(self) -> f32;
+
+    /// A value from 0.0 - 1.0, scaled to 0 - `MAX`. Integer channels are rounded.
+    fn from_unit(value: f32) -> Self;
+}
+
+impl Subpixel for u8 {
+    const MAX: u8 = 255;
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn from_f64(value: f64) -> u8 {
+        // Float to integer casts saturate
+        value as u8
+    }
+
+    fn to_unit(self) -> f32 {
+        self as f32 / 255.0
+    }
+
+    fn from_unit(value: f32) -> u8 {
+        (value * 255.0).round() as u8
+    }
+}
+
+impl Subpixel for u16 {
+    const MAX: u16 = 65535;
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn from_f64(value: f64) -> u16 {
+        value as u16
+    }
+
+    fn to_unit(self) -> f32 {
+        self as f32 / 65535.0
+    }
+
+    fn from_unit(value: f32) -> u16 {
+        (value * 65535.0).round() as u16
+    }
+}
+
+impl Subpixel for f32 {
+    const MAX: f32 = 1.0;
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    fn from_f64(value: f64) -> f32 {
+        f32::from_unit(value as f32)
+    }
+
+    fn to_unit(self) -> f32 {
+        self
+    }
+
+    fn from_unit(value: f32) -> f32 {
+        if value > 1.0 {
+            1.0
+        } else if value > 0.0 {
+            value
+        } else {
+            0.0 // Also NaN
+        }
+    }
+}
+
+/// A pixel made of one or more channels of the same type. Alpha, if any, is the last channel.
+pub trait Pixel: Copy + Default + PartialEq + Debug + Send + Sync {
+    /// Type of a channel.
+    type Subpixel: Subpixel;
+
+    /// Number of channels.
+    const CHANNELS: usize;
+
+    /// Whether the last channel is alpha.
+    const HAS_ALPHA: bool;
+
+    /// How the channels are laid out.
+    const COLOR_TYPE: ColorType;
+
+    /// The channels of the pixel.
+    fn channels(&self) -> &[Self::Subpixel];
+
+    /// The channels of the pixel, for changing them in place.
+    fn channels_mut(&mut self) -> &mut [Self::Subpixel];
+
+    /// Red, green, blue and alpha from 0.0 to 1.0. Pixels without alpha are opaque.
+    fn to_rgba(&self) -> [f32; 4];
+
+    /// Create a pixel from red, green, blue and alpha from 0.0 to 1.0. Gray pixels get the
+    /// luminance of the color, with the same weights as `filter::grayscale`, and pixels without
+    /// alpha drop it.
+    fn from_rgba(rgba: [f32; 4]) -> Self;
+}
+
+/// A gray pixel.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Gray<T>(pub [T; 1]);
+
+/// A gray pixel with alpha.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct GrayAlpha<T>(pub [T; 2]);
+
+/// A red, green and blue pixel.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rgb<T>(pub [T; 3]);
+
+/// A red, green, blue and alpha pixel.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Rgba<T>(pub [T; 4]);
+
+/// An 8 bit gray pixel.
+pub type Gray8 = Gray<u8>;
+/// A 16 bit gray pixel.
+pub type Gray16 = Gray<u16>;
+/// A floating point gray pixel.
+pub type Gray32F = Gray<f32>;
+/// An 8 bit gray pixel with alpha.
+pub type GrayAlpha8 = GrayAlpha<u8>;
+/// A 16 bit gray pixel with alpha.
+pub type GrayAlpha16 = GrayAlpha<u16>;
+/// A floating point gray pixel with alpha.
+pub type GrayAlpha32F = GrayAlpha<f32>;
+/// An 8 bit RGB pixel.
+pub type Rgb8 = Rgb<u8>;
+/// A 16 bit RGB pixel.
+pub type Rgb16 = Rgb<u16>;
+/// A floating point RGB pixel.
+pub type Rgb32F = Rgb<f32>;
+/// An 8 bit RGBA pixel, the layout of `Image`.
+pub type Rgba8 = Rgba<u8>;
+/// A 16 bit RGBA pixel.
+pub type Rgba16 = Rgba<u16>;
+/// A floating point RGBA pixel.
+pub type Rgba32F = Rgba<f32>;
+
+impl<T: Subpixel> Pixel for Gray<T> {
+    type Subpixel = T;
+    const CHANNELS: usize = 1;
+    const HAS_ALPHA: bool = false;
+    const COLOR_TYPE: ColorType = ColorType::Gray;
+
+    fn channels(&self) -> &[T] {
+        &self.0
+    }
+
+    fn channels_mut(&mut self) -> &mut [T] {
+        &mut self.0
+    }
+
+    fn to_rgba(&self) -> [f32; 4] {
+        let l = self.0[0].to_unit();
+        [l, l, l, 1.0]
+    }
+
+    fn from_rgba(rgba: [f32; 4]) -> Gray<T> {
+        Gray([T::from_unit(luminance(rgba))])
+    }
+}
+
+impl<T: Subpixel> Pixel for GrayAlpha<T> {
+    type Subpixel = T;
+    const CHANNELS: usize = 2;
+    const HAS_ALPHA: bool = true;
+    const COLOR_TYPE: ColorType = ColorType::GrayAlpha;
+
+    fn channels(&self) -> &[T] {
+        &self.0
+    }
+
+    fn channels_mut(&mut self) -> &mut [T] {
+        &mut self.0
+    }
+
+    fn to_rgba(&self) -> [f32; 4] {
+        let l = self.0[0].to_unit();
+        [l, l, l, self.0[1].to_unit()]
+    }
+
+    fn from_rgba(rgba: [f32; 4]) -> GrayAlpha<T> {
+        GrayAlpha([T::from_unit(luminance(rgba)), T::from_unit(rgba[3])])
+    }
+}
+
+impl<T: Subpixel> Pixel for Rgb<T> {
+    type Subpixel = T;
+    const CHANNELS: usize = 3;
+    const HAS_ALPHA: bool = false;
+    const COLOR_TYPE: ColorType = ColorType::Rgb;
+
+    fn channels(&self) -> &[T] {
+        &self.0
+    }
+
+    fn channels_mut(&mut self) -> &mut [T] {
+        &mut self.0
+    }
+
+    fn to_rgba(&self) -> [f32; 4] {
+        [
+            self.0[0].to_unit(),
+            self.0[1].to_unit(),
+            self.0[2].to_unit(),
+            1.0,
+        ]
+    }
+
+    fn from_rgba(rgba: [f32; 4]) -> Rgb<T> {
+        Rgb([
+            T::from_unit(rgba[0]),
+            T::from_unit(rgba[1]),
+            T::from_unit(rgba[2]),
+        ])
+    }
+}
+
+impl<T: Subpixel> Pixel for Rgba<T> {
+    type Subpixel = T;
+    const CHANNELS: usize = 4;
+    const HAS_ALPHA: bool = true;
+    const COLOR_TYPE: ColorType = ColorType::Rgba;
+
+    fn channels(&self) -> &[T] {
+        &self.0
+    }
+
+    fn channels_mut(&mut self) -> &mut [T] {
+        &mut self.0
+    }
+
+    fn to_rgba(&self) -> [f32; 4] {
+        [
+            self.0[0].to_unit(),
+            self.0[1].to_unit(),
+            self.0[2].to_unit(),
+            self.0[3].to_unit(),
+        ]
+    }
+
+    fn from_rgba(rgba: [f32; 4]) -> Rgba<T> {
+        Rgba([
+            T::from_unit(rgba[0]),
+            T::from_unit(rgba[1]),
+            T::from_unit(rgba[2]),
+            T::from_unit(rgba[3]),
+        ])
+    }
+}
+
+/// An image whose pixels can be read as a `Pixel` type, like an `Image`, an `ImageBuffer` or a
+/// view of part of one. The functions of the `compare` module accept any `GenericImageView`.
+///
+/// `Image` is a `GenericImageView` of `Rgba8` pixels. Its own `get_pixel` and `set_pixel`
+/// methods, which use `Color`, take precedence when called as methods:
+///
+/// ```
+/// use raster::{GenericImage, GenericImageView, Image, Rgba};
+///
+/// let mut image = Image::blank(2, 2);
+/// GenericImage::set_pixel(&mut image, 1, 0, Rgba([255, 0, 0, 255])).unwrap();
+///
+/// assert_eq!(255, image.get_pixel(1, 0).unwrap().r);
+/// assert!(GenericImageView::get_pixel(&image, 2, 0).is_err());
+/// ```
+pub trait GenericImageView {
+    /// Type of the pixels.
+    type Pixel: Pixel;
+
+    /// Width of image in pixels.
+    fn width(&self) -> i32;
+
+    /// Height of image in pixels.
+    fn height(&self) -> i32;
+
+    /// Get the pixel at a given x and y location.
+    ///
+    /// # Errors
+    ///
+    /// If either the x or y coordinate falls out of bounds, this will fail with
+    /// `RasterError::PixelOutOfBounds`.
+    fn get_pixel(&self, x: i32, y: i32) -> RasterResult<Self::Pixel>;
+
+    /// Call a function with the x and y coordinates and the value of every pixel, from left to
+    /// right and top to bottom. Images override this with a faster way than `get_pixel`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `RasterError::PixelOutOfBounds` if the image has fewer pixels than its width
+    /// and height call for.
+    fn for_each_pixel<F>(&self, mut f: F) -> RasterResult<()>
+    where
+        F: FnMut(i32, i32, Self::Pixel),
+    {
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                f(x, y, self.get_pixel(x, y)?);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// An image whose pixels can also be written, like an `Image`, an `ImageBuffer` or a mutable view
+/// of part of one. The functions of the `filter` module accept any `GenericImage`, so they can
+/// change a rectangle of an image in place.
+pub trait GenericImage: GenericImageView {
+    /// Set the pixel at a given x and y location.
+    ///
+    /// # Errors
+    ///
+    /// If either the x or y coordinate falls out of bounds, this will fail with
+    /// `RasterError::PixelOutOfBounds`.
+    fn set_pixel(&mut self, x: i32, y: i32, pixel: Self::Pixel) -> RasterResult<()>;
+
+    /// Replace every pixel with the result of a function of its x and y coordinates and value,
+    /// from left to right and top to bottom. Images override this with a faster way than
+    /// `get_pixel` and `set_pixel`.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `RasterError::PixelOutOfBounds` if the image has fewer pixels than its width
+    /// and height call for.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{GenericImage, ImageBuffer, Rgb16};
+    ///
+    /// let mut image: ImageBuffer<Rgb16> = ImageBuffer::new(256, 256).unwrap();
+    /// image
+    ///     .map_pixels(|x, y, _| raster::Rgb([x as u16 * 257, y as u16 * 257, 0]))
+    ///     .unwrap();
+    /// ```
+    fn map_pixels<F>(&mut self, mut f: F) -> RasterResult<()>
+    where
+        F: FnMut(i32, i32, Self::Pixel) -> Self::Pixel,
+    {
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let pixel = f(x, y, self.get_pixel(x, y)?);
+                self.set_pixel(x, y, pixel)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl GenericImageView for Image {
+    type Pixel = Rgba8;
+
+    fn width(&self) -> i32 {
+        self.width
+    }
+
+    fn height(&self) -> i32 {
+        self.height
+    }
+
+    fn get_pixel(&self, x: i32, y: i32) -> RasterResult<Rgba8> {
+        let start = image_index(self, x, y)?;
+        let p = &self.bytes[start..start + 4];
+        Ok(Rgba([p[0], p[1], p[2], p[3]]))
+    }
+
+    fn for_each_pixel<F>(&self, mut f: F) -> RasterResult<()>
+    where
+        F: FnMut(i32, i32, Rgba8),
+    {
+        check_count(self.width, self.height, self.bytes.len() / 4)?;
+        for (x, y, p) in self.enumerate_pixels() {
+            f(x, y, Rgba(*p));
+        }
+        Ok(())
+    }
+}
+
+impl GenericImage for Image {
+    fn set_pixel(&mut self, x: i32, y: i32, pixel: Rgba8) -> RasterResult<()> {
+        let start = image_index(self, x, y)?;
+        self.bytes[start..start + 4].copy_from_slice(&pixel.0);
+        Ok(())
+    }
+
+    fn map_pixels<F>(&mut self, mut f: F) -> RasterResult<()>
+    where
+        F: FnMut(i32, i32, Rgba8) -> Rgba8,
+    {
+        check_count(self.width, self.height, self.bytes.len() / 4)?;
+        for (x, y, p) in self.enumerate_pixels_mut() {
+            *p = f(x, y, Rgba(*p)).0;
+        }
+        Ok(())
+    }
+}
+
+/// A struct for an image of any `Pixel` type, like a 16 bit RGBA image or an 8 bit grayscale
+/// mask. `Image` remains the 8 bit RGBA type used to open and save files, and images convert to
+/// and from it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageBuffer<P: Pixel> {
+    /// Width of image in pixels.
+    pub width: i32,
+
+    /// Height of image in pixels.
+    pub height: i32,
+
+    /// Pixels from left to right and top to bottom.
+    pub pixels: Vec<P>,
+}
+
+impl<P: Pixel> ImageBuffer<P> {
+    /// Create a blank image. Default color is black.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `RasterError::LimitsExceeded` if the pixels would not fit in memory.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{GenericImageView, ImageBuffer, Rgba16};
+    ///
+    /// let image: ImageBuffer<Rgba16> = ImageBuffer::new(2, 2).unwrap();
+    ///
+    /// assert_eq!(4, image.pixels.len());
+    /// assert_eq!([0, 0, 0, 65535], image.get_pixel(1, 1).unwrap().0);
+    /// ```
+    pub fn new(width: i32, height: i32) -> RasterResult<ImageBuffer<P>> {
+        let count = pixel_count::<P>(width, height)?;
+        Ok(ImageBuffer {
+            width,
+            height,
+            pixels: vec![P::from_rgba([0.0, 0.0, 0.0, 1.0]); count],
+        })
+    }
+
+    /// Create an image from the pixels of an 8 bit RGBA image.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{Gray8, ImageBuffer};
+    ///
+    /// let image = raster::open("tests/in/sample.png").unwrap();
+    /// let mask: ImageBuffer<Gray8> = ImageBuffer::from_image(&image);
+    ///
+    /// assert_eq!(image.bytes.len() / 4, mask.pixels.len());
+    /// ```
+    pub fn from_image(image: &Image) -> ImageBuffer<P> {
+        let pixels = image
+            .bytes
+            .chunks(4)
+            .map(|p| {
+                P::from_rgba([
+                    p[0].to_unit(),
+                    p[1].to_unit(),
+                    p[2].to_unit(),
+                    p[3].to_unit(),
+                ])
+            })
+            .collect();
+        ImageBuffer {
+            width: image.width,
+            height: image.height,
+            pixels,
+        }
+    }
+
+    /// Convert to an 8 bit RGBA image, for saving or for the functions that only take an
+    /// `Image`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{ImageBuffer, Rgb16};
+    ///
+    /// let image: ImageBuffer<Rgb16> = ImageBuffer::new(3, 2).unwrap();
+    /// raster::save(&image.to_image(), "tests/out/test_image_buffer.png").unwrap();
+    /// ```
+    pub fn to_image(&self) -> Image {
+        let mut bytes = Vec::with_capacity(self.pixels.len() * 4);
+        for pixel in &self.pixels {
+            bytes.extend(pixel.to_rgba().iter().map(|&v| u8::from_unit(v)));
+        }
+        Image {
+            width: self.width,
+            height: self.height,
+            bytes,
+        }
+    }
+
+    /// Convert to an image of another pixel type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{GenericImage, GenericImageView, ImageBuffer, Rgba16, Rgba8};
+    ///
+    /// let mut image: ImageBuffer<Rgba8> = ImageBuffer::new(2, 2).unwrap();
+    /// image.set_pixel(0, 0, raster::Rgba([255, 128, 0, 255])).unwrap();
+    /// let deep = image.convert::<Rgba16>();
+    ///
+    /// assert_eq!([65535, 32896, 0, 65535], deep.get_pixel(0, 0).unwrap().0);
+    /// ```
+    pub fn convert<Q: Pixel>(&self) -> ImageBuffer<Q> {
+        ImageBuffer {
+            width: self.width,
+            height: self.height,
+            pixels: self
+                .pixels
+                .iter()
+                .map(|p| Q::from_rgba(p.to_rgba()))
+                .collect(),
+        }
+    }
+
+    // Index of a pixel, or an error if it is outside of the image
+    fn index(&self, x: i32, y: i32) -> RasterResult<usize> {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            return Err(RasterError::PixelOutOfBounds(x, y));
+        }
+        let index = y as usize * self.width as usize + x as usize;
+        if index >= self.pixels.len() {
+            Err(RasterError::PixelOutOfBounds(x, y))
+        } else {
+            Ok(index)
+        }
+    }
+}
+
+impl<P: Pixel> GenericImageView for ImageBuffer<P> {
+    type Pixel = P;
+
+    fn width(&self) -> i32 {
+        self.width
+    }
+
+    fn height(&self) -> i32 {
+        self.height
+    }
+
+    fn get_pixel(&self, x: i32, y: i32) -> RasterResult<P> {
+        let index = self.index(x, y)?;
+        Ok(self.pixels[index])
+    }
+
+    fn for_each_pixel<F>(&self, mut f: F) -> RasterResult<()>
+    where
+        F: FnMut(i32, i32, P),
+    {
+        check_count(self.width, self.height, self.pixels.len())?;
+        let width = self.width.max(1) as usize;
+        for (y, row) in (0..self.height).zip(self.pixels.chunks(width)) {
+            for (x, &p) in (0..self.width).zip(row) {
+                f(x, y, p);
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<P: Pixel> GenericImage for ImageBuffer<P> {
+    fn set_pixel(&mut self, x: i32, y: i32, pixel: P) -> RasterResult<()> {
+        let index = self.index(x, y)?;
+        self.pixels[index] = pixel;
+        Ok(())
+    }
+
+    fn map_pixels<F>(&mut self, mut f: F) -> RasterResult<()>
+    where
+        F: FnMut(i32, i32, P) -> P,
+    {
+        check_count(self.width, self.height, self.pixels.len())?;
+        let width = self.width.max(1) as usize;
+        for (y, row) in (0..self.height).zip(self.pixels.chunks_mut(width)) {
+            for (x, p) in (0..self.width).zip(row) {
+                *p = f(x, y, *p);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Private functions
+
+// Copy any image into an 8 bit RGBA image.
+pub fn copy_image<I: GenericImageView>(src: &I) -> RasterResult<Image> {
+    let (width, height) = (src.width(), src.height());
+    let mut bytes = Vec::with_capacity((width.max(0) * height.max(0)) as usize * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let rgba = src.get_pixel(x, y)?.to_rgba();
+            bytes.extend(rgba.iter().map(|&v| u8::from_unit(v)));
+        }
+    }
+    Ok(Image {
+        width,
+        height,
+        bytes,
+    })
+}
+
+// Check that an image has a pixel for every x and y, failing with the first one missing.
+fn check_count(width: i32, height: i32, count: usize) -> RasterResult<()> {
+    if width <= 0 || height <= 0 || count >= width as usize * height as usize {
+        Ok(())
+    } else {
+        let (x, y) = (count % width as usize, count / width as usize);
+        Err(RasterError::PixelOutOfBounds(x as i32, y as i32))
+    }
+}
+
+// Number of pixels of an image, or an error if their bytes would not fit in memory
+pub fn pixel_count<P: Pixel>(width: i32, height: i32) -> RasterResult<usize> {
+    let count = (width.max(0) as usize).checked_mul(height.max(0) as usize);
+    let bytes = count.and_then(|count| count.checked_mul(mem::size_of::<P>()));
+    match (count, bytes) {
+        (Some(count), Some(bytes)) if bytes <= isize::MAX as usize => Ok(count),
+        _ => Err(RasterError::LimitsExceeded(format!(
+            "{}x{} pixels is too many to allocate",
+            width, height
+        ))),
+    }
+}
+
+// Start of a pixel in the bytes of an image, or an error if it is outside of the image
+fn image_index(image: &Image, x: i32, y: i32) -> RasterResult<usize> {
+    if x < 0 || y < 0 || x >= image.width || y >= image.height {
+        return Err(RasterError::PixelOutOfBounds(x, y));
+    }
+    let start = (y as usize * image.width as usize + x as usize) * 4;
+    if start + 4 > image.bytes.len() {
+        Err(RasterError::PixelOutOfBounds(x, y))
+    } else {
+        Ok(start)
+    }
+}
+
+// Luminance of a color, weighted like filter::grayscale.
+fn luminance(rgba: [f32; 4]) -> f32 {
+    rgba[0] * 0.3 + rgba[1] * 0.59 + rgba[2] * 0.11
+}
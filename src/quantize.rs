@@ -0,0 +1,566 @@
+//!  A module for reducing the colors of an image to a palette.
+
+// from rust
+use std::collections::HashMap;
+
+// from external crate
+
+// from local crate
+use error::{RasterError, RasterResult};
+use Color;
+use Image;
+
+/// An enum for the algorithms that can be used to build a palette.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QuantizeMethod {
+    /// Repeatedly split the box of colors with the widest range at its median.
+    MedianCut,
+    /// Merge the least used branches of a color tree.
+    Octree,
+    /// Start from the median cut palette and move each color to the center of the pixels closest
+    /// to it. Slower, but with less error.
+    KMeans,
+}
+
+/// An enum for the ways colors missing from the palette can be approximated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Dithering {
+    /// Use the closest palette color.
+    None,
+    /// Floyd-Steinberg error diffusion.
+    FloydSteinberg,
+    /// Atkinson error diffusion. Only spreads part of the error, keeping more contrast.
+    Atkinson,
+    /// Ordered dithering with an 8x8 Bayer matrix.
+    Bayer,
+}
+
+/// A struct for an image whose pixels are indices into a palette.
+#[derive(Debug, Clone)]
+pub struct IndexedImage {
+    /// Width of image in pixels.
+    pub width: i32,
+
+    /// Height of image in pixels.
+    pub height: i32,
+
+    /// Palette index of every pixel, from left to right and top to bottom.
+    pub indices: Vec<u8>,
+
+    /// Colors of the palette, at most 256. Fully transparent pixels share a single transparent
+    /// entry.
+    pub palette: Vec<Color>,
+}
+
+impl IndexedImage {
+    /// Expand the palette back into an RGBA image.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `RasterError::InvalidPaletteSize` if an index is outside of the palette.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{quantize, Dithering, QuantizeMethod};
+    ///
+    /// let image = raster::open("tests/in/sample.png").unwrap();
+    /// let indexed =
+    ///     quantize::quantize(&image, 16, QuantizeMethod::MedianCut, Dithering::None).unwrap();
+    ///
+    /// let image = indexed.to_image().unwrap();
+    /// raster::save(&image, "tests/out/test_quantize_to_image.png").unwrap();
+    /// ```
+    pub fn to_image(&self) -> RasterResult<Image> {
+        let mut bytes = Vec::with_capacity(self.indices.len() * 4);
+        for &index in &self.indices {
+            let color = self
+                .palette
+                .get(index as usize)
+                .ok_or(RasterError::InvalidPaletteSize(self.palette.len()))?;
+            bytes.extend_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+        Ok(Image {
+            width: self.width,
+            height: self.height,
+            bytes,
+        })
+    }
+}
+
+/// Reduce an image to a palette of at most `colors` colors, from 2 to 256.
+///
+/// # Errors
+///
+/// Fails with `RasterError::InvalidPaletteSize` if `colors` is out of range.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{quantize, Dithering, QuantizeMethod};
+///
+/// let image = raster::open("tests/in/sample.jpg").unwrap();
+/// let indexed =
+///     quantize::quantize(&image, 8, QuantizeMethod::Octree, Dithering::FloydSteinberg).unwrap();
+///
+/// assert!(indexed.palette.len() <= 8);
+/// raster::save(&indexed.to_image().unwrap(), "tests/out/test_quantize_octree.png").unwrap();
+/// ```
+pub fn quantize(
+    image: &Image,
+    colors: usize,
+    method: QuantizeMethod,
+    dithering: Dithering,
+) -> RasterResult<IndexedImage> {
+    let palette = palette(image, colors, method)?;
+    remap(image, &palette, dithering)
+}
+
+/// Build a palette of at most `colors` colors, from 2 to 256, for an image. If any pixel is fully
+/// transparent one of the colors is transparent black.
+///
+/// # Errors
+///
+/// Fails with `RasterError::InvalidPaletteSize` if `colors` is out of range.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{quantize, QuantizeMethod};
+///
+/// let image = raster::open("tests/in/sample.gif").unwrap();
+/// let palette = quantize::palette(&image, 32, QuantizeMethod::KMeans).unwrap();
+///
+/// assert!(palette.len() <= 32);
+/// ```
+pub fn palette(image: &Image, colors: usize, method: QuantizeMethod) -> RasterResult<Vec<Color>> {
+    if !(2..=256).contains(&colors) {
+        return Err(RasterError::InvalidPaletteSize(colors));
+    }
+
+    // Count the distinct colors, leaving out transparent pixels
+    let mut counts = HashMap::new();
+    let mut transparent = false;
+    for pixel in image.bytes.chunks(4) {
+        if pixel[3] == 0 {
+            transparent = true;
+        } else {
+            *counts
+                .entry([pixel[0], pixel[1], pixel[2], pixel[3]])
+                .or_insert(0u32) += 1;
+        }
+    }
+    let mut histogram: Vec<([u8; 4], u32)> = counts.into_iter().collect();
+    histogram.sort();
+    let colors = if transparent { colors - 1 } else { colors };
+
+    let mut palette = if histogram.len() <= colors {
+        histogram.iter().map(|&(color, _)| color).collect()
+    } else {
+        match method {
+            QuantizeMethod::MedianCut => median_cut(histogram, colors),
+            QuantizeMethod::Octree => octree(&histogram, colors),
+            QuantizeMethod::KMeans => {
+                let palette = median_cut(histogram.clone(), colors);
+                k_means(&histogram, palette)
+            }
+        }
+    };
+    palette.sort();
+    if transparent {
+        palette.push([0, 0, 0, 0]);
+    }
+
+    Ok(palette
+        .into_iter()
+        .map(|c| Color::rgba(c[0], c[1], c[2], c[3]))
+        .collect())
+}
+
+/// Map every pixel of an image to the closest color of a palette of up to 256 colors. Colors after
+/// the first 256 are not used.
+///
+/// # Errors
+///
+/// Fails with `RasterError::InvalidPaletteSize` if the palette is empty.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{quantize, Color, Dithering};
+///
+/// let image = raster::open("tests/in/sample.jpg").unwrap();
+/// let palette = vec![Color::black(), Color::white()];
+/// let indexed = quantize::remap(&image, &palette, Dithering::Atkinson).unwrap();
+///
+/// raster::save(&indexed.to_image().unwrap(), "tests/out/test_quantize_atkinson.png").unwrap();
+/// ```
+pub fn remap(image: &Image, palette: &[Color], dithering: Dithering) -> RasterResult<IndexedImage> {
+    if palette.is_empty() {
+        return Err(RasterError::InvalidPaletteSize(0));
+    }
+    let palette: Vec<[u8; 4]> = palette
+        .iter()
+        .take(256)
+        .map(|c| [c.r, c.g, c.b, c.a])
+        .collect();
+    let width = image.width as usize;
+    let len = image.bytes.len() / 4;
+    let transparent = palette.iter().position(|c| c[3] == 0);
+
+    let indices = match dithering {
+        Dithering::None => {
+            let mut cache = HashMap::new();
+            image
+                .bytes
+                .chunks(4)
+                .map(|pixel| {
+                    let color = [pixel[0], pixel[1], pixel[2], pixel[3]];
+                    *cache
+                        .entry(color)
+                        .or_insert_with(|| closest(&palette, transparent, &color))
+                })
+                .collect()
+        }
+        Dithering::Bayer => {
+            // Spread the threshold over the average distance between palette colors
+            let spread = 255.0 / (palette.len() as f32).cbrt();
+            image
+                .bytes
+                .chunks(4)
+                .enumerate()
+                .map(|(i, pixel)| {
+                    let threshold = BAYER[i / width % 8][i % width % 8] as f32 / 64.0 - 0.5;
+                    let mut color = [0; 4];
+                    for c in 0..3 {
+                        color[c] = clamp(pixel[c] as f32 + threshold * spread);
+                    }
+                    color[3] = pixel[3];
+                    closest(&palette, transparent, &color)
+                })
+                .collect()
+        }
+        Dithering::FloydSteinberg | Dithering::Atkinson => {
+            let (kernel, divisor) = if dithering == Dithering::Atkinson {
+                (&ATKINSON[..], 8.0)
+            } else {
+                (&FLOYD_STEINBERG[..], 16.0)
+            };
+            let mut errors = vec![[0.0f32; 4]; len];
+            let mut indices = Vec::with_capacity(len);
+            for (i, pixel) in image.bytes.chunks(4).enumerate() {
+                let mut color = [0; 4];
+                for c in 0..4 {
+                    color[c] = clamp(pixel[c] as f32 + errors[i][c]);
+                }
+                let index = closest(&palette, transparent, &color);
+                indices.push(index);
+
+                // Transparent pixels don't spread any error
+                if pixel[3] == 0 {
+                    continue;
+                }
+                let chosen = palette[index as usize];
+                let (x, y) = ((i % width) as isize, (i / width) as isize);
+                for &(dx, dy, weight) in kernel {
+                    let (nx, ny) = (x + dx, y + dy);
+                    if nx < 0 || nx >= width as isize || (ny as usize) * width >= len {
+                        continue;
+                    }
+                    let n = ny as usize * width + nx as usize;
+                    for c in 0..4 {
+                        let error = color[c] as f32 - chosen[c] as f32;
+                        errors[n][c] += error * weight / divisor;
+                    }
+                }
+            }
+            indices
+        }
+    };
+
+    Ok(IndexedImage {
+        width: image.width,
+        height: image.height,
+        indices,
+        palette: palette
+            .iter()
+            .map(|c| Color::rgba(c[0], c[1], c[2], c[3]))
+            .collect(),
+    })
+}
+
+// Private functions
+
+// Offsets and weights of the neighbors that receive the error of a pixel
+const FLOYD_STEINBERG: [(isize, isize, f32); 4] =
+    [(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)];
+
+const ATKINSON: [(isize, isize, f32); 6] = [
+    (1, 0, 1.0),
+    (2, 0, 1.0),
+    (-1, 1, 1.0),
+    (0, 1, 1.0),
+    (1, 1, 1.0),
+    (0, 2, 1.0),
+];
+
+// Thresholds of ordered dithering, from 0 to 63
+const BAYER: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+// Median cut over a histogram of colors and their pixel counts.
+fn median_cut(histogram: Vec<([u8; 4], u32)>, colors: usize) -> Vec<[u8; 4]> {
+    let mut boxes = vec![histogram];
+    while boxes.len() < colors {
+        // Split the box with the widest channel range
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|&(_, b)| b.len() > 1)
+            .map(|(i, b)| {
+                let (channel, range) = widest_channel(b);
+                (range, i, channel)
+            })
+            .max();
+        let (i, channel) = match widest {
+            Some((_, i, channel)) => (i, channel),
+            None => break,
+        };
+
+        let mut entries = boxes.swap_remove(i);
+        entries.sort_by_key(|&(color, _)| color[channel]);
+
+        // Split at the weighted median, keeping at least one color on each side
+        let total: u64 = entries.iter().map(|&(_, count)| count as u64).sum();
+        let mut seen = 0;
+        let mut split = entries.len() - 1;
+        for (j, &(_, count)) in entries.iter().enumerate() {
+            seen += count as u64;
+            if seen * 2 >= total {
+                split = j + 1;
+                break;
+            }
+        }
+        let split = split.clamp(1, entries.len() - 1);
+        let rest = entries.split_off(split);
+        boxes.push(entries);
+        boxes.push(rest);
+    }
+
+    boxes.iter().map(|b| average(b)).collect()
+}
+
+// The channel with the widest range of values in a box, and that range.
+fn widest_channel(entries: &[([u8; 4], u32)]) -> (usize, u8) {
+    let mut min = [255u8; 4];
+    let mut max = [0u8; 4];
+    for &(color, _) in entries {
+        for c in 0..4 {
+            min[c] = min[c].min(color[c]);
+            max[c] = max[c].max(color[c]);
+        }
+    }
+    (0..4)
+        .map(|c| (c, max[c] - min[c]))
+        .max_by_key(|&(_, range)| range)
+        .unwrap()
+}
+
+// Average of colors weighted by their pixel counts.
+fn average(entries: &[([u8; 4], u32)]) -> [u8; 4] {
+    let mut sums = [0u64; 4];
+    let mut total = 0u64;
+    for &(color, count) in entries {
+        add_color(&mut sums, &color, count as u64);
+        total += count as u64;
+    }
+    mean_color(&sums, total)
+}
+
+// Add a color with a pixel count to per channel sums.
+fn add_color(sums: &mut [u64; 4], color: &[u8; 4], count: u64) {
+    for (sum, &value) in sums.iter_mut().zip(color.iter()) {
+        *sum += value as u64 * count;
+    }
+}
+
+// The rounded mean color of per channel sums over count pixels.
+fn mean_color(sums: &[u64; 4], count: u64) -> [u8; 4] {
+    let mut color = [0; 4];
+    for (value, &sum) in color.iter_mut().zip(sums.iter()) {
+        *value = ((sum + count / 2) / count) as u8;
+    }
+    color
+}
+
+// A node of the octree. Every level uses one bit of each of the 4 channels to pick a child.
+struct Node {
+    children: [Option<usize>; 16],
+    sums: [u64; 4],
+    count: u64,
+    leaf: bool,
+}
+
+impl Node {
+    fn new() -> Node {
+        Node {
+            children: [None; 16],
+            sums: [0; 4],
+            count: 0,
+            leaf: false,
+        }
+    }
+}
+
+// Octree quantization over a histogram of colors and their pixel counts.
+fn octree(histogram: &[([u8; 4], u32)], colors: usize) -> Vec<[u8; 4]> {
+    const DEPTH: usize = 8;
+    let mut nodes = vec![Node::new()];
+    // Inner nodes below the root at each depth, candidates for merging. Nodes at the last depth
+    // are leaves.
+    let mut levels: Vec<Vec<usize>> = vec![Vec::new(); DEPTH - 1];
+    let mut leaves = 0;
+
+    for &(color, count) in histogram {
+        let mut node = 0;
+        for depth in 0..DEPTH {
+            let shift = 7 - depth;
+            let child = (0..4).fold(0, |acc, c| acc << 1 | (color[c] as usize >> shift & 1));
+            node = match nodes[node].children[child] {
+                Some(next) => next,
+                None => {
+                    nodes.push(Node::new());
+                    let next = nodes.len() - 1;
+                    nodes[node].children[child] = Some(next);
+                    match levels.get_mut(depth) {
+                        Some(level) => level.push(next),
+                        None => {
+                            nodes[next].leaf = true;
+                            leaves += 1;
+                        }
+                    }
+                    next
+                }
+            };
+        }
+        add_color(&mut nodes[node].sums, &color, count as u64);
+        nodes[node].count += count as u64;
+    }
+
+    // Merge the children of the least used deepest nodes until the palette is small enough
+    for level in levels.iter().rev() {
+        let mut candidates = level.clone();
+        candidates.sort_by_key(|&i| subtree_count(&nodes, i));
+        for node in candidates {
+            if leaves <= colors {
+                break;
+            }
+            let children: Vec<usize> = nodes[node].children.iter().filter_map(|&c| c).collect();
+            for &child in &children {
+                let (sums, count) = (nodes[child].sums, nodes[child].count);
+                for (sum, child_sum) in nodes[node].sums.iter_mut().zip(sums.iter()) {
+                    *sum += child_sum;
+                }
+                nodes[node].count += count;
+                nodes[child].leaf = false;
+            }
+            nodes[node].children = [None; 16];
+            nodes[node].leaf = true;
+            leaves = leaves + 1 - children.len();
+        }
+    }
+
+    let leaves: Vec<([u8; 4], u32)> = nodes
+        .iter()
+        .filter(|node| node.leaf)
+        .map(|node| (mean_color(&node.sums, node.count), node.count as u32))
+        .collect();
+
+    // The root can have more children than there are colors, combine those with median cut
+    if leaves.len() > colors {
+        median_cut(leaves, colors)
+    } else {
+        leaves.into_iter().map(|(color, _)| color).collect()
+    }
+}
+
+// Number of pixels below a node.
+fn subtree_count(nodes: &[Node], node: usize) -> u64 {
+    nodes[node].count
+        + nodes[node]
+            .children
+            .iter()
+            .filter_map(|&c| c)
+            .map(|c| subtree_count(nodes, c))
+            .sum::<u64>()
+}
+
+// Refine a palette with k-means over a histogram of colors and their pixel counts.
+fn k_means(histogram: &[([u8; 4], u32)], mut palette: Vec<[u8; 4]>) -> Vec<[u8; 4]> {
+    const ITERATIONS: usize = 8;
+    for _ in 0..ITERATIONS {
+        let mut sums = vec![([0u64; 4], 0u64); palette.len()];
+        for &(color, count) in histogram {
+            let index = closest(&palette, None, &color) as usize;
+            add_color(&mut sums[index].0, &color, count as u64);
+            sums[index].1 += count as u64;
+        }
+
+        let mut changed = false;
+        for (entry, &(sum, total)) in palette.iter_mut().zip(sums.iter()) {
+            if total == 0 {
+                continue;
+            }
+            let color = mean_color(&sum, total);
+            if color != *entry {
+                *entry = color;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    // Colors that moved to the same center are left once
+    palette.sort();
+    palette.dedup();
+    palette
+}
+
+// Index of the palette color closest to a color. Transparent pixels use the transparent entry if
+// there is one.
+fn closest(palette: &[[u8; 4]], transparent: Option<usize>, color: &[u8; 4]) -> u8 {
+    if color[3] == 0 {
+        if let Some(index) = transparent {
+            return index as u8;
+        }
+    }
+    let mut best = 0;
+    let mut best_distance = u32::MAX;
+    for (i, entry) in palette.iter().enumerate() {
+        let distance = (0..4)
+            .map(|c| {
+                let d = entry[c] as i32 - color[c] as i32;
+                (d * d) as u32
+            })
+            .sum();
+        if distance < best_distance {
+            best = i;
+            best_distance = distance;
+        }
+    }
+    best as u8
+}
+
+// Round and clamp a channel value to 0 - 255.
+fn clamp(value: f32) -> u8 {
+    value.round().clamp(0.0, 255.0) as u8
+}
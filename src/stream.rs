@@ -0,0 +1,308 @@
+//!  A module for decoding and encoding images a row at a time.
+
+// from rust
+use std::io::Write;
+
+// from external crate
+
+// from local crate
+use encode_with_options;
+use error::{RasterError, RasterResult};
+use options::SaveOptions;
+use Image;
+use ImageFormat;
+
+/// A source of RGBA rows, decoded from top to bottom. Created with `raster::open_rows` or
+/// `raster::decode_rows`.
+pub trait RowReader {
+    /// Width of the image in pixels.
+    fn width(&self) -> i32;
+
+    /// Height of the image in pixels.
+    fn height(&self) -> i32;
+
+    /// Decode the next row into `row`, which must be `width * 4` bytes long. Returns `false`
+    /// without changing `row` once every row has been read.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `RasterError::Decode` if the row has the wrong length or the image data is
+    /// corrupt, and with `RasterError::Io` if the reader fails.
+    fn read_row(&mut self, row: &mut [u8]) -> RasterResult<bool>;
+}
+
+/// A destination for RGBA rows, encoded from top to bottom. Created with `raster::create_rows` or
+/// `raster::encode_rows`.
+pub trait RowWriter {
+    /// Width of the image in pixels.
+    fn width(&self) -> i32;
+
+    /// Height of the image in pixels.
+    fn height(&self) -> i32;
+
+    /// Encode the next row of `width * 4` bytes.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `RasterError::Encode` if the row has the wrong length or every row has already
+    /// been written, and with `RasterError::Io` if the writer fails.
+    fn write_row(&mut self, row: &[u8]) -> RasterResult<()>;
+
+    /// Complete the file after the last row. The file is not valid until this is called.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `RasterError::Encode` if rows are missing, and with `RasterError::Io` if the
+    /// writer fails.
+    fn finish(&mut self) -> RasterResult<()>;
+}
+
+impl<T: RowReader + ?Sized> RowReader for Box<T> {
+    fn width(&self) -> i32 {
+        (**self).width()
+    }
+
+    fn height(&self) -> i32 {
+        (**self).height()
+    }
+
+    fn read_row(&mut self, row: &mut [u8]) -> RasterResult<bool> {
+        (**self).read_row(row)
+    }
+}
+
+impl<T: RowWriter + ?Sized> RowWriter for Box<T> {
+    fn width(&self) -> i32 {
+        (**self).width()
+    }
+
+    fn height(&self) -> i32 {
+        (**self).height()
+    }
+
+    fn write_row(&mut self, row: &[u8]) -> RasterResult<()> {
+        (**self).write_row(row)
+    }
+
+    fn finish(&mut self) -> RasterResult<()> {
+        (**self).finish()
+    }
+}
+
+/// Copy an image from a row reader to a row writer in bands of `band_height` rows, applying an
+/// operation to each band. Only a single band is held in memory, so point operations like
+/// `filter::gamma` or `filter::grayscale` can be applied to images too large to open. The
+/// operation must keep the size of the band. The writer is finished after the last band.
+///
+/// Operations that look at neighboring pixels, like blurs, see the edge of the band as the edge
+/// of the image.
+///
+/// # Errors
+///
+/// Fails with any error of the reader, the writer or the operation.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{filter, OpenOptions, SaveOptions};
+///
+/// let mut reader = raster::open_rows("tests/in/sample.png", &OpenOptions::default()).unwrap();
+/// let (width, height) = (reader.width(), reader.height());
+/// let mut writer =
+///     raster::create_rows("tests/out/test_bands.png", width, height, &SaveOptions::default())
+///         .unwrap();
+///
+/// raster::process_bands(&mut reader, &mut writer, 64, |band| {
+///     filter::grayscale(band)?;
+///     filter::gamma(band, 1.5)
+/// })
+/// .unwrap();
+/// ```
+pub fn process_bands<F>(
+    reader: &mut dyn RowReader,
+    writer: &mut dyn RowWriter,
+    band_height: i32,
+    mut f: F,
+) -> RasterResult<()>
+where
+    F: FnMut(&mut Image) -> RasterResult<()>,
+{
+    let width = reader.width();
+    let row_len = width.max(0) as usize * 4;
+    let band_height = band_height.max(1);
+    let mut band = Image {
+        width,
+        height: 0,
+        bytes: Vec::with_capacity(row_len * band_height as usize),
+    };
+    let mut row = vec![0; row_len];
+    let mut done = false;
+    while !done {
+        band.width = width;
+        band.height = 0;
+        band.bytes.clear();
+        while band.height < band_height {
+            if !reader.read_row(&mut row)? {
+                done = true;
+                break;
+            }
+            band.bytes.extend_from_slice(&row);
+            band.height += 1;
+        }
+        if band.height == 0 {
+            break;
+        }
+
+        f(&mut band)?;
+        // A band that changed size fails on the row length or the row count
+        let len = band.width.max(1) as usize * 4;
+        for row in band.bytes.chunks(len) {
+            writer.write_row(row)?;
+        }
+    }
+    writer.finish()
+}
+
+// Rows of an image that is already decoded, for formats that can't be read a row at a time.
+pub struct ImageRows {
+    image: Image,
+    y: i32,
+    format: ImageFormat,
+}
+
+impl ImageRows {
+    pub fn new(image: Image, format: ImageFormat) -> ImageRows {
+        ImageRows {
+            image,
+            y: 0,
+            format,
+        }
+    }
+}
+
+impl RowReader for ImageRows {
+    fn width(&self) -> i32 {
+        self.image.width
+    }
+
+    fn height(&self) -> i32 {
+        self.image.height
+    }
+
+    fn read_row(&mut self, row: &mut [u8]) -> RasterResult<bool> {
+        if self.y >= self.image.height {
+            return Ok(false);
+        }
+        check_read(self.format, self.image.width, row)?;
+        let len = row.len();
+        let start = self.y as usize * len;
+        row.copy_from_slice(&self.image.bytes[start..start + len]);
+        self.y += 1;
+        Ok(true)
+    }
+}
+
+// Collects rows into an image that is encoded when finished, for formats that can't be written a
+// row at a time.
+pub struct ImageRowWriter<W: Write> {
+    image: Image,
+    y: i32,
+    writer: Option<W>,
+    format: ImageFormat,
+    options: SaveOptions,
+}
+
+impl<W: Write> ImageRowWriter<W> {
+    pub fn new(
+        writer: W,
+        format: ImageFormat,
+        width: i32,
+        height: i32,
+        options: &SaveOptions,
+    ) -> ImageRowWriter<W> {
+        ImageRowWriter {
+            image: Image::blank(width, height),
+            y: 0,
+            writer: Some(writer),
+            format,
+            options: options.clone(),
+        }
+    }
+}
+
+impl<W: Write> RowWriter for ImageRowWriter<W> {
+    fn width(&self) -> i32 {
+        self.image.width
+    }
+
+    fn height(&self) -> i32 {
+        self.image.height
+    }
+
+    fn write_row(&mut self, row: &[u8]) -> RasterResult<()> {
+        check_write(
+            self.format,
+            self.image.width,
+            self.image.height,
+            self.y,
+            row,
+        )?;
+        let start = self.y as usize * row.len();
+        self.image.bytes[start..start + row.len()].copy_from_slice(row);
+        self.y += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> RasterResult<()> {
+        check_finished(self.format, self.image.height, self.y)?;
+        match self.writer.take() {
+            Some(writer) => encode_with_options(&self.image, writer, self.format, &self.options),
+            None => Ok(()),
+        }
+    }
+}
+
+// Check that a row to read into fits the width of an image.
+pub fn check_read(format: ImageFormat, width: i32, row: &[u8]) -> RasterResult<()> {
+    if row.len() != width as usize * 4 {
+        return Err(RasterError::Decode(
+            format,
+            "Row length does not match width".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// Check that a row to write fits the width of an image and that it is not past the last row.
+pub fn check_write(
+    format: ImageFormat,
+    width: i32,
+    height: i32,
+    y: i32,
+    row: &[u8],
+) -> RasterResult<()> {
+    if row.len() != width as usize * 4 {
+        return Err(RasterError::Encode(
+            format,
+            "Row length does not match width".to_string(),
+        ));
+    }
+    if y >= height {
+        return Err(RasterError::Encode(
+            format,
+            "All rows already written".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// Check that every row of an image was written.
+pub fn check_finished(format: ImageFormat, height: i32, y: i32) -> RasterResult<()> {
+    if y < height {
+        return Err(RasterError::Encode(
+            format,
+            format!("Only {} of {} rows written", y, height),
+        ));
+    }
+    Ok(())
+}
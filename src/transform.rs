@@ -12,6 +12,7 @@ use Color;
 use interpolate::{resample, InterpolationMode};
 use position::PositionMode;
 use editor::crop;
+use pixel::GenericImage;
 
 /// An enum for the various modes that can be used for transforming.
 #[derive(Debug)]
@@ -54,9 +55,23 @@ pub enum TransformMode {
 ///
 /// ![](https://kosinix.github.io/raster/out/test_transform_flip_y.png)
 ///
-pub fn flip(src: &mut Image, mode: TransformMode) -> RasterResult<()> {
-    let w: i32 = src.width;
-    let h: i32 = src.height;
+/// ### Flip part of an image:
+///
+/// ```
+/// use raster::{transform, TransformMode};
+///
+/// //...
+///
+/// let mut image = raster::open("tests/in/sample.png").unwrap();
+/// let (w, h) = (image.width, image.height);
+/// transform::flip(&mut image.view_mut(w / 2, 0, w / 2, h).unwrap(), TransformMode::Vertical)
+///     .unwrap();
+/// raster::save(&image, "tests/out/test_transform_flip_half.png").unwrap();
+/// ```
+///
+pub fn flip<I: GenericImage>(src: &mut I, mode: TransformMode) -> RasterResult<()> {
+    let w: i32 = src.width();
+    let h: i32 = src.height();
 
     match mode {
         TransformMode::Horizontal => {
@@ -70,8 +85,8 @@ pub fn flip(src: &mut Image, mode: TransformMode) -> RasterResult<()> {
                     let pixel_left = src.get_pixel(src_x, y)?;
                     let pixel_right = src.get_pixel(dest_x, y)?;
 
-                    src.set_pixel(dest_x, y, &pixel_left)?;
-                    src.set_pixel(src_x, y, &pixel_right)?;
+                    src.set_pixel(dest_x, y, pixel_left)?;
+                    src.set_pixel(src_x, y, pixel_right)?;
                 }
             }
 
@@ -88,8 +103,8 @@ pub fn flip(src: &mut Image, mode: TransformMode) -> RasterResult<()> {
                     let pixel_top = src.get_pixel(x, src_y)?;
                     let pixel_bottom = src.get_pixel(x, dest_y)?;
 
-                    src.set_pixel(x, dest_y, &pixel_top)?;
-                    src.set_pixel(x, src_y, &pixel_bottom)?;
+                    src.set_pixel(x, dest_y, pixel_top)?;
+                    src.set_pixel(x, src_y, pixel_bottom)?;
                 }
             }
 
@@ -193,6 +208,93 @@ pub fn rotate(src: &mut Image, degree: i32, bg: Color) -> RasterResult<()> {
     Ok(())
 }
 
+/// Rotate an image 90 degrees clockwise. Unlike `rotate`, no background is added and no pixels
+/// are lost, so the width and height are swapped exactly.
+///
+/// # Examples
+///
+/// ```
+/// use raster::transform;
+///
+/// //...
+///
+/// let mut image = raster::open("tests/in/sample.png").unwrap();
+/// let (w, h) = (image.width, image.height);
+/// transform::rotate_90(&mut image).unwrap();
+/// raster::save(&image, "tests/out/test_transform_rotate_90.png").unwrap();
+///
+/// assert_eq!(h, image.width);
+/// assert_eq!(w, image.height);
+/// ```
+pub fn rotate_90(src: &mut Image) -> RasterResult<()> {
+    let w = src.width;
+    let h = src.height;
+    let mut dest = Image::blank(h, w);
+
+    for y in 0..h {
+        for x in 0..w {
+            let pixel = src.get_pixel(x, y)?;
+            dest.set_pixel(h - y - 1, x, &pixel)?;
+        }
+    }
+
+    src.width = dest.width;
+    src.height = dest.height;
+    src.bytes = dest.bytes;
+
+    Ok(())
+}
+
+/// Rotate an image 180 degrees without losing any pixels. Like `flip`, this keeps the size of the
+/// image, so it also works on a view of part of an image.
+pub fn rotate_180<I: GenericImage>(src: &mut I) -> RasterResult<()> {
+    flip(src, TransformMode::Horizontal)?;
+    flip(src, TransformMode::Vertical)
+}
+
+/// Rotate an image 270 degrees clockwise, or 90 degrees counter-clockwise, without losing any
+/// pixels.
+pub fn rotate_270(src: &mut Image) -> RasterResult<()> {
+    rotate_90(src)?;
+    rotate_180(src)
+}
+
+/// Transform an image stored with the given EXIF orientation so it is upright. Orientations are
+/// numbered from 1 to 8 as described in `Exif::orientation`. An orientation of 1 or any unknown
+/// value leaves the image unchanged.
+///
+/// This is done when opening a JPEG unless `OpenOptions::auto_orient` is turned off.
+///
+/// # Examples
+///
+/// ```
+/// use raster::{transform, OpenOptions};
+///
+/// //...
+///
+/// let options = OpenOptions {
+///     auto_orient: false,
+///     ..OpenOptions::default()
+/// };
+/// let (mut image, metadata) =
+///     raster::open_with_metadata("tests/in/portrait.jpg", &options).unwrap();
+/// if let Some(orientation) = metadata.orientation() {
+///     transform::orient(&mut image, orientation).unwrap();
+/// }
+/// ```
+pub fn orient(src: &mut Image, orientation: u16) -> RasterResult<()> {
+    match orientation {
+        2 => flip(src, TransformMode::Horizontal),
+        3 => rotate_180(src),
+        4 => flip(src, TransformMode::Vertical),
+        5 => rotate_90(src).and_then(|_| flip(src, TransformMode::Horizontal)),
+        6 => rotate_90(src),
+        7 => rotate_90(src).and_then(|_| flip(src, TransformMode::Vertical)),
+        8 => rotate_270(src),
+        _ => Ok(()),
+    }
+}
+
 /// Resize image to exact dimensions ignoring aspect ratio.
 /// Useful if you want to force exact width and height.
 pub fn resize_exact(src: &mut Image, w: i32, h: i32) -> RasterResult<()> {
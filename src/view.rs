@@ -0,0 +1,249 @@
+//!  A module for borrowed views of a rectangle of an image.
+
+// from rust
+use std::ops::Range;
+
+// from external crate
+
+// from local crate
+use error::{RasterError, RasterResult};
+use pixel::{GenericImage, GenericImageView, Rgba, Rgba8};
+use Image;
+
+/// A rectangle of an image, borrowed for reading. Coordinates are relative to the top left corner
+/// of the rectangle. Created with `Image::view`.
+#[derive(Debug, Clone, Copy)]
+pub struct ImageView<'a> {
+    bytes: &'a [u8],
+    rect: Rect,
+}
+
+/// A rectangle of an image, borrowed for reading and writing. Filters applied to the view change
+/// only that part of the image. Created with `Image::view_mut`.
+#[derive(Debug)]
+pub struct ImageViewMut<'a> {
+    bytes: &'a mut [u8],
+    rect: Rect,
+}
+
+impl<'a> ImageView<'a> {
+    /// Copy the pixels of the view into a new image.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::Image;
+    ///
+    /// let image = raster::open("tests/in/sample.png").unwrap();
+    /// let corner = image.view(0, 0, 20, 10).unwrap().to_image();
+    ///
+    /// assert_eq!((20, 10), (corner.width, corner.height));
+    /// assert_eq!(image.bytes[..20 * 4], corner.bytes[..20 * 4]);
+    /// ```
+    pub fn to_image(&self) -> Image {
+        self.rect.copy(self.bytes)
+    }
+}
+
+impl<'a> ImageViewMut<'a> {
+    /// Copy the pixels of the view into a new image.
+    pub fn to_image(&self) -> Image {
+        self.rect.copy(self.bytes)
+    }
+
+    /// Copy the pixels of an image of the same size into the view.
+    ///
+    /// # Errors
+    ///
+    /// Fails with `RasterError::PixelOutOfBounds` if the image is smaller than the view.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use raster::{filter, Image};
+    ///
+    /// let mut image = raster::open("tests/in/sample.png").unwrap();
+    /// let mut gray = image.view(0, 0, 100, 100).unwrap().to_image();
+    /// filter::grayscale(&mut gray).unwrap();
+    /// image.view_mut(50, 50, 100, 100).unwrap().copy_from(&gray).unwrap();
+    /// ```
+    pub fn copy_from<I: GenericImageView<Pixel = Rgba8>>(&mut self, src: &I) -> RasterResult<()> {
+        for y in 0..self.rect.height {
+            for x in 0..self.rect.width {
+                let pixel = src.get_pixel(x, y)?;
+                self.set_pixel(x, y, pixel)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> GenericImageView for ImageView<'a> {
+    type Pixel = Rgba8;
+
+    fn width(&self) -> i32 {
+        self.rect.width
+    }
+
+    fn height(&self) -> i32 {
+        self.rect.height
+    }
+
+    fn get_pixel(&self, x: i32, y: i32) -> RasterResult<Rgba8> {
+        let start = self.rect.index(x, y)?;
+        let p = &self.bytes[start..start + 4];
+        Ok(Rgba([p[0], p[1], p[2], p[3]]))
+    }
+
+    fn for_each_pixel<F>(&self, f: F) -> RasterResult<()>
+    where
+        F: FnMut(i32, i32, Rgba8),
+    {
+        self.rect.for_each_pixel(self.bytes, f);
+        Ok(())
+    }
+}
+
+impl<'a> GenericImageView for ImageViewMut<'a> {
+    type Pixel = Rgba8;
+
+    fn width(&self) -> i32 {
+        self.rect.width
+    }
+
+    fn height(&self) -> i32 {
+        self.rect.height
+    }
+
+    fn get_pixel(&self, x: i32, y: i32) -> RasterResult<Rgba8> {
+        let start = self.rect.index(x, y)?;
+        let p = &self.bytes[start..start + 4];
+        Ok(Rgba([p[0], p[1], p[2], p[3]]))
+    }
+
+    fn for_each_pixel<F>(&self, f: F) -> RasterResult<()>
+    where
+        F: FnMut(i32, i32, Rgba8),
+    {
+        self.rect.for_each_pixel(self.bytes, f);
+        Ok(())
+    }
+}
+
+impl<'a> GenericImage for ImageViewMut<'a> {
+    fn set_pixel(&mut self, x: i32, y: i32, pixel: Rgba8) -> RasterResult<()> {
+        let start = self.rect.index(x, y)?;
+        self.bytes[start..start + 4].copy_from_slice(&pixel.0);
+        Ok(())
+    }
+
+    fn map_pixels<F>(&mut self, mut f: F) -> RasterResult<()>
+    where
+        F: FnMut(i32, i32, Rgba8) -> Rgba8,
+    {
+        for y in 0..self.rect.height {
+            let row = self.rect.row(y);
+            for (x, p) in (0..).zip(self.bytes[row].chunks_exact_mut(4)) {
+                let pixel = f(x, y, Rgba([p[0], p[1], p[2], p[3]]));
+                p.copy_from_slice(&pixel.0);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Private functions
+
+// View a rectangle of an image, for Image::view.
+pub fn view(image: &Image, x: i32, y: i32, w: i32, h: i32) -> RasterResult<ImageView<'_>> {
+    Ok(ImageView {
+        rect: Rect::new(image, x, y, w, h)?,
+        bytes: &image.bytes,
+    })
+}
+
+// View a rectangle of an image mutably, for Image::view_mut.
+pub fn view_mut(
+    image: &mut Image,
+    x: i32,
+    y: i32,
+    w: i32,
+    h: i32,
+) -> RasterResult<ImageViewMut<'_>> {
+    Ok(ImageViewMut {
+        rect: Rect::new(image, x, y, w, h)?,
+        bytes: &mut image.bytes,
+    })
+}
+
+// Where a view lies in the bytes of its image.
+#[derive(Debug, Clone, Copy)]
+struct Rect {
+    start: usize,  // Index of the top left pixel
+    stride: usize, // Bytes from one row to the next
+    width: i32,
+    height: i32,
+}
+
+impl Rect {
+    // Check that a rectangle lies within an image.
+    fn new(image: &Image, x: i32, y: i32, w: i32, h: i32) -> RasterResult<Rect> {
+        if x < 0 || y < 0 || x > image.width || y > image.height {
+            return Err(RasterError::PixelOutOfBounds(x, y));
+        }
+        let (right, bottom) = (x as i64 + w as i64, y as i64 + h as i64);
+        if w < 0 || h < 0 || right > image.width as i64 || bottom > image.height as i64 {
+            return Err(RasterError::PixelOutOfBounds(
+                (right - 1) as i32,
+                (bottom - 1) as i32,
+            ));
+        }
+        let stride = image.width as usize * 4;
+        if (y + h) as usize * stride > image.bytes.len() {
+            return Err(RasterError::PixelOutOfBounds(x, y + h - 1));
+        }
+        Ok(Rect {
+            start: y as usize * stride + x as usize * 4,
+            stride,
+            width: w,
+            height: h,
+        })
+    }
+
+    // Index of a pixel of the view, or an error if it is outside of the view
+    fn index(&self, x: i32, y: i32) -> RasterResult<usize> {
+        if x < 0 || y < 0 || x >= self.width || y >= self.height {
+            Err(RasterError::PixelOutOfBounds(x, y))
+        } else {
+            Ok(self.start + y as usize * self.stride + x as usize * 4)
+        }
+    }
+
+    // Bytes of a row of the view
+    fn row(&self, y: i32) -> Range<usize> {
+        let start = self.start + y as usize * self.stride;
+        start..start + self.width as usize * 4
+    }
+
+    // Call a function with every pixel of the view
+    fn for_each_pixel<F: FnMut(i32, i32, Rgba8)>(&self, bytes: &[u8], mut f: F) {
+        for y in 0..self.height {
+            for (x, p) in (0..).zip(bytes[self.row(y)].chunks_exact(4)) {
+                f(x, y, Rgba([p[0], p[1], p[2], p[3]]));
+            }
+        }
+    }
+
+    // Copy the pixels of the view
+    fn copy(&self, bytes: &[u8]) -> Image {
+        let mut copy = Vec::with_capacity(self.width as usize * self.height as usize * 4);
+        for y in 0..self.height {
+            copy.extend_from_slice(&bytes[self.row(y)]);
+        }
+        Image {
+            width: self.width,
+            height: self.height,
+            bytes: copy,
+        }
+    }
+}
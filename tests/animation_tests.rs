@@ -0,0 +1,128 @@
+extern crate raster;
+
+use raster::error::RasterError;
+use raster::{editor, Animation, DisposalMethod, Frame, Image, LoopCount, ResizeMode};
+use raster::{GifOptions, ImageFormat, SaveOptions};
+use std::collections::HashSet;
+
+#[test]
+fn open_animation_test() {
+    let animation = raster::open_animation("tests/in/animated.gif").unwrap();
+
+    assert_eq!(11, animation.width);
+    assert_eq!(29, animation.height);
+    assert_eq!(LoopCount::Infinite, animation.loop_count);
+
+    let delays: Vec<u32> = animation.frames.iter().map(|frame| frame.delay).collect();
+    assert_eq!(vec![1000, 500, 1000], delays);
+    for frame in &animation.frames {
+        assert_eq!(11, frame.image.width);
+        assert_eq!(29, frame.image.height);
+        assert_eq!(DisposalMethod::None, frame.disposal);
+    }
+}
+
+#[test]
+fn open_animation_frames_test() {
+    let animation = raster::open_animation("tests/in/1x1anim.gif").unwrap();
+    assert_eq!(3, animation.frames.len());
+
+    // Frames hold different colors
+    let first = animation.frames[0].image.get_pixel(0, 0).unwrap();
+    let second = animation.frames[1].image.get_pixel(0, 0).unwrap();
+    assert!(first.r != second.r || first.g != second.g || first.b != second.b);
+
+    // Opening as a still image gives the first frame
+    let image = raster::open("tests/in/1x1anim.gif").unwrap();
+    assert_eq!(animation.frames[0].image.bytes, image.bytes);
+}
+
+#[test]
+fn open_animation_still_test() {
+    let animation = raster::open_animation("tests/in/sample.png").unwrap();
+    assert_eq!(1, animation.frames.len());
+}
+
+#[test]
+fn save_animation_test() {
+    let mut red = Image::blank(4, 4);
+    editor::fill(&mut red, raster::Color::red()).unwrap();
+    let mut blue = Image::blank(4, 4);
+    editor::fill(&mut blue, raster::Color::blue()).unwrap();
+
+    let mut animation = Animation::new(vec![Frame::new(red, 200), Frame::new(blue, 300)]);
+    animation.loop_count = LoopCount::Finite(3);
+    animation.frames[1].disposal = DisposalMethod::Background;
+    raster::save_animation(&animation, "tests/out/test_save_animation.gif").unwrap();
+
+    let animation = raster::open_animation("tests/out/test_save_animation.gif").unwrap();
+    assert_eq!(2, animation.frames.len());
+    assert_eq!(LoopCount::Finite(3), animation.loop_count);
+    assert_eq!(200, animation.frames[0].delay);
+    assert_eq!(300, animation.frames[1].delay);
+    assert_eq!(DisposalMethod::Background, animation.frames[1].disposal);
+
+    let pixel = animation.frames[1].image.get_pixel(2, 2).unwrap();
+    assert_eq!((0, 0, 255), (pixel.r, pixel.g, pixel.b));
+}
+
+#[test]
+fn save_animation_with_options_test() {
+    let image = raster::open("tests/in/sample.png").unwrap();
+    let mut animation = Animation::new(vec![Frame::new(image, 1_000_000_000)]);
+    let options = SaveOptions {
+        gif: GifOptions {
+            palette_size: 4,
+            ..GifOptions::default()
+        },
+        ..SaveOptions::default()
+    };
+    let out = "tests/out/test_save_animation_with_options.gif";
+    raster::save_animation_with_options(&animation, out, &options).unwrap();
+
+    // The palette size is used, and the delay is clamped to the longest a GIF can store
+    let saved = raster::open_animation(out).unwrap();
+    let colors: HashSet<&[u8]> = saved.frames[0].image.bytes.chunks(4).collect();
+    assert!(colors.len() <= 4);
+    assert_eq!(655_350, saved.frames[0].delay);
+
+    animation.frames[0].delay = 100;
+    let options = SaveOptions {
+        gif: GifOptions {
+            palette_size: 300,
+            ..GifOptions::default()
+        },
+        ..SaveOptions::default()
+    };
+    let mut buffer = Vec::new();
+    match raster::encode_animation_with_options(&animation, &mut buffer, ImageFormat::Gif, &options)
+    {
+        Err(RasterError::InvalidPaletteSize(300)) => {}
+        _ => panic!("Expected InvalidPaletteSize error"),
+    }
+}
+
+#[test]
+fn save_animation_unsupported_test() {
+    let animation = raster::open_animation("tests/in/animated.gif").unwrap();
+    match raster::save_animation(&animation, "tests/out/test_save_animation.jpg") {
+        Err(RasterError::Encode(ImageFormat::Jpeg, _)) => {}
+        _ => panic!("Expected Encode error"),
+    }
+}
+
+#[test]
+fn map_frames_test() {
+    let mut animation = raster::open_animation("tests/in/animated.gif").unwrap();
+    animation
+        .map_frames(|image| editor::resize(image, 5, 5, ResizeMode::Exact))
+        .unwrap();
+
+    assert_eq!(5, animation.width);
+    assert_eq!(5, animation.height);
+    assert_eq!(3, animation.frames.len());
+    for frame in &animation.frames {
+        assert_eq!(5, frame.image.width);
+        assert_eq!(5, frame.image.height);
+    }
+}
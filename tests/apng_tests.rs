@@ -0,0 +1,222 @@
+extern crate raster;
+
+mod common;
+
+use raster::error::RasterError;
+use raster::{editor, Animation, ColorType, DisposalMethod, Frame, Image, ImageFormat};
+use raster::{LoopCount, PositionMode};
+
+use common::pixels;
+
+const RED: [u8; 4] = [255, 0, 0, 255];
+const GREEN: [u8; 4] = [0, 255, 0, 255];
+const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+// The pixels of an image filled with one color, unfiltered and stored in a zlib stream without
+// compression
+fn zlib(width: usize, height: usize, color: [u8; 4]) -> Vec<u8> {
+    // Filter type 0 and the pixels of a row
+    let mut row = vec![0];
+    for _ in 0..width {
+        row.extend_from_slice(&color);
+    }
+    common::zlib(&row.repeat(height))
+}
+
+// An fcTL chunk: sequence number, size, offset, delay of 1/20 s, dispose and blend operations
+fn fctl(sequence: u32, size: (u32, u32), offset: (u32, u32), dispose: u8, blend: u8) -> Vec<u8> {
+    let mut data = Vec::new();
+    for value in &[sequence, size.0, size.1, offset.0, offset.1] {
+        data.extend_from_slice(&value.to_be_bytes());
+    }
+    data.extend_from_slice(&[0, 1, 0, 20, dispose, blend]);
+    data
+}
+
+fn fdat(sequence: u32, data: Vec<u8>) -> Vec<u8> {
+    let mut chunk = sequence.to_be_bytes().to_vec();
+    chunk.extend_from_slice(&data);
+    chunk
+}
+
+fn actl(frames: u32, plays: u32) -> Vec<u8> {
+    let mut data = frames.to_be_bytes().to_vec();
+    data.extend_from_slice(&plays.to_be_bytes());
+    data
+}
+
+fn filled(width: i32, height: i32, color: [u8; 4]) -> Image {
+    let mut image = Image::blank(width, height);
+    for pixel in image.bytes.chunks_mut(4) {
+        pixel.copy_from_slice(&color);
+    }
+    image
+}
+
+#[test]
+fn apng_animation_test() {
+    let mut sample = raster::open("tests/in/sample.png").unwrap();
+    editor::crop(&mut sample, 40, 30, PositionMode::TopLeft, 0, 0).unwrap();
+    let mut changed = sample.clone();
+    for y in 10..12 {
+        for x in 20..25 {
+            let index = (y * 40 + x) * 4;
+            changed.bytes[index..index + 4].copy_from_slice(&GREEN);
+        }
+    }
+    let mut frames = vec![
+        Frame::new(sample.clone(), 100),
+        Frame::new(changed.clone(), 250),
+        Frame::new(filled(40, 30, [0, 0, 255, 128]), 40),
+        Frame::new(sample, 70000),
+        Frame::new(changed, 10),
+    ];
+    frames[2].disposal = DisposalMethod::Background;
+    frames[3].disposal = DisposalMethod::Previous;
+    let mut animation = Animation::new(frames);
+    animation.loop_count = LoopCount::Finite(3);
+    raster::save_animation(&animation, "tests/out/test_apng_animation.png").unwrap();
+
+    let decoded = raster::open_animation("tests/out/test_apng_animation.png").unwrap();
+    assert_eq!((40, 30), (decoded.width, decoded.height));
+    assert_eq!(LoopCount::Finite(3), decoded.loop_count);
+    assert_eq!(animation.frames.len(), decoded.frames.len());
+    for (frame, saved) in animation.frames.iter().zip(&decoded.frames) {
+        assert_eq!(frame.image.bytes, saved.image.bytes);
+        assert_eq!(frame.delay, saved.delay);
+        assert_eq!(frame.disposal, saved.disposal);
+    }
+
+    // A single image is the first frame
+    let image = raster::open("tests/out/test_apng_animation.png").unwrap();
+    assert_eq!(animation.frames[0].image.bytes, image.bytes);
+    let images = raster::open_all("tests/out/test_apng_animation.png").unwrap();
+    assert_eq!(5, images.len());
+
+    let info = raster::probe("tests/out/test_apng_animation.png").unwrap();
+    assert_eq!((40, 30), (info.width, info.height));
+    assert_eq!(ColorType::Rgba, info.color_type);
+    assert_eq!(5, info.frames);
+
+    // A plain PNG is a single frame
+    let animation = raster::open_animation("tests/in/sample.png").unwrap();
+    assert_eq!(1, animation.frames.len());
+}
+
+#[test]
+fn apng_compose_test() {
+    // A 4x2 canvas: a red background, a half transparent blue square blended at (2, 0) and
+    // disposed, then a clear pixel replacing the top left pixel and disposed to the previous
+    // canvas
+    let buffer = common::png(
+        4,
+        2,
+        6,
+        &[
+            (b"acTL", actl(4, 0)),
+            (b"fcTL", fctl(0, (4, 2), (0, 0), 0, 0)),
+            (b"IDAT", zlib(4, 2, RED)),
+            (b"fcTL", fctl(1, (2, 2), (2, 0), 1, 1)),
+            (b"fdAT", fdat(2, zlib(2, 2, [0, 0, 255, 128]))),
+            (b"fcTL", fctl(3, (1, 1), (0, 0), 2, 0)),
+            (b"fdAT", fdat(4, zlib(1, 1, CLEAR))),
+            (b"fcTL", fctl(5, (1, 1), (1, 1), 0, 1)),
+            (b"fdAT", fdat(6, zlib(1, 1, GREEN))),
+        ],
+    );
+
+    let animation = raster::decode_animation(&buffer[..], ImageFormat::Png).unwrap();
+    assert_eq!(LoopCount::Infinite, animation.loop_count);
+    assert_eq!(4, animation.frames.len());
+    assert_eq!(50, animation.frames[0].delay);
+    let mixed = [127, 0, 128, 255];
+    assert_eq!(
+        vec![RED, RED, mixed, mixed, RED, RED, mixed, mixed],
+        pixels(&animation.frames[1].image)
+    );
+    assert_eq!(
+        vec![CLEAR, RED, CLEAR, CLEAR, RED, RED, CLEAR, CLEAR],
+        pixels(&animation.frames[2].image)
+    );
+    assert_eq!(
+        vec![RED, RED, CLEAR, CLEAR, RED, GREEN, CLEAR, CLEAR],
+        pixels(&animation.frames[3].image)
+    );
+    assert_eq!(DisposalMethod::Previous, animation.frames[2].disposal);
+    let info = raster::probe_reader(&buffer[..]).unwrap();
+    assert_eq!((4, 2, 4), (info.width, info.height, info.frames));
+
+    // A default image before the first frame control is not part of the animation
+    let buffer = common::png(
+        2,
+        1,
+        6,
+        &[
+            (b"acTL", actl(1, 2)),
+            (b"IDAT", zlib(2, 1, RED)),
+            (b"fcTL", fctl(0, (1, 1), (1, 0), 0, 0)),
+            (b"fdAT", fdat(1, zlib(1, 1, GREEN))),
+        ],
+    );
+    let image = raster::decode(&buffer[..], ImageFormat::Png).unwrap();
+    assert_eq!(vec![RED, RED], pixels(&image));
+    let animation = raster::decode_animation(&buffer[..], ImageFormat::Png).unwrap();
+    assert_eq!(LoopCount::Finite(2), animation.loop_count);
+    assert_eq!(1, animation.frames.len());
+    assert_eq!(vec![CLEAR, GREEN], pixels(&animation.frames[0].image));
+}
+
+#[test]
+fn apng_fail_test() {
+    let idat = || (b"IDAT", zlib(4, 2, RED));
+    let cases = vec![
+        // Sequence numbers out of order
+        vec![
+            (b"acTL", actl(2, 0)),
+            (b"fcTL", fctl(0, (4, 2), (0, 0), 0, 0)),
+            idat(),
+            (b"fcTL", fctl(2, (1, 1), (0, 0), 0, 0)),
+            (b"fdAT", fdat(1, zlib(1, 1, RED))),
+        ],
+        // A frame outside of the canvas
+        vec![
+            (b"acTL", actl(1, 0)),
+            idat(),
+            (b"fcTL", fctl(0, (2, 2), (3, 0), 0, 0)),
+            (b"fdAT", fdat(1, zlib(2, 2, RED))),
+        ],
+        // Frame data without a frame control
+        vec![
+            (b"acTL", actl(1, 0)),
+            idat(),
+            (b"fdAT", fdat(0, zlib(4, 2, RED))),
+        ],
+        // No frames at all
+        vec![(b"acTL", actl(1, 0)), idat()],
+        // The default image as a frame with another size
+        vec![
+            (b"acTL", actl(1, 0)),
+            (b"fcTL", fctl(0, (2, 2), (0, 0), 0, 0)),
+            idat(),
+        ],
+    ];
+    for chunks in &cases {
+        let buffer = common::png(4, 2, 6, chunks);
+        match raster::decode_animation(&buffer[..], ImageFormat::Png) {
+            Err(RasterError::Decode(ImageFormat::Png, _)) => {}
+            _ => panic!("Expected Decode error"),
+        }
+        // The default image can still be shown
+        assert!(raster::decode(&buffer[..], ImageFormat::Png).is_ok());
+    }
+
+    let frames = vec![
+        Frame::new(Image::blank(2, 2), 10),
+        Frame::new(Image::blank(3, 2), 10),
+    ];
+    let mut buffer = Vec::new();
+    match raster::encode_animation(&Animation::new(frames), &mut buffer, ImageFormat::Png) {
+        Err(RasterError::Encode(ImageFormat::Png, _)) => {}
+        _ => panic!("Expected Encode error"),
+    }
+}
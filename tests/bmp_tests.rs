@@ -0,0 +1,184 @@
+extern crate raster;
+
+mod common;
+
+use std::fs;
+
+use raster::error::RasterError;
+use raster::{ColorType, Image, ImageFormat};
+
+use common::pixels;
+
+// A DIB header of the given length. Fields after the first 40 bytes are zero.
+fn dib_header(len: usize, width: i32, height: i32, bpp: u16, compression: u32) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend_from_slice(&(len as u32).to_le_bytes());
+    header.extend_from_slice(&width.to_le_bytes());
+    header.extend_from_slice(&height.to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&bpp.to_le_bytes());
+    header.extend_from_slice(&compression.to_le_bytes());
+    header.resize(len, 0);
+    header
+}
+
+// A BMP file from a DIB header, the color table or masks after it, and the pixel data
+fn bmp(header: &[u8], tables: &[u8], data: &[u8]) -> Vec<u8> {
+    let offset = 14 + header.len() + tables.len();
+    let mut buffer = b"BM".to_vec();
+    buffer.extend_from_slice(&((offset + data.len()) as u32).to_le_bytes());
+    buffer.extend_from_slice(&[0; 4]);
+    buffer.extend_from_slice(&(offset as u32).to_le_bytes());
+    buffer.extend_from_slice(header);
+    buffer.extend_from_slice(tables);
+    buffer.extend_from_slice(data);
+    buffer
+}
+
+// A color table of BGRX entries
+fn palette(colors: &[[u8; 3]]) -> Vec<u8> {
+    colors
+        .iter()
+        .flat_map(|c| vec![c[2], c[1], c[0], 0])
+        .collect()
+}
+
+fn decode(buffer: &[u8]) -> Image {
+    raster::decode(buffer, ImageFormat::Bmp).unwrap()
+}
+
+const BLACK: [u8; 4] = [0, 0, 0, 255];
+const WHITE: [u8; 4] = [255, 255, 255, 255];
+const RED: [u8; 4] = [255, 0, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+const CLEAR: [u8; 4] = [0, 0, 0, 0];
+
+#[test]
+fn bmp_roundtrip_test() {
+    let image = raster::open("tests/in/sample.png").unwrap();
+    for image in &[image, Image::blank(3, 5)] {
+        let mut buffer = Vec::new();
+        raster::encode(image, &mut buffer, ImageFormat::Bmp).unwrap();
+        assert_eq!(Some(ImageFormat::Bmp), raster::guess_format(&buffer));
+
+        let decoded = decode(&buffer);
+        assert_eq!(image.width, decoded.width);
+        assert_eq!(image.height, decoded.height);
+        assert_eq!(image.bytes, decoded.bytes);
+    }
+}
+
+#[test]
+fn bmp_bit_depth_test() {
+    // Opaque images are written as 24 bit, transparent ones as 32 bit
+    let mut image = raster::open("tests/in/sample.jpg").unwrap();
+    raster::save(&image, "tests/out/test_bmp_opaque.bmp").unwrap();
+    let info = raster::probe("tests/out/test_bmp_opaque.bmp").unwrap();
+    assert_eq!(ColorType::Rgb, info.color_type);
+
+    image.bytes[3] = 0;
+    raster::save(&image, "tests/out/test_bmp_alpha.bmp").unwrap();
+    let info = raster::probe("tests/out/test_bmp_alpha.bmp").unwrap();
+    assert_eq!(ColorType::Rgba, info.color_type);
+    assert_eq!(image.width, info.width);
+    assert_eq!(image.height, info.height);
+
+    let saved = raster::open("tests/out/test_bmp_alpha.bmp").unwrap();
+    assert_eq!(image.bytes, saved.bytes);
+}
+
+#[test]
+fn bmp_palette_test() {
+    let colors = palette(&[[0, 0, 0], [255, 255, 255], [255, 0, 0], [0, 0, 255]]);
+
+    // 1 bit, stored bottom-up
+    let header = dib_header(40, 2, 2, 1, 0);
+    let data = [0x80, 0, 0, 0, 0x40, 0, 0, 0];
+    let image = decode(&bmp(&header, &colors[..8], &data));
+    assert_eq!(vec![BLACK, WHITE, WHITE, BLACK], pixels(&image));
+
+    // 4 bit, stored top-down
+    let header = dib_header(40, 3, -2, 4, 0);
+    let data = [0x12, 0x30, 0, 0, 0x32, 0x10, 0, 0];
+    let image = decode(&bmp(&header, &colors, &data));
+    assert_eq!(vec![WHITE, RED, BLUE, BLUE, RED, WHITE], pixels(&image));
+
+    // 8 bit, with the last row missing its padding
+    let header = dib_header(40, 3, 2, 8, 0);
+    let data = [3, 3, 3, 0, 0, 1, 2];
+    let image = decode(&bmp(&header, &colors, &data));
+    assert_eq!(vec![BLACK, WHITE, RED, BLUE, BLUE, BLUE], pixels(&image));
+}
+
+#[test]
+fn bmp_rle_test() {
+    let colors = palette(&[[0, 0, 0], [255, 255, 255], [255, 0, 0], [0, 0, 255]]);
+
+    // RLE8: a run of 4 on the bottom row, then 3 literal indices and a run of 1
+    let header = dib_header(40, 4, 2, 8, 1);
+    let data = [4, 1, 0, 0, 0, 3, 2, 0, 1, 0, 1, 2, 0, 0, 0, 1];
+    let image = decode(&bmp(&header, &colors, &data));
+    assert_eq!(
+        vec![RED, BLACK, WHITE, RED, WHITE, WHITE, WHITE, WHITE],
+        pixels(&image)
+    );
+
+    // RLE4: a run alternating two indices, then a delta that skips two pixels of the top row
+    let header = dib_header(40, 5, 2, 4, 2);
+    let data = [5, 0x23, 0, 0, 0, 2, 2, 0, 0, 3, 0x31, 0x20, 0, 1];
+    let image = decode(&bmp(&header, &colors, &data));
+    assert_eq!(
+        vec![
+            CLEAR, CLEAR, BLUE, WHITE, RED, // top row
+            RED, BLUE, RED, BLUE, RED, // bottom row
+        ],
+        pixels(&image)
+    );
+}
+
+#[test]
+fn bmp_bitfields_test() {
+    // 16 bit 5-5-5 without masks
+    let image = raster::open("tests/in/sample.bmp").unwrap();
+    assert_eq!(vec![[0, 255, 0, 255]], pixels(&image));
+
+    // 16 bit 5-6-5 with masks after an info header
+    let header = dib_header(40, 2, 1, 16, 3);
+    let mut masks = Vec::new();
+    for mask in &[0xF800u32, 0x07E0, 0x001F] {
+        masks.extend_from_slice(&mask.to_le_bytes());
+    }
+    let data = [0x1F, 0x00, 0xE0, 0x07];
+    let image = decode(&bmp(&header, &masks, &data));
+    assert_eq!(vec![BLUE, [0, 255, 0, 255]], pixels(&image));
+
+    // 32 bit ABGR with masks in a V5 header, stored top-down
+    let mut header = dib_header(124, 1, -2, 32, 3);
+    for (i, mask) in [0xFFu32, 0xFF00, 0xFF_0000, 0xFF00_0000].iter().enumerate() {
+        header[40 + i * 4..44 + i * 4].copy_from_slice(&mask.to_le_bytes());
+    }
+    let data = [10, 20, 30, 40, 255, 0, 0, 128];
+    let image = decode(&bmp(&header, &[], &data));
+    assert_eq!(vec![[10, 20, 30, 40], [255, 0, 0, 128]], pixels(&image));
+
+    let info = raster::probe_reader(&bmp(&header, &[], &data)[..]).unwrap();
+    assert_eq!(ImageFormat::Bmp, info.format);
+    assert_eq!(ColorType::Rgba, info.color_type);
+    assert_eq!((1, 2), (info.width, info.height));
+}
+
+#[test]
+fn bmp_fail_test() {
+    let header = dib_header(40, 4, 4, 24, 0);
+    let truncated = bmp(&header, &[], &[0; 20]);
+    match raster::decode(&truncated[..], ImageFormat::Bmp) {
+        Err(RasterError::Decode(ImageFormat::Bmp, _)) => {}
+        _ => panic!("Expected Decode error"),
+    }
+
+    let buffer = fs::read("tests/in/sample.png").unwrap();
+    match raster::decode(&buffer[..], ImageFormat::Bmp) {
+        Err(RasterError::Decode(ImageFormat::Bmp, _)) => {}
+        _ => panic!("Expected Decode error"),
+    }
+}
@@ -0,0 +1,60 @@
+// Helpers shared by the integration tests. Each test crate uses only some of them.
+#![allow(dead_code)]
+
+use raster::Image;
+
+// CRC-32 of the bytes, as stored after each PNG chunk
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFF_u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                0xEDB8_8320 ^ (crc >> 1)
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+// Data stored in a zlib stream without compression
+pub fn zlib(data: &[u8]) -> Vec<u8> {
+    let len = data.len() as u16;
+    let mut out = vec![0x78, 0x01, 0x01];
+    out.extend_from_slice(&len.to_le_bytes());
+    out.extend_from_slice(&(!len).to_le_bytes());
+    out.extend_from_slice(data);
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    out.extend_from_slice(&(b << 16 | a).to_be_bytes());
+    out
+}
+
+// A PNG with an 8 bit header of the given size and color type, followed by the given chunks
+pub fn png(width: u32, height: u32, color_type: u8, chunks: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let mut ihdr = width.to_be_bytes().to_vec();
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    ihdr.extend_from_slice(&[8, color_type, 0, 0, 0]);
+
+    let mut buffer = vec![0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+    let header: &[(&[u8; 4], Vec<u8>)] = &[(b"IHDR", ihdr)];
+    let end: &[(&[u8; 4], Vec<u8>)] = &[(b"IEND", Vec::new())];
+    for (name, data) in header.iter().chain(chunks).chain(end) {
+        buffer.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let mut body = name.to_vec();
+        body.extend_from_slice(data);
+        buffer.extend_from_slice(&body);
+        buffer.extend_from_slice(&crc32(&body).to_be_bytes());
+    }
+    buffer
+}
+
+// The RGBA pixels of an image, for comparing with a list of colors
+pub fn pixels(image: &Image) -> Vec<[u8; 4]> {
+    image.pixels().copied().collect()
+}
@@ -0,0 +1,107 @@
+extern crate raster;
+
+use std::io::Cursor;
+
+use raster::error::RasterError;
+use raster::{ColorType, Image, ImageFormat, OpenOptions};
+
+fn raw_options(width: u32, height: u32) -> OpenOptions {
+    OpenOptions {
+        raw_dimensions: Some((width, height)),
+        ..OpenOptions::default()
+    }
+}
+
+#[test]
+fn farbfeld_roundtrip_test() {
+    let mut image = raster::open("tests/in/sample.png").unwrap();
+    image.bytes[3] = 0;
+    let mut buffer = Vec::new();
+    raster::encode(&image, &mut buffer, ImageFormat::Farbfeld).unwrap();
+    assert_eq!(Some(ImageFormat::Farbfeld), raster::guess_format(&buffer));
+    assert_eq!(16 + image.bytes.len() * 2, buffer.len());
+
+    let decoded = raster::decode(&buffer[..], ImageFormat::Farbfeld).unwrap();
+    assert_eq!(image.width, decoded.width);
+    assert_eq!(image.height, decoded.height);
+    assert_eq!(image.bytes, decoded.bytes);
+
+    raster::save(&image, "tests/out/test_farbfeld.ff").unwrap();
+    let info = raster::probe("tests/out/test_farbfeld.ff").unwrap();
+    assert_eq!(ImageFormat::Farbfeld, info.format);
+    assert_eq!(ColorType::Rgba, info.color_type);
+    assert_eq!(16, info.bit_depth);
+    assert_eq!((image.width, image.height), (info.width, info.height));
+}
+
+#[test]
+fn farbfeld_samples_test() {
+    // 16 bit samples are rounded to the nearest 8 bit value
+    let mut buffer = b"farbfeld".to_vec();
+    buffer.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0, 1]);
+    buffer.extend_from_slice(&[0xFF, 0xFF, 0x80, 0x7F, 0x00, 0x80, 0x01, 0x01]);
+    let image = raster::decode(&buffer[..], ImageFormat::Farbfeld).unwrap();
+    assert_eq!(vec![255, 128, 0, 1], image.bytes);
+
+    let truncated = &buffer[..20];
+    match raster::decode(truncated, ImageFormat::Farbfeld) {
+        Err(RasterError::Decode(ImageFormat::Farbfeld, _)) => {}
+        _ => panic!("Expected Decode error"),
+    }
+}
+
+#[test]
+fn raw_roundtrip_test() {
+    let image = raster::open("tests/in/sample.jpg").unwrap();
+    let mut buffer = Vec::new();
+    raster::encode(&image, &mut buffer, ImageFormat::Raw).unwrap();
+    assert_eq!(image.bytes, buffer);
+
+    let options = raw_options(image.width as u32, image.height as u32);
+    let decoded = raster::decode_with_options(&buffer[..], ImageFormat::Raw, &options).unwrap();
+    assert_eq!(image.bytes, decoded.bytes);
+
+    // The extension is used even if the data happens to look like another format
+    let mut image = Image::blank(2, 1);
+    image.bytes[..4].copy_from_slice(b"qoif");
+    raster::save(&image, "tests/out/test_raw_qoif.rgba").unwrap();
+    let opened = raster::open_with_options("tests/out/test_raw_qoif.rgba", &raw_options(2, 1));
+    assert_eq!(image.bytes, opened.unwrap().bytes);
+}
+
+#[test]
+fn raw_stream_test() {
+    // Images are read back to back from a single stream
+    let first = Image::blank(3, 2);
+    let mut second = Image::blank(3, 2);
+    second.bytes[0] = 255;
+    let mut buffer = Vec::new();
+    raster::encode(&first, &mut buffer, ImageFormat::Raw).unwrap();
+    raster::encode(&second, &mut buffer, ImageFormat::Raw).unwrap();
+
+    let mut reader = Cursor::new(buffer);
+    let options = raw_options(3, 2);
+    for image in &[first, second] {
+        let decoded = raster::decode_with_options(&mut reader, ImageFormat::Raw, &options).unwrap();
+        assert_eq!(image.bytes, decoded.bytes);
+    }
+    match raster::decode_with_options(&mut reader, ImageFormat::Raw, &options) {
+        Err(RasterError::Decode(ImageFormat::Raw, _)) => {}
+        _ => panic!("Expected Decode error"),
+    }
+}
+
+#[test]
+fn raw_fail_test() {
+    // Raw data cannot be decoded or probed without its dimensions
+    let buffer = [0; 16];
+    match raster::decode(&buffer[..], ImageFormat::Raw) {
+        Err(RasterError::Decode(ImageFormat::Raw, _)) => {}
+        _ => panic!("Expected Decode error"),
+    }
+    raster::save(&Image::blank(2, 1), "tests/out/test_raw.rgba").unwrap();
+    match raster::probe("tests/out/test_raw.rgba") {
+        Err(RasterError::Decode(ImageFormat::Raw, _)) => {}
+        _ => panic!("Expected Decode error"),
+    }
+}
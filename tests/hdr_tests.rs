@@ -0,0 +1,254 @@
+extern crate raster;
+
+use raster::error::RasterError;
+use raster::{ColorType, HdrImage, ImageFormat, Limits, OpenOptions, ToneMapping};
+
+// A Radiance HDR file with the given resolution line and scanline data
+fn hdr(resolution: &str, data: &[u8]) -> Vec<u8> {
+    let mut buffer =
+        b"#?RADIANCE\n# made by hand\nFORMAT=32-bit_rle_rgbe\nEXPOSURE=1.0\n\n".to_vec();
+    buffer.extend_from_slice(resolution.as_bytes());
+    buffer.push(b'\n');
+    buffer.extend_from_slice(data);
+    buffer
+}
+
+fn decode(buffer: &[u8]) -> HdrImage {
+    raster::decode_hdr(buffer, ImageFormat::Hdr).unwrap()
+}
+
+fn pixels(image: &HdrImage) -> Vec<[f32; 3]> {
+    image.pixels.chunks(3).map(|p| [p[0], p[1], p[2]]).collect()
+}
+
+// An image of pseudo-random values over a wide range
+fn noise(width: i32, height: i32) -> HdrImage {
+    let mut image = HdrImage::new(width, height);
+    let mut seed = 1u32;
+    for (i, v) in image.pixels.iter_mut().enumerate() {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        *v = (seed >> 8) as f32 / (1 << 24) as f32 * [0.001, 1.0, 1000.0][i / 3 % 3];
+    }
+    image
+}
+
+#[test]
+fn hdr_roundtrip_test() {
+    // Flat scanlines below 8 pixels and over 32767 pixels, run length encoded ones in between
+    for &(width, height) in &[(5, 3), (8, 1), (100, 20), (32768, 1)] {
+        let original = noise(width, height);
+        let mut buffer = Vec::new();
+        raster::encode_hdr(&original, &mut buffer, ImageFormat::Hdr).unwrap();
+        assert_eq!(Some(ImageFormat::Hdr), raster::guess_format(&buffer));
+        let decoded = decode(&buffer);
+        assert_eq!((width, height), (decoded.width, decoded.height));
+
+        // The largest channel keeps 8 bits of precision
+        for (a, b) in pixels(&original).iter().zip(pixels(&decoded).iter()) {
+            let max = a[0].max(a[1]).max(a[2]);
+            for c in 0..3 {
+                assert!((a[c] - b[c]).abs() <= max / 128.0, "{:?} {:?}", a, b);
+            }
+        }
+
+        // Decoded values are stored exactly
+        let mut again = Vec::new();
+        raster::encode_hdr(&decoded, &mut again, ImageFormat::Hdr).unwrap();
+        assert_eq!(buffer, again);
+    }
+
+    // Runs compress
+    let mut image = HdrImage::new(1000, 10);
+    image.set_pixel(500, 5, [3.0, 2.0, 1.0]).unwrap();
+    raster::save_hdr(&image, "tests/out/test_hdr_runs.hdr").unwrap();
+    let decoded = raster::open_hdr("tests/out/test_hdr_runs.hdr").unwrap();
+    assert_eq!(image.pixels, decoded.pixels);
+    let info = raster::probe("tests/out/test_hdr_runs.hdr").unwrap();
+    assert_eq!(ImageFormat::Hdr, info.format);
+    assert_eq!((1000, 10), (info.width, info.height));
+    assert_eq!((ColorType::Rgb, 32), (info.color_type, info.bit_depth));
+    assert!(
+        std::fs::metadata("tests/out/test_hdr_runs.hdr")
+            .unwrap()
+            .len()
+            < 1000
+    );
+}
+
+#[test]
+fn hdr_scanlines_test() {
+    // Run length encoded channels: red as a run, green as literals, blue as a run and a literal,
+    // and a shared exponent
+    let mut data = vec![2, 2, 0, 8];
+    data.extend_from_slice(&[128 + 8, 128]);
+    data.extend_from_slice(&[8, 0, 32, 64, 128, 0, 32, 64, 128]);
+    data.extend_from_slice(&[128 + 7, 0, 1, 255]);
+    data.extend_from_slice(&[128 + 8, 129]);
+    let image = decode(&hdr("-Y 1 +X 8", &data));
+    let expected = [
+        [1.0, 0.0, 0.0],
+        [1.0, 0.25, 0.0],
+        [1.0, 0.5, 0.0],
+        [1.0, 1.0, 0.0],
+        [1.0, 0.0, 0.0],
+        [1.0, 0.25, 0.0],
+        [1.0, 0.5, 0.0],
+        [1.0, 1.0, 255.0 / 128.0],
+    ];
+    assert_eq!(expected.to_vec(), pixels(&image));
+
+    // Flat pixels with old style repeats, the second one counting in the next byte. A zero
+    // exponent is black.
+    let mut data = vec![128, 64, 32, 130, 1, 1, 1, 2, 1, 1, 1, 1];
+    data.extend_from_slice(&[0, 0, 0, 0, 200, 200, 200, 0]);
+    let image = decode(&hdr("-Y 1 +X 261", &data));
+    let mut expected = vec![[2.0, 1.0, 0.5]; 259];
+    expected.extend_from_slice(&[[0.0; 3], [0.0; 3]]);
+    assert_eq!(expected, pixels(&image));
+
+    // raster::open tone maps the image
+    let image = raster::decode(
+        &hdr("-Y 1 +X 2", &[128, 0, 0, 129, 0, 0, 0, 0])[..],
+        ImageFormat::Hdr,
+    )
+    .unwrap();
+    assert_eq!(vec![188, 0, 0, 255, 0, 0, 0, 255], image.bytes);
+}
+
+#[test]
+fn hdr_orientation_test() {
+    // Six pixels with values 1 to 6 in file order
+    let data: Vec<u8> = (1..7).flat_map(|v| vec![v * 32, 0, 0, 131]).collect();
+    let order = |resolution: &str| -> (i32, i32, Vec<f32>) {
+        let image = decode(&hdr(resolution, &data));
+        let values = pixels(&image).iter().map(|p| p[0]).collect();
+        (image.width, image.height, values)
+    };
+    let expected = |width, height, values: [f32; 6]| (width, height, values.to_vec());
+
+    assert_eq!(
+        expected(3, 2, [1.0, 2.0, 3.0, 4.0, 5.0, 6.0]),
+        order("-Y 2 +X 3")
+    );
+    assert_eq!(
+        expected(3, 2, [4.0, 5.0, 6.0, 1.0, 2.0, 3.0]),
+        order("+Y 2 +X 3")
+    );
+    assert_eq!(
+        expected(3, 2, [3.0, 2.0, 1.0, 6.0, 5.0, 4.0]),
+        order("-Y 2 -X 3")
+    );
+    assert_eq!(
+        expected(2, 3, [1.0, 4.0, 2.0, 5.0, 3.0, 6.0]),
+        order("+X 2 -Y 3")
+    );
+    assert_eq!(
+        expected(2, 3, [3.0, 6.0, 2.0, 5.0, 1.0, 4.0]),
+        order("+X 2 +Y 3")
+    );
+    assert_eq!(
+        expected(2, 3, [6.0, 3.0, 5.0, 2.0, 4.0, 1.0]),
+        order("-X 2 +Y 3")
+    );
+}
+
+#[test]
+fn hdr_tone_mapping_test() {
+    let mut hdr = HdrImage::new(4, 1);
+    hdr.pixels = vec![
+        0.0, 0.18, 1.0, 4.0, 16.0, 100.0, -1.0, 0.5, 1000.0, 1.0, 1.0, 1.0,
+    ];
+
+    let reinhard = hdr.tone_map(ToneMapping::default()).unwrap();
+    assert_eq!(
+        vec![0, 109, 188, 255, 231, 248, 254, 255, 0, 156, 255, 255, 188, 188],
+        reinhard.bytes[..14].to_vec()
+    );
+    let aces = hdr.tone_map(ToneMapping::Aces).unwrap();
+    assert_eq!([0, 206, 255, 255], aces.bytes[8..12]);
+    assert!(aces.bytes[1] > reinhard.bytes[1]);
+
+    let linear = ToneMapping::Exposure {
+        exposure: 0.0,
+        gamma: 1.0,
+    };
+    assert_eq!(
+        vec![0, 46, 255, 255],
+        hdr.tone_map(linear).unwrap().bytes[..4].to_vec()
+    );
+    let darker = ToneMapping::Exposure {
+        exposure: -3.0,
+        gamma: 1.0,
+    };
+    assert_eq!(
+        vec![0, 6, 32, 255],
+        hdr.tone_map(darker).unwrap().bytes[..4].to_vec()
+    );
+
+    let invalid = ToneMapping::Exposure {
+        exposure: 0.0,
+        gamma: 0.0,
+    };
+    match hdr.tone_map(invalid) {
+        Err(RasterError::InvalidGamma(_)) => {}
+        _ => panic!("Expected InvalidGamma"),
+    }
+
+    // 8 bit images convert to linear light and back
+    let image = raster::open("tests/in/sample.png").unwrap();
+    let mut buffer = Vec::new();
+    raster::encode(&image, &mut buffer, ImageFormat::Hdr).unwrap();
+    let hdr = raster::decode_hdr(&buffer[..], ImageFormat::Hdr).unwrap();
+    let srgb = ToneMapping::Exposure {
+        exposure: 0.0,
+        gamma: 2.2,
+    };
+    let back = hdr.tone_map(srgb).unwrap();
+    assert_eq!(image.bytes.len(), back.bytes.len());
+    for (a, b) in image.bytes.chunks(4).zip(back.bytes.chunks(4)) {
+        for c in 0..3 {
+            assert!((a[c] as i32 - b[c] as i32).abs() <= 12, "{:?} {:?}", a, b);
+        }
+    }
+}
+
+#[test]
+fn hdr_fail_test() {
+    let pixel = [128, 0, 0, 129];
+    let cases = [
+        b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n".to_vec(),
+        b"#?RADIANCE\n\n-Y 1 -Y 1\n".to_vec(),
+        b"#?RADIANCE\n\n-Y 0 +X 1\n".to_vec(),
+        b"#?RADIANCE\n\n-Y 1".to_vec(),
+        hdr("-Y 2 +X 1", &pixel),
+        // A repeat before any pixel and runs past the end of the scanline
+        hdr("-Y 1 +X 2", &[1, 1, 1, 1, 0, 0, 0, 0]),
+        hdr("-Y 1 +X 2", &[0, 0, 0, 0, 1, 1, 1, 2]),
+        hdr("-Y 1 +X 8", &[2, 2, 0, 8, 128 + 9, 0]),
+        hdr("-Y 1 +X 8", &[2, 2, 0, 9]),
+    ];
+    for buffer in &cases {
+        match raster::decode_hdr(&buffer[..], ImageFormat::Hdr) {
+            Err(RasterError::Decode(ImageFormat::Hdr, _)) => {}
+            _ => panic!("Expected Decode error"),
+        }
+    }
+
+    let options = OpenOptions {
+        limits: Limits {
+            max_width: Some(100),
+            ..Limits::default()
+        },
+        ..OpenOptions::default()
+    };
+    let buffer = hdr("-Y 1 +X 101", &[]);
+    match raster::decode_hdr_with_options(&buffer[..], ImageFormat::Hdr, &options) {
+        Err(RasterError::LimitsExceeded(_)) => {}
+        _ => panic!("Expected LimitsExceeded"),
+    }
+
+    match raster::encode_hdr(&HdrImage::new(0, 1), Vec::new(), ImageFormat::Hdr) {
+        Err(RasterError::Encode(ImageFormat::Hdr, _)) => {}
+        _ => panic!("Expected Encode error"),
+    }
+}
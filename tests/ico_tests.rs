@@ -0,0 +1,169 @@
+extern crate raster;
+
+mod common;
+
+use raster::error::RasterError;
+use raster::{IcoOptions, Image, ImageFormat, SaveOptions, TgaOptions};
+
+use common::pixels;
+
+// An ICO file with an entry for each payload
+fn ico(entries: &[(u8, u8, &[u8])]) -> Vec<u8> {
+    let mut buffer = vec![0, 0, 1, 0];
+    buffer.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    let mut offset = 6 + 16 * entries.len();
+    for &(width, height, payload) in entries {
+        buffer.extend_from_slice(&[width, height, 0, 0, 1, 0, 32, 0]);
+        buffer.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        buffer.extend_from_slice(&(offset as u32).to_le_bytes());
+        offset += payload.len();
+    }
+    for &(_, _, payload) in entries {
+        buffer.extend_from_slice(payload);
+    }
+    buffer
+}
+
+// An icon DIB header with the height doubled for the AND mask
+fn dib_header(width: i32, height: i32, bpp: u16) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend_from_slice(&40u32.to_le_bytes());
+    header.extend_from_slice(&width.to_le_bytes());
+    header.extend_from_slice(&(height * 2).to_le_bytes());
+    header.extend_from_slice(&1u16.to_le_bytes());
+    header.extend_from_slice(&bpp.to_le_bytes());
+    header.resize(40, 0);
+    header
+}
+
+// An image with a pattern of colors and transparency
+fn pattern(size: i32) -> Image {
+    let mut image = Image::blank(size, size);
+    for (i, pixel) in image.bytes.chunks_mut(4).enumerate() {
+        let (x, y) = ((i as i32 % size) as u8, (i as i32 / size) as u8);
+        let alpha = if x < y { 0 } else { x.wrapping_mul(3) | 1 };
+        pixel.copy_from_slice(&[x, y, x ^ y, alpha]);
+    }
+    image
+}
+
+const CLEAR: [u8; 4] = [0, 0, 0, 0];
+const RED: [u8; 4] = [255, 0, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+#[test]
+fn ico_roundtrip_test() {
+    let sizes: Vec<Image> = [16, 32, 48, 256]
+        .iter()
+        .map(|&size| pattern(size))
+        .collect();
+    raster::save_all(&sizes, "tests/out/test_ico_sizes.ico").unwrap();
+
+    let images = raster::open_all("tests/out/test_ico_sizes.ico").unwrap();
+    assert_eq!(sizes.len(), images.len());
+    for (image, saved) in sizes.iter().zip(&images) {
+        assert_eq!(image.width, saved.width);
+        assert_eq!(image.height, saved.height);
+        assert_eq!(image.bytes, saved.bytes);
+    }
+
+    // A single image is the largest size
+    let image = raster::open("tests/out/test_ico_sizes.ico").unwrap();
+    assert_eq!(sizes[3].bytes, image.bytes);
+
+    let info = raster::probe("tests/out/test_ico_sizes.ico").unwrap();
+    assert_eq!(ImageFormat::Ico, info.format);
+    assert_eq!((256, 256), (info.width, info.height));
+    assert_eq!(4, info.frames);
+}
+
+#[test]
+fn ico_payload_test() {
+    // 256 pixel images are stored as PNG, smaller ones as DIB
+    let mut buffer = Vec::new();
+    raster::encode_all(&[pattern(256), pattern(8)], &mut buffer, ImageFormat::Ico).unwrap();
+    assert_eq!(Some(ImageFormat::Ico), raster::guess_format(&buffer));
+    assert_eq!(&[0, 0], &buffer[6..8]);
+    let offset = u32::from_le_bytes([buffer[18], buffer[19], buffer[20], buffer[21]]) as usize;
+    assert_eq!(
+        Some(ImageFormat::Png),
+        raster::guess_format(&buffer[offset..])
+    );
+    let offset = u32::from_le_bytes([buffer[34], buffer[35], buffer[36], buffer[37]]) as usize;
+    assert_eq!(&[40, 0, 0, 0], &buffer[offset..offset + 4]);
+}
+
+#[test]
+fn ico_mask_test() {
+    // 8 bit with a palette, made transparent by the AND mask. Rows are stored bottom-up.
+    let mut dib = dib_header(2, 2, 8);
+    dib.extend_from_slice(&[0, 0, 255, 0, 255, 0, 0, 0]);
+    dib.resize(40 + 256 * 4, 0);
+    dib.extend_from_slice(&[0, 1, 0, 0, 1, 0, 0, 0]);
+    dib.extend_from_slice(&[0x40, 0, 0, 0, 0x00, 0, 0, 0]);
+    let images = raster::decode_all(&ico(&[(2, 2, &dib)])[..], ImageFormat::Ico).unwrap();
+    assert_eq!(vec![BLUE, RED, RED, CLEAR], pixels(&images[0]));
+
+    // 32 bit with an empty alpha channel falls back to the mask
+    let mut dib = dib_header(2, 1, 32);
+    dib.extend_from_slice(&[0, 0, 255, 0, 255, 0, 0, 0]);
+    dib.extend_from_slice(&[0x80, 0, 0, 0]);
+    let images = raster::decode_all(&ico(&[(2, 1, &dib)])[..], ImageFormat::Ico).unwrap();
+    assert_eq!(vec![CLEAR, BLUE], pixels(&images[0]));
+
+    // 32 bit with alpha ignores the mask
+    let mut dib = dib_header(2, 1, 32);
+    dib.extend_from_slice(&[0, 0, 255, 128, 255, 0, 0, 255]);
+    dib.extend_from_slice(&[0xC0, 0, 0, 0]);
+    let images = raster::decode_all(&ico(&[(2, 1, &dib)])[..], ImageFormat::Ico).unwrap();
+    assert_eq!(vec![[255, 0, 0, 128], BLUE], pixels(&images[0]));
+}
+
+#[test]
+fn cur_hotspot_test() {
+    let options = SaveOptions {
+        ico: IcoOptions { hotspot: (3, 5) },
+        ..SaveOptions::default()
+    };
+    let sizes = [pattern(32), pattern(16)];
+    let mut buffer = Vec::new();
+    raster::encode_all_with_options(&sizes, &mut buffer, ImageFormat::Cur, &options).unwrap();
+    assert_eq!(Some(ImageFormat::Cur), raster::guess_format(&buffer));
+    assert_eq!(&[3, 0, 5, 0], &buffer[10..14]);
+
+    let images = raster::decode_all(&buffer[..], ImageFormat::Cur).unwrap();
+    assert_eq!(sizes[1].bytes, images[1].bytes);
+}
+
+#[test]
+fn ico_fail_test() {
+    match raster::encode_all(&[pattern(257)], Vec::new(), ImageFormat::Ico) {
+        Err(RasterError::Encode(ImageFormat::Ico, _)) => {}
+        _ => panic!("Expected Encode error"),
+    }
+    match raster::encode_all(&[pattern(4), pattern(2)], Vec::new(), ImageFormat::Png) {
+        Err(RasterError::Encode(ImageFormat::Png, _)) => {}
+        _ => panic!("Expected Encode error"),
+    }
+
+    // A directory that points past the end of the file
+    let mut buffer = ico(&[(2, 2, &dib_header(2, 2, 32))]);
+    buffer.truncate(30);
+    match raster::decode_all(&buffer[..], ImageFormat::Ico) {
+        Err(RasterError::Decode(ImageFormat::Ico, _)) => {}
+        _ => panic!("Expected Decode error"),
+    }
+}
+
+#[test]
+fn ico_tga_signature_test() {
+    // Uncompressed TGA files start like a CUR, but with no images
+    let options = SaveOptions {
+        tga: TgaOptions { rle: false },
+        ..SaveOptions::default()
+    };
+    let mut buffer = Vec::new();
+    raster::encode_with_options(&pattern(4), &mut buffer, ImageFormat::Tga, &options).unwrap();
+    assert_eq!(&[0, 0, 2, 0], &buffer[..4]);
+    assert_eq!(None, raster::guess_format(&buffer));
+}
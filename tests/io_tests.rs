@@ -1,5 +1,7 @@
 extern crate raster;
 
+use std::fs;
+
 #[test]
 fn open_fail() {
     assert!({
@@ -123,3 +125,78 @@ fn read_png_format_fail() {
         }
     });
 }
+
+#[test]
+fn load_from_memory() {
+    let buffer = fs::read("tests/in/sample.png").unwrap();
+    let image = raster::load_from_memory(&buffer).unwrap();
+    let expected = raster::open("tests/in/sample.png").unwrap();
+
+    assert_eq!(expected.width, image.width);
+    assert_eq!(expected.height, image.height);
+    assert_eq!(expected.bytes, image.bytes);
+}
+
+#[test]
+fn load_from_memory_fail() {
+    let buffer = fs::read("tests/in/unsupported.txt").unwrap();
+    match raster::load_from_memory(&buffer) {
+        Err(raster::error::RasterError::UnsupportedFormat(_)) => {}
+        _ => panic!("Expected UnsupportedFormat"),
+    }
+}
+
+#[test]
+fn decode_from_reader() {
+    let file = fs::File::open("tests/in/sample.jpg").unwrap();
+    let image = raster::decode(file, raster::ImageFormat::Jpeg).unwrap();
+    let expected = raster::open("tests/in/sample.jpg").unwrap();
+
+    assert_eq!(expected.bytes, image.bytes);
+}
+
+#[test]
+fn encode_decode_memory() {
+    let image = raster::open("tests/in/sample.png").unwrap();
+
+    let mut buffer = Vec::new();
+    raster::encode(&image, &mut buffer, raster::ImageFormat::Png).unwrap();
+    let decoded = raster::decode(&buffer[..], raster::ImageFormat::Png).unwrap();
+    assert_eq!(image.bytes, decoded.bytes);
+
+    for format in &[raster::ImageFormat::Gif, raster::ImageFormat::Jpeg] {
+        let mut buffer = Vec::new();
+        raster::encode(&image, &mut buffer, *format).unwrap();
+        let decoded = raster::load_from_memory(&buffer).unwrap();
+        assert_eq!(image.width, decoded.width);
+        assert_eq!(image.height, decoded.height);
+    }
+}
+
+#[test]
+fn guess_format() {
+    let buffer = fs::read("tests/in/sample.gif").unwrap();
+    assert_eq!(Some(raster::ImageFormat::Gif), raster::guess_format(&buffer));
+
+    let buffer = fs::read("tests/in/sample.jpg").unwrap();
+    assert_eq!(Some(raster::ImageFormat::Jpeg), raster::guess_format(&buffer));
+
+    let buffer = fs::read("tests/in/sample.png").unwrap();
+    assert_eq!(Some(raster::ImageFormat::Png), raster::guess_format(&buffer));
+
+    let buffer = fs::read("tests/in/unsupported.txt").unwrap();
+    assert_eq!(None, raster::guess_format(&buffer));
+
+    assert_eq!(None, raster::guess_format(&[]));
+}
+
+#[test]
+fn read_misnamed_format() {
+    let image = raster::open("tests/in/misnamed-png.jpg").unwrap();
+    let expected = raster::open("tests/in/in2x2.png").unwrap();
+    assert_eq!(expected.bytes, image.bytes);
+
+    let image = raster::open("tests/in/no-extension").unwrap();
+    let expected = raster::open("tests/in/sample.gif").unwrap();
+    assert_eq!(expected.bytes, image.bytes);
+}
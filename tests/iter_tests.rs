@@ -0,0 +1,129 @@
+extern crate raster;
+
+use raster::error::RasterError;
+use raster::{GenericImage, GenericImageView, Image, ImageBuffer, Rgba, Rgba8};
+
+#[test]
+fn pixels_test() {
+    let image = raster::open("tests/in/sample.png").unwrap();
+    let count = (image.width * image.height) as usize;
+
+    // Pixels match get_pixel, in order
+    let pixels = image.pixels();
+    assert_eq!(count, pixels.len());
+    for (x, y, p) in image.enumerate_pixels() {
+        let color = image.get_pixel(x, y).unwrap();
+        assert_eq!(&[color.r, color.g, color.b, color.a], p);
+    }
+    assert_eq!(count, image.enumerate_pixels().count());
+    let (x, y, _) = image.enumerate_pixels().last().unwrap();
+    assert_eq!((image.width - 1, image.height - 1), (x, y));
+    assert_eq!(
+        &image.bytes[image.bytes.len() - 4..],
+        image.pixels().next_back().unwrap()
+    );
+
+    // Rows are width * 4 bytes, from top to bottom
+    assert_eq!(image.height as usize, image.rows().len());
+    for (y, row) in image.rows().enumerate() {
+        let start = y * image.width as usize * 4;
+        assert_eq!(&image.bytes[start..start + row.len()], row);
+    }
+    let last = image.rows().next_back().unwrap();
+    assert_eq!(&image.bytes[image.bytes.len() - last.len()..], last);
+
+    // A buffer shorter than width * height only yields whole rows
+    let short = Image {
+        width: 3,
+        height: 2,
+        bytes: vec![0; 3 * 4 + 5],
+    };
+    assert_eq!(3, short.pixels().count());
+    assert_eq!(1, short.rows().count());
+
+    // An image without width still has its rows
+    let empty = Image {
+        width: 0,
+        height: 2,
+        bytes: Vec::new(),
+    };
+    assert_eq!(0, empty.pixels().count());
+    assert_eq!(
+        vec![0, 0],
+        empty.rows().map(|r| r.len()).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn pixels_mut_test() {
+    let mut image = Image::blank(3, 2);
+    for (x, y, p) in image.enumerate_pixels_mut() {
+        *p = [x as u8, y as u8, 0, 255];
+    }
+    assert_eq!(&[2, 1, 0, 255], &image.bytes[20..24]);
+
+    for p in image.pixels_mut().rev().take(1) {
+        p[2] = 9;
+    }
+    assert_eq!(9, image.bytes[22]);
+
+    for (y, row) in image.rows_mut().enumerate() {
+        for b in row.iter_mut() {
+            *b = y as u8;
+        }
+    }
+    assert_eq!(vec![0; 12], &image.bytes[..12]);
+    assert_eq!(vec![1; 12], &image.bytes[12..]);
+    image.rows_mut().next_back().unwrap()[0] = 7;
+    assert_eq!(7, image.bytes[12]);
+}
+
+#[test]
+fn map_pixels_test() {
+    let original = raster::open("tests/in/sample.png").unwrap();
+
+    // Image, ImageBuffer and views visit the same pixels at the same coordinates
+    let mut image = original.clone();
+    let mut buffer = ImageBuffer::<Rgba8>::from_image(&original);
+    let (w, h) = (image.width, image.height);
+    let invert = |x: i32, y: i32, p: Rgba8| Rgba([255 - p.0[0], x as u8, y as u8, p.0[3]]);
+    image.map_pixels(invert).unwrap();
+    buffer.map_pixels(invert).unwrap();
+    assert_eq!(image.bytes, buffer.to_image().bytes);
+
+    let mut part = original.clone();
+    part.view_mut(0, 0, w, h)
+        .unwrap()
+        .map_pixels(invert)
+        .unwrap();
+    assert_eq!(image.bytes, part.bytes);
+
+    let mut seen = Vec::new();
+    image
+        .view(1, 2, 3, 2)
+        .unwrap()
+        .for_each_pixel(|x, y, p| seen.push((x, y, p)))
+        .unwrap();
+    assert_eq!(6, seen.len());
+    assert_eq!(
+        (2, 1, GenericImageView::get_pixel(&image, 3, 3).unwrap()),
+        seen[5]
+    );
+
+    // Missing pixels are an error rather than a panic
+    let mut short = Image {
+        width: 3,
+        height: 2,
+        bytes: vec![0; 3 * 4 + 4],
+    };
+    match short.map_pixels(|_, _, p| p) {
+        Err(RasterError::PixelOutOfBounds(x, y)) => assert_eq!((1, 1), (x, y)),
+        _ => panic!("Expected PixelOutOfBounds error"),
+    }
+    let buffer = ImageBuffer {
+        width: 2,
+        height: 2,
+        pixels: vec![Rgba([0u8; 4]); 3],
+    };
+    assert!(buffer.for_each_pixel(|_, _, _| {}).is_err());
+}
@@ -0,0 +1,129 @@
+extern crate raster;
+
+mod common;
+
+use std::fs;
+
+use raster::error::RasterError;
+use raster::{ImageFormat, Limits, OpenOptions};
+
+fn options(limits: Limits) -> OpenOptions {
+    OpenOptions {
+        limits,
+        ..OpenOptions::default()
+    }
+}
+
+fn assert_exceeded<T>(result: Result<T, RasterError>) {
+    match result {
+        Err(RasterError::LimitsExceeded(_)) => {}
+        Err(e) => panic!("Expected LimitsExceeded, got {:?}", e),
+        Ok(_) => panic!("Expected LimitsExceeded"),
+    }
+}
+
+#[test]
+fn dimension_limits_test() {
+    for file in &[
+        "tests/in/sample.gif",
+        "tests/in/sample.jpg",
+        "tests/in/sample.png",
+    ] {
+        let info = raster::probe(file).unwrap();
+        let width = info.width as u32;
+        let height = info.height as u32;
+        let pixels = width as u64 * height as u64;
+
+        for limits in &[
+            Limits {
+                max_width: Some(width - 1),
+                ..Limits::default()
+            },
+            Limits {
+                max_height: Some(height - 1),
+                ..Limits::default()
+            },
+            Limits {
+                max_pixels: Some(pixels - 1),
+                ..Limits::default()
+            },
+            Limits {
+                max_alloc: Some(pixels * 4 - 1),
+                ..Limits::default()
+            },
+        ] {
+            assert_exceeded(raster::open_with_options(file, &options(limits.clone())));
+        }
+
+        let limits = Limits {
+            max_width: Some(width),
+            max_height: Some(height),
+            max_pixels: Some(pixels),
+            ..Limits::default()
+        };
+        let image = raster::open_with_options(file, &options(limits)).unwrap();
+        assert_eq!(info.width, image.width);
+        assert_eq!(info.height, image.height);
+    }
+}
+
+#[test]
+fn frame_limit_test() {
+    let limits = Limits {
+        max_frames: Some(2),
+        ..Limits::default()
+    };
+    assert_exceeded(raster::open_animation_with_options(
+        "tests/in/animated.gif",
+        &options(limits),
+    ));
+
+    let limits = Limits {
+        max_frames: Some(3),
+        ..Limits::default()
+    };
+    let animation =
+        raster::open_animation_with_options("tests/in/animated.gif", &options(limits)).unwrap();
+    assert_eq!(3, animation.frames.len());
+}
+
+#[test]
+fn huge_header_test() {
+    // A GIF that claims to be 65535x65535 with no image data
+    let mut buffer = b"GIF89a".to_vec();
+    buffer.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0x3B]);
+
+    assert_exceeded(raster::decode(&buffer[..], ImageFormat::Gif));
+    assert_exceeded(raster::decode_animation(&buffer[..], ImageFormat::Gif));
+    assert_exceeded(raster::load_from_memory(&buffer));
+}
+
+#[test]
+fn no_limits_test() {
+    let buffer = fs::read("tests/in/sample.png").unwrap();
+    let image =
+        raster::decode_with_options(&buffer[..], ImageFormat::Png, &options(Limits::none()))
+            .unwrap();
+    let expected = raster::open("tests/in/sample.png").unwrap();
+    assert_eq!(expected.bytes, image.bytes);
+
+    // Sizes whose bytes do not fit in a u64 are still rejected
+    assert_exceeded(Limits::none().check_dimensions(u32::MAX, u32::MAX));
+
+    // Without limits, a PNG too large to address is a decoding error, and so is one wider than
+    // an image can be
+    let huge = common::png(i32::MAX as u32, i32::MAX as u32, 6, &[]);
+    let wide = common::png(i32::MAX as u32 + 1, 1, 6, &[]);
+    let options = options(Limits::none());
+    for result in &[
+        raster::decode_with_options(&huge[..], ImageFormat::Png, &options).map(|_| ()),
+        raster::decode_animation_with_options(&huge[..], ImageFormat::Png, &options).map(|_| ()),
+        raster::decode_with_options(&wide[..], ImageFormat::Png, &options).map(|_| ()),
+        raster::probe_reader(&wide[..]).map(|_| ()),
+    ] {
+        match result {
+            Err(RasterError::Decode(ImageFormat::Png, _)) => {}
+            _ => panic!("Expected Decode error"),
+        }
+    }
+}
@@ -0,0 +1,217 @@
+extern crate raster;
+
+use raster::error::RasterError;
+use raster::{
+    editor, ChromaSubsampling, Color, CompressionLevel, Dithering, GifOptions, Image, ImageFormat,
+    JpegOptions, PngFilter, PngOptions, ResizeMode, SaveOptions,
+};
+
+// An image with a size that is not a multiple of the JPEG block size
+fn sample() -> Image {
+    let mut image = raster::open("tests/in/sample.jpg").unwrap();
+    editor::resize(&mut image, 101, 67, ResizeMode::Exact).unwrap();
+    image
+}
+
+fn encode(image: &Image, format: ImageFormat, options: &SaveOptions) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    raster::encode_with_options(image, &mut buffer, format, options).unwrap();
+    buffer
+}
+
+// A smooth gradient that survives chroma subsampling well
+fn gradient() -> Image {
+    let mut image = Image::blank(64, 48);
+    for y in 0..48 {
+        for x in 0..64 {
+            let color = Color::rgba((x * 4) as u8, (y * 5) as u8, 128, 255);
+            image.set_pixel(x, y, &color).unwrap();
+        }
+    }
+    image
+}
+
+// Mean absolute difference of the color channels
+fn mean_difference(a: &Image, b: &Image) -> f64 {
+    let sum: u64 = a
+        .bytes
+        .chunks(4)
+        .zip(b.bytes.chunks(4))
+        .map(|(p, q)| {
+            (0..3)
+                .map(|c| (p[c] as i64 - q[c] as i64).unsigned_abs())
+                .sum::<u64>()
+        })
+        .sum();
+    sum as f64 / (a.width * a.height * 3) as f64
+}
+
+fn jpeg_options(quality: u8, subsampling: ChromaSubsampling) -> SaveOptions {
+    SaveOptions {
+        jpeg: JpegOptions {
+            quality,
+            subsampling,
+        },
+        ..SaveOptions::default()
+    }
+}
+
+#[test]
+fn jpeg_quality_test() {
+    let image = sample();
+    let low = encode(
+        &image,
+        ImageFormat::Jpeg,
+        &jpeg_options(10, ChromaSubsampling::Ratio444),
+    );
+    let high = encode(
+        &image,
+        ImageFormat::Jpeg,
+        &jpeg_options(95, ChromaSubsampling::Ratio444),
+    );
+    assert!(low.len() < high.len());
+
+    let low = raster::decode(&low[..], ImageFormat::Jpeg).unwrap();
+    let high = raster::decode(&high[..], ImageFormat::Jpeg).unwrap();
+    assert_eq!(image.width, high.width);
+    assert_eq!(image.height, high.height);
+    assert!(mean_difference(&image, &high) < mean_difference(&image, &low));
+    assert!(mean_difference(&image, &high) < 4.0);
+}
+
+#[test]
+fn jpeg_subsampling_test() {
+    let image = sample();
+    let mut sizes = Vec::new();
+    for subsampling in &[
+        ChromaSubsampling::Ratio444,
+        ChromaSubsampling::Ratio422,
+        ChromaSubsampling::Ratio420,
+    ] {
+        let options = jpeg_options(90, *subsampling);
+        let buffer = encode(&image, ImageFormat::Jpeg, &options);
+        let decoded = raster::decode(&buffer[..], ImageFormat::Jpeg).unwrap();
+        assert_eq!(image.width, decoded.width);
+        assert_eq!(image.height, decoded.height);
+        sizes.push(buffer.len());
+
+        let smooth = gradient();
+        let buffer = encode(&smooth, ImageFormat::Jpeg, &options);
+        let decoded = raster::decode(&buffer[..], ImageFormat::Jpeg).unwrap();
+        assert!(mean_difference(&smooth, &decoded) < 1.0);
+    }
+    assert!(sizes[0] > sizes[1]);
+    assert!(sizes[1] > sizes[2]);
+}
+
+#[test]
+fn jpeg_invalid_quality_test() {
+    let image = Image::blank(8, 8);
+    let mut buffer = Vec::new();
+    let options = jpeg_options(0, ChromaSubsampling::Ratio444);
+    match raster::encode_with_options(&image, &mut buffer, ImageFormat::Jpeg, &options) {
+        Err(RasterError::Encode(ImageFormat::Jpeg, _)) => {}
+        _ => panic!("Expected Encode error"),
+    }
+}
+
+#[test]
+fn png_filters_test() {
+    let image = raster::open("tests/in/sample.png").unwrap();
+    for filter in &[
+        PngFilter::None,
+        PngFilter::Sub,
+        PngFilter::Up,
+        PngFilter::Average,
+        PngFilter::Paeth,
+        PngFilter::Adaptive,
+    ] {
+        for interlaced in &[false, true] {
+            let options = SaveOptions {
+                png: PngOptions {
+                    compression: CompressionLevel::Fast,
+                    filter: *filter,
+                    interlaced: *interlaced,
+                },
+                ..SaveOptions::default()
+            };
+            let buffer = encode(&image, ImageFormat::Png, &options);
+            let decoded = raster::decode(&buffer[..], ImageFormat::Png).unwrap();
+            assert_eq!(image.width, decoded.width);
+            assert_eq!(image.height, decoded.height);
+            assert_eq!(image.bytes, decoded.bytes);
+        }
+    }
+}
+
+#[test]
+fn png_compression_test() {
+    let image = raster::open("tests/in/sample.png").unwrap();
+    let mut sizes = Vec::new();
+    for compression in &[CompressionLevel::Fast, CompressionLevel::Best] {
+        let options = SaveOptions {
+            png: PngOptions {
+                compression: *compression,
+                ..PngOptions::default()
+            },
+            ..SaveOptions::default()
+        };
+        let buffer = encode(&image, ImageFormat::Png, &options);
+        let decoded = raster::decode(&buffer[..], ImageFormat::Png).unwrap();
+        assert_eq!(image.bytes, decoded.bytes);
+        sizes.push(buffer.len());
+    }
+    assert!(sizes[0] > sizes[1]);
+}
+
+#[test]
+fn gif_palette_size_test() {
+    let image = sample();
+    for dithering in &[Dithering::None, Dithering::FloydSteinberg] {
+        let options = SaveOptions {
+            gif: GifOptions {
+                palette_size: 8,
+                dithering: *dithering,
+                ..GifOptions::default()
+            },
+            ..SaveOptions::default()
+        };
+        let buffer = encode(&image, ImageFormat::Gif, &options);
+        let decoded = raster::decode(&buffer[..], ImageFormat::Gif).unwrap();
+        assert_eq!(image.width, decoded.width);
+        assert_eq!(image.height, decoded.height);
+
+        let mut colors: Vec<&[u8]> = decoded.bytes.chunks(4).collect();
+        colors.sort();
+        colors.dedup();
+        assert!(colors.len() <= 8);
+    }
+}
+
+#[test]
+fn gif_invalid_palette_size_test() {
+    let image = Image::blank(8, 8);
+    let mut buffer = Vec::new();
+    let options = SaveOptions {
+        gif: GifOptions {
+            palette_size: 300,
+            ..GifOptions::default()
+        },
+        ..SaveOptions::default()
+    };
+    match raster::encode_with_options(&image, &mut buffer, ImageFormat::Gif, &options) {
+        Err(RasterError::InvalidPaletteSize(300)) => {}
+        _ => panic!("Expected InvalidPaletteSize error"),
+    }
+}
+
+#[test]
+fn save_with_options_test() {
+    let image = sample();
+    let options = jpeg_options(40, ChromaSubsampling::Ratio420);
+    raster::save_with_options(&image, "tests/out/test_save_with_options.jpg", &options).unwrap();
+
+    let saved = raster::open("tests/out/test_save_with_options.jpg").unwrap();
+    assert_eq!(image.width, saved.width);
+    assert_eq!(image.height, saved.height);
+}
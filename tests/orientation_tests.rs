@@ -0,0 +1,210 @@
+extern crate raster;
+
+use raster::OpenOptions;
+use raster::{transform, Animation, Color, Exif, ExifIfd, ExifValue, Frame, Image, ImageFormat};
+
+// A 4x2 image with a different color in each pixel
+fn quadrants() -> Image {
+    let mut image = Image::blank(4, 2);
+    for y in 0..2 {
+        for x in 0..4 {
+            let color = Color::rgba((x * 60) as u8, (y * 200) as u8, 0, 255);
+            image.set_pixel(x, y, &color).unwrap();
+        }
+    }
+    image
+}
+
+// Where the pixel at x, y of a w by h image ends up after orienting it
+fn oriented(orientation: u16, x: i32, y: i32, w: i32, h: i32) -> (i32, i32) {
+    match orientation {
+        2 => (w - 1 - x, y),
+        3 => (w - 1 - x, h - 1 - y),
+        4 => (x, h - 1 - y),
+        5 => (y, x),
+        6 => (h - 1 - y, x),
+        7 => (h - 1 - y, w - 1 - x),
+        8 => (y, w - 1 - x),
+        _ => (x, y),
+    }
+}
+
+// Big endian EXIF data with a camera model, an orientation and an EXIF sub-directory holding the
+// date taken
+fn exif_data(orientation: u16) -> Vec<u8> {
+    let mut data = vec![b'M', b'M', 0, 42, 0, 0, 0, 8];
+    // Three fields in the primary directory
+    data.extend_from_slice(&[0, 3]);
+    data.extend_from_slice(&[0x01, 0x10, 0, 2, 0, 0, 0, 4, b'X', b'1', b'0', 0]);
+    data.extend_from_slice(&[0x01, 0x12, 0, 3, 0, 0, 0, 1]);
+    data.extend_from_slice(&[(orientation >> 8) as u8, orientation as u8, 0, 0]);
+    data.extend_from_slice(&[0x87, 0x69, 0, 4, 0, 0, 0, 1, 0, 0, 0, 50]);
+    data.extend_from_slice(&[0, 0, 0, 0]);
+    // The EXIF directory at offset 50, with its text stored after it
+    data.extend_from_slice(&[0, 1]);
+    data.extend_from_slice(&[0x90, 0x03, 0, 2, 0, 0, 0, 20, 0, 0, 0, 68]);
+    data.extend_from_slice(&[0, 0, 0, 0]);
+    data.extend_from_slice(b"2020:01:02 03:04:05\0");
+    data
+}
+
+// Insert an APP1 segment with the EXIF data after the start of image marker
+fn with_exif(jpeg: &[u8], exif: &[u8]) -> Vec<u8> {
+    let len = exif.len() + 8;
+    let mut buffer = jpeg[..2].to_vec();
+    buffer.extend_from_slice(&[0xFF, 0xE1, (len >> 8) as u8, len as u8]);
+    buffer.extend_from_slice(b"Exif\0\0");
+    buffer.extend_from_slice(exif);
+    buffer.extend_from_slice(&jpeg[2..]);
+    buffer
+}
+
+#[test]
+fn orient_test() {
+    let image = quadrants();
+    for orientation in 1..9 {
+        let mut result = image.clone();
+        transform::orient(&mut result, orientation).unwrap();
+        if orientation >= 5 {
+            assert_eq!((image.height, image.width), (result.width, result.height));
+        } else {
+            assert_eq!((image.width, image.height), (result.width, result.height));
+        }
+
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let (dest_x, dest_y) = oriented(orientation, x, y, image.width, image.height);
+                let expected = image.get_pixel(x, y).unwrap();
+                let actual = result.get_pixel(dest_x, dest_y).unwrap();
+                assert_eq!((expected.r, expected.g), (actual.r, actual.g));
+            }
+        }
+    }
+}
+
+#[test]
+fn rotate_right_angles_test() {
+    let image = quadrants();
+
+    let mut result = image.clone();
+    transform::rotate_90(&mut result).unwrap();
+    transform::rotate_270(&mut result).unwrap();
+    assert_eq!(image.bytes, result.bytes);
+
+    transform::rotate_180(&mut result).unwrap();
+    transform::rotate_180(&mut result).unwrap();
+    assert_eq!(image.bytes, result.bytes);
+}
+
+#[test]
+fn exif_parse_test() {
+    let exif = Exif::parse(&exif_data(6)).unwrap();
+    assert_eq!(Some(6), exif.orientation());
+    assert_eq!(Some("X10"), exif.text(Exif::MODEL));
+    assert_eq!(
+        Some("2020:01:02 03:04:05"),
+        exif.text(Exif::DATE_TIME_ORIGINAL)
+    );
+    assert_eq!(3, exif.fields().len());
+    assert_eq!(ExifIfd::Exif, exif.fields()[2].ifd);
+    assert_eq!(
+        Some(&ExifValue::Short(vec![6])),
+        exif.get(Exif::ORIENTATION)
+    );
+
+    // Orientations outside 1 to 8 are ignored
+    let exif = Exif::parse(&exif_data(9)).unwrap();
+    assert_eq!(None, exif.orientation());
+
+    assert!(Exif::parse(b"Not EXIF").is_none());
+    assert!(Exif::parse(&exif_data(6)[..12]).is_none());
+}
+
+#[test]
+fn jpeg_auto_orient_test() {
+    // Big blocks of color survive JPEG compression
+    let mut image = Image::blank(32, 16);
+    for y in 0..8 {
+        for x in 0..8 {
+            image.set_pixel(x, y, &Color::white()).unwrap();
+        }
+    }
+    let mut jpeg = Vec::new();
+    raster::encode(&image, &mut jpeg, ImageFormat::Jpeg).unwrap();
+    let jpeg = with_exif(&jpeg, &exif_data(6));
+
+    let (upright, metadata) =
+        raster::decode_with_metadata(&jpeg[..], ImageFormat::Jpeg, &OpenOptions::default())
+            .unwrap();
+    assert_eq!(Some(6), metadata.orientation());
+    assert_eq!(Some("X10"), metadata.exif.unwrap().text(Exif::MODEL));
+    assert_eq!((16, 32), (upright.width, upright.height));
+    // The white block moves from the top left to the top right
+    assert!(upright.get_pixel(12, 4).unwrap().r > 200);
+    assert!(upright.get_pixel(4, 4).unwrap().r < 50);
+
+    let options = OpenOptions {
+        auto_orient: false,
+        ..OpenOptions::default()
+    };
+    let stored = raster::decode_with_options(&jpeg[..], ImageFormat::Jpeg, &options).unwrap();
+    assert_eq!((32, 16), (stored.width, stored.height));
+    let stored = raster::load_from_memory_with_options(&jpeg, &options).unwrap();
+    assert_eq!((32, 16), (stored.width, stored.height));
+
+    let plain = raster::decode(&jpeg[..], ImageFormat::Jpeg).unwrap();
+    assert_eq!(upright.bytes, plain.bytes);
+}
+
+#[test]
+fn animation_auto_orient_test() {
+    let mut second = quadrants();
+    transform::flip(&mut second, raster::TransformMode::Horizontal).unwrap();
+    let animation = Animation::new(vec![Frame::new(quadrants(), 100), Frame::new(second, 100)]);
+
+    // Append an EXIF chunk to an animated WebP
+    let exif = exif_data(6);
+    let mut webp = Vec::new();
+    raster::encode_animation(&animation, &mut webp, ImageFormat::WebP).unwrap();
+    webp.extend_from_slice(b"EXIF");
+    webp.extend_from_slice(&(exif.len() as u32).to_le_bytes());
+    webp.extend_from_slice(&exif);
+    let riff_size = (webp.len() - 8) as u32;
+    webp[4..8].copy_from_slice(&riff_size.to_le_bytes());
+
+    let upright = raster::decode_animation(&webp[..], ImageFormat::WebP).unwrap();
+    assert_eq!((2, 4), (upright.width, upright.height));
+    for (frame, decoded) in animation.frames.iter().zip(&upright.frames) {
+        let mut expected = frame.image.clone();
+        transform::orient(&mut expected, 6).unwrap();
+        assert_eq!(expected.bytes, decoded.image.bytes);
+    }
+
+    let options = OpenOptions {
+        auto_orient: false,
+        ..OpenOptions::default()
+    };
+    let stored =
+        raster::decode_animation_with_options(&webp[..], ImageFormat::WebP, &options).unwrap();
+    assert_eq!((4, 2), (stored.width, stored.height));
+    assert_eq!(
+        animation.frames[1].image.bytes,
+        stored.frames[1].image.bytes
+    );
+}
+
+#[test]
+fn jpeg_without_orientation_test() {
+    let (image, metadata) =
+        raster::open_with_metadata("tests/in/portrait.jpg", &OpenOptions::default()).unwrap();
+    assert!(metadata.exif.is_some());
+    assert_eq!(None, metadata.orientation());
+    assert_eq!(
+        raster::open("tests/in/portrait.jpg").unwrap().bytes,
+        image.bytes
+    );
+
+    let (_, metadata) =
+        raster::open_with_metadata("tests/in/sample.png", &OpenOptions::default()).unwrap();
+    assert!(metadata.exif.is_none());
+}
@@ -0,0 +1,91 @@
+extern crate raster;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+
+use raster::error::RasterError;
+use raster::{editor, filter, interpolate, BlendMode, BlurMode, Image, InterpolationMode};
+use raster::{Parallelism, PositionMode};
+use std::panic;
+
+// Apply every parallel kernel, for comparing the results of different settings.
+fn process(image: &Image) -> Vec<Vec<u8>> {
+    let watermark = raster::open("tests/in/watermark.png").unwrap();
+    let mut results = Vec::new();
+
+    let mut blurred = image.clone();
+    filter::blur(&mut blurred, BlurMode::Gaussian).unwrap();
+    filter::sobel(&mut blurred, raster::Orientation::Both).unwrap();
+    results.push(blurred.bytes);
+
+    for mode in [InterpolationMode::Bilinear, InterpolationMode::Nearest] {
+        let mut resized = image.clone();
+        interpolate::resample(&mut resized, 333, 517, mode).unwrap();
+        results.push(resized.bytes);
+    }
+
+    let blended = editor::blend(
+        image,
+        &watermark,
+        BlendMode::Overlay,
+        0.6,
+        PositionMode::TopRight,
+        20,
+        -10,
+    )
+    .unwrap();
+    results.push(blended.bytes);
+    results
+}
+
+#[test]
+fn parallel_test() {
+    let image = raster::open("tests/in/sample.jpg").unwrap();
+    let expected = Parallelism::Serial.install(|| process(&image));
+    assert_eq!(expected, process(&image));
+    assert_eq!(expected, Parallelism::Parallel.install(|| process(&image)));
+
+    #[cfg(feature = "rayon")]
+    {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(3)
+            .build()
+            .unwrap();
+        let pool = Parallelism::Pool(std::sync::Arc::new(pool));
+        assert_eq!(expected, pool.install(|| process(&image)));
+    }
+}
+
+#[test]
+fn parallel_install_test() {
+    match Parallelism::current() {
+        Parallelism::Parallel => {}
+        other => panic!("Expected Parallel by default, got {:?}", other),
+    }
+
+    // Settings nest and are restored, even after a panic
+    Parallelism::Serial.install(|| {
+        let result = panic::catch_unwind(|| {
+            Parallelism::Parallel.install(|| panic!("in install"));
+        });
+        assert!(result.is_err());
+        match Parallelism::current() {
+            Parallelism::Serial => {}
+            other => panic!("Expected Serial, got {:?}", other),
+        }
+    });
+    match Parallelism::current() {
+        Parallelism::Parallel => {}
+        other => panic!("Expected Parallel, got {:?}", other),
+    }
+
+    // Missing pixels are still an error
+    let mut short = Image {
+        width: 4,
+        height: 3,
+        bytes: vec![0; 4 * 4 * 2],
+    };
+    match interpolate::resample(&mut short, 8, 6, InterpolationMode::Nearest) {
+        Err(RasterError::PixelOutOfBounds(0, 2)) => {}
+        _ => panic!("Expected PixelOutOfBounds error"),
+    }
+}
@@ -0,0 +1,162 @@
+extern crate raster;
+
+use raster::error::RasterError;
+use raster::Pixel;
+use raster::{filter, BlurMode, GenericImage, GenericImageView, Image, ImageBuffer, Orientation};
+use raster::{Gray, Gray8, GrayAlpha8, Rgb, Rgb8, Rgba, Rgba16, Rgba32F, Rgba8};
+
+type Filter<I> = dyn Fn(&mut I) -> raster::error::RasterResult<()>;
+
+// Every filter on an image of the given pixel type, as 8 bit RGBA
+fn filter_all<P: Pixel>(image: &Image) -> Vec<Image> {
+    let mut results = Vec::new();
+    let mut run = |f: &Filter<ImageBuffer<P>>| {
+        let mut buffer = ImageBuffer::<P>::from_image(image);
+        f(&mut buffer).unwrap();
+        results.push(buffer.to_image());
+    };
+    run(&|i| filter::blur(i, BlurMode::Gaussian));
+    run(&|i| filter::brightness(i, 1.5));
+    run(&|i| filter::emboss(i));
+    run(&|i| filter::gamma(i, 2.0));
+    run(&|i| filter::grayscale(i));
+    run(&|i| filter::saturation(i, 0.5));
+    run(&|i| filter::sharpen(i));
+    run(&|i| filter::sobel(i, Orientation::Both));
+    results
+}
+
+// Largest difference between the bytes of two images
+fn max_diff(a: &Image, b: &Image) -> u8 {
+    assert_eq!((a.width, a.height), (b.width, b.height));
+    a.bytes
+        .iter()
+        .zip(&b.bytes)
+        .map(|(&a, &b)| a.abs_diff(b))
+        .max()
+        .unwrap_or(0)
+}
+
+#[test]
+fn pixel_convert_test() {
+    let image = raster::open("tests/in/sample.png").unwrap();
+
+    // 8 bit RGBA round trips through every deeper type
+    let rgba8: ImageBuffer<Rgba8> = ImageBuffer::from_image(&image);
+    assert_eq!(image.bytes, rgba8.to_image().bytes);
+    assert_eq!(image.bytes, rgba8.convert::<Rgba16>().to_image().bytes);
+    assert_eq!(image.bytes, rgba8.convert::<Rgba32F>().to_image().bytes);
+    let deep = rgba8.convert::<Rgba16>();
+    assert_eq!(rgba8, deep.convert::<Rgba32F>().convert::<Rgba8>());
+
+    // Layouts without alpha are opaque, gray keeps the luminance
+    let mut pixel = ImageBuffer::<Rgba8>::new(1, 1).unwrap();
+    pixel.set_pixel(0, 0, Rgba([200, 100, 50, 128])).unwrap();
+    assert_eq!(Rgb([200, 100, 50]), pixel.convert::<Rgb8>().pixels[0]);
+    assert_eq!(Gray([125]), pixel.convert::<Gray8>().pixels[0]);
+    assert_eq!(
+        vec![125, 125, 125, 128],
+        pixel.convert::<GrayAlpha8>().to_image().bytes
+    );
+    assert_eq!(
+        vec![125, 125, 125, 255],
+        pixel.convert::<Gray8>().to_image().bytes
+    );
+    assert_eq!(
+        Rgba([51400, 25700, 12850, 32896]),
+        pixel.convert::<Rgba16>().pixels[0]
+    );
+
+    // Float channels are clamped to 0.0 - 1.0
+    let mut float = ImageBuffer::<Rgba32F>::new(1, 1).unwrap();
+    float.set_pixel(0, 0, Rgba([2.0, -1.0, 0.5, 1.0])).unwrap();
+    assert_eq!(vec![255, 0, 128, 255], float.to_image().bytes);
+
+    // Blank images are opaque black
+    let blank = ImageBuffer::<Gray8>::new(3, 2).unwrap();
+    assert_eq!(Image::blank(3, 2).bytes, blank.to_image().bytes);
+    assert_eq!(6, blank.pixels.len());
+
+    // Images too large to allocate fail instead of overflowing
+    match ImageBuffer::<Rgba32F>::new(i32::MAX, i32::MAX) {
+        Err(RasterError::LimitsExceeded(_)) => {}
+        _ => panic!("Expected LimitsExceeded error"),
+    }
+}
+
+#[test]
+fn pixel_bounds_test() {
+    let mut image = ImageBuffer::<Rgba16>::new(3, 2).unwrap();
+    for &(x, y) in &[(3, 0), (0, 2), (-1, 0), (0, -1)] {
+        match image.get_pixel(x, y) {
+            Err(RasterError::PixelOutOfBounds(px, py)) => assert_eq!((x, y), (px, py)),
+            _ => panic!("Expected PixelOutOfBounds error"),
+        }
+        assert!(image.set_pixel(x, y, Rgba([0; 4])).is_err());
+    }
+
+    // Rows don't wrap on an Image either
+    let mut image = Image::blank(3, 2);
+    assert!(GenericImageView::get_pixel(&image, 3, 0).is_err());
+    assert!(GenericImage::set_pixel(&mut image, -1, 1, Rgba([0; 4])).is_err());
+    assert_eq!(
+        Rgba([0, 0, 0, 255]),
+        GenericImageView::get_pixel(&image, 2, 1).unwrap()
+    );
+}
+
+#[test]
+fn pixel_filter_test() {
+    let mut image = raster::open("tests/in/sample.png").unwrap();
+    raster::editor::resize(&mut image, 64, 48, raster::ResizeMode::Exact).unwrap();
+
+    // Filters give the same results on 8 bit RGBA buffers as on an Image
+    let mut expected = Vec::new();
+    let ops: Vec<&Filter<Image>> = vec![
+        &|i| filter::blur(i, BlurMode::Gaussian),
+        &|i| filter::brightness(i, 1.5),
+        &|i| filter::emboss(i),
+        &|i| filter::gamma(i, 2.0),
+        &|i| filter::grayscale(i),
+        &|i| filter::saturation(i, 0.5),
+        &|i| filter::sharpen(i),
+        &|i| filter::sobel(i, Orientation::Both),
+    ];
+    for op in ops {
+        let mut copy = image.clone();
+        op(&mut copy).unwrap();
+        expected.push(copy);
+    }
+    let rgba8 = filter_all::<Rgba8>(&image);
+    for (a, b) in expected.iter().zip(&rgba8) {
+        assert_eq!(a.bytes, b.bytes);
+    }
+
+    // Deeper types only differ by the rounding of 8 bit channels, which edge detection magnifies
+    for results in &[filter_all::<Rgba16>(&image), filter_all::<Rgba32F>(&image)] {
+        for (i, (a, b)) in expected.iter().zip(results).enumerate() {
+            let tolerance = if i == 7 { 8 } else { 1 };
+            assert!(max_diff(a, b) <= tolerance, "filter {}", i);
+        }
+    }
+
+    // Layouts without alpha or color still filter
+    for result in filter_all::<Rgb8>(&image) {
+        assert!(result.bytes.chunks(4).all(|p| p[3] == 255));
+    }
+    for result in filter_all::<Gray8>(&image) {
+        assert!(result.bytes.chunks(4).all(|p| p[0] == p[1] && p[1] == p[2]));
+    }
+
+    // A gray image stays gray through the gamma curve of an RGB image
+    let mut gray = ImageBuffer::<Gray8>::from_image(&image);
+    let mut rgb = gray.convert::<Rgb8>();
+    filter::gamma(&mut gray, 0.5).unwrap();
+    filter::gamma(&mut rgb, 0.5).unwrap();
+    assert_eq!(gray.to_image().bytes, rgb.to_image().bytes);
+
+    match filter::gamma(&mut gray, 10.0) {
+        Err(RasterError::InvalidGamma(_)) => {}
+        _ => panic!("Expected InvalidGamma error"),
+    }
+}
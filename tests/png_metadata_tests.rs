@@ -0,0 +1,189 @@
+extern crate raster;
+
+mod common;
+
+use raster::error::RasterError;
+use raster::{ColorSpace, Density, DensityUnit, Image, ImageFormat, Metadata, OpenOptions};
+use raster::{RenderingIntent, SaveOptions, TextEntry};
+
+use common::zlib;
+
+// A 1x1 gray PNG with the given chunks before the image data
+fn png(chunks: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let mut all = chunks.to_vec();
+    all.push((b"IDAT", zlib(&[0, 128])));
+    common::png(1, 1, 0, &all)
+}
+
+// Names of the chunks of a PNG
+fn chunk_names(buffer: &[u8]) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut pos = 8;
+    while pos + 8 <= buffer.len() {
+        let len = u32::from_be_bytes([
+            buffer[pos],
+            buffer[pos + 1],
+            buffer[pos + 2],
+            buffer[pos + 3],
+        ]);
+        names.push(String::from_utf8_lossy(&buffer[pos + 4..pos + 8]).into_owned());
+        pos += 12 + len as usize;
+    }
+    names
+}
+
+fn decode(buffer: &[u8]) -> Metadata {
+    let (_, metadata) =
+        raster::decode_with_metadata(buffer, ImageFormat::Png, &OpenOptions::default()).unwrap();
+    metadata
+}
+
+#[test]
+fn png_metadata_roundtrip_test() {
+    let image = raster::open("tests/in/sample.png").unwrap();
+    let mut metadata = Metadata::default();
+    metadata.set_text("Title", "Sample");
+    metadata.set_text("Copyright", "© 2017 Raster");
+    metadata.text.push(TextEntry {
+        compressed: true,
+        ..TextEntry::new("Description", &"A long description. ".repeat(20))
+    });
+    metadata.text.push(TextEntry {
+        language: "ja".to_string(),
+        translated_keyword: "タイトル".to_string(),
+        ..TextEntry::new("Title", "サンプル")
+    });
+    metadata.color_space = Some(ColorSpace::Srgb);
+    metadata.rendering_intent = Some(RenderingIntent::RelativeColorimetric);
+    metadata.gamma = Some(0.45455);
+    metadata.set_dpi(300.0, 150.0);
+
+    let mut buffer = Vec::new();
+    let options = SaveOptions::default();
+    raster::encode_with_metadata(&image, &metadata, &mut buffer, ImageFormat::Png, &options)
+        .unwrap();
+    assert_eq!(
+        vec!["IHDR", "sRGB", "gAMA", "pHYs", "tEXt", "tEXt", "zTXt", "iTXt", "IDAT", "IEND"],
+        chunk_names(&buffer)
+    );
+
+    let (decoded, saved) =
+        raster::decode_with_metadata(&buffer[..], ImageFormat::Png, &OpenOptions::default())
+            .unwrap();
+    assert_eq!(image.bytes, decoded.bytes);
+    assert_eq!(metadata, saved);
+    assert_eq!(Some("Sample"), saved.get_text("Title"));
+    assert_eq!(
+        Some(Density {
+            x: 11811,
+            y: 5906,
+            unit: DensityUnit::Meter
+        }),
+        saved.density
+    );
+    let (x, y) = saved.dpi().unwrap();
+    assert!((x - 300.0).abs() < 0.02 && (y - 150.0).abs() < 0.02);
+
+    // A profile takes the place of the sRGB chunk, and linear color is a gamma of 1.0
+    let metadata = Metadata {
+        icc_profile: Some(b"not really a profile".to_vec()),
+        color_space: Some(ColorSpace::Linear),
+        ..Metadata::default()
+    };
+    raster::save_with_metadata(
+        &image,
+        &metadata,
+        "tests/out/test_png_metadata.png",
+        &options,
+    )
+    .unwrap();
+    let (_, saved) =
+        raster::open_with_metadata("tests/out/test_png_metadata.png", &OpenOptions::default())
+            .unwrap();
+    assert_eq!(metadata.icc_profile, saved.icc_profile);
+    assert_eq!(Some(ColorSpace::Linear), saved.color_space);
+    assert_eq!(Some(1.0), saved.gamma);
+
+    // Saving without metadata writes none
+    let mut buffer = Vec::new();
+    raster::encode(&image, &mut buffer, ImageFormat::Png).unwrap();
+    assert_eq!(vec!["IHDR", "IDAT", "IEND"], chunk_names(&buffer));
+}
+
+#[test]
+fn png_metadata_chunks_test() {
+    let mut itxt = b"Author\0\x01\0en\0Autor\0".to_vec();
+    itxt.extend_from_slice(&zlib("Zoë".as_bytes()));
+    let mut ztxt = b"Comment\0\0".to_vec();
+    ztxt.extend_from_slice(&zlib(b"caf\xE9"));
+    let mut iccp = b"sRGB profile\0\0".to_vec();
+    iccp.extend_from_slice(&zlib(b"profile"));
+    let buffer = png(&[
+        (b"tEXt", b"Title\0First".to_vec()),
+        (b"tEXt", b"Title\0Second".to_vec()),
+        (b"zTXt", ztxt),
+        (b"iTXt", itxt),
+        (b"gAMA", 100_000u32.to_be_bytes().to_vec()),
+        (b"pHYs", vec![0, 0, 0, 2, 0, 0, 0, 1, 0]),
+        (b"iCCP", iccp),
+        // Broken chunks are skipped
+        (b"tEXt", b"No keyword".to_vec()),
+        (b"zTXt", b"Bad\0\0not zlib".to_vec()),
+        (b"sRGB", vec![0, 0]),
+    ]);
+    let mut metadata = decode(&buffer);
+
+    assert_eq!(4, metadata.text.len());
+    assert_eq!(Some("First"), metadata.get_text("Title"));
+    assert_eq!(Some("café"), metadata.get_text("Comment"));
+    assert!(metadata.text[2].compressed);
+    let author = &metadata.text[3];
+    assert_eq!(
+        ("Zoë", "en", "Autor", true),
+        (
+            author.text.as_str(),
+            author.language.as_str(),
+            author.translated_keyword.as_str(),
+            author.compressed
+        )
+    );
+    assert_eq!(Some(ColorSpace::Linear), metadata.color_space);
+    assert_eq!(None, metadata.rendering_intent);
+    assert_eq!(
+        Some(Density {
+            x: 2,
+            y: 1,
+            unit: DensityUnit::Unknown
+        }),
+        metadata.density
+    );
+    assert_eq!(None, metadata.dpi());
+    assert_eq!(Some(b"profile".to_vec()), metadata.icc_profile);
+
+    // Setting a keyword replaces all of its entries
+    metadata.set_text("Title", "Third");
+    assert_eq!(
+        vec!["Third", "café", "Zoë"],
+        metadata
+            .text
+            .iter()
+            .map(|e| e.text.as_str())
+            .collect::<Vec<_>>()
+    );
+    metadata.remove_text("Comment");
+    assert_eq!(None, metadata.get_text("Comment"));
+
+    // Keywords must be 1 to 79 Latin-1 characters
+    let image = Image::blank(2, 2);
+    for keyword in &["", "Ключ", &"K".repeat(80)] {
+        let mut metadata = Metadata::default();
+        metadata.set_text(keyword, "text");
+        let options = SaveOptions::default();
+        let result =
+            raster::encode_with_metadata(&image, &metadata, Vec::new(), ImageFormat::Png, &options);
+        match result {
+            Err(RasterError::Encode(ImageFormat::Png, _)) => {}
+            _ => panic!("Expected Encode error"),
+        }
+    }
+}
@@ -0,0 +1,177 @@
+extern crate raster;
+
+use raster::Image;
+
+// The fixtures in tests/in/png are 13x7 images whose samples follow the formulas below, stored
+// with every filter type.
+const WIDTH: i32 = 13;
+const HEIGHT: i32 = 7;
+
+// Raw sample value of channel c at x, y for the given bit depth.
+fn raw(x: i32, y: i32, c: i32, depth: u32) -> u32 {
+    let v = (x * 7 + y * 13 + c * 5 + x * y) as u32;
+    if depth == 16 {
+        (v * 977) % 65536
+    } else {
+        v % (1 << depth)
+    }
+}
+
+// Expected 8 bit value of a raw sample.
+fn scale(v: u32, depth: u32) -> u8 {
+    if depth == 16 {
+        ((v * 255 + 32895) >> 16) as u8
+    } else {
+        (v * 255 / ((1 << depth) - 1)) as u8
+    }
+}
+
+fn palette_entry(i: u32) -> [u8; 3] {
+    [
+        ((i * 37) % 256) as u8,
+        ((i * 91 + 11) % 256) as u8,
+        ((i * 53 + 7) % 256) as u8,
+    ]
+}
+
+fn open(name: &str) -> Image {
+    let image = raster::open(&format!("tests/in/png/{}", name)).unwrap();
+    assert_eq!(WIDTH, image.width);
+    assert_eq!(HEIGHT, image.height);
+    assert_eq!((WIDTH * HEIGHT * 4) as usize, image.bytes.len());
+    image
+}
+
+fn check<F>(image: &Image, expected: F)
+where
+    F: Fn(i32, i32) -> [u8; 4],
+{
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let p = image.get_pixel(x, y).unwrap();
+            assert_eq!(expected(x, y), [p.r, p.g, p.b, p.a], "pixel {}, {}", x, y);
+        }
+    }
+}
+
+fn check_gray(name: &str, depth: u32, trns: bool) {
+    let image = open(name);
+    check(&image, |x, y| {
+        let v = raw(x, y, 0, depth);
+        let l = scale(v, depth);
+        let a = if trns && v == raw(1, 1, 0, depth) {
+            0
+        } else {
+            255
+        };
+        [l, l, l, a]
+    });
+}
+
+fn check_rgb(name: &str, depth: u32, trns: bool) {
+    let image = open(name);
+    check(&image, |x, y| {
+        let rgb: Vec<u32> = (0..3).map(|c| raw(x, y, c, depth)).collect();
+        let key: Vec<u32> = (0..3).map(|c| raw(1, 1, c, depth)).collect();
+        let a = if trns && rgb == key { 0 } else { 255 };
+        [
+            scale(rgb[0], depth),
+            scale(rgb[1], depth),
+            scale(rgb[2], depth),
+            a,
+        ]
+    });
+}
+
+fn check_palette(name: &str, depth: u32, trns: bool) {
+    let image = open(name);
+    let len = if depth == 1 {
+        2
+    } else {
+        std::cmp::min(1 << depth, 20)
+    };
+    check(&image, |x, y| {
+        let i = raw(x, y, 0, 8) % len;
+        let rgb = palette_entry(i);
+        let a = if trns && i < 5 { (i * 50) as u8 } else { 255 };
+        [rgb[0], rgb[1], rgb[2], a]
+    });
+}
+
+fn check_gray_alpha(name: &str, depth: u32) {
+    let image = open(name);
+    check(&image, |x, y| {
+        let l = scale(raw(x, y, 0, depth), depth);
+        [l, l, l, scale(raw(x, y, 1, depth), depth)]
+    });
+}
+
+fn check_rgba(name: &str, depth: u32) {
+    let image = open(name);
+    check(&image, |x, y| {
+        [
+            scale(raw(x, y, 0, depth), depth),
+            scale(raw(x, y, 1, depth), depth),
+            scale(raw(x, y, 2, depth), depth),
+            scale(raw(x, y, 3, depth), depth),
+        ]
+    });
+}
+
+#[test]
+fn gray_test() {
+    for &depth in &[1, 2, 4, 8, 16] {
+        check_gray(&format!("gray{}.png", depth), depth, false);
+    }
+}
+
+#[test]
+fn gray_trns_test() {
+    check_gray("gray4_trns.png", 4, true);
+    check_gray("gray16_trns.png", 16, true);
+}
+
+#[test]
+fn rgb_test() {
+    check_rgb("rgb8.png", 8, false);
+    check_rgb("rgb16.png", 16, false);
+}
+
+#[test]
+fn rgb_trns_test() {
+    check_rgb("rgb8_trns.png", 8, true);
+    check_rgb("rgb16_trns.png", 16, true);
+}
+
+#[test]
+fn palette_test() {
+    for &depth in &[1, 2, 4, 8] {
+        check_palette(&format!("palette{}.png", depth), depth, false);
+    }
+}
+
+#[test]
+fn palette_trns_test() {
+    check_palette("palette4_trns.png", 4, true);
+    check_palette("palette8_trns.png", 8, true);
+}
+
+#[test]
+fn gray_alpha_test() {
+    check_gray_alpha("gray_alpha8.png", 8);
+    check_gray_alpha("gray_alpha16.png", 16);
+}
+
+#[test]
+fn rgba_test() {
+    check_rgba("rgba8.png", 8);
+    check_rgba("rgba16.png", 16);
+}
+
+#[test]
+fn interlaced_test() {
+    check_rgb("rgb8_interlaced.png", 8, false);
+    check_gray("gray2_interlaced.png", 2, false);
+    check_palette("palette1_interlaced.png", 1, false);
+    check_rgba("rgba16_interlaced.png", 16);
+}
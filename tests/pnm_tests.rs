@@ -0,0 +1,156 @@
+extern crate raster;
+
+mod common;
+
+use raster::error::RasterError;
+use raster::{editor, ColorType, Image, ImageFormat, PnmOptions, ResizeMode, SaveOptions};
+
+use common::pixels;
+
+fn encode(image: &Image, format: ImageFormat, ascii: bool) -> Vec<u8> {
+    let options = SaveOptions {
+        pnm: PnmOptions { ascii },
+        ..SaveOptions::default()
+    };
+    let mut buffer = Vec::new();
+    raster::encode_with_options(image, &mut buffer, format, &options).unwrap();
+    buffer
+}
+
+// An opaque image small enough to write as text
+fn sample() -> Image {
+    let mut image = raster::open("tests/in/sample.jpg").unwrap();
+    editor::resize(&mut image, 40, 30, ResizeMode::Exact).unwrap();
+    image
+}
+
+#[test]
+fn pnm_roundtrip_test() {
+    // PAM keeps the alpha channel
+    let image = raster::open("tests/in/sample.png").unwrap();
+    let buffer = encode(&image, ImageFormat::Pam, false);
+    assert_eq!(Some(ImageFormat::Pam), raster::guess_format(&buffer));
+    let decoded = raster::load_from_memory(&buffer).unwrap();
+    assert_eq!(image.bytes, decoded.bytes);
+
+    let image = sample();
+    for ascii in &[false, true] {
+        let buffer = encode(&image, ImageFormat::Ppm, *ascii);
+        assert_eq!(Some(ImageFormat::Ppm), raster::guess_format(&buffer));
+        let decoded = raster::load_from_memory(&buffer).unwrap();
+        assert_eq!(image.width, decoded.width);
+        assert_eq!(image.height, decoded.height);
+        assert_eq!(image.bytes, decoded.bytes);
+    }
+}
+
+#[test]
+fn pnm_gray_test() {
+    let mut image = Image::blank(9, 3);
+    for x in 0..9 {
+        let l = (x * 30) as u8;
+        image.set_pixel(x, 1, &raster::Color::rgb(l, l, l)).unwrap();
+    }
+    image.set_pixel(2, 2, &raster::Color::white()).unwrap();
+
+    for ascii in &[false, true] {
+        let buffer = encode(&image, ImageFormat::Pgm, *ascii);
+        let decoded = raster::decode(&buffer[..], ImageFormat::Pgm).unwrap();
+        assert_eq!(image.bytes, decoded.bytes);
+
+        // Bitmaps keep only black and white
+        let buffer = encode(&image, ImageFormat::Pbm, *ascii);
+        assert_eq!(Some(ImageFormat::Pbm), raster::guess_format(&buffer));
+        let decoded = raster::decode(&buffer[..], ImageFormat::Pbm).unwrap();
+        assert_eq!([0, 0, 0, 255], pixels(&decoded)[9 + 4]);
+        assert_eq!([255, 255, 255, 255], pixels(&decoded)[9 + 5]);
+        assert_eq!([255, 255, 255, 255], pixels(&decoded)[9 * 2 + 2]);
+    }
+}
+
+#[test]
+fn pnm_plain_test() {
+    // Plain PBM samples need no whitespace, and comments can go anywhere in the header
+    let buffer = b"P1\n# A comment\n3 2\n010\n1 1 0\n";
+    let image = raster::decode(&buffer[..], ImageFormat::Pbm).unwrap();
+    let (b, w) = ([0, 0, 0, 255], [255, 255, 255, 255]);
+    assert_eq!(vec![w, b, w, b, b, w], pixels(&image));
+
+    // Samples are scaled from the maximum value
+    let buffer = b"P3 2 1 15 # comment\n15 0 5\n0 15 10";
+    let image = raster::decode(&buffer[..], ImageFormat::Ppm).unwrap();
+    assert_eq!(vec![[255, 0, 85, 255], [0, 255, 170, 255]], pixels(&image));
+
+    // Lines are at most 70 characters
+    let buffer = encode(&sample(), ImageFormat::Ppm, true);
+    assert!(buffer.split(|&b| b == b'\n').all(|line| line.len() <= 70));
+}
+
+#[test]
+fn pnm_16_bit_test() {
+    let mut buffer = b"P5\n2 1\n65535\n".to_vec();
+    buffer.extend_from_slice(&[0xFF, 0xFF, 0x80, 0x00]);
+    let image = raster::decode(&buffer[..], ImageFormat::Pgm).unwrap();
+    assert_eq!(
+        vec![[255, 255, 255, 255], [128, 128, 128, 255]],
+        pixels(&image)
+    );
+
+    let info = raster::probe_reader(&buffer[..]).unwrap();
+    assert_eq!(ImageFormat::Pgm, info.format);
+    assert_eq!(ColorType::Gray, info.color_type);
+    assert_eq!(16, info.bit_depth);
+
+    let mut buffer =
+        b"P7\nWIDTH 1\nHEIGHT 2\nDEPTH 2\nMAXVAL 1000\nTUPLTYPE GRAYSCALE_ALPHA\nENDHDR\n".to_vec();
+    buffer.extend_from_slice(&[0x03, 0xE8, 0x01, 0xF4, 0x00, 0x00, 0x03, 0xE8]);
+    let image = raster::decode(&buffer[..], ImageFormat::Pam).unwrap();
+    assert_eq!(vec![[255, 255, 255, 128], [0, 0, 0, 255]], pixels(&image));
+
+    let info = raster::probe_reader(&buffer[..]).unwrap();
+    assert_eq!(ColorType::GrayAlpha, info.color_type);
+    assert_eq!((1, 2), (info.width, info.height));
+}
+
+#[test]
+fn pam_black_and_white_test() {
+    // Unlike PBM, PAM stores white as 1
+    let mut buffer =
+        b"P7\nWIDTH 2\nHEIGHT 1\nDEPTH 1\nMAXVAL 1\nTUPLTYPE BLACKANDWHITE\nENDHDR\n".to_vec();
+    buffer.extend_from_slice(&[1, 0]);
+    let image = raster::decode(&buffer[..], ImageFormat::Pam).unwrap();
+    assert_eq!(vec![[255, 255, 255, 255], [0, 0, 0, 255]], pixels(&image));
+}
+
+#[test]
+fn pnm_save_test() {
+    let image = sample();
+    for &(file, format) in &[
+        ("tests/out/test_pnm.pbm", ImageFormat::Pbm),
+        ("tests/out/test_pnm.pgm", ImageFormat::Pgm),
+        ("tests/out/test_pnm.ppm", ImageFormat::Ppm),
+        ("tests/out/test_pnm.pam", ImageFormat::Pam),
+    ] {
+        raster::save(&image, file).unwrap();
+        let info = raster::probe(file).unwrap();
+        assert_eq!(format, info.format);
+        let saved = raster::open(file).unwrap();
+        assert_eq!(image.width, saved.width);
+        assert_eq!(image.height, saved.height);
+    }
+}
+
+#[test]
+fn pnm_fail_test() {
+    for buffer in &[
+        &b"P6\n4 4\n255\n\x00\x00\x00"[..],
+        &b"P2\n2 2\n0\n0 0 0 0"[..],
+        &b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 5\nMAXVAL 255\nENDHDR\n\x00\x00\x00\x00\x00"[..],
+        &b"P7\nWIDTH 1\nHEIGHT 1\n"[..],
+    ] {
+        match raster::load_from_memory(buffer) {
+            Err(RasterError::Decode(_, _)) => {}
+            other => panic!("Expected Decode error, got {:?}", other),
+        }
+    }
+}
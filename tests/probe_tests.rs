@@ -0,0 +1,118 @@
+extern crate raster;
+
+use std::fs;
+use std::io::{self, Read};
+
+use raster::error::RasterError;
+use raster::{ColorType, ImageFormat};
+
+#[test]
+fn probe_matches_open_test() {
+    for &(file, format) in &[
+        ("tests/in/sample.gif", ImageFormat::Gif),
+        ("tests/in/sample.jpg", ImageFormat::Jpeg),
+        ("tests/in/sample.png", ImageFormat::Png),
+        ("tests/in/misnamed-png.jpg", ImageFormat::Png),
+        ("tests/in/no-extension", ImageFormat::Gif),
+    ] {
+        let info = raster::probe(file).unwrap();
+        let image = raster::open(file).unwrap();
+        assert_eq!(format, info.format);
+        assert_eq!(image.width, info.width);
+        assert_eq!(image.height, info.height);
+
+        let animation = raster::open_animation(file).unwrap();
+        assert_eq!(animation.frames.len(), info.frames);
+    }
+}
+
+#[test]
+fn probe_png_color_types_test() {
+    for &(file, color_type, bit_depth) in &[
+        ("gray1", ColorType::Gray, 1),
+        ("gray16", ColorType::Gray, 16),
+        ("rgb8", ColorType::Rgb, 8),
+        ("gray_alpha16", ColorType::GrayAlpha, 16),
+        ("rgba8", ColorType::Rgba, 8),
+        ("palette4", ColorType::Indexed, 4),
+    ] {
+        let info = raster::probe(&format!("tests/in/png/{}.png", file)).unwrap();
+        assert_eq!(color_type, info.color_type);
+        assert_eq!(bit_depth, info.bit_depth);
+        assert_eq!(13, info.width);
+        assert_eq!(7, info.height);
+    }
+}
+
+#[test]
+fn probe_jpeg_test() {
+    let info = raster::probe("tests/in/sample.jpg").unwrap();
+    assert_eq!(ColorType::Rgb, info.color_type);
+    assert_eq!(8, info.bit_depth);
+}
+
+#[test]
+fn probe_animation_test() {
+    let info = raster::probe("tests/in/animated.gif").unwrap();
+    assert_eq!(ColorType::Indexed, info.color_type);
+    assert_eq!(11, info.width);
+    assert_eq!(29, info.height);
+    assert_eq!(3, info.frames);
+
+    let animation = raster::open_animation("tests/in/1x1anim.gif").unwrap();
+    let info = raster::probe("tests/in/1x1anim.gif").unwrap();
+    assert_eq!(animation.frames.len(), info.frames);
+
+    // Nothing after the trailer is read, and only the header is needed for the size
+    let buffer = fs::read("tests/in/animated.gif").unwrap();
+    let info = raster::probe_reader((&buffer[..]).chain(io::repeat(0))).unwrap();
+    assert_eq!(3, info.frames);
+    let info = raster::probe_reader(&buffer[..13]).unwrap();
+    assert_eq!((11, 29), (info.width, info.height));
+}
+
+#[test]
+fn probe_reader_test() {
+    let buffer = fs::read("tests/in/sample.png").unwrap();
+    let info = raster::probe_reader(&buffer[..]).unwrap();
+    assert_eq!(ImageFormat::Png, info.format);
+
+    // Only the header is needed
+    let info = raster::probe_reader(&buffer[..33]).unwrap();
+    assert_eq!(ImageFormat::Png, info.format);
+
+    // Readers that return less than asked still have the whole signature read
+    let info = raster::probe_reader(Trickle(&buffer[..])).unwrap();
+    assert_eq!(ImageFormat::Png, info.format);
+
+    let buffer = fs::read("tests/in/unsupported.txt").unwrap();
+    match raster::probe_reader(&buffer[..]) {
+        Err(RasterError::UnsupportedFormat(_)) => {}
+        _ => panic!("Expected UnsupportedFormat"),
+    }
+}
+
+#[test]
+fn probe_fail_test() {
+    for &(file, format) in &[
+        ("tests/in/not-a-gif.gif", ImageFormat::Gif),
+        ("tests/in/not-a-jpeg.jpg", ImageFormat::Jpeg),
+        ("tests/in/not-a-png.png", ImageFormat::Png),
+    ] {
+        match raster::probe(file) {
+            Err(RasterError::Decode(f, _)) => assert_eq!(format, f),
+            Err(RasterError::Io(_)) => {}
+            other => panic!("Expected Decode error, got {:?}", other),
+        }
+    }
+}
+
+// A reader that returns one byte at a time.
+struct Trickle<R>(R);
+
+impl<R: Read> Read for Trickle<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len().min(1);
+        self.0.read(&mut buf[..len])
+    }
+}
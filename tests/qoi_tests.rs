@@ -0,0 +1,125 @@
+extern crate raster;
+
+use raster::error::RasterError;
+use raster::{
+    ColorSpace, ColorType, Image, ImageFormat, Limits, OpenOptions, QoiOptions, SaveOptions,
+};
+
+// A QOI file from its header fields and chunks, followed by the end marker
+fn qoi(width: u32, height: u32, channels: u8, color_space: u8, chunks: &[u8]) -> Vec<u8> {
+    let mut buffer = b"qoif".to_vec();
+    buffer.extend_from_slice(&width.to_be_bytes());
+    buffer.extend_from_slice(&height.to_be_bytes());
+    buffer.extend_from_slice(&[channels, color_space]);
+    buffer.extend_from_slice(chunks);
+    buffer.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1]);
+    buffer
+}
+
+#[test]
+fn qoi_roundtrip_test() {
+    // A gradient with changing alpha exercises every chunk type
+    let mut gradient = Image::blank(40, 30);
+    for (i, pixel) in gradient.bytes.chunks_mut(4).enumerate() {
+        let (x, y) = ((i % 40) as u8, (i / 40) as u8);
+        pixel.copy_from_slice(&[x * 6, y * 3, x ^ y, if x < 20 { 255 } else { y * 8 }]);
+    }
+    for image in &[raster::open("tests/in/sample.png").unwrap(), gradient] {
+        let mut buffer = Vec::new();
+        raster::encode(image, &mut buffer, ImageFormat::Qoi).unwrap();
+        assert_eq!(Some(ImageFormat::Qoi), raster::guess_format(&buffer));
+
+        let decoded = raster::decode(&buffer[..], ImageFormat::Qoi).unwrap();
+        assert_eq!(image.width, decoded.width);
+        assert_eq!(image.height, decoded.height);
+        assert_eq!(image.bytes, decoded.bytes);
+    }
+}
+
+#[test]
+fn qoi_chunks_test() {
+    // RGB, DIFF, LUMA, INDEX, RUN and RGBA chunks
+    let chunks = [
+        0xFE, 10, 20, 30, 0x76, 0xA5, 0x6B, 0x09, 0xC0, 0xFF, 1, 2, 3, 4,
+    ];
+    let buffer = qoi(3, 2, 4, 0, &chunks);
+    let image = raster::decode(&buffer[..], ImageFormat::Qoi).unwrap();
+    let expected = [
+        10, 20, 30, 255, 11, 19, 30, 255, 14, 24, 38, 255, // first row
+        10, 20, 30, 255, 10, 20, 30, 255, 1, 2, 3, 4, // second row
+    ];
+    assert_eq!(&expected[..], &image.bytes[..]);
+
+    // The encoder picks the same chunks
+    let mut encoded = Vec::new();
+    raster::encode(&image, &mut encoded, ImageFormat::Qoi).unwrap();
+    assert_eq!(buffer, encoded);
+}
+
+#[test]
+fn qoi_color_space_test() {
+    let image = raster::open("tests/in/sample.jpg").unwrap();
+    let options = SaveOptions {
+        qoi: QoiOptions {
+            color_space: ColorSpace::Linear,
+        },
+        ..SaveOptions::default()
+    };
+    raster::save_with_options(&image, "tests/out/test_qoi_linear.qoi", &options).unwrap();
+
+    let (saved, metadata) =
+        raster::open_with_metadata("tests/out/test_qoi_linear.qoi", &OpenOptions::default())
+            .unwrap();
+    assert_eq!(Some(ColorSpace::Linear), metadata.color_space);
+    assert_eq!(image.bytes, saved.bytes);
+
+    let mut buffer = Vec::new();
+    raster::encode(&image, &mut buffer, ImageFormat::Qoi).unwrap();
+    let (_, metadata) =
+        raster::decode_with_metadata(&buffer[..], ImageFormat::Qoi, &OpenOptions::default())
+            .unwrap();
+    assert_eq!(Some(ColorSpace::Srgb), metadata.color_space);
+}
+
+#[test]
+fn qoi_probe_test() {
+    // Opaque images are marked as RGB, transparent ones as RGBA
+    let mut image = raster::open("tests/in/sample.jpg").unwrap();
+    let mut buffer = Vec::new();
+    raster::encode(&image, &mut buffer, ImageFormat::Qoi).unwrap();
+    let info = raster::probe_reader(&buffer[..]).unwrap();
+    assert_eq!(ImageFormat::Qoi, info.format);
+    assert_eq!(ColorType::Rgb, info.color_type);
+    assert_eq!((image.width, image.height), (info.width, info.height));
+
+    image.bytes[3] = 0;
+    raster::save(&image, "tests/out/test_qoi_alpha.qoi").unwrap();
+    let info = raster::probe("tests/out/test_qoi_alpha.qoi").unwrap();
+    assert_eq!(ColorType::Rgba, info.color_type);
+    assert_eq!(8, info.bit_depth);
+}
+
+#[test]
+fn qoi_fail_test() {
+    let truncated = qoi(4, 4, 3, 0, &[0xFE, 1, 2, 3]);
+    let invalid = qoi(1, 1, 2, 0, &[0xFE, 1, 2, 3]);
+    for buffer in &[&truncated[..20], &invalid[..]] {
+        match raster::decode(*buffer, ImageFormat::Qoi) {
+            Err(RasterError::Decode(ImageFormat::Qoi, _)) => {}
+            _ => panic!("Expected Decode error"),
+        }
+    }
+
+    let options = OpenOptions {
+        limits: Limits {
+            max_width: Some(2),
+            ..Limits::default()
+        },
+        ..OpenOptions::default()
+    };
+    let buffer = qoi(3, 1, 3, 0, &[0xC2]);
+    match raster::decode_with_options(&buffer[..], ImageFormat::Qoi, &options) {
+        Err(RasterError::LimitsExceeded(_)) => {}
+        _ => panic!("Expected LimitsExceeded error"),
+    }
+}
@@ -0,0 +1,174 @@
+extern crate raster;
+
+use raster::error::RasterError;
+use raster::{editor, quantize, Color, Dithering, Image, QuantizeMethod, ResizeMode};
+
+// A horizontal gray ramp
+fn ramp() -> Image {
+    let mut image = Image::blank(64, 16);
+    for y in 0..16 {
+        for x in 0..64 {
+            let l = (x * 4) as u8;
+            image.set_pixel(x, y, &Color::rgba(l, l, l, 255)).unwrap();
+        }
+    }
+    image
+}
+
+// Mean absolute difference of the color channels
+fn mean_difference(a: &Image, b: &Image) -> f64 {
+    let sum: u64 = a
+        .bytes
+        .chunks(4)
+        .zip(b.bytes.chunks(4))
+        .map(|(p, q)| {
+            (0..3)
+                .map(|c| (p[c] as i64 - q[c] as i64).unsigned_abs())
+                .sum::<u64>()
+        })
+        .sum();
+    sum as f64 / (a.width * a.height * 3) as f64
+}
+
+fn mean_intensity(image: &Image) -> f64 {
+    let sum: u64 = image.bytes.chunks(4).map(|p| p[0] as u64).sum();
+    sum as f64 / (image.width * image.height) as f64
+}
+
+#[test]
+fn quantize_methods_test() {
+    let mut image = raster::open("tests/in/sample.jpg").unwrap();
+    editor::resize(&mut image, 160, 160, ResizeMode::Fit).unwrap();
+    for method in &[
+        QuantizeMethod::MedianCut,
+        QuantizeMethod::Octree,
+        QuantizeMethod::KMeans,
+    ] {
+        let indexed = quantize::quantize(&image, 16, *method, Dithering::None).unwrap();
+        assert!(indexed.palette.len() <= 16);
+        assert!(indexed.palette.len() > 1);
+        assert_eq!((image.width * image.height) as usize, indexed.indices.len());
+
+        let quantized = indexed.to_image().unwrap();
+        assert_eq!(image.width, quantized.width);
+        assert_eq!(image.height, quantized.height);
+        assert!(mean_difference(&image, &quantized) < 20.0);
+
+        // The same image always gives the same palette
+        let again = quantize::quantize(&image, 16, *method, Dithering::None).unwrap();
+        assert_eq!(indexed.indices, again.indices);
+    }
+}
+
+#[test]
+fn quantize_few_colors_test() {
+    let mut image = Image::blank(4, 4);
+    image.set_pixel(1, 1, &Color::red()).unwrap();
+    image.set_pixel(2, 2, &Color::blue()).unwrap();
+
+    for method in &[
+        QuantizeMethod::MedianCut,
+        QuantizeMethod::Octree,
+        QuantizeMethod::KMeans,
+    ] {
+        let indexed = quantize::quantize(&image, 4, *method, Dithering::FloydSteinberg).unwrap();
+        assert_eq!(3, indexed.palette.len());
+        assert_eq!(image.bytes, indexed.to_image().unwrap().bytes);
+    }
+}
+
+#[test]
+fn quantize_transparency_test() {
+    let mut image = raster::open("tests/in/sample.png").unwrap();
+    for x in 0..10 {
+        image
+            .set_pixel(x, 0, &Color::rgba(255, 255, 255, 0))
+            .unwrap();
+    }
+
+    let indexed = quantize::quantize(&image, 8, QuantizeMethod::Octree, Dithering::None).unwrap();
+    assert!(indexed.palette.len() <= 8);
+
+    let transparent: Vec<usize> = indexed
+        .palette
+        .iter()
+        .enumerate()
+        .filter(|&(_, color)| color.a == 0)
+        .map(|(i, _)| i)
+        .collect();
+    assert_eq!(1, transparent.len());
+    for x in 0..10 {
+        assert_eq!(transparent[0], indexed.indices[x] as usize);
+    }
+}
+
+#[test]
+fn dithering_test() {
+    let image = ramp();
+    let palette = vec![Color::black(), Color::white()];
+
+    // Without dithering the ramp becomes a hard edge, with dithering the average brightness is
+    // kept
+    for dithering in &[
+        Dithering::FloydSteinberg,
+        Dithering::Atkinson,
+        Dithering::Bayer,
+    ] {
+        let dithered = quantize::remap(&image, &palette, *dithering)
+            .unwrap()
+            .to_image()
+            .unwrap();
+        assert!((mean_intensity(&image) - mean_intensity(&dithered)).abs() < 8.0);
+
+        // Some white pixels end up in the dark half
+        let white = (0..16)
+            .flat_map(|y| (0..32).map(move |x| (x, y)))
+            .filter(|&(x, y)| dithered.get_pixel(x, y).unwrap().r == 255)
+            .count();
+        assert!(white > 0);
+    }
+
+    let plain = quantize::remap(&image, &palette, Dithering::None)
+        .unwrap()
+        .to_image()
+        .unwrap();
+    for y in 0..16 {
+        assert_eq!(0, plain.get_pixel(31, y).unwrap().r);
+        assert_eq!(255, plain.get_pixel(32, y).unwrap().r);
+    }
+}
+
+#[test]
+fn invalid_palette_size_test() {
+    let image = ramp();
+    for colors in &[0, 1, 257] {
+        match quantize::palette(&image, *colors, QuantizeMethod::MedianCut) {
+            Err(RasterError::InvalidPaletteSize(size)) => assert_eq!(*colors, size),
+            _ => panic!("Expected InvalidPaletteSize"),
+        }
+    }
+}
+
+#[test]
+fn empty_palette_test() {
+    let image = ramp();
+    for dithering in &[
+        Dithering::None,
+        Dithering::FloydSteinberg,
+        Dithering::Atkinson,
+        Dithering::Bayer,
+    ] {
+        match quantize::remap(&image, &[], *dithering) {
+            Err(RasterError::InvalidPaletteSize(0)) => {}
+            _ => panic!("Expected InvalidPaletteSize"),
+        }
+    }
+
+    // Indices outside of the palette are an error rather than a panic
+    let mut indexed = quantize::remap(&image, &[Color::black()], Dithering::None).unwrap();
+    indexed.palette.clear();
+    match indexed.to_image() {
+        Err(RasterError::InvalidPaletteSize(0)) => {}
+        _ => panic!("Expected InvalidPaletteSize"),
+    }
+}
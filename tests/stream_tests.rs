@@ -0,0 +1,232 @@
+extern crate raster;
+
+use raster::error::RasterError;
+use raster::{filter, Image, ImageFormat, Limits, OpenOptions, PngFilter, PngOptions};
+use raster::{RowReader, SaveOptions};
+
+// Read every row of a row reader into an image
+fn read_all(reader: &mut dyn RowReader) -> Image {
+    let mut image = Image::blank(reader.width(), reader.height());
+    let len = reader.width() as usize * 4;
+    for row in image.bytes.chunks_mut(len) {
+        assert!(reader.read_row(row).unwrap());
+    }
+    let mut row = vec![0; len];
+    assert!(!reader.read_row(&mut row).unwrap());
+    image
+}
+
+// An image of pseudo-random pixels, which compresses badly
+fn noise(width: i32, height: i32) -> Image {
+    let mut image = Image::blank(width, height);
+    let mut seed = 1u32;
+    for v in image.bytes.iter_mut() {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        *v = (seed >> 16) as u8;
+    }
+    image
+}
+
+// Encode an image a row at a time
+fn encode(image: &Image, format: ImageFormat, options: &SaveOptions) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    {
+        let mut writer =
+            raster::encode_rows(&mut buffer, format, image.width, image.height, options).unwrap();
+        for row in image.bytes.chunks(image.width as usize * 4) {
+            writer.write_row(row).unwrap();
+        }
+        writer.finish().unwrap();
+    }
+    buffer
+}
+
+#[test]
+fn stream_png_test() {
+    // Every color type, bit depth, filter type and interlacing
+    let options = OpenOptions::default();
+    for entry in std::fs::read_dir("tests/in/png").unwrap() {
+        let path = entry.unwrap().path();
+        let path = path.to_str().unwrap();
+        let image = raster::open(path).unwrap();
+        let mut reader = raster::open_rows(path, &options).unwrap();
+        assert_eq!(image.bytes, read_all(&mut *reader).bytes, "{}", path);
+    }
+
+    // Large enough for several IDAT chunks
+    let image = noise(300, 200);
+    let buffer = encode(&image, ImageFormat::Png, &SaveOptions::default());
+    assert!(buffer.windows(4).filter(|name| name == b"IDAT").count() > 1);
+    let decoded = raster::decode(&buffer[..], ImageFormat::Png).unwrap();
+    assert!(image.bytes == decoded.bytes);
+    let mut reader = raster::decode_rows(&buffer[..], ImageFormat::Png, &options).unwrap();
+    assert_eq!((300, 200), (reader.width(), reader.height()));
+    assert_eq!(image.bytes, read_all(&mut *reader).bytes);
+
+    // Every filter option
+    let image = raster::open("tests/in/sample.png").unwrap();
+    for &filter in &[PngFilter::None, PngFilter::Paeth, PngFilter::Adaptive] {
+        let options = SaveOptions {
+            png: PngOptions {
+                filter,
+                ..PngOptions::default()
+            },
+            ..SaveOptions::default()
+        };
+        let buffer = encode(&image, ImageFormat::Png, &options);
+        let decoded = raster::decode(&buffer[..], ImageFormat::Png).unwrap();
+        assert!(image.bytes == decoded.bytes, "{:?}", filter);
+    }
+
+    // Only the rows count against the memory limit
+    let options = OpenOptions {
+        limits: Limits {
+            max_alloc: Some(10_000),
+            ..Limits::default()
+        },
+        ..OpenOptions::default()
+    };
+    assert!(raster::open_with_options("tests/in/sample.png", &options).is_err());
+    let mut reader = raster::open_rows("tests/in/sample.png", &options).unwrap();
+    assert_eq!(image.bytes, read_all(&mut *reader).bytes);
+}
+
+#[test]
+fn stream_formats_test() {
+    let image = noise(37, 11);
+    let options = OpenOptions {
+        raw_dimensions: Some((37, 11)),
+        ..OpenOptions::default()
+    };
+    for &format in &[
+        ImageFormat::Farbfeld,
+        ImageFormat::Raw,
+        ImageFormat::Bmp,
+        ImageFormat::Qoi,
+    ] {
+        let buffer = encode(&image, format, &SaveOptions::default());
+        let mut whole = Vec::new();
+        raster::encode(&image, &mut whole, format).unwrap();
+        assert_eq!(whole, buffer, "{:?}", format);
+
+        let mut reader = raster::decode_rows(&buffer[..], format, &options).unwrap();
+        assert_eq!(image.bytes, read_all(&mut *reader).bytes, "{:?}", format);
+    }
+}
+
+#[test]
+fn stream_bands_test() {
+    let mut expected = raster::open("tests/in/sample.png").unwrap();
+    filter::grayscale(&mut expected).unwrap();
+    filter::gamma(&mut expected, 2.0).unwrap();
+
+    let mut reader = raster::open_rows("tests/in/sample.png", &OpenOptions::default()).unwrap();
+    let (width, height) = (reader.width(), reader.height());
+    let mut writer = raster::create_rows(
+        "tests/out/test_stream_bands.png",
+        width,
+        height,
+        &SaveOptions::default(),
+    )
+    .unwrap();
+    let mut bands = 0;
+    raster::process_bands(&mut reader, &mut writer, 7, |band| {
+        bands += 1;
+        assert_eq!(width, band.width);
+        assert!(band.height <= 7);
+        filter::grayscale(band)?;
+        filter::gamma(band, 2.0)
+    })
+    .unwrap();
+    assert_eq!((height + 6) / 7, bands);
+
+    let image = raster::open("tests/out/test_stream_bands.png").unwrap();
+    assert_eq!(expected.bytes, image.bytes);
+
+    // A band that changes size can't be written
+    let mut reader = raster::open_rows("tests/in/sample.png", &OpenOptions::default()).unwrap();
+    let mut writer = raster::encode_rows(
+        Vec::new(),
+        ImageFormat::Png,
+        width,
+        height,
+        &SaveOptions::default(),
+    )
+    .unwrap();
+    let result = raster::process_bands(&mut reader, &mut writer, 16, |band| {
+        raster::editor::crop(band, 10, 10, raster::PositionMode::TopLeft, 0, 0)
+    });
+    match result {
+        Err(RasterError::Encode(ImageFormat::Png, _)) => {}
+        _ => panic!("Expected Encode error"),
+    }
+}
+
+#[test]
+fn stream_fail_test() {
+    let image = noise(20, 10);
+    let buffer = encode(&image, ImageFormat::Png, &SaveOptions::default());
+    let options = OpenOptions::default();
+
+    // Truncated and corrupt image data fail on the row they are found in
+    let truncated = &buffer[..buffer.len() / 2];
+    let mut reader = raster::decode_rows(truncated, ImageFormat::Png, &options).unwrap();
+    let mut row = vec![0; 20 * 4];
+    let mut result = Ok(true);
+    while let Ok(true) = result {
+        result = reader.read_row(&mut row);
+    }
+    match result {
+        Err(RasterError::Decode(ImageFormat::Png, _)) => {}
+        _ => panic!("Expected Decode error"),
+    }
+    let mut corrupt = buffer.clone();
+    corrupt[100] ^= 1;
+    let mut reader = raster::decode_rows(&corrupt[..], ImageFormat::Png, &options).unwrap();
+    let mut result = Ok(true);
+    while let Ok(true) = result {
+        result = reader.read_row(&mut row);
+    }
+    match result {
+        Err(RasterError::Decode(ImageFormat::Png, _)) => {}
+        _ => panic!("Expected Decode error"),
+    }
+
+    // Rows of the wrong length
+    let mut reader = raster::decode_rows(&buffer[..], ImageFormat::Png, &options).unwrap();
+    match reader.read_row(&mut [0; 4]) {
+        Err(RasterError::Decode(ImageFormat::Png, _)) => {}
+        _ => panic!("Expected Decode error"),
+    }
+
+    let options = SaveOptions::default();
+    for &format in &[ImageFormat::Png, ImageFormat::Farbfeld, ImageFormat::Tga] {
+        let mut writer = raster::encode_rows(Vec::new(), format, 2, 2, &options).unwrap();
+        let errors = vec![
+            writer.write_row(&[0; 4]).err(),
+            writer.write_row(&[0; 8]).and(writer.finish()).err(),
+            writer
+                .write_row(&[0; 8])
+                .and(writer.write_row(&[0; 8]))
+                .err(),
+        ];
+        for error in errors {
+            match error {
+                Some(RasterError::Encode(f, _)) if f == format => {}
+                _ => panic!("Expected Encode error"),
+            }
+        }
+    }
+
+    let options = SaveOptions {
+        png: PngOptions {
+            interlaced: true,
+            ..PngOptions::default()
+        },
+        ..SaveOptions::default()
+    };
+    match raster::encode_rows(Vec::new(), ImageFormat::Png, 2, 2, &options) {
+        Err(RasterError::Encode(ImageFormat::Png, _)) => {}
+        _ => panic!("Expected Encode error"),
+    }
+}
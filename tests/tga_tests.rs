@@ -0,0 +1,168 @@
+extern crate raster;
+
+use raster::error::RasterError;
+use raster::{ColorType, Image, ImageFormat, SaveOptions, TgaOptions};
+
+// A TGA file from its header fields, color map and pixel data
+fn tga(
+    image_type: u8,
+    map: (u16, u16, u8),
+    size: (u16, u16),
+    depth: u8,
+    descriptor: u8,
+    data: &[u8],
+) -> Vec<u8> {
+    let (first, len, map_depth) = map;
+    let mut buffer = vec![0, (len > 0) as u8, image_type];
+    buffer.extend_from_slice(&first.to_le_bytes());
+    buffer.extend_from_slice(&len.to_le_bytes());
+    buffer.extend_from_slice(&[map_depth, 0, 0, 0, 0]);
+    buffer.extend_from_slice(&size.0.to_le_bytes());
+    buffer.extend_from_slice(&size.1.to_le_bytes());
+    buffer.extend_from_slice(&[depth, descriptor]);
+    buffer.extend_from_slice(data);
+    buffer
+}
+
+fn decode(buffer: &[u8]) -> Vec<[u8; 4]> {
+    let image = raster::decode(buffer, ImageFormat::Tga).unwrap();
+    image
+        .bytes
+        .chunks(4)
+        .map(|p| [p[0], p[1], p[2], p[3]])
+        .collect()
+}
+
+const BLACK: [u8; 4] = [0, 0, 0, 255];
+const WHITE: [u8; 4] = [255, 255, 255, 255];
+const RED: [u8; 4] = [255, 0, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+#[test]
+fn tga_roundtrip_test() {
+    let mut transparent = raster::open("tests/in/sample.jpg").unwrap();
+    transparent.bytes[3] = 0;
+    for image in &[raster::open("tests/in/sample.png").unwrap(), transparent] {
+        let mut sizes = Vec::new();
+        for rle in &[false, true] {
+            let options = SaveOptions {
+                tga: TgaOptions { rle: *rle },
+                ..SaveOptions::default()
+            };
+            let mut buffer = Vec::new();
+            raster::encode_with_options(image, &mut buffer, ImageFormat::Tga, &options).unwrap();
+            let decoded = raster::decode(&buffer[..], ImageFormat::Tga).unwrap();
+            assert_eq!(image.width, decoded.width);
+            assert_eq!(image.height, decoded.height);
+            assert_eq!(image.bytes, decoded.bytes);
+            sizes.push(buffer.len());
+        }
+        assert!(sizes[1] < sizes[0]);
+    }
+}
+
+#[test]
+fn tga_origin_test() {
+    let data = [0, 0, 255, 255, 0, 0, 255, 255, 255, 0, 0, 0];
+
+    // Bottom left origin, the default
+    let buffer = tga(2, (0, 0, 0), (2, 2), 24, 0, &data);
+    assert_eq!(vec![WHITE, BLACK, RED, BLUE], decode(&buffer));
+
+    // Top left origin
+    let buffer = tga(2, (0, 0, 0), (2, 2), 24, 0x20, &data);
+    assert_eq!(vec![RED, BLUE, WHITE, BLACK], decode(&buffer));
+
+    // Top right origin
+    let buffer = tga(2, (0, 0, 0), (2, 2), 24, 0x30, &data);
+    assert_eq!(vec![BLUE, RED, BLACK, WHITE], decode(&buffer));
+}
+
+#[test]
+fn tga_color_mapped_test() {
+    // A color map starting at index 1, with RLE packets that cross rows
+    let mut data = vec![255, 255, 255, 0, 0, 255, 255, 0, 0];
+    data.extend_from_slice(&[0x82, 2, 0x02, 1, 3, 2]);
+    let buffer = tga(9, (1, 3, 24), (3, 2), 8, 0x20, &data);
+    assert_eq!(vec![RED, RED, RED, WHITE, BLUE, RED], decode(&buffer));
+}
+
+#[test]
+fn tga_16_bit_test() {
+    // ARGB 1555 with one alpha bit: opaque red, then transparent blue
+    let data = [0x00, 0xFC, 0x1F, 0x00];
+    let buffer = tga(2, (0, 0, 0), (2, 1), 16, 0x21, &data);
+    assert_eq!(vec![RED, [0, 0, 255, 0]], decode(&buffer));
+
+    // Without alpha bits the top bit is ignored
+    let buffer = tga(2, (0, 0, 0), (2, 1), 16, 0x20, &data);
+    assert_eq!(vec![RED, BLUE], decode(&buffer));
+}
+
+#[test]
+fn tga_grayscale_test() {
+    // RLE grayscale: a run of 3 then 1 raw pixel
+    let buffer = tga(11, (0, 0, 0), (2, 2), 8, 0x20, &[0x82, 128, 0x00, 64]);
+    let gray = [128, 128, 128, 255];
+    assert_eq!(vec![gray, gray, gray, [64, 64, 64, 255]], decode(&buffer));
+
+    // Gray with alpha
+    let buffer = tga(3, (0, 0, 0), (1, 1), 16, 0x28, &[200, 100]);
+    assert_eq!(vec![[200, 200, 200, 100]], decode(&buffer));
+}
+
+#[test]
+fn tga_save_test() {
+    let image = raster::open("tests/in/sample.png").unwrap();
+    raster::save(&image, "tests/out/test_tga.tga").unwrap();
+
+    let info = raster::probe("tests/out/test_tga.tga").unwrap();
+    assert_eq!(ImageFormat::Tga, info.format);
+    assert_eq!(image.width, info.width);
+    assert_eq!(image.height, info.height);
+
+    let saved = raster::open("tests/out/test_tga.tga").unwrap();
+    assert_eq!(image.bytes, saved.bytes);
+}
+
+#[test]
+fn tga_fail_test() {
+    let buffer = tga(2, (0, 0, 0), (4, 4), 24, 0, &[0; 20]);
+    match raster::decode(&buffer[..], ImageFormat::Tga) {
+        Err(RasterError::Decode(ImageFormat::Tga, _)) => {}
+        _ => panic!("Expected Decode error"),
+    }
+
+    let buffer = tga(2, (0, 0, 0), (1, 1), 12, 0, &[0; 2]);
+    assert!(raster::decode(&buffer[..], ImageFormat::Tga).is_err());
+
+    // The image ID runs past the end of the file
+    let mut buffer = tga(2, (0, 0, 0), (1, 1), 24, 0, &[0; 2]);
+    buffer[0] = 200;
+    match raster::decode(&buffer[..], ImageFormat::Tga) {
+        Err(RasterError::Decode(ImageFormat::Tga, _)) => {}
+        _ => panic!("Expected Decode error"),
+    }
+
+    let image = Image::blank(70000, 1);
+    let mut buffer = Vec::new();
+    match raster::encode(&image, &mut buffer, ImageFormat::Tga) {
+        Err(RasterError::Encode(ImageFormat::Tga, _)) => {}
+        _ => panic!("Expected Encode error"),
+    }
+}
+
+#[test]
+fn tga_probe_test() {
+    // TGA has no signature, so it is only recognized by its file extension
+    let buffer = tga(10, (0, 0, 0), (3, 5), 32, 0x28, &[]);
+    match raster::probe_reader(&buffer[..]) {
+        Err(RasterError::UnsupportedFormat(_)) => {}
+        _ => panic!("Expected UnsupportedFormat"),
+    }
+
+    raster::save(&Image::blank(3, 5), "tests/out/test_tga_probe.tga").unwrap();
+    let info = raster::probe("tests/out/test_tga_probe.tga").unwrap();
+    assert_eq!(ColorType::Rgb, info.color_type);
+    assert_eq!((3, 5), (info.width, info.height));
+}
@@ -0,0 +1,310 @@
+extern crate raster;
+
+use raster::error::RasterError;
+use raster::{ColorType, Image, ImageFormat, SaveOptions, TiffCompression, TiffOptions};
+
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+
+// A single page TIFF with the given fields, in either byte order. The image data is stored right
+// after the header and strip or tile offsets are relative to it.
+fn tiff(big_endian: bool, fields: &[(u16, u16, Vec<u32>)], data: &[u8]) -> Vec<u8> {
+    let u16_bytes = |v: u16| {
+        if big_endian {
+            v.to_be_bytes()
+        } else {
+            v.to_le_bytes()
+        }
+    };
+    let u32_bytes = |v: u32| {
+        if big_endian {
+            v.to_be_bytes()
+        } else {
+            v.to_le_bytes()
+        }
+    };
+
+    let mut buffer = if big_endian {
+        b"MM\0*".to_vec()
+    } else {
+        b"II*\0".to_vec()
+    };
+    let ifd = 8 + data.len() + data.len() % 2;
+    buffer.extend_from_slice(&u32_bytes(ifd as u32));
+    buffer.extend_from_slice(data);
+    buffer.resize(ifd, 0);
+
+    let mut extra_pos = ifd + 2 + fields.len() * 12 + 4;
+    let mut extra = Vec::new();
+    buffer.extend_from_slice(&u16_bytes(fields.len() as u16));
+    for (tag, kind, values) in fields {
+        let mut bytes = Vec::new();
+        for &value in values {
+            let value = if *tag == 273 || *tag == 324 {
+                value + 8
+            } else {
+                value
+            };
+            if *kind == SHORT {
+                bytes.extend_from_slice(&u16_bytes(value as u16));
+            } else {
+                bytes.extend_from_slice(&u32_bytes(value));
+            }
+        }
+        buffer.extend_from_slice(&u16_bytes(*tag));
+        buffer.extend_from_slice(&u16_bytes(*kind));
+        buffer.extend_from_slice(&u32_bytes(values.len() as u32));
+        if bytes.len() <= 4 {
+            bytes.resize(4, 0);
+            buffer.extend_from_slice(&bytes);
+        } else {
+            buffer.extend_from_slice(&u32_bytes(extra_pos as u32));
+            extra_pos += bytes.len();
+            extra.extend_from_slice(&bytes);
+        }
+    }
+    buffer.extend_from_slice(&[0; 4]);
+    buffer.extend_from_slice(&extra);
+    buffer
+}
+
+fn decode(buffer: &[u8]) -> Vec<[u8; 4]> {
+    let image = raster::decode(buffer, ImageFormat::Tiff).unwrap();
+    image
+        .bytes
+        .chunks(4)
+        .map(|p| [p[0], p[1], p[2], p[3]])
+        .collect()
+}
+
+// An image of pseudo-random colors, which compresses badly
+fn noise(width: i32, height: i32) -> Image {
+    let mut image = Image::blank(width, height);
+    let mut seed = 1u32;
+    for pixel in image.bytes.chunks_mut(4) {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        pixel[..3].copy_from_slice(&seed.to_be_bytes()[..3]);
+    }
+    image
+}
+
+const BLACK: [u8; 4] = [0, 0, 0, 255];
+const WHITE: [u8; 4] = [255, 255, 255, 255];
+const RED: [u8; 4] = [255, 0, 0, 255];
+const BLUE: [u8; 4] = [0, 0, 255, 255];
+
+#[test]
+fn tiff_roundtrip_test() {
+    let mut transparent = raster::open("tests/in/sample.jpg").unwrap();
+    transparent.bytes[3] = 0;
+    transparent.bytes[7] = 128;
+    let images = [
+        raster::open("tests/in/sample.png").unwrap(),
+        transparent,
+        noise(128, 64),
+    ];
+    for image in &images {
+        for compression in &[
+            TiffCompression::None,
+            TiffCompression::PackBits,
+            TiffCompression::Lzw,
+            TiffCompression::Deflate,
+        ] {
+            let options = SaveOptions {
+                tiff: TiffOptions {
+                    compression: *compression,
+                },
+                ..SaveOptions::default()
+            };
+            let mut buffer = Vec::new();
+            raster::encode_with_options(image, &mut buffer, ImageFormat::Tiff, &options).unwrap();
+            assert_eq!(Some(ImageFormat::Tiff), raster::guess_format(&buffer));
+
+            let decoded = raster::decode(&buffer[..], ImageFormat::Tiff).unwrap();
+            assert_eq!(image.width, decoded.width);
+            assert_eq!(image.height, decoded.height);
+            assert_eq!(image.bytes, decoded.bytes);
+        }
+    }
+}
+
+#[test]
+fn tiff_pages_test() {
+    let pages = [
+        raster::open("tests/in/sample.png").unwrap(),
+        Image::blank(3, 5),
+        noise(7, 2),
+    ];
+    raster::save_all(&pages, "tests/out/test_tiff_pages.tif").unwrap();
+
+    let images = raster::open_all("tests/out/test_tiff_pages.tif").unwrap();
+    assert_eq!(pages.len(), images.len());
+    for (page, image) in pages.iter().zip(&images) {
+        assert_eq!((page.width, page.height), (image.width, image.height));
+        assert_eq!(page.bytes, image.bytes);
+    }
+
+    // A single image is the first page
+    let image = raster::open("tests/out/test_tiff_pages.tif").unwrap();
+    assert_eq!(pages[0].bytes, image.bytes);
+
+    let info = raster::probe("tests/out/test_tiff_pages.tif").unwrap();
+    assert_eq!(ImageFormat::Tiff, info.format);
+    assert_eq!(ColorType::Rgb, info.color_type);
+    assert_eq!((pages[0].width, pages[0].height), (info.width, info.height));
+    assert_eq!(3, info.frames);
+}
+
+#[test]
+fn tiff_gray_test() {
+    // 16 bit big endian in two strips, the second one shorter
+    let fields = vec![
+        (256, SHORT, vec![2]),
+        (257, SHORT, vec![3]),
+        (258, SHORT, vec![16]),
+        (262, SHORT, vec![1]),
+        (273, LONG, vec![0, 8]),
+        (278, SHORT, vec![2]),
+        (279, LONG, vec![8, 4]),
+    ];
+    let data = [0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0x80, 0x00, 0x7F, 0x00];
+    let buffer = tiff(true, &fields, &data);
+    let gray = |v| [v, v, v, 255];
+    assert_eq!(
+        vec![BLACK, WHITE, WHITE, BLACK, gray(128), gray(127)],
+        decode(&buffer)
+    );
+    let info = raster::probe_reader(&buffer[..]).unwrap();
+    assert_eq!((ColorType::Gray, 16), (info.color_type, info.bit_depth));
+
+    // 1 bit white-is-zero, PackBits compressed: a run of 2 bytes and 4 literal bytes
+    let fields = vec![
+        (256, SHORT, vec![10]),
+        (257, SHORT, vec![3]),
+        (258, SHORT, vec![1]),
+        (259, SHORT, vec![32773]),
+        (262, SHORT, vec![0]),
+        (273, LONG, vec![0]),
+        (279, LONG, vec![7]),
+    ];
+    let data = [0xFF, 0x00, 0x03, 0x80, 0x40, 0xC0, 0x00];
+    let pixels = decode(&tiff(false, &fields, &data));
+    assert_eq!(vec![WHITE; 10], pixels[..10].to_vec());
+    assert_eq!(vec![BLACK, WHITE, WHITE], pixels[10..13].to_vec());
+    assert_eq!(BLACK, pixels[19]);
+    assert_eq!(vec![BLACK, BLACK, WHITE], pixels[20..23].to_vec());
+}
+
+#[test]
+fn tiff_tiles_test() {
+    // A 3x3 RGB image in 2x2 tiles, padded at the right and bottom
+    let tile =
+        |colors: [[u8; 4]; 4]| -> Vec<u8> { colors.iter().flat_map(|c| c[..3].to_vec()).collect() };
+    let mut data = tile([RED, BLUE, BLUE, RED]);
+    data.extend(tile([WHITE, BLACK, WHITE, BLACK]));
+    data.extend(tile([BLUE, BLUE, BLACK, BLACK]));
+    data.extend(tile([RED, BLACK, BLACK, BLACK]));
+    let fields = vec![
+        (256, SHORT, vec![3]),
+        (257, SHORT, vec![3]),
+        (258, SHORT, vec![8, 8, 8]),
+        (262, SHORT, vec![2]),
+        (277, SHORT, vec![3]),
+        (322, SHORT, vec![2]),
+        (323, SHORT, vec![2]),
+        (324, LONG, vec![0, 12, 24, 36]),
+        (325, LONG, vec![12, 12, 12, 12]),
+    ];
+    assert_eq!(
+        vec![RED, BLUE, WHITE, BLUE, RED, WHITE, BLUE, BLUE, RED],
+        decode(&tiff(false, &fields, &data))
+    );
+}
+
+#[test]
+fn tiff_planar_test() {
+    // Separate red, green and blue planes with horizontal differencing
+    let fields = vec![
+        (256, SHORT, vec![3]),
+        (257, SHORT, vec![1]),
+        (258, SHORT, vec![8, 8, 8]),
+        (262, SHORT, vec![2]),
+        (273, LONG, vec![0, 3, 6]),
+        (277, SHORT, vec![3]),
+        (279, LONG, vec![3, 3, 3]),
+        (284, SHORT, vec![2]),
+        (317, SHORT, vec![2]),
+    ];
+    let data = [255, 1, 255, 0, 0, 0, 0, 0, 255];
+    assert_eq!(
+        vec![RED, [0, 0, 0, 255], [255, 0, 255, 255]],
+        decode(&tiff(true, &fields, &data))
+    );
+}
+
+#[test]
+fn tiff_palette_alpha_test() {
+    // 4 bit palette with 16 bit color map entries
+    let mut map = vec![0; 48];
+    map[1] = 0xFFFF;
+    map[16 + 2] = 0xFFFF;
+    map[32 + 3] = 0xFFFF;
+    let fields = vec![
+        (256, SHORT, vec![4]),
+        (257, SHORT, vec![1]),
+        (258, SHORT, vec![4]),
+        (262, SHORT, vec![3]),
+        (273, LONG, vec![0]),
+        (279, LONG, vec![2]),
+        (320, SHORT, map),
+    ];
+    let buffer = tiff(false, &fields, &[0x01, 0x23]);
+    assert_eq!(vec![BLACK, RED, [0, 255, 0, 255], BLUE], decode(&buffer));
+    let info = raster::probe_reader(&buffer[..]).unwrap();
+    assert_eq!((ColorType::Indexed, 4), (info.color_type, info.bit_depth));
+
+    // 16 bit gray with premultiplied alpha
+    let fields = vec![
+        (256, SHORT, vec![2]),
+        (257, SHORT, vec![1]),
+        (258, SHORT, vec![16, 16]),
+        (262, SHORT, vec![1]),
+        (273, LONG, vec![0]),
+        (277, SHORT, vec![2]),
+        (279, LONG, vec![8]),
+        (338, SHORT, vec![1]),
+    ];
+    let data = [0x40, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00];
+    assert_eq!(
+        vec![[128, 128, 128, 128], [0, 0, 0, 0]],
+        decode(&tiff(true, &fields, &data))
+    );
+}
+
+#[test]
+fn tiff_fail_test() {
+    let fields = vec![
+        (256, SHORT, vec![4]),
+        (257, SHORT, vec![4]),
+        (258, SHORT, vec![8]),
+        (259, SHORT, vec![1]),
+        (262, SHORT, vec![1]),
+        (273, LONG, vec![0]),
+        (279, LONG, vec![16]),
+    ];
+    // Strips that point past the end of the file
+    let mut truncated = fields.clone();
+    truncated[5] = (273, LONG, vec![1000]);
+    // JPEG compression and CMYK are not supported
+    let mut jpeg = fields.clone();
+    jpeg[3] = (259, SHORT, vec![7]);
+    let mut cmyk = fields.clone();
+    cmyk[4] = (262, SHORT, vec![5]);
+    for fields in &[truncated, jpeg, cmyk] {
+        match raster::decode(&tiff(false, fields, &[0; 16])[..], ImageFormat::Tiff) {
+            Err(RasterError::Decode(ImageFormat::Tiff, _)) => {}
+            _ => panic!("Expected Decode error"),
+        }
+    }
+    assert!(raster::decode(&tiff(false, &fields, &[0; 16])[..], ImageFormat::Tiff).is_ok());
+}
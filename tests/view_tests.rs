@@ -0,0 +1,112 @@
+extern crate raster;
+
+use raster::error::RasterError;
+use raster::{compare, filter, transform, BlurMode, GenericImage, GenericImageView};
+use raster::{Image, PositionMode, Rgba, TransformMode};
+
+#[test]
+fn view_test() {
+    let image = raster::open("tests/in/sample.png").unwrap();
+    let view = image.view(10, 20, 30, 40).unwrap();
+    assert_eq!((30, 40), (view.width(), view.height()));
+    assert_eq!(
+        image.get_pixel(12, 25).unwrap().g,
+        view.get_pixel(2, 5).unwrap().0[1]
+    );
+
+    // Same pixels as a crop
+    let mut cropped = image.clone();
+    raster::editor::crop(&mut cropped, 30, 40, PositionMode::TopLeft, 10, 20).unwrap();
+    assert_eq!(cropped.bytes, view.to_image().bytes);
+    assert!(compare::equal(&view, &cropped).unwrap());
+    assert_eq!(0, compare::similar(&cropped, &view).unwrap());
+    assert!(!compare::equal(&view, &image.view(11, 20, 30, 40).unwrap()).unwrap());
+
+    // Pixels outside of the view can't be read even though they are in the image
+    for &(x, y) in &[(30, 0), (0, 40), (-1, 0), (0, -1)] {
+        match view.get_pixel(x, y) {
+            Err(RasterError::PixelOutOfBounds(px, py)) => assert_eq!((x, y), (px, py)),
+            _ => panic!("Expected PixelOutOfBounds error"),
+        }
+    }
+
+    // The rectangle must lie within the image
+    let (w, h) = (image.width, image.height);
+    assert!(image.view(0, 0, w, h).is_ok());
+    assert!(image.view(w, h, 0, 0).is_ok());
+    for &(x, y, vw, vh) in &[
+        (-1, 0, 2, 2),
+        (0, -1, 2, 2),
+        (w - 1, 0, 2, 2),
+        (0, h - 1, 2, 2),
+        (0, 0, -1, 2),
+        (1, 1, i32::MAX, 2),
+    ] {
+        match image.view(x, y, vw, vh) {
+            Err(RasterError::PixelOutOfBounds(_, _)) => {}
+            _ => panic!("Expected PixelOutOfBounds error"),
+        }
+    }
+}
+
+#[test]
+fn view_mut_test() {
+    let original = raster::open("tests/in/sample.png").unwrap();
+    let mut image = original.clone();
+    let (x, y, w, h) = (20, 10, 60, 50);
+
+    // Blurring a view matches blurring a crop and blending it back
+    let mut expected = original.clone();
+    raster::editor::crop(&mut expected, w, h, PositionMode::TopLeft, x, y).unwrap();
+    filter::blur(&mut expected, BlurMode::Gaussian).unwrap();
+    filter::sharpen(&mut expected).unwrap();
+    {
+        let mut view = image.view_mut(x, y, w, h).unwrap();
+        filter::blur(&mut view, BlurMode::Gaussian).unwrap();
+        filter::sharpen(&mut view).unwrap();
+        assert_eq!(expected.bytes, view.to_image().bytes);
+    }
+    assert_eq!(
+        expected.bytes,
+        image.view(x, y, w, h).unwrap().to_image().bytes
+    );
+
+    // Everything outside of the view is unchanged
+    for py in 0..image.height {
+        for px in 0..image.width {
+            if px < x || px >= x + w || py < y || py >= y + h {
+                let a = GenericImageView::get_pixel(&image, px, py).unwrap();
+                let b = GenericImageView::get_pixel(&original, px, py).unwrap();
+                assert_eq!(a, b);
+            }
+        }
+    }
+
+    // Flips and writes stay within the view
+    let mut image = Image::blank(4, 3);
+    {
+        let mut view = image.view_mut(1, 1, 3, 2).unwrap();
+        view.set_pixel(0, 0, Rgba([1, 2, 3, 4])).unwrap();
+        transform::flip(&mut view, TransformMode::Horizontal).unwrap();
+        transform::rotate_180(&mut view).unwrap();
+        assert!(view.set_pixel(3, 0, Rgba([0; 4])).is_err());
+    }
+    assert_eq!(&[0, 0, 0, 255], &image.bytes[20..24]);
+    assert_eq!(&[1, 2, 3, 4], &image.bytes[36..40]);
+
+    // Copying an image into a view
+    let mut gray = original.view(0, 0, w, h).unwrap().to_image();
+    filter::grayscale(&mut gray).unwrap();
+    let mut image = original.clone();
+    image
+        .view_mut(x, y, w, h)
+        .unwrap()
+        .copy_from(&gray)
+        .unwrap();
+    assert!(compare::equal(&gray, &image.view(x, y, w, h).unwrap()).unwrap());
+    assert!(image
+        .view_mut(0, 0, w + 1, h)
+        .unwrap()
+        .copy_from(&gray)
+        .is_err());
+}
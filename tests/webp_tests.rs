@@ -0,0 +1,323 @@
+extern crate raster;
+
+mod common;
+
+use raster::error::RasterError;
+use raster::{
+    editor, Animation, ColorType, DisposalMethod, Frame, Image, ImageFormat, LoopCount,
+    PositionMode,
+};
+use std::io::{self, Read};
+
+use common::pixels;
+
+// Packs values into bytes from the least significant bit, as in a VP8L bitstream
+struct Bits {
+    bytes: Vec<u8>,
+    count: usize,
+}
+
+impl Bits {
+    fn put(&mut self, value: u32, n: usize) {
+        for i in 0..n {
+            if self.count == self.bytes.len() * 8 {
+                self.bytes.push(0);
+            }
+            let last = self.bytes.len() - 1;
+            self.bytes[last] |= (((value >> i) & 1) as u8) << (self.count % 8);
+            self.count += 1;
+        }
+    }
+}
+
+// A WebP file with the given chunks
+fn webp(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    let mut body = b"WEBP".to_vec();
+    for &(id, data) in chunks {
+        body.extend_from_slice(id);
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            body.push(0);
+        }
+    }
+    let mut buffer = b"RIFF".to_vec();
+    buffer.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(&body);
+    buffer
+}
+
+// The VP8L bitstream of an image, taken from a simple WebP
+fn vp8l(image: &Image) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    raster::encode(image, &mut buffer, ImageFormat::WebP).unwrap();
+    assert_eq!(b"VP8L", &buffer[12..16]);
+    buffer[20..].to_vec()
+}
+
+fn filled(width: i32, height: i32, color: [u8; 4]) -> Image {
+    let mut image = Image::blank(width, height);
+    for pixel in image.bytes.chunks_mut(4) {
+        pixel.copy_from_slice(&color);
+    }
+    image
+}
+
+const CLEAR: [u8; 4] = [0, 0, 0, 0];
+const RED: [u8; 4] = [255, 0, 0, 255];
+
+#[test]
+fn webp_roundtrip_test() {
+    let mut transparent = raster::open("tests/in/sample.jpg").unwrap();
+    for (i, pixel) in transparent.bytes.chunks_mut(4).enumerate() {
+        pixel[3] = (i % 7 * 40) as u8;
+    }
+    let mut images = vec![
+        raster::open("tests/in/sample.png").unwrap(),
+        transparent,
+        Image::blank(1, 1),
+    ];
+    // Palettes small enough to pack 8, 4, 2 and 1 pixels into each byte
+    for &(colors, width) in &[(2, 19), (3, 7), (16, 13), (200, 31)] {
+        let mut image = Image::blank(width, 9);
+        for (i, pixel) in image.bytes.chunks_mut(4).enumerate() {
+            let color = (i * 7 % colors) as u8;
+            pixel.copy_from_slice(&[color, color ^ 0x55, 255 - color, 128 | color]);
+        }
+        images.push(image);
+    }
+
+    for image in &images {
+        let mut buffer = Vec::new();
+        raster::encode(image, &mut buffer, ImageFormat::WebP).unwrap();
+        assert_eq!(Some(ImageFormat::WebP), raster::guess_format(&buffer));
+
+        let decoded = raster::decode(&buffer[..], ImageFormat::WebP).unwrap();
+        assert_eq!(image.width, decoded.width);
+        assert_eq!(image.height, decoded.height);
+        assert_eq!(image.bytes, decoded.bytes);
+    }
+
+    raster::save(&images[0], "tests/out/test_webp.webp").unwrap();
+    let info = raster::probe("tests/out/test_webp.webp").unwrap();
+    assert_eq!(ImageFormat::WebP, info.format);
+    assert_eq!(ColorType::Rgb, info.color_type);
+    assert_eq!(
+        (images[0].width, images[0].height),
+        (info.width, info.height)
+    );
+    assert_eq!(1, info.frames);
+}
+
+#[test]
+fn webp_bitstream_test() {
+    // A 2x2 image with a color transform, subtract green and a color cache of 2 entries
+    let mut bits = Bits {
+        bytes: Vec::new(),
+        count: 0,
+    };
+    bits.put(0x2F, 8);
+    bits.put(1, 14);
+    bits.put(1, 14);
+    bits.put(1, 1);
+    bits.put(0, 3);
+
+    // Color transform with one block: green to red is 1.0, the rest 0. Every code has a single
+    // symbol, so the pixel takes no bits.
+    bits.put(1, 1);
+    bits.put(1, 2);
+    bits.put(0, 3);
+    bits.put(0, 1);
+    for &symbol in &[0, 0, 0x20, 0, 0] {
+        bits.put(1, 1);
+        bits.put(0, 1);
+        if symbol < 2 {
+            bits.put(0, 1);
+            bits.put(symbol, 1);
+        } else {
+            bits.put(1, 1);
+            bits.put(symbol, 8);
+        }
+    }
+    // Subtract green
+    bits.put(1, 1);
+    bits.put(2, 2);
+    bits.put(0, 1);
+
+    // A color cache of 1 bit and no entropy image
+    bits.put(1, 1);
+    bits.put(1, 4);
+    bits.put(0, 1);
+    // Green: lengths of 2 for two literals, a length code and a cache index, written with a code
+    // length code where 0 and 2 take one bit each
+    bits.put(0, 1);
+    bits.put(1, 4);
+    for &len in &[0, 0, 1, 0, 1] {
+        bits.put(len, 3);
+    }
+    bits.put(0, 1);
+    for symbol in 0..256 + 24 + 2 {
+        bits.put([0x10, 0x70, 256, 280].contains(&symbol) as u32, 1);
+    }
+    // Red, blue and alpha with two symbols, and distance code 1
+    bits.put(0b111, 3);
+    bits.put(0x05, 8);
+    bits.put(0x00, 8);
+    bits.put(0b011, 3);
+    bits.put(1, 1);
+    bits.put(0xF4, 8);
+    bits.put(0b111, 3);
+    bits.put(0xFF, 8);
+    bits.put(0x80, 8);
+    bits.put(0b1001, 4);
+
+    // Two literals, a copy of the pixel to the left and the first pixel from the cache
+    for &(value, n) in &[(0b00, 2), (1, 1), (0, 1), (1, 1)] {
+        bits.put(value, n);
+    }
+    for &(value, n) in &[(0b10, 2), (0, 1), (1, 1), (0, 1)] {
+        bits.put(value, n);
+    }
+    bits.put(0b01, 2);
+    bits.put(0b11, 2);
+
+    let buffer = webp(&[(b"VP8L", &bits.bytes)]);
+    let image = raster::decode(&buffer[..], ImageFormat::WebP).unwrap();
+    let first = [0x25, 0x10, 0x11, 0xFF];
+    let second = [0xE0, 0x70, 0x64, 0x80];
+    assert_eq!(vec![first, second, second, first], pixels(&image));
+
+    let info = raster::probe_reader(&buffer[..]).unwrap();
+    assert_eq!((2, 2), (info.width, info.height));
+    assert_eq!(ColorType::Rgba, info.color_type);
+}
+
+#[test]
+fn webp_animation_test() {
+    let mut frames = vec![
+        Frame::new(filled(6, 4, RED), 100),
+        Frame::new(filled(6, 4, [0, 255, 0, 128]), 250),
+        Frame::new(raster::open("tests/in/sample.png").unwrap(), 40),
+    ];
+    editor::crop(&mut frames[2].image, 6, 4, PositionMode::TopLeft, 0, 0).unwrap();
+    frames[1].disposal = DisposalMethod::Background;
+    let mut animation = Animation::new(frames);
+    animation.loop_count = LoopCount::Finite(3);
+    raster::save_animation(&animation, "tests/out/test_webp_animation.webp").unwrap();
+
+    let decoded = raster::open_animation("tests/out/test_webp_animation.webp").unwrap();
+    assert_eq!((6, 4), (decoded.width, decoded.height));
+    assert_eq!(LoopCount::Finite(3), decoded.loop_count);
+    assert_eq!(animation.frames.len(), decoded.frames.len());
+    for (frame, saved) in animation.frames.iter().zip(&decoded.frames) {
+        assert_eq!(frame.image.bytes, saved.image.bytes);
+        assert_eq!(frame.delay, saved.delay);
+        assert_eq!(frame.disposal, saved.disposal);
+    }
+
+    // A single image is the first frame
+    let image = raster::open("tests/out/test_webp_animation.webp").unwrap();
+    assert_eq!(animation.frames[0].image.bytes, image.bytes);
+    let images = raster::open_all("tests/out/test_webp_animation.webp").unwrap();
+    assert_eq!(3, images.len());
+
+    let info = raster::probe("tests/out/test_webp_animation.webp").unwrap();
+    assert_eq!((6, 4), (info.width, info.height));
+    assert_eq!(ColorType::Rgba, info.color_type);
+    assert_eq!(3, info.frames);
+}
+
+#[test]
+fn webp_compose_test() {
+    // A 4x2 canvas: a red background, a half transparent blue square blended at (2, 0) and
+    // disposed, then a red pixel replacing the top left pixel without blending
+    let mut header = vec![0x12, 0, 0, 0];
+    header.extend_from_slice(&[3, 0, 0, 1, 0, 0]);
+    let frame = |x: u8, y: u8, image: &Image, flags: u8| {
+        let mut data = vec![x / 2, 0, 0, y / 2, 0, 0];
+        data.extend_from_slice(&[image.width as u8 - 1, 0, 0, image.height as u8 - 1, 0, 0]);
+        data.extend_from_slice(&[50, 0, 0, flags]);
+        let bitstream = vp8l(image);
+        data.extend_from_slice(b"VP8L");
+        data.extend_from_slice(&(bitstream.len() as u32).to_le_bytes());
+        data.extend_from_slice(&bitstream);
+        if bitstream.len() % 2 == 1 {
+            data.push(0);
+        }
+        data
+    };
+    let background = frame(0, 0, &filled(4, 2, RED), 0);
+    let square = frame(2, 0, &filled(2, 2, [0, 0, 255, 128]), 0x01);
+    let pixel = frame(0, 0, &filled(1, 1, CLEAR), 0x02);
+    let buffer = webp(&[
+        (b"VP8X", &header),
+        (b"ANIM", &[0, 0, 0, 0, 0, 0]),
+        (b"ANMF", &background),
+        (b"ANMF", &square),
+        (b"ANMF", &pixel),
+    ]);
+
+    let animation = raster::decode_animation(&buffer[..], ImageFormat::WebP).unwrap();
+    assert_eq!(LoopCount::Infinite, animation.loop_count);
+    assert_eq!(50, animation.frames[0].delay);
+    let mixed = [127, 0, 128, 255];
+    assert_eq!(
+        vec![RED, RED, mixed, mixed, RED, RED, mixed, mixed],
+        pixels(&animation.frames[1].image)
+    );
+    assert_eq!(
+        vec![CLEAR, RED, CLEAR, CLEAR, RED, RED, CLEAR, CLEAR],
+        pixels(&animation.frames[2].image)
+    );
+    let info = raster::probe_reader(&buffer[..]).unwrap();
+    assert_eq!((4, 2, 3), (info.width, info.height, info.frames));
+}
+
+#[test]
+fn webp_fail_test() {
+    // Lossy images can be probed but not decoded
+    let lossy = webp(&[(
+        b"VP8 ",
+        &[0x50, 0x01, 0x00, 0x9D, 0x01, 0x2A, 0x10, 0x00, 0x08, 0x00],
+    )]);
+    let info = raster::probe_reader(&lossy[..]).unwrap();
+    assert_eq!((16, 8), (info.width, info.height));
+
+    // Probing reads the headers only, so an endless stream is fine
+    let endless = (&lossy[..]).chain(io::repeat(0));
+    assert_eq!(16, raster::probe_reader(endless).unwrap().width);
+
+    // A RIFF size that is too small for the form type
+    let mut small = lossy.clone();
+    small[4..8].copy_from_slice(&2u32.to_le_bytes());
+    small.resize(36, 0);
+    match raster::decode(&small[..], ImageFormat::WebP) {
+        Err(RasterError::Decode(ImageFormat::WebP, _)) => {}
+        _ => panic!("Expected Decode error"),
+    }
+    assert!(raster::probe_reader(&small[..]).is_err());
+
+    let mut truncated = vp8l(&raster::open("tests/in/sample.png").unwrap());
+    truncated.truncate(100);
+    let truncated = webp(&[(b"VP8L", &truncated)]);
+    let missing = webp(&[(b"EXIF", b"II*\0")]);
+    for buffer in &[lossy, truncated, missing] {
+        match raster::decode(&buffer[..], ImageFormat::WebP) {
+            Err(RasterError::Decode(ImageFormat::WebP, _)) => {}
+            _ => panic!("Expected Decode error"),
+        }
+    }
+
+    match raster::encode(&Image::blank(16385, 1), Vec::new(), ImageFormat::WebP) {
+        Err(RasterError::Encode(ImageFormat::WebP, _)) => {}
+        _ => panic!("Expected Encode error"),
+    }
+    let frames = vec![
+        Frame::new(Image::blank(2, 2), 10),
+        Frame::new(Image::blank(3, 2), 10),
+    ];
+    match raster::encode_animation(&Animation::new(frames), Vec::new(), ImageFormat::WebP) {
+        Err(RasterError::Encode(ImageFormat::WebP, _)) => {}
+        _ => panic!("Expected Encode error"),
+    }
+}